@@ -0,0 +1,94 @@
+use clap::Parser;
+use std::io::Write;
+
+use brush_core::{ExecutionExitCode, ExecutionResult, builtins};
+
+/// Return the context of the current subroutine call.
+#[derive(Parser)]
+pub(crate) struct CallerCommand {
+    /// How many call frames to go back from the current one (frame 0).
+    expr: Option<String>,
+
+    /// Ignored, as in bash.
+    #[clap(hide = true, allow_hyphen_values = true)]
+    _rest: Vec<String>,
+}
+
+impl builtins::Command for CallerCommand {
+    type Error = brush_core::Error;
+
+    async fn execute(
+        &self,
+        context: brush_core::ExecutionContext<'_>,
+    ) -> Result<brush_core::ExecutionResult, Self::Error> {
+        let frames = context.shell.subroutines();
+
+        // Outside of any subroutine there is no context to report.
+        let Some(current) = frames.first() else {
+            return Ok(ExecutionResult::general_error());
+        };
+
+        let Some(expr) = &self.expr else {
+            let source = frames.get(1).map_or("NULL", |frame| frame.source);
+            writeln!(context.stdout(), "{} {source}", current.call_line)?;
+            return Ok(ExecutionResult::success());
+        };
+
+        let Ok(index) = expr.parse::<usize>() else {
+            writeln!(
+                context.stderr(),
+                "{}: {expr}: invalid number",
+                context.command_name
+            )?;
+            return Ok(ExecutionExitCode::InvalidUsage.into());
+        };
+
+        // Report where frame `index` was called from: the line, and the caller's name and source.
+        match (frames.get(index), frames.get(index.saturating_add(1))) {
+            (Some(frame), Some(caller)) => {
+                writeln!(
+                    context.stdout(),
+                    "{} {} {}",
+                    frame.call_line,
+                    caller.name,
+                    caller.source
+                )?;
+                Ok(ExecutionResult::success())
+            }
+            _ => Ok(ExecutionResult::general_error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use brush_core::Shell;
+    use pretty_assertions::assert_eq;
+
+    use crate::{BuiltinSet, ShellBuilderExt};
+
+    #[tokio::test]
+    async fn test_caller_reports_calling_frames() -> Result<()> {
+        let mut shell = Shell::builder()
+            .default_builtins(BuiltinSet::BashMode)
+            .build()
+            .await?;
+        let params = shell.default_exec_params();
+        shell
+            .run_string(
+                "f() { a=$(caller); b=$(caller 0); caller 1; printf -v c %s $?; caller x 2>/dev/null; \
+                 printf -v d %s $?; }\ng() {\n  f\n}\n\ng; caller; printf -v e %s $?",
+                &params,
+            )
+            .await?;
+
+        let var = |name| shell.env_str(name).map(|value| value.into_owned());
+        assert_eq!(var("a").as_deref(), Some("3 main"));
+        assert_eq!(var("b").as_deref(), Some("3 g main"));
+        assert_eq!(var("c").as_deref(), Some("1"));
+        assert_eq!(var("d").as_deref(), Some("2"));
+        assert_eq!(var("e").as_deref(), Some("1"));
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use clap::Parser;
+use std::io::Write;
+
+use brush_core::{ExecutionResult, builtins, jobs};
+
+/// Remove jobs from the job table.
+#[derive(Parser)]
+#[clap(disable_help_flag = true)]
+pub(crate) struct DisownCommand {
+    /// Keep the jobs in the table but don't send them a hangup signal when the
+    /// shell hangs up.
+    #[arg(short = 'h')]
+    no_hangup: bool,
+
+    /// Select all jobs when no job specs are given.
+    #[arg(short = 'a')]
+    all_jobs: bool,
+
+    /// Select only running jobs.
+    #[arg(short = 'r')]
+    running_jobs_only: bool,
+
+    /// Job specs or process IDs of the jobs to select; if not specified, the
+    /// current job is selected.
+    job_specs: Vec<String>,
+}
+
+impl builtins::Command for DisownCommand {
+    type Error = brush_core::Error;
+
+    async fn execute(
+        &self,
+        context: brush_core::ExecutionContext<'_>,
+    ) -> Result<brush_core::ExecutionResult, Self::Error> {
+        let mut result = ExecutionResult::success();
+        let mut ids = vec![];
+
+        if !self.job_specs.is_empty() {
+            for job_spec in &self.job_specs {
+                if let Some(job) = resolve(&mut context.shell.jobs, job_spec) {
+                    ids.push(job.id);
+                } else {
+                    writeln!(
+                        context.stderr(),
+                        "{}: {job_spec}: no such job",
+                        context.command_name
+                    )?;
+                    result = ExecutionResult::general_error();
+                }
+            }
+        } else if self.all_jobs || self.running_jobs_only {
+            ids.extend(context.shell.jobs.jobs.iter().map(|job| job.id));
+        } else if let Some(job) = context.shell.jobs.current_job() {
+            ids.push(job.id);
+        } else {
+            writeln!(
+                context.stderr(),
+                "{}: current: no such job",
+                context.command_name
+            )?;
+            return Ok(ExecutionResult::general_error());
+        }
+
+        for id in ids {
+            let Some(job) = context.shell.jobs.jobs.iter_mut().find(|job| job.id == id) else {
+                continue;
+            };
+            if self.running_jobs_only && !matches!(job.state, jobs::JobState::Running) {
+                continue;
+            }
+            if self.no_hangup {
+                job.no_hangup = true;
+            } else {
+                context.shell.jobs.disown(id);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Resolves a job spec (`%n`, `%+`, ...) or the process ID of a job.
+fn resolve<'a>(manager: &'a mut jobs::JobManager, job_spec: &str) -> Option<&'a mut jobs::Job> {
+    if job_spec.starts_with('%') {
+        return manager.resolve_job_spec(job_spec);
+    }
+    let pid = job_spec.parse::<i32>().ok()?;
+    manager
+        .jobs
+        .iter_mut()
+        .find(|job| job.representative_pid() == Some(pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use brush_core::Shell;
+    use pretty_assertions::assert_eq;
+
+    use crate::{BuiltinSet, ShellBuilderExt};
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_disown_removes_and_marks_jobs() -> Result<()> {
+        let mut shell = Shell::builder()
+            .default_builtins(BuiltinSet::BashMode)
+            .build()
+            .await?;
+        let params = shell.default_exec_params();
+        shell
+            .run_string(
+                "sleep 30 & first=$!; sleep 30 & second=$!; sleep 30 & third=$!; disown -h %1; \
+                 disown; disown %7 2>/dev/null; printf -v missing %s $?",
+                &params,
+            )
+            .await?;
+
+        let ids: Vec<_> = shell.jobs.jobs.iter().map(|job| job.id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert!(shell.jobs.jobs[0].no_hangup);
+        assert!(shell.jobs.jobs[1].is_current());
+        assert!(shell.jobs.jobs[0].is_prev());
+        assert_eq!(shell.env_str("missing").as_deref(), Some("1"));
+
+        let first = shell.env_str("first").map(|pid| pid.into_owned());
+        shell
+            .run_string(format!("disown {}", first.unwrap_or_default()), &params)
+            .await?;
+        assert_eq!(shell.jobs.jobs.len(), 1);
+        shell.run_string("disown -a", &params).await?;
+        assert!(shell.jobs.jobs.is_empty());
+
+        // Disowned jobs keep running.
+        shell.run_string("kill $first $second $third", &params).await?;
+        Ok(())
+    }
+}
@@ -34,3 +34,33 @@ impl builtins::Command for EvalCommand {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use brush_core::Shell;
+    use pretty_assertions::assert_eq;
+
+    use crate::{BuiltinSet, ShellBuilderExt};
+
+    #[tokio::test]
+    async fn test_eval_lines_count_from_eval_command() -> Result<()> {
+        let mut shell = Shell::builder()
+            .default_builtins(BuiltinSet::BashMode)
+            .build()
+            .await?;
+        let params = shell.default_exec_params();
+        shell
+            .run_string(
+                "trap 'debug=\"$debug $LINENO\"' DEBUG\n\neval 'a=$LINENO\n: b'\ntrap - DEBUG\nc=$LINENO",
+                &params,
+            )
+            .await?;
+
+        let var = |name| shell.env_str(name).map(|value| value.into_owned());
+        assert_eq!(var("a").as_deref(), Some("3"));
+        assert_eq!(var("c").as_deref(), Some("6"));
+        assert_eq!(var("debug").as_deref(), Some(" 3 4 5"));
+        Ok(())
+    }
+}
@@ -166,14 +166,15 @@ pub fn default_builtins(set: BuiltinSet) -> HashMap<String, builtins::Registrati
 		#[cfg(feature = "builtin.history")]
 		m.insert("history".into(), builtin::<history::HistoryCommand>());
 
-		// TODO: implement caller builtin
-		m.insert("caller".into(), builtin::<unimp::UnimplementedCommand>());
-
-		// TODO: implement disown builtin
-		m.insert("disown".into(), builtin::<unimp::UnimplementedCommand>());
-
-		// TODO: implement logout builtin
-		m.insert("logout".into(), builtin::<unimp::UnimplementedCommand>());
+		// Call stack
+		#[cfg(feature = "builtin.caller")]
+		m.insert("caller".into(), builtin::<caller::CallerCommand>());
+
+		// Session and job table
+		#[cfg(feature = "builtin.disown")]
+		m.insert("disown".into(), builtin::<disown::DisownCommand>());
+		#[cfg(feature = "builtin.logout")]
+		m.insert("logout".into(), builtin::<logout::LogoutCommand>());
 	}
 
 	//
@@ -12,6 +12,8 @@ mod break_;
 mod brushinfo;
 #[cfg(feature = "builtin.builtin")]
 mod builtin_;
+#[cfg(feature = "builtin.caller")]
+mod caller;
 #[cfg(feature = "builtin.cd")]
 mod cd;
 #[cfg(feature = "builtin.colon")]
@@ -30,6 +32,8 @@ mod continue_;
 mod declare;
 #[cfg(feature = "builtin.dirs")]
 mod dirs;
+#[cfg(feature = "builtin.disown")]
+mod disown;
 #[cfg(feature = "builtin.dot")]
 mod dot;
 #[cfg(feature = "builtin.echo")]
@@ -64,6 +68,8 @@ mod jobs;
 mod kill;
 #[cfg(feature = "builtin.let")]
 mod let_;
+#[cfg(feature = "builtin.logout")]
+mod logout;
 #[cfg(feature = "builtin.mapfile")]
 mod mapfile;
 #[cfg(feature = "builtin.popd")]
@@ -109,10 +115,11 @@ mod wait;
 
 mod builder;
 mod factory;
-mod unimp;
 
 pub use builder::ShellBuilderExt;
 pub use factory::{BuiltinSet, default_builtins};
+#[cfg(all(feature = "builtin.ulimit", unix))]
+pub use ulimit::LimitResource;
 
 /// Macro to define a struct that represents a shell built-in flag argument that can be
 /// enabled or disabled by specifying an option with a leading '+' or '-' character.
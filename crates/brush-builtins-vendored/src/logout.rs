@@ -0,0 +1,68 @@
+use clap::Parser;
+use std::io::Write;
+
+use brush_core::{ExecutionControlFlow, ExecutionResult, builtins};
+
+/// Exit a login shell.
+#[derive(Parser)]
+pub(crate) struct LogoutCommand {
+    /// The exit code to return.
+    code: Option<i32>,
+}
+
+impl builtins::Command for LogoutCommand {
+    type Error = brush_core::Error;
+
+    async fn execute(
+        &self,
+        context: brush_core::ExecutionContext<'_>,
+    ) -> Result<brush_core::ExecutionResult, Self::Error> {
+        if !context.shell.options.login_shell {
+            writeln!(
+                context.stderr(),
+                "{}: not login shell: use `exit'",
+                context.command_name
+            )?;
+            return Ok(ExecutionResult::general_error());
+        }
+
+        #[expect(clippy::cast_sign_loss)]
+        let code_8bit = if let Some(code_32bit) = &self.code {
+            (code_32bit & 0xFF) as u8
+        } else {
+            context.shell.last_result()
+        };
+
+        let mut result = ExecutionResult::new(code_8bit);
+        result.next_control_flow = ExecutionControlFlow::ExitShell;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use brush_core::Shell;
+    use pretty_assertions::assert_eq;
+
+    use crate::{BuiltinSet, ShellBuilderExt};
+
+    #[tokio::test]
+    async fn test_logout_only_exits_login_shells() -> Result<()> {
+        let mut shell = Shell::builder()
+            .default_builtins(BuiltinSet::BashMode)
+            .build()
+            .await?;
+        let params = shell.default_exec_params();
+        shell.run_string("logout 2>/dev/null; printf -v status %s $?", &params).await?;
+        assert_eq!(shell.env_str("status").as_deref(), Some("1"));
+
+        shell.options.login_shell = true;
+        let result = shell.run_string("logout 3; status=ran", &params).await?;
+        assert_eq!(u8::from(result.exit_code), 3);
+        assert!(result.is_return_or_exit());
+        assert_eq!(shell.env_str("status").as_deref(), Some("1"));
+        Ok(())
+    }
+}
@@ -251,6 +251,30 @@ impl ResourceDescription {
         unit: Unit::Number,
     };
 
+    const ALL: [Self; 21] = [
+        Self::SBSIZE,
+        Self::CORE,
+        Self::DATA,
+        Self::NICE,
+        Self::FSIZE,
+        Self::SIGPENDING,
+        Self::MEMLOCK,
+        Self::KQUEUES,
+        Self::RSS,
+        Self::LOCKS,
+        Self::NOFILE,
+        Self::MSGQUEUE,
+        Self::PIPE,
+        Self::RTPRIO,
+        Self::RTTIME,
+        Self::STACK,
+        Self::CPU,
+        Self::NPROC,
+        Self::VMEM,
+        Self::THREADS,
+        Self::NPTS,
+    ];
+
     fn get(&self, hard: bool) -> std::io::Result<String> {
         let (soft_limit, hard_limit) = self.resource.get()?;
         let val = if hard { hard_limit } else { soft_limit };
@@ -317,6 +341,43 @@ impl ResourceDescription {
     }
 }
 
+/// A resource limit as `ulimit` maps it onto the platform, for embedders that
+/// apply limits to the processes they spawn.
+#[derive(Clone, Copy)]
+pub struct LimitResource(ResourceDescription);
+
+impl LimitResource {
+    /// Returns the resource `ulimit` controls with the option `-<short>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `short` - The option character, e.g. `'n'` for open files.
+    pub fn from_option(short: char) -> Option<Self> {
+        ResourceDescription::ALL
+            .into_iter()
+            .find(|description| description.short == short)
+            .map(Self)
+    }
+
+    /// Returns whether the platform supports the resource.
+    pub const fn is_supported(self) -> bool {
+        self.0.resource.is_supported()
+    }
+
+    /// Sets both the soft and the hard limit. Unlike the `ulimit` builtin,
+    /// `value` is in the resource's base unit (bytes, seconds or a count);
+    /// `rlimit::INFINITY` removes the limit.
+    ///
+    /// Only issues system calls, so it may run between `fork` and `exec`.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The new limit.
+    pub fn set(self, value: u64) -> std::io::Result<()> {
+        self.0.resource.set(value, value)
+    }
+}
+
 impl IntoResettable<StyledStr> for ResourceDescription {
     fn into_resettable(self) -> clap::builder::Resettable<StyledStr> {
         clap::builder::Resettable::Value(self.help().into())
@@ -1,6 +1,6 @@
 //! Command execution
 
-use std::{borrow::Cow, ffi::OsStr, fmt::Display, io, sync::Arc};
+use std::{borrow::Cow, ffi::OsStr, fmt::Display, io, path::PathBuf, sync::Arc, time::SystemTime};
 
 use brush_parser::ast;
 use itertools::Itertools;
@@ -9,10 +9,11 @@ use sys::commands::{CommandExt, CommandFdInjectionExt, CommandFgControlExt};
 use crate::{
 	ErrorKind, ExecutionControlFlow, ExecutionParameters, ExecutionResult, Shell, ShellFd, builtins,
 	env, error, escape,
+	interfaces::{CommandKind, CommandTrace, CommandTracer, PolicyDecision},
 	interp::{self, Execute, ProcessGroupPolicy},
 	openfiles::{self, OpenFiles},
 	pathsearch, processes,
-	results::ExecutionSpawnResult,
+	results::{ExecutionExitCode, ExecutionSpawnResult},
 	sys, trace_categories, traps, variables,
 };
 
@@ -21,7 +22,7 @@ pub enum CommandWaitResult {
 	/// The command completed.
 	CommandCompleted(ExecutionResult),
 	/// The command was stopped before it completed.
-	CommandStopped(ExecutionResult, processes::ChildProcess),
+	CommandStopped(ExecutionResult, Box<processes::ChildProcess>),
 }
 
 /// Represents the context for executing a command.
@@ -76,7 +77,9 @@ impl ExecutionContext<'_> {
 	}
 
 	pub(crate) const fn should_cmd_lead_own_process_group(&self) -> bool {
-		self.shell.options.interactive
+		// Background jobs lead their own process groups so they can be signaled
+		// as a unit without reaching the shell itself.
+		(self.shell.options.interactive || self.shell.job_processes.is_some())
 			&& matches!(self.params.process_group_policy, ProcessGroupPolicy::NewProcessGroup)
 	}
 }
@@ -302,6 +305,68 @@ pub async fn execute(
 	use_functions: bool,
 	path_dirs: Option<Vec<String>>,
 ) -> Result<ExecutionSpawnResult, error::Error> {
+	// Give the shell's policy a chance to veto the fully expanded command
+	// before anything is dispatched.
+	if let Some(policy) = cmd_context.shell.command_policy() {
+		let argv: Vec<String> = args.iter().map(ToString::to_string).collect();
+		if let PolicyDecision::Deny(reason) = policy.check(&argv, cmd_context.shell) {
+			let err: error::Error = ErrorKind::CommandBlocked(cmd_context.command_name, reason).into();
+			if let Some(tracer) = cmd_context.shell.command_tracer() {
+				let blocked = PendingTrace {
+					tracer: tracer.clone(),
+					argv,
+					cwd: cmd_context.shell.working_dir().to_path_buf(),
+					kind: CommandKind::Blocked,
+					started_at: SystemTime::now(),
+				};
+				blocked.finish(None, (&ExecutionExitCode::from(&err)).into());
+			}
+			return Err(err);
+		}
+	}
+
+	let Some(tracer) = cmd_context.shell.command_tracer().cloned() else {
+		return dispatch(cmd_context, process_group_id, args, use_functions, path_dirs)
+			.await
+			.1;
+	};
+
+	let mut pending = PendingTrace {
+		tracer,
+		argv: args.iter().map(ToString::to_string).collect(),
+		cwd: cmd_context.shell.working_dir().to_path_buf(),
+		kind: CommandKind::External,
+		started_at: SystemTime::now(),
+	};
+	let (kind, result) =
+		dispatch(cmd_context, process_group_id, args, use_functions, path_dirs).await;
+	pending.kind = kind;
+
+	match result {
+		// External processes are reported once they are reaped.
+		Ok(ExecutionSpawnResult::StartedProcess(child)) => {
+			Ok(ExecutionSpawnResult::StartedProcess(child.with_trace(pending)))
+		},
+		Ok(ExecutionSpawnResult::Completed(result)) => {
+			pending.finish(None, (&result.exit_code).into());
+			Ok(ExecutionSpawnResult::Completed(result))
+		},
+		Err(err) => {
+			pending.finish(None, (&ExecutionExitCode::from(&err)).into());
+			Err(err)
+		},
+	}
+}
+
+/// Dispatches an expanded command to a builtin, function, or external program,
+/// reporting which of those it resolved to.
+async fn dispatch(
+	cmd_context: ExecutionContext<'_>,
+	process_group_id: &mut Option<i32>,
+	args: Vec<CommandArg>,
+	use_functions: bool,
+	path_dirs: Option<Vec<String>>,
+) -> (CommandKind, Result<ExecutionSpawnResult, error::Error>) {
 	// First see if it's the name of a builtin.
 	let builtin = cmd_context
 		.shell
@@ -314,7 +379,10 @@ pub async fn execute(
 		.as_ref()
 		.is_some_and(|r| !r.disabled && r.special_builtin)
 	{
-		return execute_builtin_command(&builtin.unwrap(), cmd_context, args).await;
+		return (
+			CommandKind::Builtin,
+			execute_builtin_command(&builtin.unwrap(), cmd_context, args).await,
+		);
 	}
 
 	// Assuming we weren't requested not to do so, check if it's the name of
@@ -326,20 +394,23 @@ pub async fn execute(
 			.get(cmd_context.command_name.as_str())
 		{
 			// Strip the function name off args.
-			return invoke_shell_function(func_reg.definition.clone(), cmd_context, &args[1..]).await;
+			return (
+				CommandKind::Function,
+				invoke_shell_function(func_reg.definition.clone(), cmd_context, &args[1..]).await,
+			);
 		}
 	}
 
 	// If we found a (non-special) builtin and it's not disabled, then invoke it.
 	if let Some(builtin) = builtin {
 		if !builtin.disabled {
-			return execute_builtin_command(&builtin, cmd_context, args).await;
+			return (CommandKind::Builtin, execute_builtin_command(&builtin, cmd_context, args).await);
 		}
 	}
 
 	// We still haven't found a command to invoke. We'll need to look for an
 	// external command.
-	if !cmd_context.command_name.contains(std::path::MAIN_SEPARATOR) {
+	let result = if !cmd_context.command_name.contains(std::path::MAIN_SEPARATOR) {
 		// All else failed; if we were given path directories to search, try to look
 		// through them for a matching executable. Otherwise, use our default search
 		// logic.
@@ -366,6 +437,32 @@ pub async fn execute(
 
 		// Strip the command name off args.
 		execute_external_command(cmd_context, resolved_path.as_str(), process_group_id, &args[1..])
+	};
+
+	(CommandKind::External, result)
+}
+
+/// Trace record for a dispatched command that has not completed yet.
+pub(crate) struct PendingTrace {
+	tracer:     Arc<dyn CommandTracer>,
+	argv:       Vec<String>,
+	cwd:        PathBuf,
+	kind:       CommandKind,
+	started_at: SystemTime,
+}
+
+impl PendingTrace {
+	/// Reports the completed command to the tracer.
+	pub(crate) fn finish(self, pid: Option<i32>, exit_code: u8) {
+		self.tracer.on_command(CommandTrace {
+			argv: self.argv,
+			cwd: self.cwd,
+			kind: self.kind,
+			pid,
+			started_at: self.started_at,
+			finished_at: SystemTime::now(),
+			exit_code,
+		});
 	}
 }
 
@@ -402,8 +499,18 @@ pub(crate) fn execute_external_command(
 		false, /* empty environment? */
 	)?;
 
+	// Foreground processes of a shell with a controlling terminal to hand out
+	// lead sessions of their own, which rules out joining a process group.
+	let terminal = context
+		.shell
+		.process_setup()
+		.filter(|_| context.shell.job_processes.is_none())
+		.and_then(|setup| setup.controlling_terminal().map(ToOwned::to_owned));
+
 	// Set up process group state.
-	if new_pg {
+	if let Some(terminal) = &terminal {
+		cmd.lead_session(terminal);
+	} else if new_pg {
 		// We need to set up a new process group.
 		cmd.process_group(0);
 	} else {
@@ -413,13 +520,18 @@ pub(crate) fn execute_external_command(
 		}
 	}
 
-	// If we're to lead our own process group and stdin is a terminal,
-	// then we need to arrange for the new process to move itself
-	// to the foreground.
-	if new_pg && child_stdin_is_terminal {
+	// If we're an interactive shell leading our own process group and stdin
+	// is a terminal, then we need to arrange for the new process to move
+	// itself to the foreground.
+	if new_pg && child_stdin_is_terminal && context.shell.options.interactive {
 		cmd.take_foreground();
 	}
 
+	// Give the embedder a chance to adjust the process.
+	if let Some(setup) = context.shell.process_setup() {
+		setup.before_spawn(&mut cmd)?;
+	}
+
 	// When tracing is enabled, report.
 	tracing::debug!(
 		 target: trace_categories::COMMANDS,
@@ -439,11 +551,18 @@ pub(crate) fn execute_external_command(
 				if new_pg {
 					*process_group_id = Some(*pid);
 				}
+				if let Some(processes) = &context.shell.job_processes {
+					processes.record(*pid, *process_group_id);
+				}
 			} else {
 				tracing::warn!("could not retrieve pid for child process");
 			}
 
-			Ok(ExecutionSpawnResult::StartedProcess(processes::ChildProcess::new(pid, child)))
+			let mut process = processes::ChildProcess::new(pid, child);
+			if let Some(setup) = context.shell.process_setup() {
+				process = process.with_setup(setup.clone());
+			}
+			Ok(ExecutionSpawnResult::StartedProcess(process))
 		},
 		Err(spawn_err) => {
 			if context.shell.options.interactive {
@@ -595,12 +714,8 @@ async fn run_substitution_command(
 		}
 	}
 
-	let source_info = brush_parser::SourceInfo { source: String::from("main") };
-
 	// Handle the parse result using default shell behavior.
-	shell
-		.run_parsed_result(parse_result, &source_info, &params)
-		.await
+	shell.run_parsed_string(parse_result, &params).await
 }
 
 #[cfg(unix)]
@@ -1109,7 +1109,31 @@ fn get_command_completions(shell: &Shell, context: &Context<'_>) -> IndexSet<Str
 	candidates.into_iter().collect()
 }
 
+fn get_variable_completions(shell: &Shell, reference: &str) -> IndexSet<String> {
+	let (sigil, suffix, prefix) = match reference.strip_prefix('{') {
+		Some(prefix) => ("${", "}", prefix),
+		None => ("$", "", reference),
+	};
+
+	let mut candidates: IndexSet<String> = shell
+		.env
+		.iter()
+		.filter(|(name, _)| name.starts_with(prefix))
+		.map(|(name, _)| std::format!("{sigil}{name}{suffix}"))
+		.collect();
+	candidates.sort();
+	candidates
+}
+
 async fn get_completions_using_basic_lookup(shell: &Shell, context: &Context<'_>) -> Answer {
+	// Variable references complete to variable names rather than files.
+	if let Some(reference) = context.token_to_complete.strip_prefix('$') {
+		return Answer::Candidates(get_variable_completions(shell, reference), ProcessingOptions {
+			treat_as_filenames: false,
+			..ProcessingOptions::default()
+		});
+	}
+
 	let mut candidates = get_file_completions(shell, context.token_to_complete, false).await;
 
 	// If this appears to be the command token (and if there's *some* prefix without
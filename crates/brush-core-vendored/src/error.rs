@@ -68,6 +68,10 @@ pub enum ErrorKind {
     #[error("failed to execute command '{0}': {1}")]
     FailedToExecuteCommand(String, #[source] std::io::Error),
 
+    /// Command was refused by the shell's command policy.
+    #[error("{0}: blocked by policy: {1}")]
+    CommandBlocked(String, String),
+
     /// History item was not found.
     #[error("history item not found")]
     HistoryItemNotFound,
@@ -254,6 +258,13 @@ pub enum ErrorKind {
     UnknownKeyBindingFunction(String),
 }
 
+impl Error {
+    /// Returns the kind of this error.
+    pub const fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+}
+
 impl BuiltinError for Error {}
 
 /// Trait implementable by built-in commands to represent errors.
@@ -283,7 +294,9 @@ impl From<&ErrorKind> for results::ExecutionExitCode {
             }
             ErrorKind::ParseError(..) => Self::InvalidUsage,
             ErrorKind::FunctionParseError(..) => Self::InvalidUsage,
-            ErrorKind::FailedToExecuteCommand(..) => Self::CannotExecute,
+            ErrorKind::FailedToExecuteCommand(..) | ErrorKind::CommandBlocked(..) => {
+                Self::CannotExecute
+            }
             ErrorKind::BuiltinError(inner, ..) => inner.as_exit_code(),
             _ => Self::GeneralError,
         }
@@ -329,8 +342,16 @@ impl DefaultErrorFormatter {
 }
 
 impl ErrorFormatter for DefaultErrorFormatter {
-    fn format_error(&self, err: &Error, _shell: &Shell) -> String {
-        std::format!("error: {err:#}\n")
+    fn format_error(&self, err: &Error, shell: &Shell) -> String {
+        // N.B. Parse errors already identify where they were found.
+        let line = shell.current_line_number();
+        if line == 0 || matches!(err.kind(), ErrorKind::ParseError(..)) {
+            return std::format!("error: {err:#}\n");
+        }
+
+        let subroutines = shell.subroutines();
+        let source = subroutines.first().map_or("main", |s| s.source);
+        std::format!("{source}:{line}: error: {err:#}\n")
     }
 }
 
@@ -1,10 +1,11 @@
 use brush_parser::ast;
-use std::path::Path;
+use std::{cmp::Ordering, path::Path};
 
 use crate::{
     ExecutionParameters, Shell, ShellFd, arithmetic, env, error, escape, expansion, namedoptions,
     patterns,
     sys::{
+        self,
         fs::{MetadataExt, PathExt},
         users,
     },
@@ -212,16 +213,11 @@ async fn apply_binary_predicate(
                     .await?;
             }
 
-            let (matches, captures) = match regex.matches(s.as_str()) {
-                Ok(Some(captures)) => (true, captures),
-                Ok(None) => (false, vec![]),
-                // If we can't compile the regex, don't abort the whole operation but make sure to
-                // report it.
-                // TODO: Docs indicate we should yield 2 on an invalid regex (not 1).
-                Err(e) => {
-                    tracing::warn!("error using regex: {}", e);
-                    (false, vec![])
-                }
+            // N.B. An invalid regex fails the whole test; the caller turns that into
+            // an exit status of 2.
+            let (matches, captures) = match regex.matches(s.as_str())? {
+                Some(captures) => (true, captures),
+                None => (false, vec![]),
             };
 
             let captures_value = variables::ShellValueLiteral::Array(ArrayLiteral(
@@ -323,8 +319,7 @@ async fn apply_binary_predicate(
                     .await?;
             }
 
-            // TODO: According to docs, should be lexicographical order of the current locale.
-            Ok(left < right)
+            Ok(collate(shell, &left, &right).is_lt())
         }
         ast::BinaryPredicate::LeftSortsAfterRight => {
             let left = expansion::basic_expand_word(shell, params, left).await?;
@@ -336,8 +331,7 @@ async fn apply_binary_predicate(
                     .await?;
             }
 
-            // TODO: According to docs, should be lexicographical order of the current locale.
-            Ok(left > right)
+            Ok(collate(shell, &left, &right).is_gt())
         }
         ast::BinaryPredicate::ArithmeticEqualTo => {
             let left =
@@ -425,8 +419,7 @@ async fn apply_binary_predicate(
         }
         // N.B. The "=", "==", and "!=" operators don't compare 2 strings; they check
         // for whether the lefthand operand (a string) is matched by the righthand
-        // operand (treated as a shell pattern).
-        // TODO: implement case-insensitive matching if relevant via shopt options (nocasematch).
+        // operand (treated as a shell pattern), case-insensitively with `nocasematch`.
         ast::BinaryPredicate::StringExactlyMatchesPattern => {
             let s = expansion::basic_expand_word(shell, params, left).await?;
             let pattern = expansion::basic_expand_pattern(shell, params, right)
@@ -488,12 +481,10 @@ pub(crate) fn apply_binary_predicate_to_strs(
             left_file_is_older_or_does_not_exist_when_right_does(shell, left, right)
         }
         ast::BinaryPredicate::LeftSortsBeforeRight => {
-            // TODO: According to docs, should be lexicographical order of the current locale.
-            Ok(left < right)
+            Ok(collate(shell, left, right).is_lt())
         }
         ast::BinaryPredicate::LeftSortsAfterRight => {
-            // TODO: According to docs, should be lexicographical order of the current locale.
-            Ok(left > right)
+            Ok(collate(shell, left, right).is_gt())
         }
         ast::BinaryPredicate::ArithmeticEqualTo => Ok(apply_test_binary_arithmetic_predicate(
             left,
@@ -542,6 +533,22 @@ pub(crate) fn apply_binary_predicate_to_strs(
     }
 }
 
+/// Orders two strings for the `<` and `>` operators: in the collation order of
+/// the shell's locale (`LC_ALL`, then `LC_COLLATE`, then `LANG`), or by byte
+/// values in the C locale or when the locale isn't available.
+fn collate(shell: &Shell, left: &str, right: &str) -> Ordering {
+    let locale = ["LC_ALL", "LC_COLLATE", "LANG"]
+        .into_iter()
+        .find_map(|name| shell.env_str(name).filter(|value| !value.is_empty()));
+
+    match locale {
+        Some(locale) if locale != "C" && locale != "POSIX" => {
+            sys::locale::collate(&locale, left, right).unwrap_or_else(|| left.cmp(right))
+        }
+        _ => left.cmp(right),
+    }
+}
+
 fn apply_test_binary_arithmetic_predicate(
     left: &str,
     right: &str,
@@ -607,3 +614,88 @@ fn files_refer_to_same_device_and_inode_numbers(
 
     Ok(l_path.get_device_and_inode()? == r_path.get_device_and_inode()?)
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    async fn statuses(shell: &mut Shell, commands: &[&str]) -> Result<Vec<u8>> {
+        let params = shell.default_exec_params();
+        let mut statuses = vec![];
+        for command in commands {
+            let result = shell.run_string(*command, &params).await?;
+            statuses.push(u8::from(result.exit_code));
+        }
+        Ok(statuses)
+    }
+
+    #[tokio::test]
+    async fn test_nocasematch() -> Result<()> {
+        let mut shell = Shell::builder().build().await?;
+        let commands = [
+            "[[ abc == A?C ]]",
+            "[[ abc != A?C ]]",
+            "[[ abc =~ ^A.C$ ]]",
+            "case abc in A?C) true ;; *) false ;; esac",
+        ];
+
+        assert_eq!(statuses(&mut shell, &commands).await?, [1, 0, 1, 1]);
+
+        shell.options.case_insensitive_conditionals = true;
+        assert_eq!(statuses(&mut shell, &commands).await?, [0, 1, 0, 0]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_regex() -> Result<()> {
+        let mut shell = Shell::builder().build().await?;
+
+        assert_eq!(
+            statuses(&mut shell, &["re='a('; [[ a =~ $re ]]", "[[ a =~ a ]]"]).await?,
+            [2, 0]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_string_ordering() -> Result<()> {
+        let mut shell = Shell::builder().build().await?;
+        let commands = ["[[ a < B ]]", "[[ B > a ]]", "[[ a < b ]]"];
+
+        // The C locale orders by byte value, as does a locale that isn't installed.
+        for locale in ["C", "POSIX", "xx_XX.nonexistent"] {
+            statuses(&mut shell, &[&format!("LC_ALL={locale}")]).await?;
+            assert_eq!(statuses(&mut shell, &commands).await?, [1, 1, 0], "{locale}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_string_ordering_in_locale() -> Result<()> {
+        // Any installed locale that sorts case-insensitively first will do.
+        let Some(locale) = [
+            "en_US.UTF-8",
+            "en_US.utf8",
+            "en_GB.UTF-8",
+            "de_DE.UTF-8",
+            "fr_FR.UTF-8",
+        ]
+        .into_iter()
+        .find(|locale| sys::locale::collate(locale, "a", "B") == Some(Ordering::Less)) else {
+            eprintln!("skipping: no UTF-8 locale with a < B is installed");
+            return Ok(());
+        };
+
+        let mut shell = Shell::builder().build().await?;
+        statuses(&mut shell, &[&format!("LC_ALL={locale}")]).await?;
+        let commands = ["[[ a < B ]]", "[[ B > a ]]", "[[ a < b ]]"];
+        assert_eq!(statuses(&mut shell, &commands).await?, [0, 0, 0], "{locale}");
+
+        Ok(())
+    }
+}
@@ -5,8 +5,6 @@ use std::{
     sync::Arc,
 };
 
-use brush_parser::ast;
-
 /// An environment for defined, named functions.
 #[derive(Clone, Default)]
 pub struct FunctionEnv {
@@ -110,6 +108,12 @@ pub struct FunctionCall {
     pub function_name: String,
     /// The definition of the invoked function.
     pub function_definition: Arc<brush_parser::ast::FunctionDefinition>,
+    /// The line number from which the function was called.
+    pub call_line: u32,
+    /// The depth of the script call stack when the function was called.
+    pub script_depth: usize,
+    /// The caller's line offset, restored when the function returns.
+    pub(crate) caller_line_offset: u32,
 }
 
 /// Encapsulates a function call stack.
@@ -150,13 +154,9 @@ impl CallStack {
     ///
     /// # Arguments
     ///
-    /// * `name` - The name of the function being called.
-    /// * `function_def` - The definition of the function being called.
-    pub fn push(&mut self, name: impl Into<String>, function_def: &Arc<ast::FunctionDefinition>) {
-        self.frames.push_front(FunctionCall {
-            function_name: name.into(),
-            function_definition: function_def.clone(),
-        });
+    /// * `call` - The function call being entered.
+    pub fn push(&mut self, call: FunctionCall) {
+        self.frames.push_front(call);
     }
 
     /// Returns the current depth of the function call stack.
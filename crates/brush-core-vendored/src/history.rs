@@ -12,12 +12,12 @@ use crate::error;
 type ItemId = i64;
 
 /// Interface for querying and manipulating the shell's recorded history of commands.
-// TODO: support maximum item count
 #[derive(Clone, Default)]
 pub struct History {
     items: rpds::VectorSync<ItemId>,
     id_map: rpds::HashTrieMapSync<ItemId, Item>,
     next_id: ItemId,
+    max_items: Option<usize>,
 }
 
 impl History {
@@ -130,10 +130,61 @@ impl History {
 
         self.items.push_back_mut(item.id);
         self.id_map.insert_mut(item.id, item);
+        self.truncate_to_max();
 
         Ok(id)
     }
 
+    /// Returns the maximum number of items retained, if limited.
+    pub const fn max_items(&self) -> Option<usize> {
+        self.max_items
+    }
+
+    /// Limits the number of items retained; when the limit is exceeded, the oldest items are
+    /// discarded. Passing `None` removes the limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_items` - The maximum number of items to retain.
+    pub fn set_max_items(&mut self, max_items: Option<usize>) {
+        self.max_items = max_items;
+        self.truncate_to_max();
+    }
+
+    fn truncate_to_max(&mut self) {
+        let Some(max_items) = self.max_items else {
+            return;
+        };
+
+        let excess = self.items.len().saturating_sub(max_items);
+        if excess == 0 {
+            return;
+        }
+
+        for id in self.items.iter().take(excess) {
+            self.id_map.remove_mut(id);
+        }
+        self.items = self.items.iter().skip(excess).copied().collect();
+    }
+
+    /// Deletes all items whose command line exactly matches the given one.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_line` - The command line to remove.
+    pub fn delete_items_matching(&mut self, command_line: &str) {
+        let (removed, kept): (Vec<ItemId>, Vec<ItemId>) = self.items.iter().partition(|id| {
+            self.id_map
+                .get(id)
+                .is_some_and(|item| item.command_line == command_line)
+        });
+
+        for id in &removed {
+            self.id_map.remove_mut(id);
+        }
+        self.items = kept.into_iter().collect();
+    }
+
     /// Deletes a history item by its unique identifier. Returns an error if the item cannot be
     /// deleted.
     ///
@@ -488,3 +539,45 @@ impl<'a> Iterator for Search<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command_lines(history: &History) -> Vec<&str> {
+        history
+            .iter()
+            .map(|item| item.command_line.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_max_items() -> Result<(), error::Error> {
+        let mut history = History::default();
+        for command in ["a", "b", "c"] {
+            history.add(Item::new(command))?;
+        }
+
+        history.set_max_items(Some(2));
+        assert_eq!(command_lines(&history), ["b", "c"]);
+
+        history.add(Item::new("d"))?;
+        assert_eq!(command_lines(&history), ["c", "d"]);
+        assert!(history.get_by_id(1)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_items_matching() -> Result<(), error::Error> {
+        let mut history = History::default();
+        for command in ["ls", "pwd", "ls"] {
+            history.add(Item::new(command))?;
+        }
+
+        history.delete_items_matching("ls");
+        assert_eq!(command_lines(&history), ["pwd"]);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,13 @@
 //! Exports traits for shell interfaces implemented by callers.
 
+mod commandpolicy;
+mod commandtrace;
+mod joboutput;
 mod keybindings;
+mod processsetup;
 
+pub use commandpolicy::{CommandPolicy, PolicyDecision};
+pub use commandtrace::{CommandKind, CommandTrace, CommandTracer};
+pub use joboutput::JobOutput;
 pub use keybindings::{InputFunction, Key, KeyAction, KeyBindings, KeySequence, KeyStroke};
+pub use processsetup::ProcessSetup;
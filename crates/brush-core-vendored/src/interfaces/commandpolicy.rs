@@ -0,0 +1,25 @@
+use crate::Shell;
+
+/// Outcome of checking a command against a [`CommandPolicy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolicyDecision {
+    /// The command may run.
+    Allow,
+    /// The command must not run; carries a human-readable reason.
+    Deny(String),
+}
+
+/// Policy consulted before each simple command is dispatched.
+///
+/// The check runs after alias, parameter, and word expansion, so `args` holds
+/// exactly what would be executed (command name first). It applies uniformly to
+/// builtins, shell functions, and external programs.
+pub trait CommandPolicy: Send + Sync {
+    /// Decides whether the given command may run.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The expanded command name and arguments.
+    /// * `shell` - The shell that is about to run the command.
+    fn check(&self, args: &[String], shell: &Shell) -> PolicyDecision;
+}
@@ -0,0 +1,48 @@
+use std::{path::PathBuf, time::SystemTime};
+
+/// How a traced command was carried out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandKind {
+    /// A shell builtin ran in-process.
+    Builtin,
+    /// A shell function was invoked.
+    Function,
+    /// An external program was spawned.
+    External,
+    /// The command policy refused the command; nothing ran.
+    Blocked,
+}
+
+/// Record of a single simple command, reported once it completes.
+#[derive(Clone, Debug)]
+pub struct CommandTrace {
+    /// The expanded command name and arguments.
+    pub argv: Vec<String>,
+    /// Working directory the command ran in.
+    pub cwd: PathBuf,
+    /// How the command was carried out.
+    pub kind: CommandKind,
+    /// Process ID, for external commands.
+    pub pid: Option<i32>,
+    /// When the command was dispatched.
+    pub started_at: SystemTime,
+    /// When the command completed.
+    pub finished_at: SystemTime,
+    /// Exit status of the command.
+    pub exit_code: u8,
+}
+
+/// Receiver for per-command execution records.
+///
+/// Commands that fail to dispatch (e.g. not found) or that the command policy
+/// refuses are reported as well; external processes are reported when they are
+/// reaped, so commands that are stopped or abandoned before exiting produce no
+/// record.
+pub trait CommandTracer: Send + Sync {
+    /// Called once for each completed simple command.
+    ///
+    /// # Arguments
+    ///
+    /// * `trace` - The record describing the command.
+    fn on_command(&self, trace: CommandTrace);
+}
@@ -0,0 +1,19 @@
+use crate::openfiles::OpenFile;
+
+/// Destination for the output of background jobs.
+///
+/// By default a background job writes to the same standard output and error
+/// as the command that launched it. Implementations can instead give each job
+/// its own destination, so its output can be read independently of whatever
+/// runs in the foreground afterwards.
+pub trait JobOutput: Send + Sync {
+    /// Opens the files a newly started background job should write its standard
+    /// output and error to, in that order; returns `None` to leave them
+    /// inherited.
+    ///
+    /// # Arguments
+    ///
+    /// * `job_id` - The shell-internal ID the job will be assigned.
+    /// * `command_line` - The command line of the job.
+    fn open(&self, job_id: usize, command_line: &str) -> Option<(OpenFile, OpenFile)>;
+}
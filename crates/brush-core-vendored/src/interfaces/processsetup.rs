@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use crate::error;
+
+/// Hook for adjusting external processes before they are spawned, for
+/// vetting the files the shell opens for their output redirections, and for
+/// terminating them when their command is cancelled.
+///
+/// Implementations can set resource limits, register `pre_exec` callbacks, or
+/// otherwise reconfigure the fully composed command. Builtins and shell
+/// functions run in-process and are only affected through their
+/// redirections.
+pub trait ProcessSetup: Send + Sync {
+    /// Adjusts the command for an external process about to be spawned.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The composed command, with its arguments, environment,
+    ///   working directory, and redirections already applied.
+    fn before_spawn(&self, command: &mut std::process::Command) -> Result<(), error::Error>;
+
+    /// Checks that the shell may open `path` for writing, as it does in its
+    /// own process for output redirections of any command.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path about to be opened.
+    fn check_write(&self, path: &Path) -> Result<(), std::io::Error> {
+        let _ = path;
+        Ok(())
+    }
+
+    /// Returns the terminal that foreground external processes should take as
+    /// their controlling terminal, if any.
+    ///
+    /// Each such process then starts a session of its own instead of joining
+    /// a process group, and acquires the terminal unless another session
+    /// still holds it, so that the terminal's interrupt and window size
+    /// signals reach it. Background jobs are left alone.
+    fn controlling_terminal(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Starts terminating an external process whose command was cancelled.
+    ///
+    /// Returns `false` to have the shell kill the process right away. When
+    /// this returns `true`, the shell instead waits for the process to exit,
+    /// so the implementation must make sure it eventually does.
+    ///
+    /// # Arguments
+    ///
+    /// * `pid` - The process ID of the spawned process.
+    fn terminate(&self, pid: i32) -> bool {
+        let _ = pid;
+        false
+    }
+}
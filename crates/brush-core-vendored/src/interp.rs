@@ -4,7 +4,7 @@ use std::{
 	path::{Path, PathBuf},
 };
 
-use brush_parser::ast::{self, CommandPrefixOrSuffixItem};
+use brush_parser::ast::{self, CommandPrefixOrSuffixItem, SourceLocation};
 use itertools::Itertools;
 use sys::commands::ExitStatusExt;
 use tokio_util::sync::CancellationToken;
@@ -273,7 +273,7 @@ impl Execute for ast::CompoundList {
 				// TODO: Reenable launching in child process?
 				// let job = spawn_ao_list_in_child(ao_list, shell, params).await?;
 
-				let job = spawn_ao_list_in_task(ao_list, shell, params);
+				let job = spawn_ao_list_in_task(ao_list, shell, params)?;
 				let job_formatted = job.to_pid_style_string();
 
 				if shell.options.interactive && !shell.is_subshell() {
@@ -299,27 +299,44 @@ fn spawn_ao_list_in_task<'a>(
 	ao_list: &ast::AndOrList,
 	shell: &'a mut Shell,
 	params: &ExecutionParameters,
-) -> &'a jobs::Job {
+) -> Result<&'a jobs::Job, error::Error> {
 	// Clone the inputs.
 	let mut cloned_shell = shell.clone();
-	let cloned_params = params.clone();
+	let mut cloned_params = params.clone();
 	let cloned_ao_list = ao_list.clone();
+	let command_line = ao_list.to_string();
 
 	// Mark the child shell as not interactive; we don't want it messing with the
 	// terminal too much.
 	cloned_shell.options.interactive = false;
 
+	// Have the task report the processes it spawns, so the job can be signaled.
+	let processes = jobs::JobProcesses::default();
+	cloned_shell.job_processes = Some(processes.clone());
+
+	// Give the job its own output if the embedder wants one.
+	if let Some((stdout, stderr)) = shell
+		.job_output()
+		.and_then(|output| output.open(shell.jobs.next_id(), &command_line))
+	{
+		cloned_params.set_fd(OpenFiles::STDOUT_FD, stdout);
+		cloned_params.set_fd(OpenFiles::STDERR_FD, stderr);
+	}
+
 	let join_handle = tokio::spawn(async move {
 		cloned_ao_list
 			.execute(&mut cloned_shell, &cloned_params)
 			.await
 	});
 
-	shell.jobs.add_as_current(jobs::Job::new(
+	let job = jobs::Job::new(
 		[jobs::JobTask::Internal(join_handle)],
-		ao_list.to_string(),
+		command_line,
 		jobs::JobState::Running,
-	))
+	)
+	.with_processes(processes);
+
+	Ok(shell.jobs.add_as_current(job))
 }
 
 #[async_trait::async_trait]
@@ -563,6 +580,10 @@ impl ExecuteInPipeline for ast::Command {
 			return Ok(ExecutionSpawnResult::Completed(ExecutionResult::success()));
 		}
 
+		if let Some(line) = start_line(self) {
+			pipeline_context.shell.set_current_line(line);
+		}
+
 		match self {
 			Self::Simple(simple) => simple.execute_in_pipeline(pipeline_context, params).await,
 			Self::Compound(compound, redirects) => {
@@ -584,12 +605,23 @@ impl ExecuteInPipeline for ast::Command {
 			Self::Function(func) => Ok(func.execute(pipeline_context.shell, &params).await?.into()),
 			Self::ExtendedTest(e) => {
 				let result =
-					if extendedtests::eval_extended_test_expr(&e.expr, pipeline_context.shell, &params)
-						.await?
+					match extendedtests::eval_extended_test_expr(&e.expr, pipeline_context.shell, &params)
+						.await
 					{
-						0
-					} else {
-						1
+						Ok(true) => 0,
+						Ok(false) => 1,
+						// A regex that can't be compiled or applied fails the test with status 2
+						// rather than aborting the command.
+						Err(err)
+							if matches!(
+								err.kind(),
+								error::ErrorKind::InvalidRegexError(..) | error::ErrorKind::RegexError(..)
+							) =>
+						{
+							tracing::warn!("error using regex: {err}");
+							2
+						},
+						Err(err) => return Err(err),
 					};
 				Ok(ExecutionResult::new(result).into())
 			},
@@ -597,6 +629,34 @@ impl ExecuteInPipeline for ast::Command {
 	}
 }
 
+/// Returns the line on which the given command starts, if known.
+fn start_line(command: &ast::Command) -> Option<usize> {
+	// N.B. The parser doesn't report locations for assignments or process substitutions
+	// in a simple command's prefix or suffix, so we look for the first item that has one.
+	let item_location = |item: &CommandPrefixOrSuffixItem| match item {
+		CommandPrefixOrSuffixItem::AssignmentWord(assignment, _) => assignment.location(),
+		CommandPrefixOrSuffixItem::ProcessSubstitution(_, subshell) => subshell.location(),
+		_ => item.location(),
+	};
+
+	let location = match command {
+		ast::Command::Simple(simple) => {
+			let prefix = simple.prefix.iter().flat_map(|prefix| prefix.0.iter());
+			let suffix = simple.suffix.iter().flat_map(|suffix| suffix.0.iter());
+
+			prefix
+				.map(item_location)
+				.chain(std::iter::once(simple.word_or_name.as_ref().and_then(SourceLocation::location)))
+				.chain(suffix.map(item_location))
+				.flatten()
+				.next()
+		},
+		_ => command.location(),
+	};
+
+	location.map(|location| location.start.line)
+}
+
 enum WhileOrUntil {
 	While,
 	Until,
@@ -1071,6 +1131,9 @@ impl ExecuteInPipeline for ast::SimpleCommand {
 
 			match execute_command(context, params, cmd_name, assignments, args).await {
 				Ok(result) => Ok(result),
+				// A policy refusal aborts the whole program instead of just
+				// failing this one command.
+				Err(err) if matches!(err.kind(), error::ErrorKind::CommandBlocked(..)) => Err(err),
 				Err(err) => {
 					let _ = context.shell.display_error(&mut stderr, &err).await;
 					let exit_code = ExecutionExitCode::from(&err);
@@ -1390,6 +1453,17 @@ fn setup_pipeline_redirection(
 	Ok(())
 }
 
+/// Lets the embedder's process setup hook refuse a file the shell is about to
+/// open for writing.
+fn check_redirect_write(shell: &Shell, path: &Path) -> Result<(), error::Error> {
+	if let Some(setup) = shell.process_setup() {
+		setup.check_write(path).map_err(|err| {
+			error::ErrorKind::RedirectionFailure(path.to_string_lossy().to_string(), err.to_string())
+		})?;
+	}
+	Ok(())
+}
+
 pub(crate) async fn setup_redirect(
 	shell: &mut Shell,
 	params: &'_ mut ExecutionParameters,
@@ -1411,6 +1485,7 @@ pub(crate) async fn setup_redirect(
 				.write(true)
 				.truncate(!*append)
 				.append(*append);
+			check_redirect_write(shell, &expanded_file_path)?;
 
 			let stdout_file = shell
 				.open_file(&file_options, &expanded_file_path, params)
@@ -1491,6 +1566,13 @@ pub(crate) async fn setup_redirect(
 
 					let fd_num = specified_fd_num.unwrap_or(default_fd_if_unspecified);
 
+					if !matches!(
+						kind,
+						ast::IoFileRedirectKind::Read | ast::IoFileRedirectKind::DuplicateInput
+					) {
+						check_redirect_write(shell, &expanded_file_path)?;
+					}
+
 					let opened_file = shell
 						.open_file(&options, &expanded_file_path, params)
 						.map_err(|err| {
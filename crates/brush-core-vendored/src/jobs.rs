@@ -2,6 +2,8 @@
 
 use std::collections::VecDeque;
 use std::fmt::Display;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
 
 use futures::FutureExt;
 
@@ -20,6 +22,8 @@ pub(crate) type JobResult = (Job, Result<ExecutionResult, error::Error>);
 pub struct JobManager {
     /// The jobs that are currently managed by the shell.
     pub jobs: Vec<Job>,
+    /// Processes of disowned jobs, reaped as they exit.
+    disowned: Vec<processes::ChildProcess>,
 }
 
 /// Represents a task that is part of a job.
@@ -88,20 +92,27 @@ impl JobManager {
     ///
     /// * `job` - The job to add.
     pub fn add_as_current(&mut self, mut job: Job) -> &Job {
+        self.reap_disowned();
         for j in &mut self.jobs {
-            if matches!(j.annotation, JobAnnotation::Current) {
-                j.annotation = JobAnnotation::Previous;
-                break;
+            match j.annotation {
+                JobAnnotation::Current => j.annotation = JobAnnotation::Previous,
+                JobAnnotation::Previous => j.annotation = JobAnnotation::None,
+                JobAnnotation::None => (),
             }
         }
 
-        let id = self.jobs.len() + 1;
-        job.id = id;
+        job.id = self.next_id();
         job.annotation = JobAnnotation::Current;
         self.jobs.push(job);
         self.jobs.last().unwrap()
     }
 
+    /// Returns the ID the next job added to the manager will receive; IDs of
+    /// jobs that have been removed are only reused once no later job remains.
+    pub fn next_id(&self) -> usize {
+        self.jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1
+    }
+
     /// Returns the current job, if there is one.
     pub fn current_job(&self) -> Option<&Job> {
         self.jobs
@@ -152,8 +163,50 @@ impl JobManager {
         }
     }
 
+    /// Removes a job from the table without terminating it, as `disown` does;
+    /// returns the removed job. The remaining jobs take over the current and
+    /// previous annotations as needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the job to remove.
+    pub fn disown(&mut self, id: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|j| j.id == id)?;
+        let mut job = self.jobs.remove(index);
+        self.disowned.extend(job.detach());
+
+        if self.current_job().is_none() {
+            if let Some(prev) = self.prev_job_mut() {
+                prev.annotation = JobAnnotation::Current;
+            } else if let Some(latest) = self.latest_unannotated_job_mut() {
+                latest.annotation = JobAnnotation::Current;
+            }
+        }
+        if self.prev_job().is_none() {
+            if let Some(latest) = self.latest_unannotated_job_mut() {
+                latest.annotation = JobAnnotation::Previous;
+            }
+        }
+
+        Some(job)
+    }
+
+    fn latest_unannotated_job_mut(&mut self) -> Option<&mut Job> {
+        self.jobs
+            .iter_mut()
+            .rev()
+            .find(|j| matches!(j.annotation, JobAnnotation::None))
+    }
+
+    /// Reaps the processes of disowned jobs that have exited since the last
+    /// call, so they do not linger as zombies.
+    pub fn reap_disowned(&mut self) {
+        self.disowned.retain_mut(|process| !process.try_reap());
+    }
+
     /// Waits for all managed jobs to complete.
     pub async fn wait_all(&mut self) -> Result<Vec<Job>, error::Error> {
+        self.reap_disowned();
         for job in &mut self.jobs {
             job.wait().await?;
         }
@@ -163,6 +216,7 @@ impl JobManager {
 
     /// Polls all managed jobs for completion.
     pub fn poll(&mut self) -> Result<Vec<JobResult>, error::Error> {
+        self.reap_disowned();
         let mut results = vec![];
 
         let mut i = 0;
@@ -243,6 +297,40 @@ impl Display for JobAnnotation {
     }
 }
 
+/// An external process spawned on behalf of a job.
+#[derive(Clone, Copy)]
+struct JobProcess {
+    pid: sys::process::ProcessId,
+    pgid: Option<sys::process::ProcessId>,
+}
+
+/// Records the external processes spawned by a job that runs as an internal
+/// task, so the job can still be identified and signaled by pid. Only the most
+/// recently spawned process is kept, since that's the one still running.
+#[derive(Clone, Default)]
+pub(crate) struct JobProcesses(Arc<Mutex<Option<JobProcess>>>);
+
+impl JobProcesses {
+    /// Records a newly spawned process.
+    ///
+    /// # Arguments
+    ///
+    /// * `pid` - The process ID of the spawned process.
+    /// * `pgid` - The process group the process was placed in, if it was
+    ///   explicitly assigned one.
+    pub(crate) fn record(
+        &self,
+        pid: sys::process::ProcessId,
+        pgid: Option<sys::process::ProcessId>,
+    ) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(JobProcess { pid, pgid });
+    }
+
+    fn latest(&self) -> Option<JobProcess> {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 /// Encapsulates a set of processes managed by the shell as a single unit.
 pub struct Job {
     /// The tasks that make up the job.
@@ -251,6 +339,9 @@ pub struct Job {
     /// If available, the process group ID of the job's processes.
     pgid: Option<sys::process::ProcessId>,
 
+    /// Processes spawned by the job's internal tasks.
+    processes: JobProcesses,
+
     /// The annotation of the job (e.g., current, previous).
     annotation: JobAnnotation,
 
@@ -262,6 +353,16 @@ pub struct Job {
 
     /// The current operational state of the job.
     pub state: JobState,
+
+    /// Whether the job is exempt from the hangup signal sent to jobs when the
+    /// shell hangs up (`disown -h`).
+    pub no_hangup: bool,
+
+    /// The time at which the job was started.
+    pub started_at: SystemTime,
+
+    /// The exit status of the job, once it has completed.
+    pub exit_status: Option<u8>,
 }
 
 impl Display for Job {
@@ -293,12 +394,27 @@ impl Job {
             id: 0,
             tasks: tasks.into_iter().collect(),
             pgid: None,
+            processes: JobProcesses::default(),
             annotation: JobAnnotation::None,
             command_line,
             state,
+            no_hangup: false,
+            started_at: SystemTime::now(),
+            exit_status: None,
         }
     }
 
+    /// Associates the job with the recorder its internal tasks report spawned
+    /// processes to.
+    ///
+    /// # Arguments
+    ///
+    /// * `processes` - The recorder shared with the job's tasks.
+    pub(crate) fn with_processes(mut self, processes: JobProcesses) -> Self {
+        self.processes = processes;
+        self
+    }
+
     /// Returns a pid-style string for the job.
     pub fn to_pid_style_string(&self) -> String {
         let display_pid = self
@@ -354,6 +470,9 @@ impl Job {
         tracing::debug!(target: trace_categories::JOBS, "Job {} has completed.", self.id);
 
         self.state = JobState::Done;
+        if let Some(Ok(execution_result)) = &result {
+            self.exit_status = Some(u8::from(&execution_result.exit_code));
+        }
 
         Ok(result)
     }
@@ -376,6 +495,7 @@ impl Job {
         }
 
         self.state = JobState::Done;
+        self.exit_status = Some(u8::from(&result.exit_code));
 
         Ok(result)
     }
@@ -426,6 +546,22 @@ impl Job {
         }
     }
 
+    /// Releases the job's processes so that dropping them leaves them
+    /// running; returns them so they can still be reaped.
+    fn detach(&mut self) -> Vec<processes::ChildProcess> {
+        let mut detached = vec![];
+        for task in std::mem::take(&mut self.tasks) {
+            match task {
+                JobTask::External(mut process) => {
+                    process.detach();
+                    detached.push(process);
+                }
+                internal @ JobTask::Internal(_) => self.tasks.push_back(internal),
+            }
+        }
+        detached
+    }
+
     /// Tries to retrieve a "representative" pid for the job.
     pub fn representative_pid(&self) -> Option<sys::process::ProcessId> {
         for task in &self.tasks {
@@ -438,13 +574,65 @@ impl Job {
                 JobTask::Internal(_) => (),
             }
         }
-        None
+        self.processes.latest().map(|p| p.pid)
     }
 
     /// Tries to retrieve the process group ID (PGID) of the job.
     pub fn process_group_id(&self) -> Option<sys::process::ProcessId> {
         // TODO: Don't assume that the first PID is the PGID.
-        self.pgid.or_else(|| self.representative_pid())
+        self.pgid
+            .or_else(|| self.processes.latest().and_then(|p| p.pgid))
+            .or_else(|| self.representative_pid())
+    }
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use super::*;
+
+    fn proc_state(pid: i32) -> Option<char> {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        stat.rsplit_once(") ")?.1.chars().next()
+    }
+
+    /// Adds a `sleep 0.2` job to `manager` and disowns it; returns its pid.
+    fn disown_sleep(manager: &mut JobManager) -> i32 {
+        let mut command = std::process::Command::new("sleep");
+        command.arg("0.2");
+        let child = sys::process::spawn(command).unwrap();
+        #[expect(clippy::cast_possible_wrap)]
+        let pid = child.id().unwrap() as i32;
+        let process = processes::ChildProcess::new(Some(pid), child);
+        let job = Job::new([JobTask::External(process)], "sleep 0.2".into(), JobState::Stopped);
+        let id = manager.add_as_current(job).id;
+        drop(manager.disown(id));
+        pid
     }
 
+    #[tokio::test]
+    async fn disowned_processes_outlive_the_shell() {
+        let mut manager = JobManager::new();
+        let pid = disown_sleep(&mut manager);
+        drop(manager);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(proc_state(pid).is_some_and(|state| state != 'Z'));
+    }
+
+    #[tokio::test]
+    async fn disowned_processes_keep_running_and_are_reaped() {
+        let mut manager = JobManager::new();
+        let pid = disown_sleep(&mut manager);
+        assert!(proc_state(pid).is_some_and(|state| state != 'Z'));
+
+        for _ in 0..100 {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            manager.reap_disowned();
+            if manager.disowned.is_empty() {
+                break;
+            }
+        }
+        assert!(manager.disowned.is_empty());
+        assert_eq!(proc_state(pid), None);
+    }
 }
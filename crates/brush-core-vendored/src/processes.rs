@@ -2,7 +2,9 @@
 
 use tokio_util::sync::CancellationToken;
 
-use crate::{error, sys};
+use crate::{
+    commands::PendingTrace, error, results::ExecutionResult, shell::ProcessSetupHelper, sys,
+};
 
 /// Tracks a child process being awaited.
 pub struct ChildProcess {
@@ -12,12 +14,43 @@ pub struct ChildProcess {
     child: sys::process::Child,
     /// Tracks whether this process has already been reaped.
     reaped: bool,
+    /// Whether the process may outlive this handle.
+    detached: bool,
+    /// Trace record to report once the process is reaped.
+    trace: Option<Box<PendingTrace>>,
+    /// Embedder hook asked to terminate the process on cancellation.
+    setup: Option<ProcessSetupHelper>,
 }
 
 impl ChildProcess {
     /// Wraps a child process and its future.
     pub fn new(pid: Option<sys::process::ProcessId>, child: sys::process::Child) -> Self {
-        Self { pid, child, reaped: false }
+        Self {
+            pid,
+            child,
+            reaped: false,
+            detached: false,
+            trace: None,
+            setup: None,
+        }
+    }
+
+    /// Lets `setup` terminate the process when its command is cancelled.
+    pub(crate) fn with_setup(mut self, setup: ProcessSetupHelper) -> Self {
+        self.setup = Some(setup);
+        self
+    }
+
+    /// Attaches a trace record to report once the process is reaped.
+    pub(crate) fn with_trace(mut self, trace: PendingTrace) -> Self {
+        self.trace = Some(Box::new(trace));
+        self
+    }
+
+    fn report_trace(&mut self, exit_code: u8) {
+        if let Some(trace) = self.trace.take() {
+            trace.finish(self.pid, exit_code);
+        }
     }
 
     /// Returns the process's ID.
@@ -73,23 +106,49 @@ impl ChildProcess {
                 Some(status) => {
                     let status = status?;
                     self.reaped = true;
-                    Ok(ProcessWaitResult::Completed(output_from_status(status)))
+                    let output = output_from_status(status);
+                    self.report_trace(ExecutionResult::from(output.clone()).exit_code.into());
+                    Ok(ProcessWaitResult::Completed(output))
                 }
                 None => {
-                    if self.child.kill().await.is_ok() {
+                    let terminating = self
+                        .pid
+                        .zip(self.setup.as_ref())
+                        .is_some_and(|(pid, setup)| setup.terminate(pid));
+                    if terminating {
+                        if self.child.wait().await.is_ok() {
+                            self.reaped = true;
+                        }
+                    } else if self.child.kill().await.is_ok() {
                         self.reaped = true;
                     } else if let Ok(Some(_)) = self.child.try_wait() {
                         self.reaped = true;
                     }
+                    self.report_trace(130);
                     Ok(ProcessWaitResult::Cancelled)
                 }
             };
         }
     }
 
+    /// Lets the process outlive this handle: dropping it no longer kills the
+    /// process. It still has to be reaped with [`Self::try_reap`].
+    pub(crate) const fn detach(&mut self) {
+        self.detached = true;
+    }
+
+    /// Reaps the process if it has exited, without waiting; returns whether it
+    /// is gone.
+    pub(crate) fn try_reap(&mut self) -> bool {
+        if !self.reaped && !matches!(self.child.try_wait(), Ok(None)) {
+            self.reaped = true;
+        }
+        self.reaped
+    }
+
     /// Sends a kill signal and attempts a synchronous reap if still running.
     fn kill(&mut self) {
-        if self.reaped {
+        if self.reaped || self.detached {
             return;
         }
 
@@ -107,7 +166,9 @@ impl ChildProcess {
         match self.child.try_wait() {
             Ok(Some(status)) => {
                 self.reaped = true;
-                Some(Ok(output_from_status(status)))
+                let output = output_from_status(status);
+                self.report_trace(ExecutionResult::from(output.clone()).exit_code.into());
+                Some(Ok(output))
             }
             Ok(None) => None,
             Err(err) => Some(Err(err.into())),
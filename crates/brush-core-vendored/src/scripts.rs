@@ -27,6 +27,8 @@ pub struct CallFrame {
     pub call_type: CallType,
     /// The source of the script (e.g., file path).
     pub source: String,
+    /// The line number from which the script was called.
+    pub call_line: u32,
 }
 
 /// Encapsulates a script call stack.
@@ -69,10 +71,12 @@ impl CallStack {
     ///
     /// * `call_type` - The type of script call (sourced or executed).
     /// * `source` - The source of the script (e.g., file path).
-    pub fn push(&mut self, call_type: CallType, source: impl Into<String>) {
+    /// * `call_line` - The line number from which the script was called.
+    pub fn push(&mut self, call_type: CallType, source: impl Into<String>, call_line: u32) {
         self.frames.push_front(CallFrame {
             call_type,
             source: source.into(),
+            call_line,
         });
     }
 
@@ -100,6 +104,19 @@ impl CallStack {
     }
 }
 
+/// An active subroutine call, as reported by `FUNCNAME`, `BASH_SOURCE`,
+/// `BASH_LINENO` and `caller`: a shell function, a sourced script (named
+/// `source`), or the executing script (named `main`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subroutine<'a> {
+    /// The name of the subroutine.
+    pub name: &'a str,
+    /// The source the subroutine's commands were read from (e.g., file path).
+    pub source: &'a str,
+    /// The line number from which the subroutine was called.
+    pub call_line: u32,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,11 +145,11 @@ mod tests {
     fn test_call_stack_push_pop() {
         let mut stack = CallStack::new();
 
-        stack.push(CallType::Sourced, "script1.sh");
+        stack.push(CallType::Sourced, "script1.sh", 0);
         assert!(!stack.is_empty());
         assert_eq!(stack.depth(), 1);
 
-        stack.push(CallType::Executed, "script2.sh");
+        stack.push(CallType::Executed, "script2.sh", 0);
         assert_eq!(stack.depth(), 2);
 
         let frame = stack.pop().unwrap();
@@ -158,10 +175,10 @@ mod tests {
         let mut stack = CallStack::new();
         assert!(!stack.in_sourced_script());
 
-        stack.push(CallType::Executed, "script1.sh");
+        stack.push(CallType::Executed, "script1.sh", 0);
         assert!(!stack.in_sourced_script());
 
-        stack.push(CallType::Sourced, "script2.sh");
+        stack.push(CallType::Sourced, "script2.sh", 0);
         assert!(stack.in_sourced_script());
 
         stack.pop();
@@ -171,9 +188,9 @@ mod tests {
     #[test]
     fn test_call_stack_iter() {
         let mut stack = CallStack::new();
-        stack.push(CallType::Sourced, "script1.sh");
-        stack.push(CallType::Executed, "script2.sh");
-        stack.push(CallType::Sourced, "script3.sh");
+        stack.push(CallType::Sourced, "script1.sh", 0);
+        stack.push(CallType::Executed, "script2.sh", 0);
+        stack.push(CallType::Sourced, "script3.sh", 0);
 
         let frames: Vec<_> = stack.iter().collect();
         assert_eq!(frames.len(), 3);
@@ -191,8 +208,8 @@ mod tests {
     #[test]
     fn test_call_stack_display_with_frames() {
         let mut stack = CallStack::new();
-        stack.push(CallType::Sourced, "script1.sh");
-        stack.push(CallType::Executed, "script2.sh");
+        stack.push(CallType::Sourced, "script1.sh", 0);
+        stack.push(CallType::Executed, "script2.sh", 0);
 
         let output = stack.to_string();
         assert!(output.contains("Script call stack (most recent first):"));
@@ -205,10 +222,12 @@ mod tests {
         let frame1 = CallFrame {
             call_type: CallType::Sourced,
             source: "test.sh".to_string(),
+            call_line: 3,
         };
         let frame2 = frame1.clone();
 
         assert_eq!(frame1.source, frame2.source);
+        assert_eq!(frame1.call_line, frame2.call_line);
         assert!(matches!(frame1.call_type, CallType::Sourced));
         assert!(matches!(frame2.call_type, CallType::Sourced));
     }
@@ -216,8 +235,8 @@ mod tests {
     #[test]
     fn test_call_stack_clone() {
         let mut stack1 = CallStack::new();
-        stack1.push(CallType::Sourced, "script1.sh");
-        stack1.push(CallType::Executed, "script2.sh");
+        stack1.push(CallType::Sourced, "script1.sh", 0);
+        stack1.push(CallType::Executed, "script2.sh", 0);
 
         let stack2 = stack1.clone();
         assert_eq!(stack1.depth(), stack2.depth());
@@ -232,14 +251,14 @@ mod tests {
         let mut stack = CallStack::new();
 
         // Test with &str
-        stack.push(CallType::Sourced, "script1.sh");
+        stack.push(CallType::Sourced, "script1.sh", 0);
 
         // Test with String
-        stack.push(CallType::Executed, String::from("script2.sh"));
+        stack.push(CallType::Executed, String::from("script2.sh"), 0);
 
         // Test with owned string reference
         let owned = "script3.sh".to_string();
-        stack.push(CallType::Sourced, &owned);
+        stack.push(CallType::Sourced, &owned, 0);
 
         assert_eq!(stack.depth(), 3);
     }
@@ -29,6 +29,18 @@ pub type KeyBindingsHelper = Arc<Mutex<dyn interfaces::KeyBindings>>;
 /// Type for storing an error formatter.
 pub type ErrorFormatterHelper = Arc<Mutex<dyn error::ErrorFormatter>>;
 
+/// Type for storing a command policy.
+pub type CommandPolicyHelper = Arc<dyn interfaces::CommandPolicy>;
+
+/// Type for storing a command tracer.
+pub type CommandTracerHelper = Arc<dyn interfaces::CommandTracer>;
+
+/// Type for storing a process setup hook.
+pub type ProcessSetupHelper = Arc<dyn interfaces::ProcessSetup>;
+
+/// Type for storing a background job output destination.
+pub type JobOutputHelper = Arc<dyn interfaces::JobOutput>;
+
 /// Type alias for shell file descriptors.
 pub type ShellFd = i32;
 
@@ -91,6 +103,14 @@ pub struct Shell {
     /// Current line number being processed.
     current_line_number: u32,
 
+    /// Offset added to the line numbers of the program being executed; lets
+    /// strings run from within a command (e.g., by `eval` or a trap handler)
+    /// report lines relative to that command.
+    line_offset: u32,
+
+    /// Number of strings currently being run via [`Shell::run_string`].
+    string_depth: usize,
+
     /// Completion configuration.
     pub completion_config: completion::Config,
 
@@ -114,6 +134,21 @@ pub struct Shell {
 
     /// Error formatter for customizing error display.
     error_formatter: ErrorFormatterHelper,
+
+    /// Policy consulted before each command is dispatched.
+    command_policy: Option<CommandPolicyHelper>,
+
+    /// Receiver for per-command execution records.
+    command_tracer: Option<CommandTracerHelper>,
+
+    /// Hook applied to external processes before they are spawned.
+    process_setup: Option<ProcessSetupHelper>,
+
+    /// Destination for the output of background jobs.
+    job_output: Option<JobOutputHelper>,
+
+    /// When running as a background job, records the processes it spawns.
+    pub(crate) job_processes: Option<jobs::JobProcesses>,
 }
 
 impl Clone for Shell {
@@ -137,6 +172,8 @@ impl Clone for Shell {
             script_call_stack: self.script_call_stack.clone(),
             directory_stack: self.directory_stack.clone(),
             current_line_number: self.current_line_number,
+            line_offset: self.line_offset,
+            string_depth: self.string_depth,
             completion_config: self.completion_config.clone(),
             builtins: self.builtins.clone(),
             program_location_cache: self.program_location_cache.clone(),
@@ -145,6 +182,13 @@ impl Clone for Shell {
             key_bindings: self.key_bindings.clone(),
             history: self.history.clone(),
             error_formatter: self.error_formatter.clone(),
+            command_policy: self.command_policy.clone(),
+            command_tracer: self.command_tracer.clone(),
+            process_setup: self.process_setup.clone(),
+            // Jobs started in a subshell belong to its own job table, which the
+            // embedder can't see; they keep writing to the inherited output.
+            job_output: None,
+            job_processes: self.job_processes.clone(),
             depth: self.depth + 1,
         }
     }
@@ -330,6 +374,14 @@ pub struct CreateOptions {
     pub key_bindings: Option<KeyBindingsHelper>,
     /// Error formatter helper for the shell to use.
     pub error_formatter: Option<ErrorFormatterHelper>,
+    /// Command policy for the shell to enforce.
+    pub command_policy: Option<CommandPolicyHelper>,
+    /// Command tracer to report executed commands to.
+    pub command_tracer: Option<CommandTracerHelper>,
+    /// Hook to apply to external processes before they are spawned.
+    pub process_setup: Option<ProcessSetupHelper>,
+    /// Destination for the output of background jobs.
+    pub job_output: Option<JobOutputHelper>,
     /// Brush implementation version.
     pub shell_version: Option<String>,
 }
@@ -367,6 +419,8 @@ impl Shell {
             script_call_stack: scripts::CallStack::new(),
             directory_stack: vec![],
             current_line_number: 0,
+            line_offset: 0,
+            string_depth: 0,
             completion_config: completion::Config::default(),
             builtins: options.builtins,
             program_location_cache: pathcache::PathCache::default(),
@@ -377,6 +431,11 @@ impl Shell {
             error_formatter: options
                 .error_formatter
                 .unwrap_or_else(|| Arc::new(Mutex::new(error::DefaultErrorFormatter::new()))),
+            command_policy: options.command_policy,
+            command_tracer: options.command_tracer,
+            process_setup: options.process_setup,
+            job_output: options.job_output,
+            job_processes: None,
             depth: 0,
         };
 
@@ -394,20 +453,7 @@ impl Shell {
 
         // Set up history, if relevant.
         if shell.options.enable_command_history {
-            if let Some(history_path) = shell.history_file_path() {
-                let mut options = std::fs::File::options();
-                options.read(true);
-
-                if let Ok(history_file) =
-                    shell.open_file(&options, history_path, &shell.default_exec_params())
-                {
-                    shell.history = Some(history::History::import(history_file)?);
-                }
-            }
-
-            if shell.history.is_none() {
-                shell.history = Some(history::History::default());
-            }
+            shell.load_history()?;
         }
 
         // Load profiles/configuration.
@@ -427,6 +473,17 @@ impl Shell {
         self.current_line_number
     }
 
+    /// Updates the current line number to reflect that the command starting
+    /// on the given line of the executing program is being processed.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The 1-based line number within the executing program.
+    pub(crate) fn set_current_line(&mut self, line: usize) {
+        let line = u32::try_from(line).unwrap_or(u32::MAX);
+        self.current_line_number = self.line_offset.saturating_add(line);
+    }
+
     /// Returns the shell's official version string (if available).
     pub const fn version(&self) -> &Option<String> {
         &self.version
@@ -447,6 +504,42 @@ impl Shell {
         &self.script_call_stack
     }
 
+    /// Returns the active subroutine calls -- shell functions and sourced or
+    /// executed scripts -- in the order they will return, starting from the
+    /// most recent.
+    pub fn subroutines(&self) -> Vec<scripts::Subroutine<'_>> {
+        let mut functions = self.function_call_stack.iter().peekable();
+        let mut scripts = self.script_call_stack.iter();
+        let mut remaining_scripts = self.script_call_stack.depth();
+        let mut subroutines = vec![];
+
+        // A function call is more recent than every script frame that was already
+        // on the stack when it was made.
+        loop {
+            if let Some(call) = functions.next_if(|call| call.script_depth >= remaining_scripts) {
+                subroutines.push(scripts::Subroutine {
+                    name: call.function_name.as_str(),
+                    source: call.function_definition.source.as_str(),
+                    call_line: call.call_line,
+                });
+            } else if let Some(frame) = scripts.next() {
+                remaining_scripts -= 1;
+                subroutines.push(scripts::Subroutine {
+                    name: match frame.call_type {
+                        scripts::CallType::Sourced => "source",
+                        scripts::CallType::Executed => "main",
+                    },
+                    source: frame.source.as_str(),
+                    call_line: frame.call_line,
+                });
+            } else {
+                break;
+            }
+        }
+
+        subroutines
+    }
+
     /// Returns a mutable reference to the last exit status.
     pub const fn last_exit_status_mut(&mut self) -> &mut u8 {
         &mut self.last_exit_status
@@ -457,6 +550,63 @@ impl Shell {
         &self.key_bindings
     }
 
+    /// Returns the command policy enforced by the shell, if any.
+    pub const fn command_policy(&self) -> Option<&CommandPolicyHelper> {
+        self.command_policy.as_ref()
+    }
+
+    /// Sets (or clears) the command policy enforced by the shell.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy to enforce, or `None` to allow all commands.
+    pub fn set_command_policy(&mut self, policy: Option<CommandPolicyHelper>) {
+        self.command_policy = policy;
+    }
+
+    /// Returns the command tracer receiving execution records, if any.
+    pub const fn command_tracer(&self) -> Option<&CommandTracerHelper> {
+        self.command_tracer.as_ref()
+    }
+
+    /// Sets (or clears) the command tracer receiving execution records.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracer` - The tracer to report to, or `None` to disable tracing.
+    pub fn set_command_tracer(&mut self, tracer: Option<CommandTracerHelper>) {
+        self.command_tracer = tracer;
+    }
+
+    /// Returns the hook applied to external processes before they are spawned, if any.
+    pub const fn process_setup(&self) -> Option<&ProcessSetupHelper> {
+        self.process_setup.as_ref()
+    }
+
+    /// Sets (or clears) the hook applied to external processes before they are spawned.
+    ///
+    /// # Arguments
+    ///
+    /// * `setup` - The hook to apply, or `None` to spawn processes unmodified.
+    pub fn set_process_setup(&mut self, setup: Option<ProcessSetupHelper>) {
+        self.process_setup = setup;
+    }
+
+    /// Returns the destination for the output of background jobs, if any.
+    pub const fn job_output(&self) -> Option<&JobOutputHelper> {
+        self.job_output.as_ref()
+    }
+
+    /// Sets (or clears) the destination for the output of background jobs.
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - The destination to use, or `None` to have background jobs
+    ///   inherit the output of the command that launched them.
+    pub fn set_job_output(&mut self, output: Option<JobOutputHelper>) {
+        self.job_output = output;
+    }
+
     /// Returns the registered builtins for the shell.
     pub const fn builtins(&self) -> &HashMap<String, builtins::Registration> {
         &self.builtins
@@ -777,14 +927,21 @@ impl Shell {
             );
         }
 
-        self.script_call_stack
-            .push(call_type, source_info.source.clone());
+        self.script_call_stack.push(
+            call_type,
+            source_info.source.clone(),
+            self.current_line_number,
+        );
+        let caller_line_offset = std::mem::take(&mut self.line_offset);
 
         let result = self
             .run_parsed_result(parse_result, source_info, params)
             .await;
 
-        self.script_call_stack.pop();
+        if let Some(frame) = self.script_call_stack.pop() {
+            self.current_line_number = frame.call_line;
+        }
+        self.line_offset = caller_line_offset;
 
         // Restore.
         std::mem::swap(&mut self.shell_name, &mut other_shell_name);
@@ -853,17 +1010,45 @@ impl Shell {
         command: S,
         params: &ExecutionParameters,
     ) -> Result<ExecutionResult, error::Error> {
-        // TODO: Actually track line numbers; this is something of a hack, assuming each time
-        // this function is invoked we are on the next line of the input. For one thing,
-        // each string we run could be multiple lines.
-        self.current_line_number += 1;
-
         let parse_result = self.parse_string(command.into());
+        self.run_parsed_string(parse_result, params).await
+    }
+
+    /// Runs a program parsed from a string. Its line numbers count from 1, unless
+    /// it's run from within another command (e.g., by `eval`, a trap handler, or a
+    /// command substitution), in which case they count from that command's line.
+    pub(crate) async fn run_parsed_string(
+        &mut self,
+        parse_result: Result<brush_parser::ast::Program, brush_parser::ParseError>,
+        params: &ExecutionParameters,
+    ) -> Result<ExecutionResult, error::Error> {
+        let nested = self.string_depth > 0
+            || self.in_function()
+            || !self.script_call_stack.is_empty();
+        let caller_line = self.current_line_number;
+        let caller_line_offset = self.line_offset;
+        self.line_offset = if nested {
+            caller_line.saturating_sub(1)
+        } else {
+            0
+        };
+
         let source_info = brush_parser::SourceInfo {
             source: String::from("main"),
         };
-        self.run_parsed_result(parse_result, &source_info, params)
-            .await
+
+        self.string_depth += 1;
+        let result = self
+            .run_parsed_result(parse_result, &source_info, params)
+            .await;
+        self.string_depth -= 1;
+
+        self.line_offset = caller_line_offset;
+        if nested {
+            self.current_line_number = caller_line;
+        }
+
+        result
     }
 
     /// Parses the given reader as a shell program, returning the resulting Abstract Syntax Tree
@@ -1120,9 +1305,19 @@ impl Shell {
             tracing::debug!(target: trace_categories::FUNCTIONS, "Entering func [depth={depth}]: {prefix}{name}");
         }
 
-        self.function_call_stack.push(name, function_def);
+        self.function_call_stack.push(functions::FunctionCall {
+            function_name: name.to_owned(),
+            function_definition: function_def.clone(),
+            call_line: self.current_line_number,
+            script_depth: self.script_call_stack.depth(),
+            caller_line_offset: self.line_offset,
+        });
         self.env.push_scope(env::EnvironmentScope::Local);
 
+        // The function body's line numbers are relative to the source it was
+        // defined in, not to the caller.
+        self.line_offset = 0;
+
         Ok(())
     }
 
@@ -1132,6 +1327,9 @@ impl Shell {
         self.env.pop_scope(env::EnvironmentScope::Local)?;
 
         if let Some(exited_call) = self.function_call_stack.pop() {
+            self.current_line_number = exited_call.call_line;
+            self.line_offset = exited_call.caller_line_offset;
+
             if tracing::enabled!(target: trace_categories::FUNCTIONS, tracing::Level::DEBUG) {
                 let depth = self.function_call_stack.depth();
                 let prefix = repeated_char_str(' ', depth);
@@ -1153,18 +1351,49 @@ impl Shell {
         self.env_str("HISTTIMEFORMAT").map(|s| s.into_owned())
     }
 
-    /// Saves history back to any backing storage.
+    /// (Re)loads history from the history file, if one is set; otherwise starts
+    /// with empty history. The size limit is taken from `HISTSIZE`.
+    pub fn load_history(&mut self) -> Result<(), error::Error> {
+        let mut history = None;
+        if let Some(history_path) = self.history_file_path() {
+            let mut options = std::fs::File::options();
+            options.read(true);
+
+            if let Ok(history_file) =
+                self.open_file(&options, history_path, &self.default_exec_params())
+            {
+                history = Some(history::History::import(history_file)?);
+            }
+        }
+
+        let mut history = history.unwrap_or_default();
+        history.set_max_items(self.history_size());
+        self.history = Some(history);
+
+        Ok(())
+    }
+
+    /// Returns the maximum number of history items to retain, per `HISTSIZE`.
+    fn history_size(&self) -> Option<usize> {
+        self.env_str("HISTSIZE")
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .and_then(|size| usize::try_from(size).ok())
+    }
+
+    /// Saves history back to any backing storage. With `histappend` set, only
+    /// new items are appended to the history file; otherwise the file is
+    /// rewritten with the current history.
     pub fn save_history(&mut self) -> Result<(), error::Error> {
         if let Some(history_file_path) = self.history_file_path() {
             if let Some(history) = &mut self.history {
                 // See if there's *any* time format configured. That triggers writing out timestamps.
                 let write_timestamps = self.env.is_set("HISTTIMEFORMAT");
+                let append = self.options.append_to_history_file;
 
-                // TODO: Observe options.append_to_history_file
                 history.flush(
                     history_file_path,
-                    true, /*append?*/
-                    true, /*unsaved items only?*/
+                    append, /*append?*/
+                    append, /*unsaved items only?*/
                     write_timestamps,
                 )?;
             }
@@ -1173,9 +1402,22 @@ impl Shell {
         Ok(())
     }
 
-    /// Adds a command to history.
+    /// Adds a command to history, observing `HISTCONTROL` and `HISTSIZE`.
     pub fn add_to_history(&mut self, command: &str) -> Result<(), error::Error> {
+        let control = self
+            .env_str("HISTCONTROL")
+            .map(std::borrow::Cow::into_owned)
+            .unwrap_or_default();
+        let control: Vec<&str> = control.split(':').collect();
+        let history_size = self.history_size();
+
         if let Some(history) = &mut self.history {
+            if (control.contains(&"ignorespace") || control.contains(&"ignoreboth"))
+                && command.starts_with(' ')
+            {
+                return Ok(());
+            }
+
             // Trim.
             let command = command.trim();
 
@@ -1184,6 +1426,24 @@ impl Shell {
                 return Ok(());
             }
 
+            if (control.contains(&"ignoredups") || control.contains(&"ignoreboth"))
+                && history
+                    .count()
+                    .checked_sub(1)
+                    .and_then(|last| history.get(last))
+                    .is_some_and(|item| item.command_line == command)
+            {
+                return Ok(());
+            }
+
+            if control.contains(&"erasedups") {
+                history.delete_items_matching(command);
+            }
+
+            if history.max_items() != history_size {
+                history.set_max_items(history_size);
+            }
+
             // Add it to history.
             history.add(history::Item {
                 id: 0,
@@ -1486,10 +1746,24 @@ impl Shell {
         self.open_files = openfiles::OpenFiles::from(open_fds);
     }
 
-    pub(crate) const fn persistent_open_files(&self) -> &openfiles::OpenFiles {
+    /// Returns the shell's currently configured open files.
+    pub const fn persistent_open_files(&self) -> &openfiles::OpenFiles {
         &self.open_files
     }
 
+    /// Replaces the shell's currently configured open files, returning the
+    /// previous set so it can be restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `open_files` - The new set of open files to use.
+    pub const fn set_persistent_open_files(
+        &mut self,
+        open_files: openfiles::OpenFiles,
+    ) -> openfiles::OpenFiles {
+        std::mem::replace(&mut self.open_files, open_files)
+    }
+
     /// Returns a value that can be used to write to the shell's currently configured
     /// standard output stream using `write!` at al.
     pub fn stdout(&self) -> impl std::io::Write {
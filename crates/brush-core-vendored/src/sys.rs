@@ -30,6 +30,7 @@ pub mod fs;
 pub use platform::commands;
 pub use platform::fd;
 pub use platform::input;
+pub(crate) use platform::locale;
 pub(crate) use platform::network;
 pub use platform::process;
 pub use platform::resource;
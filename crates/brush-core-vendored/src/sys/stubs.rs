@@ -10,6 +10,7 @@ pub mod commands;
 pub mod fd;
 pub mod fs;
 pub mod input;
+pub(crate) mod locale;
 pub(crate) mod network;
 pub(crate) mod pipes;
 pub mod process;
@@ -4,7 +4,7 @@ pub(crate) type ProcessId = i32;
 pub(crate) use tokio::process::Child;
 
 pub(crate) fn spawn(command: std::process::Command) -> std::io::Result<Child> {
-    let mut command = tokio::process::Command::from(command);
-    command.kill_on_drop(true);
-    command.spawn()
+    // Not `kill_on_drop`: `ChildProcess` kills the children it still owns when
+    // dropped, and detached ones must survive.
+    tokio::process::Command::from(command).spawn()
 }
@@ -2,6 +2,7 @@ pub mod commands;
 pub mod fd;
 pub mod fs;
 pub mod input;
+pub(crate) mod locale;
 pub(crate) mod network;
 use crate::error;
 pub use crate::sys::tokio_process as process;
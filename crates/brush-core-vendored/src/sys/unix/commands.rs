@@ -1,6 +1,6 @@
 //! Command execution utilities.
 
-use std::io;
+use std::{ffi::CString, io, os::unix::ffi::OsStrExt, path::Path};
 pub use std::os::unix::process::{CommandExt, ExitStatusExt};
 
 use command_fds::{CommandFdExt, FdMapping};
@@ -43,6 +43,15 @@ impl CommandFdInjectionExt for std::process::Command {
 pub trait CommandFgControlExt {
 	/// Arranges for the command to take the foreground when it is executed.
 	fn take_foreground(&mut self);
+
+	/// Arranges for the command to start a new session when it is executed,
+	/// taking `terminal` as its controlling terminal if no other session holds
+	/// it.
+	///
+	/// # Arguments
+	///
+	/// * `terminal` - Path of the terminal device.
+	fn lead_session(&mut self, terminal: &Path);
 }
 
 impl CommandFgControlExt for std::process::Command {
@@ -55,6 +64,16 @@ impl CommandFgControlExt for std::process::Command {
 			self.pre_exec(setup_process_before_exec);
 		}
 	}
+
+	fn lead_session(&mut self, terminal: &Path) {
+		let terminal = CString::new(terminal.as_os_str().as_bytes()).ok();
+		// SAFETY:
+		// The callback only makes async-signal-safe calls; the path was
+		// allocated before forking.
+		unsafe {
+			self.pre_exec(move || lead_session_before_exec(terminal.as_deref()));
+		}
+	}
 }
 
 fn setup_process_before_exec() -> Result<(), io::Error> {
@@ -63,3 +82,23 @@ fn setup_process_before_exec() -> Result<(), io::Error> {
 	sys::terminal::move_self_to_foreground().map_err(io::Error::other)?;
 	Ok(())
 }
+
+fn lead_session_before_exec(terminal: Option<&std::ffi::CStr>) -> Result<(), io::Error> {
+	use nix::{fcntl, libc, sys::stat::Mode, unistd};
+
+	unistd::setsid()?;
+
+	// Failing to acquire the terminal, e.g. while an earlier command of a
+	// pipeline holds it, leaves the process without one.
+	if let Some(terminal) = terminal {
+		if let Ok(fd) = fcntl::open(
+			terminal,
+			fcntl::OFlag::O_RDWR | fcntl::OFlag::O_NOCTTY | fcntl::OFlag::O_CLOEXEC,
+			Mode::empty(),
+		) {
+			// SAFETY: TIOCSCTTY takes an integer argument and `fd` is open.
+			let _ = unsafe { libc::ioctl(std::os::fd::AsRawFd::as_raw_fd(&fd), libc::TIOCSCTTY as _, 0) };
+		}
+	}
+	Ok(())
+}
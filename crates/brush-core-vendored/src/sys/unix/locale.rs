@@ -0,0 +1,41 @@
+//! Locale-dependent string collation.
+
+use std::{cmp::Ordering, ffi::CString};
+
+use nix::libc;
+
+/// Compares two strings in the collation order of the named locale (e.g.
+/// `en_US.UTF-8`). Returns `None` if the locale isn't available or either
+/// string can't be passed to the C library.
+///
+/// # Arguments
+///
+/// * `locale` - The name of the locale whose collation order to use.
+/// * `left` - The first string to compare.
+/// * `right` - The second string to compare.
+pub(crate) fn collate(locale: &str, left: &str, right: &str) -> Option<Ordering> {
+    let locale = CString::new(locale).ok()?;
+    let left = CString::new(left).ok()?;
+    let right = CString::new(right).ok()?;
+
+    // SAFETY: All strings are valid and NUL-terminated. The locale object is only
+    // installed for the current thread (and restored) around the comparison, so
+    // the process-wide locale is never changed.
+    unsafe {
+        let collation = libc::newlocale(
+            libc::LC_COLLATE_MASK,
+            locale.as_ptr(),
+            std::ptr::null_mut(),
+        );
+        if collation.is_null() {
+            return None;
+        }
+
+        let previous = libc::uselocale(collation);
+        let result = libc::strcoll(left.as_ptr(), right.as_ptr());
+        libc::uselocale(previous);
+        libc::freelocale(collation);
+
+        Some(result.cmp(&0))
+    }
+}
@@ -2,6 +2,7 @@ pub use crate::sys::stubs::commands;
 pub use crate::sys::stubs::fd;
 pub use crate::sys::stubs::fs;
 pub use crate::sys::stubs::input;
+pub(crate) use crate::sys::stubs::locale;
 pub(crate) use crate::sys::stubs::network;
 pub(crate) use crate::sys::stubs::pipes;
 pub use crate::sys::stubs::process;
@@ -2,6 +2,7 @@ pub mod commands;
 pub mod fd;
 pub mod fs;
 pub use crate::sys::stubs::input;
+pub(crate) use crate::sys::stubs::locale;
 pub(crate) mod network;
 pub use crate::sys::stubs::resource;
 
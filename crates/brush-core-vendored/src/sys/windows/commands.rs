@@ -89,10 +89,23 @@ impl CommandFdInjectionExt for std::process::Command {
 pub trait CommandFgControlExt {
 	/// Arranges for the command to take the foreground when it is executed.
 	fn take_foreground(&mut self);
+
+	/// Arranges for the command to start a new session when it is executed,
+	/// taking `terminal` as its controlling terminal if no other session holds
+	/// it.
+	///
+	/// # Arguments
+	///
+	/// * `terminal` - Path of the terminal device.
+	fn lead_session(&mut self, terminal: &std::path::Path);
 }
 
 impl CommandFgControlExt for std::process::Command {
 	fn take_foreground(&mut self) {
 		self.creation_flags(CREATE_NEW_PROCESS_GROUP);
 	}
+
+	fn lead_session(&mut self, _terminal: &std::path::Path) {
+		// NOTE: This is a no-op.
+	}
 }
@@ -1,6 +1,6 @@
 use rand::Rng;
 
-use crate::{Shell, ShellValue, ShellVariable, error, sys, variables};
+use crate::{Shell, ShellValue, ShellVariable, error, scripts, sys, variables};
 
 const BASH_MAJOR: u32 = 5;
 const BASH_MINOR: u32 = 2;
@@ -115,7 +115,15 @@ pub(crate) fn initialize_vars(
 
     // TODO(vars): implement BASH_COMMAND
     // TODO(vars): implement BASH_EXECUTIION_STRING
-    // TODO(vars): implement BASH_LINENO
+
+    // BASH_LINENO
+    shell.env.set_global(
+        "BASH_LINENO",
+        ShellVariable::new(ShellValue::Dynamic {
+            getter: |shell| get_bash_lineno_value(shell),
+            setter: |_| (),
+        }),
+    )?;
 
     // BASH_SOURCE
     shell.env.set_global(
@@ -488,30 +496,80 @@ fn get_srandom_value(_shell: &Shell) -> ShellValue {
 }
 
 fn get_funcname_value(shell: &Shell) -> variables::ShellValue {
-    if shell.function_call_stack().is_empty() {
+    // N.B. FUNCNAME only exists while a function or sourced script is executing.
+    let in_sourced_script = shell
+        .script_call_stack()
+        .iter()
+        .any(|frame| matches!(frame.call_type, scripts::CallType::Sourced));
+
+    if shell.function_call_stack().is_empty() && !in_sourced_script {
         ShellValue::Unset(variables::ShellValueUnsetType::IndexedArray)
     } else {
         shell
-            .function_call_stack()
+            .subroutines()
             .iter()
-            .map(|s| s.function_name.as_str())
+            .map(|s| s.name)
             .collect::<Vec<_>>()
             .into()
     }
 }
 
 fn get_bash_source_value(shell: &Shell) -> variables::ShellValue {
-    if shell.function_call_stack().is_empty() {
-        let top_frame = shell.script_call_stack().iter().next();
-        top_frame
-            .map_or_else(Vec::new, |frame| vec![frame.source.as_ref()])
-            .into()
-    } else {
+    shell
+        .subroutines()
+        .iter()
+        .map(|s| s.source)
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn get_bash_lineno_value(shell: &Shell) -> variables::ShellValue {
+    ShellValue::indexed_array_from_strings(
         shell
-            .function_call_stack()
+            .subroutines()
             .iter()
-            .map(|s| s.function_definition.source.as_ref())
-            .collect::<Vec<_>>()
-            .into()
+            .map(|s| s.call_line.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    const LOCATION: &str = r#""${FUNCNAME[*]}|${BASH_SOURCE[*]}|${BASH_LINENO[*]}|$LINENO""#;
+
+    #[tokio::test]
+    async fn test_call_location_vars() -> Result<()> {
+        let mut shell = Shell::builder().build().await?;
+        let params = shell.default_exec_params();
+
+        let script = std::env::temp_dir().join(format!("brush-lineno-{}.sh", std::process::id()));
+        std::fs::write(
+            &script,
+            format!("g() {{\n  in_g={LOCATION}\n}}\n\ntop={LOCATION}\n"),
+        )?;
+        let result = shell
+            .source_script(&script, std::iter::empty::<&str>(), &params)
+            .await;
+        std::fs::remove_file(&script)?;
+        result?;
+
+        shell
+            .run_string(
+                format!("f() {{\n  g\n}}\n\nf\nafter={LOCATION}\nsub=$(printf %s $LINENO)"),
+                &params,
+            )
+            .await?;
+
+        let path = script.to_string_lossy();
+        let var = |name| shell.env_str(name).map(|value| value.into_owned());
+        assert_eq!(var("top"), Some(format!("source|{path}|0|5")));
+        assert_eq!(var("in_g"), Some(format!("g f|{path} main|2 5|2")));
+        assert_eq!(var("after").as_deref(), Some("|||6"));
+        assert_eq!(var("sub").as_deref(), Some("7"));
+
+        Ok(())
     }
 }
@@ -0,0 +1,286 @@
+//! Output capture limits shared by the shell and PTY runners.
+//!
+//! # Overview
+//! Enforces a byte budget on streamed command output before it reaches the
+//! JS callback. The first part of the budget is forwarded live; the rest is
+//! held back as a rolling tail that is flushed, behind an elision marker,
+//! once the command finishes. The full output can optionally be spilled to a
+//! temp file so nothing is lost.
+
+use std::{
+	collections::VecDeque,
+	fs,
+	io::Write,
+	path::PathBuf,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use napi_derive::napi;
+
+/// Byte budget and head/tail retention policy for command output.
+#[napi(object)]
+#[derive(Clone, Copy)]
+pub struct OutputLimit {
+	/// Total bytes of output forwarded to the callback.
+	pub max_bytes:  u32,
+	/// Portion of `max_bytes` kept from the end of the output; the remainder is
+	/// kept from the start. Defaults to half of `max_bytes`.
+	pub tail_bytes: Option<u32>,
+	/// Write the full output to a temp file when anything is elided.
+	pub spill:      Option<bool>,
+}
+
+/// Totals reported once a capture is finished.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CaptureSummary {
+	/// Bytes of decoded output produced by the command.
+	pub total_bytes:  u64,
+	/// Bytes dropped from the middle of the output.
+	pub elided_bytes: u64,
+	/// Temp file holding the full output, when spilling was requested and
+	/// output was elided.
+	pub output_path:  Option<String>,
+}
+
+/// Streaming head/tail limiter over tagged text chunks.
+///
+/// `T` identifies the stream a chunk came from so the retained tail can be
+/// replayed with its original tags.
+pub struct OutputCapture<T> {
+	head_budget: usize,
+	tail_budget: usize,
+	head_used:   usize,
+	head_copy:   Option<String>,
+	tail:        VecDeque<(T, String)>,
+	tail_len:    usize,
+	total:       u64,
+	elided:      u64,
+	spill:       Option<Spill>,
+	limited:     bool,
+	finished:    bool,
+}
+
+enum Spill {
+	Pending,
+	Open { path: PathBuf, file: fs::File },
+	Failed,
+}
+
+impl<T: Copy> OutputCapture<T> {
+	pub fn new(limit: Option<OutputLimit>) -> Self {
+		let (head_budget, tail_budget, spill) = limit.map_or((0, 0, false), |limit| {
+			let max = limit.max_bytes as usize;
+			let tail = limit
+				.tail_bytes
+				.map_or(max / 2, |tail| (tail as usize).min(max));
+			(max - tail, tail, limit.spill.unwrap_or(false))
+		});
+		Self {
+			head_budget,
+			tail_budget,
+			head_used: 0,
+			head_copy: spill.then(String::new),
+			tail: VecDeque::new(),
+			tail_len: 0,
+			total: 0,
+			elided: 0,
+			spill: spill.then_some(Spill::Pending),
+			limited: limit.is_some(),
+			finished: false,
+		}
+	}
+
+	/// Feed a decoded chunk, forwarding whatever fits in the head budget.
+	pub fn push(&mut self, tag: T, text: &str, mut emit: impl FnMut(T, &str)) {
+		if text.is_empty() || self.finished {
+			return;
+		}
+		self.total += text.len() as u64;
+		if !self.limited {
+			emit(tag, text);
+			return;
+		}
+
+		let mut rest = text;
+		if self.head_used < self.head_budget {
+			let split = rest.floor_char_boundary(self.head_budget - self.head_used);
+			let (head, tail) = rest.split_at(split);
+			if !head.is_empty() {
+				emit(tag, head);
+				self.head_used += head.len();
+				if let Some(copy) = self.head_copy.as_mut() {
+					copy.push_str(head);
+				}
+			}
+			if !tail.is_empty() {
+				// Close the head even if a multi-byte char left budget unused, so
+				// later chunks cannot jump ahead of the held-back tail.
+				self.head_used = self.head_budget;
+			}
+			rest = tail;
+		}
+		if rest.is_empty() {
+			return;
+		}
+
+		if matches!(self.spill, Some(Spill::Open { .. })) {
+			self.write_spill(rest);
+		}
+		self.tail.push_back((tag, rest.to_string()));
+		self.tail_len += rest.len();
+		self.trim_tail();
+	}
+
+	/// Flush the retained tail and return the totals.
+	///
+	/// Subsequent calls return the same totals without emitting anything.
+	pub fn finish(&mut self, mut emit: impl FnMut(T, &str)) -> CaptureSummary {
+		if !self.finished {
+			self.finished = true;
+			if self.elided > 0
+				&& let Some((tag, _)) = self.tail.front()
+			{
+				emit(*tag, &elision_marker(self.elided));
+			}
+			for (tag, text) in self.tail.drain(..) {
+				emit(tag, &text);
+			}
+			self.tail_len = 0;
+		}
+		CaptureSummary {
+			total_bytes:  self.total,
+			elided_bytes: self.elided,
+			output_path:  match &self.spill {
+				Some(Spill::Open { path, .. }) => Some(path.to_string_lossy().into_owned()),
+				_ => None,
+			},
+		}
+	}
+
+	fn trim_tail(&mut self) {
+		while self.tail_len > self.tail_budget {
+			if matches!(self.spill, Some(Spill::Pending)) {
+				self.open_spill();
+			}
+			let excess = self.tail_len - self.tail_budget;
+			let Some((_, front)) = self.tail.front_mut() else {
+				break;
+			};
+			if front.len() <= excess {
+				let dropped = front.len();
+				self.tail.pop_front();
+				self.tail_len -= dropped;
+				self.elided += dropped as u64;
+			} else {
+				let cut = front.ceil_char_boundary(excess);
+				front.drain(..cut);
+				self.tail_len -= cut;
+				self.elided += cut as u64;
+			}
+		}
+	}
+
+	/// Create the spill file and replay everything seen so far into it.
+	fn open_spill(&mut self) {
+		let unique = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |elapsed| elapsed.as_nanos());
+		let path =
+			std::env::temp_dir().join(format!("pi-output-{}-{unique}.log", std::process::id()));
+		let Ok(file) = fs::OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(&path)
+		else {
+			self.spill = Some(Spill::Failed);
+			return;
+		};
+		self.spill = Some(Spill::Open { path, file });
+		if let Some(head) = self.head_copy.take() {
+			self.write_spill(&head);
+		}
+		let pending: Vec<String> = self.tail.iter().map(|(_, text)| text.clone()).collect();
+		for text in pending {
+			self.write_spill(&text);
+		}
+	}
+
+	fn write_spill(&mut self, text: &str) {
+		if let Some(Spill::Open { file, .. }) = self.spill.as_mut()
+			&& file.write_all(text.as_bytes()).is_err()
+		{
+			self.spill = Some(Spill::Failed);
+		}
+	}
+}
+
+fn elision_marker(elided: u64) -> String {
+	format!("\n[... {elided} bytes elided ...]\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run(limit: Option<OutputLimit>, chunks: &[&str]) -> (String, CaptureSummary) {
+		let mut capture = OutputCapture::new(limit);
+		let mut out = String::new();
+		for chunk in chunks {
+			capture.push((), chunk, |(), text| out.push_str(text));
+		}
+		let summary = capture.finish(|(), text| out.push_str(text));
+		(out, summary)
+	}
+
+	const fn limit(max_bytes: u32, tail_bytes: u32) -> OutputLimit {
+		OutputLimit { max_bytes, tail_bytes: Some(tail_bytes), spill: None }
+	}
+
+	#[test]
+	fn passes_output_through_without_limit() {
+		let (out, summary) = run(None, &["hello ", "world"]);
+		assert_eq!(out, "hello world");
+		assert_eq!(summary, CaptureSummary { total_bytes: 11, elided_bytes: 0, output_path: None });
+	}
+
+	#[test]
+	fn keeps_everything_that_fits_the_budget() {
+		let (out, summary) = run(Some(limit(8, 4)), &["abc", "defgh"]);
+		assert_eq!(out, "abcdefgh");
+		assert_eq!(summary.elided_bytes, 0);
+	}
+
+	#[test]
+	fn keeps_head_and_tail_with_marker() {
+		let (out, summary) = run(Some(limit(6, 3)), &["0123", "4567", "89"]);
+		assert_eq!(out, "012\n[... 4 bytes elided ...]\n789");
+		assert_eq!(summary.total_bytes, 10);
+		assert_eq!(summary.elided_bytes, 4);
+	}
+
+	#[test]
+	fn never_splits_multibyte_chars() {
+		let (out, summary) = run(Some(limit(4, 2)), &["ééé", "ééé"]);
+		assert!(out.starts_with("é"));
+		assert!(out.ends_with("é"));
+		assert_eq!(summary.total_bytes, 12);
+		assert_eq!(summary.elided_bytes, 8);
+	}
+
+	#[test]
+	fn spills_full_output_when_elided() {
+		let limit = OutputLimit { max_bytes: 4, tail_bytes: Some(2), spill: Some(true) };
+		let (_, summary) = run(Some(limit), &["abcdef", "ghij"]);
+		let path = summary.output_path.expect("spill file should be created");
+		let spilled = fs::read_to_string(&path).expect("spill file should be readable");
+		let _ = fs::remove_file(&path);
+		assert_eq!(spilled, "abcdefghij");
+	}
+
+	#[test]
+	fn skips_spill_when_nothing_is_elided() {
+		let limit = OutputLimit { max_bytes: 16, tail_bytes: None, spill: Some(true) };
+		let (_, summary) = run(Some(limit), &["short"]);
+		assert_eq!(summary.output_path, None);
+	}
+}
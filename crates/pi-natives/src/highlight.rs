@@ -1,15 +1,30 @@
-//! Syntax highlighting using syntect.
+//! Syntax highlighting using tree-sitter and syntect.
 //!
 //! Provides ANSI-colored output for code blocks. Takes theme colors as input
-//! and maps syntect scopes to 11 semantic categories:
+//! and maps tree-sitter captures or syntect scopes to 11 semantic categories:
 //! - comment, keyword, function, variable, string, number, type, operator,
 //!   punctuation, inserted, deleted
+//!
+//! Languages with a tree-sitter highlights query use it (see [`treesitter`]);
+//! the rest fall back to syntect's default syntaxes.
+
+mod diff;
+mod markup;
+mod theme;
+mod treesitter;
 
 use std::{cell::RefCell, collections::HashMap, sync::OnceLock};
 
+use napi::{Error, Result};
 use napi_derive::napi;
 use syntect::parsing::{ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
 
+pub use self::{
+	diff::{DiffHighlightOptions, highlight_diff},
+	markup::HighlightFormat,
+	theme::ColorDepth,
+};
+
 static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
 static SCOPE_MATCHERS: OnceLock<ScopeMatchers> = OnceLock::new();
 
@@ -345,53 +360,136 @@ fn find_syntax<'a>(ss: &'a SyntaxSet, lang: &str) -> Option<&'a SyntaxReference>
 		.or_else(|| ss.find_syntax_by_token(alias))
 }
 
-/// Highlight code and return ANSI-colored lines.
+/// Options for `highlight_code`.
+#[derive(Debug, Default)]
+#[napi(object)]
+pub struct HighlightOptions {
+	/// Theme to color with instead of `colors`: a `.tmTheme` or VS Code JSON
+	/// theme, given as a file path or the theme's text. Parsed themes are
+	/// cached.
+	pub theme:         Option<String>,
+	/// Color depth of themed ANSI output (default: "truecolor").
+	pub color_depth:   Option<ColorDepth>,
+	/// Output format (default: "ansi").
+	pub format:        Option<HighlightFormat>,
+	/// Style HTML runs with `style` attributes instead of `hl-<category>`
+	/// classes such as `hl-keyword` (default: false). Themes apply to inline
+	/// styles only; SVG is always styled inline.
+	pub inline_styles: Option<bool>,
+	/// Number the lines of HTML and SVG output (default: true for SVG, false
+	/// for HTML).
+	pub line_numbers:  Option<bool>,
+}
+
+/// Highlight code and return ANSI-colored lines, HTML or SVG.
 ///
 /// # Arguments
 /// * `code` - The source code to highlight
 /// * `lang` - Language identifier (e.g., "rust", "typescript", "python")
 /// * `colors` - Theme colors as ANSI escape sequences
+/// * `options` - A `.tmTheme` or VS Code theme that replaces `colors`, and the
+///   output format
 ///
 /// # Returns
 /// Highlighted code with ANSI color codes, or the original code if highlighting
-/// fails.
+/// fails or neither `colors` nor a theme is given. HTML and SVG output color
+/// runs from the same categories or theme styles, reading colors back from
+/// the ANSI sequences of `colors`.
 #[napi]
-pub fn highlight_code(code: String, lang: Option<String>, colors: HighlightColors) -> String {
-	let inserted = colors.inserted.as_deref().unwrap_or("");
-	let deleted = colors.deleted.as_deref().unwrap_or("");
-
-	// Color palette as array for quick indexing
-	let palette = [
-		colors.comment.as_str(),     // 0
-		colors.keyword.as_str(),     // 1
-		colors.function.as_str(),    // 2
-		colors.variable.as_str(),    // 3
-		colors.string.as_str(),      // 4
-		colors.number.as_str(),      // 5
-		colors.r#type.as_str(),      // 6
-		colors.operator.as_str(),    // 7
-		colors.punctuation.as_str(), // 8
-		inserted,                    // 9
-		deleted,                     // 10
-	];
+pub fn highlight_code(
+	code: String,
+	lang: Option<String>,
+	colors: Option<HighlightColors>,
+	options: Option<HighlightOptions>,
+) -> Result<String> {
+	let options = options.unwrap_or_default();
+	let lang = lang.as_deref();
+	let theme = options
+		.theme
+		.as_deref()
+		.map(theme::load)
+		.transpose()
+		.map_err(Error::from_reason)?;
+	let palette = colors.as_ref().map(HighlightColors::palette);
+
+	let format = options.format.unwrap_or(HighlightFormat::Ansi);
+	if format == HighlightFormat::Ansi {
+		if let Some(theme) = theme {
+			let styles = theme::byte_styles(&code, lang, &theme);
+			let depth = options.color_depth.unwrap_or(ColorDepth::TrueColor);
+			return Ok(render_runs(&code, &styles.bytes, |result, text, index| {
+				styles.push(result, text, index, depth);
+			}));
+		}
+		let Some(palette) = palette else {
+			return Ok(code);
+		};
+		return Ok(render_byte_colors(&code, &category_colors(&code, lang), &palette));
+	}
+
+	let classes = format == HighlightFormat::Html && !options.inline_styles.unwrap_or(false);
+	let markup = match theme {
+		Some(theme) if !classes => theme::byte_styles(&code, lang, &theme).into_markup(),
+		_ => markup::Markup::from_categories(
+			&category_colors(&code, lang),
+			palette
+				.as_ref()
+				.map(|palette| &palette[..])
+				.filter(|_| !classes),
+			classes,
+		),
+	};
+	let line_numbers = options
+		.line_numbers
+		.unwrap_or(format == HighlightFormat::Svg);
+	Ok(match format {
+		HighlightFormat::Svg => markup.svg(&code, line_numbers),
+		_ => markup.html(&code, line_numbers),
+	})
+}
+
+impl HighlightColors {
+	/// Colors indexed by category.
+	fn palette(&self) -> [&str; 11] {
+		[
+			self.comment.as_str(),                  // 0
+			self.keyword.as_str(),                  // 1
+			self.function.as_str(),                 // 2
+			self.variable.as_str(),                 // 3
+			self.string.as_str(),                   // 4
+			self.number.as_str(),                   // 5
+			self.r#type.as_str(),                   // 6
+			self.operator.as_str(),                 // 7
+			self.punctuation.as_str(),              // 8
+			self.inserted.as_deref().unwrap_or(""), // 9
+			self.deleted.as_deref().unwrap_or(""),  // 10
+		]
+	}
+}
+
+/// Color category of each byte of `code`, from the tree-sitter query for
+/// `lang` when there is one and syntect otherwise. Uncolored bytes are
+/// [`treesitter::NO_COLOR`].
+fn category_colors(code: &str, lang: Option<&str>) -> Vec<u8> {
+	if let Some(colors) = lang.and_then(|lang| treesitter::byte_colors(lang, code)) {
+		return colors;
+	}
 
 	let ss = get_syntax_set();
 
 	// Find syntax for the language
-	let syntax = match &lang {
-		Some(l) => find_syntax(ss, l),
-		None => None,
-	}
-	.unwrap_or_else(|| ss.find_syntax_plain_text());
+	let syntax = lang
+		.and_then(|l| find_syntax(ss, l))
+		.unwrap_or_else(|| ss.find_syntax_plain_text());
 
 	let mut parse_state = ParseState::new(syntax);
 	let mut scope_stack = ScopeStack::new();
-	let mut result = String::with_capacity(code.len() * 2);
+	let mut colors = Vec::with_capacity(code.len());
 
-	for line in syntect::util::LinesWithEndings::from(code.as_str()) {
+	for line in syntect::util::LinesWithEndings::from(code) {
 		let Ok(ops) = parse_state.parse_line(line, ss) else {
-			// Parse error - append unhighlighted line and continue
-			result.push_str(line);
+			// Parse error - leave the line uncolored and continue
+			colors.resize(colors.len() + line.len(), treesitter::NO_COLOR);
 			continue;
 		};
 
@@ -399,18 +497,10 @@ pub fn highlight_code(code: String, lang: Option<String>, colors: HighlightColor
 		for (offset, op) in ops {
 			let offset = offset.min(line.len());
 
-			// Output text BEFORE this operation using current scope
+			// Color text BEFORE this operation using current scope
 			if offset > prev_end {
-				let text = &line[prev_end..offset];
-				let color_idx = scope_to_color_index(&scope_stack);
-
-				if color_idx < palette.len() && !palette[color_idx].is_empty() {
-					result.push_str(palette[color_idx]);
-					result.push_str(text);
-					result.push_str("\x1b[39m");
-				} else {
-					result.push_str(text);
-				}
+				let color = scope_color(&scope_stack);
+				colors.resize(colors.len() + offset - prev_end, color);
 			}
 			prev_end = offset;
 
@@ -428,30 +518,100 @@ pub fn highlight_code(code: String, lang: Option<String>, colors: HighlightColor
 			}
 		}
 
-		// Output remaining text with current scope
+		// Color remaining text with current scope
 		if prev_end < line.len() {
-			let text = &line[prev_end..];
-			let color_idx = scope_to_color_index(&scope_stack);
-
-			if color_idx < palette.len() && !palette[color_idx].is_empty() {
-				result.push_str(palette[color_idx]);
-				result.push_str(text);
-				result.push_str("\x1b[39m");
-			} else {
-				result.push_str(text);
-			}
+			let color = scope_color(&scope_stack);
+			colors.resize(colors.len() + line.len() - prev_end, color);
 		}
 	}
 
+	colors
+}
+
+/// [`scope_to_color_index`] as a byte color category.
+fn scope_color(scope: &ScopeStack) -> u8 {
+	u8::try_from(scope_to_color_index(scope)).unwrap_or(treesitter::NO_COLOR)
+}
+
+/// Append `text` wrapped in the palette color for `color_idx`, or plain when
+/// the category has no color.
+fn push_colored(result: &mut String, text: &str, color_idx: usize, palette: &[&str]) {
+	match palette.get(color_idx) {
+		Some(color) if !color.is_empty() => {
+			result.push_str(color);
+			result.push_str(text);
+			result.push_str("\x1b[39m");
+		},
+		_ => result.push_str(text),
+	}
+}
+
+/// Render `code` from per-byte color categories, one colored run per
+/// same-color stretch.
+fn render_byte_colors(code: &str, colors: &[u8], palette: &[&str]) -> String {
+	render_runs(code, colors, |result, text, color| {
+		push_colored(result, text, usize::from(color), palette);
+	})
+}
+
+/// Render `code` from a value per byte, calling `push` once per stretch of
+/// equal values. Newlines are never included, so each line stands alone.
+fn render_runs<T: Copy + PartialEq>(
+	code: &str,
+	values: &[T],
+	mut push: impl FnMut(&mut String, &str, T),
+) -> String {
+	let mut result = String::with_capacity(code.len() * 2);
+	for (line, ending, values) in lines(code, values) {
+		for (text, value) in runs(line, values) {
+			push(&mut result, text, value);
+		}
+		result.push_str(ending);
+	}
 	result
 }
 
+/// Lines of `code` without their `\n` or `\r\n`, with that line ending and
+/// the line's slice of `values`.
+fn lines<'a, T>(
+	code: &'a str,
+	values: &'a [T],
+) -> impl Iterator<Item = (&'a str, &'a str, &'a [T])> {
+	code.split_inclusive('\n').map(move |line| {
+		let start = line.as_ptr() as usize - code.as_ptr() as usize;
+		let text = line
+			.strip_suffix("\r\n")
+			.or_else(|| line.strip_suffix('\n'))
+			.unwrap_or(line);
+		(text, &line[text.len()..], &values[start..start + text.len()])
+	})
+}
+
+/// Stretches of `text` whose bytes share a value.
+fn runs<'a, T: Copy + PartialEq>(
+	text: &'a str,
+	values: &'a [T],
+) -> impl Iterator<Item = (&'a str, T)> {
+	let mut run_start = 0;
+	(1..=text.len())
+		.filter(|&end| text.is_char_boundary(end))
+		.filter_map(move |end| {
+			let value = values[run_start];
+			if end < text.len() && values[end] == value {
+				return None;
+			}
+			let run = &text[run_start..end];
+			run_start = end;
+			Some((run, value))
+		})
+}
+
 /// Check if a language is supported for highlighting.
 /// Returns true if the language has either direct support or a fallback
 /// mapping.
 #[napi]
 pub fn supports_language(lang: String) -> bool {
-	if is_known_alias(&lang) {
+	if is_known_alias(&lang) || treesitter::supports(&lang) {
 		return true;
 	}
 
@@ -464,5 +624,75 @@ pub fn supports_language(lang: String) -> bool {
 #[napi]
 pub fn get_supported_languages() -> Vec<String> {
 	let ss = get_syntax_set();
-	ss.syntaxes().iter().map(|s| s.name.clone()).collect()
+	let mut languages: Vec<String> = ss.syntaxes().iter().map(|s| s.name.clone()).collect();
+	for name in treesitter::languages() {
+		if !languages
+			.iter()
+			.any(|known| known.eq_ignore_ascii_case(name))
+		{
+			languages.push(name.to_string());
+		}
+	}
+	languages
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn colors() -> HighlightColors {
+		let color = |code: u8| format!("\x1b[{code}m");
+		HighlightColors {
+			comment:     color(30),
+			keyword:     color(31),
+			function:    color(32),
+			variable:    color(33),
+			string:      color(34),
+			number:      color(35),
+			r#type:      color(36),
+			operator:    color(37),
+			punctuation: color(90),
+			inserted:    None,
+			deleted:     None,
+		}
+	}
+
+	#[test]
+	fn highlights_rust_with_multibyte_text_and_crlf() {
+		let code = "// café\r\nfn naïve() -> &'static str { \"ü\" }\r\n";
+		let ansi = highlight_code(code.to_string(), Some("rust".to_string()), Some(colors()), None)
+			.expect("rust should highlight");
+		assert_eq!(
+			ansi,
+			"\x1b[30m// café\x1b[39m\r\n\x1b[31mfn\x1b[39m \x1b[32mnaïve\x1b[39m\x1b[90m()\x1b[39m \
+			 -> \x1b[37m&'\x1b[39m\x1b[33mstatic\x1b[39m \x1b[36mstr\x1b[39m \x1b[90m{\x1b[39m \
+			 \x1b[34m\"ü\"\x1b[39m \x1b[90m}\x1b[39m\r\n"
+		);
+	}
+
+	#[test]
+	fn highlights_svelte_markup_with_multibyte_text_and_crlf() {
+		let code = "<p>{é}</p>\r\n";
+		let options = HighlightOptions { format: Some(HighlightFormat::Html), ..Default::default() };
+		let html = highlight_code(code.to_string(), Some("svelte".to_string()), None, Some(options))
+			.expect("svelte should highlight");
+		assert_eq!(
+			html,
+			"<pre class=\"hl\"><code><span class=\"hl-punctuation\">&lt;</span><span \
+			 class=\"hl-variable\">p</span><span class=\"hl-punctuation\">&gt;{</span>é<span \
+			 class=\"hl-punctuation\">}&lt;/</span><span class=\"hl-variable\">p</span><span \
+			 class=\"hl-punctuation\">&gt;</span>\r\n</code></pre>"
+		);
+
+		let options = HighlightOptions { format: Some(HighlightFormat::Svg), ..Default::default() };
+		let svg = highlight_code(
+			code.to_string(),
+			Some("svelte".to_string()),
+			Some(colors()),
+			Some(options),
+		)
+		.expect("svelte should highlight");
+		assert!(svg.contains("{</tspan>é<tspan"), "{svg}");
+		assert!(!svg.contains('\r'));
+	}
 }
@@ -0,0 +1,461 @@
+//! Syntax highlighting for unified diffs.
+//!
+//! The old and new sides of each hunk are reassembled and highlighted as the
+//! diffed file's language, so constructs spanning lines color correctly, and
+//! the colors are mapped back onto the hunk lines. Added and removed lines are
+//! tinted; changed words between paired removed and added lines can be marked.
+
+use std::ops::Range;
+
+use napi_derive::napi;
+use similar::{Algorithm, DiffTag};
+
+use super::{HighlightColors, category_colors, push_colored, treesitter::NO_COLOR};
+
+const COMMENT: u8 = 0;
+const KEYWORD: u8 = 1;
+const INSERTED: usize = 9;
+const DELETED: usize = 10;
+const RESET: &str = "\x1b[0m";
+/// Marks changed words when no `inserted_word`/`deleted_word` is set.
+const DEFAULT_WORD_MARK: &str = "\x1b[7m";
+/// Share of unchanged bytes below which paired lines count as rewritten and
+/// get no word marks.
+const MIN_WORD_SIMILARITY: f32 = 0.5;
+
+/// Options for `highlight_diff`.
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct DiffHighlightOptions {
+	/// SGR sequence tinting added lines, usually a background color.
+	pub inserted_line: Option<String>,
+	/// SGR sequence tinting removed lines, usually a background color.
+	pub deleted_line:  Option<String>,
+	/// Mark the words that changed between paired removed and added lines
+	/// (default: false).
+	pub word_diff:     Option<bool>,
+	/// SGR sequence marking changed words in added lines (default: reverse
+	/// video).
+	pub inserted_word: Option<String>,
+	/// SGR sequence marking changed words in removed lines (default: reverse
+	/// video).
+	pub deleted_word:  Option<String>,
+}
+
+/// Highlight a unified diff, coloring the code in each hunk as `lang`.
+///
+/// # Arguments
+/// * `diff` - Unified diff text, as produced by `git diff` or `diff -u`
+/// * `lang` - Language of the diffed file (e.g., "rust", "typescript")
+/// * `colors` - Theme colors as ANSI escape sequences; `inserted`/`deleted`
+///   color the `+`/`-` markers
+/// * `options` - Line tints and word-level change marks
+///
+/// # Returns
+/// The diff with ANSI color codes. Headers and hunk ranges use the keyword
+/// color.
+#[napi]
+pub fn highlight_diff(
+	diff: String,
+	lang: Option<String>,
+	colors: HighlightColors,
+	options: Option<DiffHighlightOptions>,
+) -> String {
+	let options = options.unwrap_or_default();
+	let style = Style {
+		palette:       colors.palette(),
+		inserted_line: options.inserted_line.as_deref().unwrap_or(""),
+		deleted_line:  options.deleted_line.as_deref().unwrap_or(""),
+		inserted_word: options
+			.inserted_word
+			.as_deref()
+			.unwrap_or(DEFAULT_WORD_MARK),
+		deleted_word:  options.deleted_word.as_deref().unwrap_or(DEFAULT_WORD_MARK),
+		word_diff:     options.word_diff.unwrap_or(false),
+	};
+	let lang = lang.as_deref();
+
+	let mut result = String::with_capacity(diff.len() * 2);
+	let mut hunk: Option<Hunk> = None;
+	for line in diff.split_inclusive('\n') {
+		if let Some(open) = &mut hunk {
+			if open.push(line) {
+				if open.is_complete() {
+					hunk.take().unwrap().render(&mut result, lang, &style);
+				}
+				continue;
+			}
+			// A line that cannot belong to the hunk ends it early.
+			hunk.take().unwrap().render(&mut result, lang, &style);
+		}
+
+		let (text, ending) = split_ending(line);
+		if let Some((counts, header_len)) = parse_hunk_header(text) {
+			push_uniform(&mut result, &text[..header_len], KEYWORD, &style.palette);
+			result.push_str(&text[header_len..]);
+			hunk = Some(Hunk::new(counts));
+		} else if text.starts_with('\\') {
+			push_uniform(&mut result, text, COMMENT, &style.palette);
+		} else if ["diff ", "index ", "--- ", "+++ "]
+			.iter()
+			.any(|prefix| text.starts_with(prefix))
+		{
+			push_uniform(&mut result, text, KEYWORD, &style.palette);
+		} else {
+			result.push_str(text);
+		}
+		result.push_str(ending);
+	}
+	if let Some(hunk) = hunk {
+		hunk.render(&mut result, lang, &style);
+	}
+	result
+}
+
+struct Style<'a> {
+	palette:       [&'a str; 11],
+	inserted_line: &'a str,
+	deleted_line:  &'a str,
+	inserted_word: &'a str,
+	deleted_word:  &'a str,
+	word_diff:     bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+	Context,
+	Deleted,
+	Inserted,
+	/// `\ No newline at end of file`.
+	Note,
+}
+
+struct HunkLine<'a> {
+	kind:   Kind,
+	/// Content without the marker and line ending.
+	text:   &'a str,
+	ending: &'a str,
+	/// Start of `text` in the reassembled side it belongs to.
+	offset: usize,
+	/// Byte ranges of `text` that changed against its paired line.
+	marks:  Marks,
+}
+
+/// A hunk being collected, until its header's line counts are used up.
+struct Hunk<'a> {
+	old_left: usize,
+	new_left: usize,
+	lines:    Vec<HunkLine<'a>>,
+	old:      String,
+	new:      String,
+}
+
+impl<'a> Hunk<'a> {
+	const fn new((old_left, new_left): (usize, usize)) -> Self {
+		Self { old_left, new_left, lines: Vec::new(), old: String::new(), new: String::new() }
+	}
+
+	const fn is_complete(&self) -> bool {
+		self.old_left == 0 && self.new_left == 0
+	}
+
+	/// Add `line` to the hunk, or return false if it is not a hunk line.
+	fn push(&mut self, line: &'a str) -> bool {
+		let (text, ending) = split_ending(line);
+		let (kind, text) = match text.as_bytes().first() {
+			// Some tools strip the trailing space of empty context lines.
+			None => (Kind::Context, text),
+			Some(b' ') => (Kind::Context, &text[1..]),
+			Some(b'-') => (Kind::Deleted, &text[1..]),
+			Some(b'+') => (Kind::Inserted, &text[1..]),
+			Some(b'\\') => (Kind::Note, text),
+			Some(_) => return false,
+		};
+		let (uses_old, uses_new) = match kind {
+			Kind::Context => (true, true),
+			Kind::Deleted => (true, false),
+			Kind::Inserted => (false, true),
+			Kind::Note => (false, false),
+		};
+		if (uses_old && self.old_left == 0) || (uses_new && self.new_left == 0) {
+			return false;
+		}
+
+		let offset = if uses_new {
+			self.new.len()
+		} else {
+			self.old.len()
+		};
+		for (used, side, left) in [
+			(uses_old, &mut self.old, &mut self.old_left),
+			(uses_new, &mut self.new, &mut self.new_left),
+		] {
+			if used {
+				side.push_str(text);
+				side.push('\n');
+				*left -= 1;
+			}
+		}
+		self
+			.lines
+			.push(HunkLine { kind, text, ending, offset, marks: Vec::new() });
+		true
+	}
+
+	fn render(mut self, result: &mut String, lang: Option<&str>, style: &Style) {
+		if style.word_diff {
+			self.mark_changed_words();
+		}
+		let old_colors = category_colors(&self.old, lang);
+		let new_colors = category_colors(&self.new, lang);
+
+		for line in &self.lines {
+			let range = line.offset..line.offset + line.text.len();
+			let (marker, marker_color, colors, tint, word) = match line.kind {
+				Kind::Context => (" ", usize::MAX, &new_colors[range], "", ""),
+				Kind::Deleted => {
+					("-", DELETED, &old_colors[range], style.deleted_line, style.deleted_word)
+				},
+				Kind::Inserted => {
+					("+", INSERTED, &new_colors[range], style.inserted_line, style.inserted_word)
+				},
+				Kind::Note => {
+					push_uniform(result, line.text, COMMENT, &style.palette);
+					result.push_str(line.ending);
+					continue;
+				},
+			};
+
+			result.push_str(tint);
+			push_colored(result, marker, marker_color, &style.palette);
+			push_marked_line(result, line.text, colors, &line.marks, &style.palette, tint, word);
+			if !tint.is_empty() {
+				result.push_str(RESET);
+			}
+			result.push_str(line.ending);
+		}
+	}
+
+	/// Pair each run of removed lines with the added lines right after it, in
+	/// order, and mark the words that differ.
+	fn mark_changed_words(&mut self) {
+		let mut i = 0;
+		while i < self.lines.len() {
+			let deleted = self.run(&mut i, Kind::Deleted);
+			let inserted = self.run(&mut i, Kind::Inserted);
+			if deleted.is_empty() && inserted.is_empty() {
+				i += 1;
+				continue;
+			}
+			for (&old, &new) in deleted.iter().zip(&inserted) {
+				if let Some((old_marks, new_marks)) =
+					word_changes(self.lines[old].text, self.lines[new].text)
+				{
+					self.lines[old].marks = old_marks;
+					self.lines[new].marks = new_marks;
+				}
+			}
+		}
+	}
+
+	/// Indices of the consecutive lines of `kind` from `*i`, skipping notes;
+	/// advances `*i` past them.
+	fn run(&self, i: &mut usize, kind: Kind) -> Vec<usize> {
+		let mut found = Vec::new();
+		while let Some(line) = self.lines.get(*i) {
+			if line.kind == kind {
+				found.push(*i);
+			} else if line.kind != Kind::Note {
+				break;
+			}
+			*i += 1;
+		}
+		found
+	}
+}
+
+/// Byte ranges of a line.
+type Marks = Vec<Range<usize>>;
+
+/// Changed byte ranges of `old` and `new`, or `None` when too little is shared
+/// for word marks to help.
+fn word_changes(old: &str, new: &str) -> Option<(Marks, Marks)> {
+	let old_tokens = tokenize(old);
+	let new_tokens = tokenize(new);
+	let old_words: Vec<&str> = old_tokens.iter().map(|range| &old[range.clone()]).collect();
+	let new_words: Vec<&str> = new_tokens.iter().map(|range| &new[range.clone()]).collect();
+
+	let mut old_marks: Marks = Vec::new();
+	let mut new_marks: Marks = Vec::new();
+	let mut unchanged = 0;
+	for op in similar::capture_diff_slices(Algorithm::Myers, &old_words, &new_words) {
+		let (tag, old_range, new_range) = op.as_tag_tuple();
+		if tag == DiffTag::Equal {
+			unchanged += old_tokens[old_range]
+				.iter()
+				.map(|range| range.len())
+				.sum::<usize>();
+			continue;
+		}
+		for (tokens, range, marks) in
+			[(&old_tokens, old_range, &mut old_marks), (&new_tokens, new_range, &mut new_marks)]
+		{
+			if range.is_empty() {
+				continue;
+			}
+			let bytes = tokens[range.start].start..tokens[range.end - 1].end;
+			match marks.last_mut() {
+				Some(last) if last.end == bytes.start => last.end = bytes.end,
+				_ => marks.push(bytes),
+			}
+		}
+	}
+
+	let total = old.len() + new.len();
+	let similarity = if total == 0 {
+		1.0
+	} else {
+		(2 * unchanged) as f32 / total as f32
+	};
+	(similarity >= MIN_WORD_SIMILARITY).then_some((old_marks, new_marks))
+}
+
+/// Split `text` into identifier runs, whitespace runs and single other
+/// characters.
+fn tokenize(text: &str) -> Vec<Range<usize>> {
+	let class = |c: char| {
+		if c.is_alphanumeric() || c == '_' {
+			1
+		} else if c.is_whitespace() {
+			2
+		} else {
+			0
+		}
+	};
+	let mut tokens: Vec<Range<usize>> = Vec::new();
+	let mut prev = None;
+	for (start, c) in text.char_indices() {
+		let end = start + c.len_utf8();
+		let current = class(c);
+		match tokens.last_mut() {
+			Some(last) if current != 0 && prev == Some(current) => last.end = end,
+			_ => tokens.push(start..end),
+		}
+		prev = Some(current);
+	}
+	tokens
+}
+
+/// Append `text` colored per byte, wrapping the `marks` ranges in `word` and
+/// restoring `tint` after each.
+fn push_marked_line(
+	result: &mut String,
+	text: &str,
+	colors: &[u8],
+	marks: &[Range<usize>],
+	palette: &[&str],
+	tint: &str,
+	word: &str,
+) {
+	let marked = |i: usize| marks.iter().any(|range| range.contains(&i));
+	let mut run_start = 0;
+	for end in (1..=text.len()).filter(|&end| text.is_char_boundary(end)) {
+		let key = (colors[run_start], marked(run_start));
+		if end < text.len() && (colors[end], marked(end)) == key {
+			continue;
+		}
+		if key.1 {
+			result.push_str(word);
+		}
+		push_colored(result, &text[run_start..end], usize::from(key.0), palette);
+		if key.1 {
+			result.push_str(RESET);
+			result.push_str(tint);
+		}
+		run_start = end;
+	}
+}
+
+/// Append `text` in a single color category.
+fn push_uniform(result: &mut String, text: &str, color: u8, palette: &[&str]) {
+	if !text.is_empty() && color != NO_COLOR {
+		push_colored(result, text, usize::from(color), palette);
+	} else {
+		result.push_str(text);
+	}
+}
+
+/// Split a line into its content and its `\n` or `\r\n` ending.
+fn split_ending(line: &str) -> (&str, &str) {
+	let text = line.strip_suffix('\n').unwrap_or(line);
+	let text = text.strip_suffix('\r').unwrap_or(text);
+	line.split_at(text.len())
+}
+
+/// Old and new line counts of a `@@ -a,b +c,d @@` header, and the length of
+/// the `@@ … @@` part.
+fn parse_hunk_header(line: &str) -> Option<((usize, usize), usize)> {
+	let rest = line.strip_prefix("@@ -")?;
+	let close = rest.find(" @@")?;
+	let (old, new) = rest[..close].split_once(" +")?;
+	let count = |range: &str| match range.split_once(',') {
+		Some((start, len)) => start.parse::<usize>().ok().and_then(|_| len.parse().ok()),
+		None => range.parse::<usize>().ok().map(|_| 1),
+	};
+	Some(((count(old)?, count(new)?), "@@ -".len() + close + " @@".len()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn colors() -> HighlightColors {
+		HighlightColors {
+			comment:     "<c>".into(),
+			keyword:     "<k>".into(),
+			function:    "<f>".into(),
+			variable:    "<v>".into(),
+			string:      "<s>".into(),
+			number:      "<n>".into(),
+			r#type:      "<t>".into(),
+			operator:    "<o>".into(),
+			punctuation: "<p>".into(),
+			inserted:    Some("<+>".into()),
+			deleted:     Some("<->".into()),
+		}
+	}
+
+	#[test]
+	fn highlights_hunk_lines_as_the_file_language() {
+		let diff = "--- a/x.zig\n+++ b/x.zig\n@@ -1,2 +1,2 @@ main\n const a = 1;\n-const b = \
+		            2;\n+var b = 3;\n";
+		let out =
+			highlight_diff(diff.into(), Some("zig".into()), colors(), None).replace("\x1b[39m", "</>");
+		assert_eq!(out.lines().collect::<Vec<_>>(), [
+			"<k>--- a/x.zig</>",
+			"<k>+++ b/x.zig</>",
+			"<k>@@ -1,2 +1,2 @@</> main",
+			" <k>const</> <v>a</> <o>=</> <n>1</><p>;</>",
+			"<->-</><k>const</> <v>b</> <o>=</> <n>2</><p>;</>",
+			"<+>+</><k>var</> <v>b</> <o>=</> <n>3</><p>;</>",
+		]);
+	}
+
+	#[test]
+	fn marks_changed_words_between_paired_lines() {
+		let (old, new) = word_changes("let total = count + 1;", "let sum = count + 2;").unwrap();
+		assert_eq!(old, [4..9, 20..21]);
+		assert_eq!(new, [4..7, 18..19]);
+		assert!(word_changes("alpha beta", "gamma delta").is_none());
+
+		let options = DiffHighlightOptions {
+			inserted_line: Some("[bg+]".into()),
+			word_diff: Some(true),
+			inserted_word: Some("[w]".into()),
+			..Default::default()
+		};
+		let diff = "@@ -1 +1 @@\n-a(1)\n+a(2)\n";
+		let out = highlight_diff(diff.into(), None, colors(), Some(options));
+		assert_eq!(out.lines().nth(2), Some("[bg+]<+>+\x1b[39ma([w]2\x1b[0m[bg+])\x1b[0m"));
+	}
+}
@@ -0,0 +1,361 @@
+//! HTML and SVG rendering of highlighted code.
+//!
+//! Runs are styled from the same color categories or theme styles as the
+//! terminal output: category classes (`hl-keyword`, …) for HTML meant to be
+//! styled by a stylesheet, or inline colors taken from `HighlightColors`'
+//! ANSI sequences or the theme.
+
+use std::fmt::Write as _;
+
+use napi_derive::napi;
+use unicode_width::UnicodeWidthStr;
+
+use super::{lines, runs, theme, treesitter::NO_COLOR};
+
+/// Class-name suffix of each color category, as in `HighlightColors`.
+const CATEGORY_NAMES: [&str; 11] = [
+	"comment",
+	"keyword",
+	"function",
+	"variable",
+	"string",
+	"number",
+	"type",
+	"operator",
+	"punctuation",
+	"inserted",
+	"deleted",
+];
+const SVG_FONT_SIZE: f32 = 14.0;
+/// Advance of a monospace glyph at `SVG_FONT_SIZE`.
+const SVG_CHAR_WIDTH: f32 = SVG_FONT_SIZE * 0.6;
+const SVG_LINE_HEIGHT: f32 = 20.0;
+const SVG_PADDING: f32 = 12.0;
+/// Room left below each baseline for descenders.
+const SVG_DESCENT: f32 = 5.0;
+/// Columns a tab advances in SVG output.
+const SVG_TAB_WIDTH: usize = 4;
+
+/// Output format of `highlight_code`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum HighlightFormat {
+	/// ANSI escape sequences for terminals.
+	#[napi(value = "ansi")]
+	Ansi,
+	/// A `<pre><code>` block.
+	#[napi(value = "html")]
+	Html,
+	/// A standalone SVG image.
+	#[napi(value = "svg")]
+	Svg,
+}
+
+/// How a run of code is styled; colors are CSS hex colors.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunStyle {
+	pub class:      Option<&'static str>,
+	pub color:      Option<String>,
+	pub background: Option<String>,
+	pub bold:       bool,
+	pub italic:     bool,
+	pub underline:  bool,
+}
+
+impl RunStyle {
+	fn css(&self) -> String {
+		let mut css = String::new();
+		if let Some(color) = &self.color {
+			let _ = write!(css, "color:{color};");
+		}
+		if let Some(background) = &self.background {
+			let _ = write!(css, "background-color:{background};");
+		}
+		if self.bold {
+			css.push_str("font-weight:bold;");
+		}
+		if self.italic {
+			css.push_str("font-style:italic;");
+		}
+		if self.underline {
+			css.push_str("text-decoration:underline;");
+		}
+		css.pop();
+		css
+	}
+
+	/// SVG presentation attributes, each with a leading space. SVG text has no
+	/// background, so token backgrounds are dropped.
+	fn svg_attributes(&self) -> String {
+		let mut attributes = String::new();
+		if let Some(color) = &self.color {
+			let _ = write!(attributes, r#" fill="{color}""#);
+		}
+		if self.bold {
+			attributes.push_str(r#" font-weight="bold""#);
+		}
+		if self.italic {
+			attributes.push_str(r#" font-style="italic""#);
+		}
+		if self.underline {
+			attributes.push_str(r#" text-decoration="underline""#);
+		}
+		attributes
+	}
+}
+
+/// Code styled for markup: a style per byte plus the block's default colors.
+pub struct Markup {
+	/// Index into `styles` per byte.
+	pub bytes:      Vec<u16>,
+	pub styles:     Vec<RunStyle>,
+	pub foreground: Option<String>,
+	pub background: Option<String>,
+}
+
+impl Markup {
+	/// Style bytes by color category, with `hl-*` classes or with the colors
+	/// of `palette`'s ANSI sequences.
+	pub fn from_categories(colors: &[u8], palette: Option<&[&str]>, classes: bool) -> Self {
+		let styles = (0..CATEGORY_NAMES.len())
+			.map(|category| RunStyle {
+				class: classes.then_some(CATEGORY_NAMES[category]),
+				color: palette
+					.and_then(|palette| palette.get(category))
+					.and_then(|sequence| ansi_color(sequence)),
+				..RunStyle::default()
+			})
+			.chain([RunStyle::default()])
+			.collect();
+		let bytes = colors
+			.iter()
+			.map(|&color| {
+				if color == NO_COLOR || usize::from(color) >= CATEGORY_NAMES.len() {
+					CATEGORY_NAMES.len() as u16
+				} else {
+					u16::from(color)
+				}
+			})
+			.collect();
+		Self { bytes, styles, foreground: None, background: None }
+	}
+
+	/// Render as `<pre class="hl"><code>…</code></pre>`, with `hl-*` classes or
+	/// `style` attributes depending on how the runs were styled.
+	pub fn html(&self, code: &str, line_numbers: bool) -> String {
+		let mut result = String::with_capacity(code.len() * 3);
+		result.push_str(r#"<pre class="hl""#);
+		let block = RunStyle {
+			color: self.foreground.clone(),
+			background: self.background.clone(),
+			..RunStyle::default()
+		};
+		if block != RunStyle::default() {
+			let _ = write!(result, r#" style="{}""#, block.css());
+		}
+		result.push_str("><code>");
+
+		let width = line_count(code).to_string().len();
+		for (number, (line, ending, bytes)) in lines(code, &self.bytes).enumerate() {
+			if line_numbers {
+				let _ = write!(
+					result,
+					r#"<span class="hl-line-number" style="user-select:none;opacity:0.5">{:>width$} </span>"#,
+					number + 1
+				);
+			}
+			for (text, index) in runs(line, bytes) {
+				let style = &self.styles[usize::from(index)];
+				if *style == RunStyle::default() {
+					escape(&mut result, text);
+					continue;
+				}
+				result.push_str("<span");
+				if let Some(class) = style.class {
+					let _ = write!(result, r#" class="hl-{class}""#);
+				}
+				let css = style.css();
+				if !css.is_empty() {
+					let _ = write!(result, r#" style="{css}""#);
+				}
+				result.push('>');
+				escape(&mut result, text);
+				result.push_str("</span>");
+			}
+			result.push_str(ending);
+		}
+		result.push_str("</code></pre>");
+		result
+	}
+
+	/// Render as a standalone SVG image with a line per `<text>` element and
+	/// an optional line-number gutter.
+	pub fn svg(&self, code: &str, line_numbers: bool) -> String {
+		let lines: Vec<_> = lines(code, &self.bytes).collect();
+		let columns = lines
+			.iter()
+			.map(|(line, ..)| expand_tabs(line).width())
+			.max()
+			.unwrap_or(0);
+		let digits = lines.len().to_string().len();
+		let gutter = if line_numbers {
+			(digits + 2) as f32 * SVG_CHAR_WIDTH
+		} else {
+			0.0
+		};
+		let width = 2.0f32.mul_add(SVG_PADDING, (columns as f32).mul_add(SVG_CHAR_WIDTH, gutter));
+		let height = 2.0f32.mul_add(SVG_PADDING, lines.len() as f32 * SVG_LINE_HEIGHT);
+		let foreground = self.foreground.as_deref().unwrap_or("currentColor");
+
+		let mut result = String::with_capacity(code.len() * 4);
+		let _ = write!(
+			result,
+			r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.1}" height="{height:.1}" viewBox="0 0 {width:.1} {height:.1}" font-family="ui-monospace, SFMono-Regular, Menlo, Consolas, monospace" font-size="{SVG_FONT_SIZE}">"#
+		);
+		if let Some(background) = &self.background {
+			let _ = write!(result, r#"<rect width="100%" height="100%" fill="{background}"/>"#);
+		}
+		for (number, (line, _, bytes)) in lines.iter().enumerate() {
+			let y = ((number + 1) as f32).mul_add(SVG_LINE_HEIGHT, SVG_PADDING - SVG_DESCENT);
+			if line_numbers {
+				let x = (digits as f32).mul_add(SVG_CHAR_WIDTH, SVG_PADDING);
+				let _ = write!(
+					result,
+					r#"<text x="{x:.1}" y="{y:.1}" fill="{foreground}" opacity="0.5" text-anchor="end">{}</text>"#,
+					number + 1
+				);
+			}
+			let _ = write!(
+				result,
+				r#"<text x="{:.1}" y="{y:.1}" fill="{foreground}" xml:space="preserve">"#,
+				SVG_PADDING + gutter
+			);
+			for (text, index) in runs(line, bytes) {
+				let attributes = self.styles[usize::from(index)].svg_attributes();
+				let text = expand_tabs(text);
+				if attributes.is_empty() {
+					escape(&mut result, &text);
+				} else {
+					let _ = write!(result, "<tspan{attributes}>");
+					escape(&mut result, &text);
+					result.push_str("</tspan>");
+				}
+			}
+			result.push_str("</text>");
+		}
+		result.push_str("</svg>");
+		result
+	}
+}
+
+/// Hex color of the first foreground color an ANSI SGR sequence sets.
+fn ansi_color(sequence: &str) -> Option<String> {
+	let params = sequence.strip_prefix("\x1b[")?.strip_suffix('m')?;
+	let params: Vec<u8> = params
+		.split(';')
+		.map(|param| param.parse().ok())
+		.collect::<Option<_>>()?;
+	let mut params = params.into_iter();
+	while let Some(param) = params.next() {
+		let rgb = match param {
+			38 => match params.next()? {
+				2 => (params.next()?, params.next()?, params.next()?),
+				5 => theme::ansi256_rgb(params.next()?),
+				_ => return None,
+			},
+			30..=37 => theme::ANSI16_PALETTE[usize::from(param - 30)],
+			90..=97 => theme::ANSI16_PALETTE[usize::from(param - 90 + 8)],
+			_ => continue,
+		};
+		return Some(hex(rgb));
+	}
+	None
+}
+
+/// `#rrggbb` for an RGB triple.
+pub fn hex((r, g, b): (u8, u8, u8)) -> String {
+	format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn line_count(code: &str) -> usize {
+	code.split_inclusive('\n').count()
+}
+
+fn expand_tabs(text: &str) -> String {
+	text.replace('\t', &" ".repeat(SVG_TAB_WIDTH))
+}
+
+/// Append `text` with the characters HTML and XML reserve escaped. Control
+/// characters XML does not allow, such as the ESC of a stray SGR sequence,
+/// become their control pictures (`␛`).
+fn escape(result: &mut String, text: &str) {
+	for c in text.chars() {
+		match c {
+			'&' => result.push_str("&amp;"),
+			'<' => result.push_str("&lt;"),
+			'>' => result.push_str("&gt;"),
+			'"' => result.push_str("&quot;"),
+			'\t' | '\n' | '\r' => result.push(c),
+			'\0'..='\x1f' => result.extend(char::from_u32(0x2400 + c as u32)),
+			_ => result.push(c),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reads_colors_from_ansi_sequences() {
+		assert_eq!(ansi_color("\x1b[38;2;255;128;0m").as_deref(), Some("#ff8000"));
+		assert_eq!(ansi_color("\x1b[1;38;5;196m").as_deref(), Some("#ff0000"));
+		assert_eq!(ansi_color("\x1b[92m").as_deref(), Some("#00ff00"));
+		assert_eq!(ansi_color("\x1b[1m"), None);
+		assert_eq!(ansi_color(""), None);
+	}
+
+	#[test]
+	fn renders_html_and_svg() {
+		let code = "a<b\n\tc\n";
+		let colors = [1, 8, 1, NO_COLOR, NO_COLOR, 4, NO_COLOR];
+		let markup = Markup::from_categories(&colors, None, true);
+		assert_eq!(
+			markup.html(code, false),
+			"<pre class=\"hl\"><code><span class=\"hl-keyword\">a</span><span \
+			 class=\"hl-punctuation\">&lt;</span><span class=\"hl-keyword\">b</span>\n\t<span \
+			 class=\"hl-string\">c</span>\n</code></pre>"
+		);
+
+		let palette = ["", "\x1b[38;2;1;2;3m"];
+		let markup = Markup::from_categories(&colors, Some(&palette), false);
+		let svg = markup.svg(code, true);
+		assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+		assert!(svg.contains(">1</text>") && svg.contains(">2</text>"));
+		assert!(
+			svg.contains("<tspan fill=\"#010203\">a</tspan>&lt;<tspan fill=\"#010203\">b</tspan>")
+		);
+		assert!(svg.contains("xml:space=\"preserve\">    c</text>"));
+		assert!(svg.ends_with("</svg>"));
+	}
+
+	#[test]
+	fn svg_of_control_characters_is_well_formed() {
+		let code = "\x1b[0mreset\x07\n";
+		let markup = Markup::from_categories(&[NO_COLOR; 11], None, false);
+		let svg = markup.svg(code, false);
+
+		let mut reader = quick_xml::Reader::from_str(&svg);
+		let mut text = String::new();
+		loop {
+			match reader.read_event().expect("SVG should be well-formed XML") {
+				quick_xml::events::Event::Text(chunk) => {
+					text.push_str(&chunk.decode().expect("text should decode"));
+				},
+				quick_xml::events::Event::Eof => break,
+				_ => {},
+			}
+		}
+		assert!(text.contains("\u{241b}[0mreset\u{2407}"));
+	}
+}
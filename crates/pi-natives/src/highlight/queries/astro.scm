@@ -0,0 +1,28 @@
+; Vendored from tree-sitter-astro-next 0.1.1 (MIT OR Apache-2.0); the crate does
+; not export its queries.
+
+(tag_name) @tag
+(erroneous_end_tag_name) @keyword
+(doctype) @tag.doctype
+(attribute_name) @property
+(attribute_value) @string
+(comment) @comment
+
+[
+  (attribute_value)
+  (quoted_attribute_value)
+] @string
+
+"=" @operator
+
+[
+  "{"
+  "}"
+] @punctuation.bracket
+
+[
+  "<"
+  ">"
+  "</"
+  "/>"
+] @tag.delimiter
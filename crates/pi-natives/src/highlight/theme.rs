@@ -0,0 +1,615 @@
+//! `.tmTheme` and VS Code color themes.
+//!
+//! `.tmTheme` property lists and VS Code JSON themes (comments, trailing
+//! commas and `include` next to the file allowed) load into syntect themes.
+//! Their scope selectors style syntect's scopes, or the scopes that
+//! tree-sitter captures map onto, and styles render as SGR sequences at the
+//! requested color depth.
+
+use std::{
+	collections::HashMap,
+	fs,
+	io::Cursor,
+	path::Path,
+	str::FromStr as _,
+	sync::{Arc, LazyLock},
+	time::{Instant, SystemTime},
+};
+
+use napi_derive::napi;
+use parking_lot::Mutex;
+use serde_json::Value;
+use syntect::{
+	highlighting::{
+		Color, FontStyle, HighlightIterator, HighlightState, Highlighter, ScopeSelectors, Style,
+		StyleModifier, Theme, ThemeItem, ThemeSet,
+	},
+	parsing::{ParseState, Scope, ScopeStack},
+};
+use xxhash_rust::xxh64::xxh64;
+
+use super::{
+	find_syntax, get_syntax_set,
+	markup::{self, Markup, RunStyle},
+	treesitter,
+};
+
+/// How deep `include` chains may nest before loading gives up.
+const MAX_INCLUDE_DEPTH: u8 = 8;
+
+/// Color depth of themed output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ColorDepth {
+	/// 24-bit colors.
+	#[napi(value = "truecolor")]
+	TrueColor,
+	/// The xterm 256-color palette.
+	#[napi(value = "256")]
+	Ansi256,
+	/// The 16 basic ANSI colors.
+	#[napi(value = "16")]
+	Ansi16,
+}
+
+/// How many parsed themes are kept; the least recently used goes first.
+const MAX_CACHED_THEMES: usize = 16;
+
+struct CachedTheme {
+	modified: Option<SystemTime>,
+	used:     Instant,
+	theme:    Arc<Theme>,
+}
+
+/// Parsed themes by hash of their source text or path. Themes loaded from a
+/// path are parsed again once the file's modification time changes.
+static THEMES: LazyLock<Mutex<HashMap<u64, CachedTheme>>> =
+	LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Load a theme from its text or a file path.
+pub fn load(source: &str) -> Result<Arc<Theme>, String> {
+	let trimmed = source.trim_start();
+	let is_text = trimmed.starts_with('{') || trimmed.starts_with('<');
+	let modified = if is_text {
+		None
+	} else {
+		fs::metadata(source)
+			.and_then(|metadata| metadata.modified())
+			.ok()
+	};
+	let key = xxh64(source.as_bytes(), 0);
+	if let Some(cached) = THEMES.lock().get_mut(&key)
+		&& cached.modified == modified
+	{
+		cached.used = Instant::now();
+		return Ok(cached.theme.clone());
+	}
+
+	let mut theme = Theme::default();
+	if is_text {
+		apply_text(&mut theme, source, None, 0)?;
+	} else {
+		apply_file(&mut theme, Path::new(source), 0)?;
+	}
+	let theme = Arc::new(theme);
+	let mut themes = THEMES.lock();
+	themes.insert(key, CachedTheme { modified, used: Instant::now(), theme: theme.clone() });
+	if themes.len() > MAX_CACHED_THEMES
+		&& let Some(oldest) = themes
+			.iter()
+			.min_by_key(|(_, cached)| cached.used)
+			.map(|(&key, _)| key)
+	{
+		themes.remove(&oldest);
+	}
+	Ok(theme)
+}
+
+fn apply_file(theme: &mut Theme, path: &Path, depth: u8) -> Result<(), String> {
+	let text = fs::read_to_string(path)
+		.map_err(|err| format!("Failed to read theme {}: {err}", path.display()))?;
+	apply_text(theme, &text, path.parent(), depth)
+}
+
+/// Apply a `.tmTheme` or VS Code theme to `theme`. `dir` resolves the files a
+/// VS Code theme refers to.
+fn apply_text(theme: &mut Theme, text: &str, dir: Option<&Path>, depth: u8) -> Result<(), String> {
+	if text.trim_start().starts_with('<') {
+		let loaded = ThemeSet::load_from_reader(&mut Cursor::new(text))
+			.map_err(|err| format!("Failed to parse theme: {err}"))?;
+		if loaded.name.is_some() {
+			theme.name = loaded.name;
+		}
+		theme.settings.foreground = loaded.settings.foreground.or(theme.settings.foreground);
+		theme.settings.background = loaded.settings.background.or(theme.settings.background);
+		theme.scopes.extend(loaded.scopes);
+		return Ok(());
+	}
+
+	let json: Value = serde_json::from_str(&strip_jsonc(text))
+		.map_err(|err| format!("Failed to parse theme: {err}"))?;
+	let referenced = |file: &str| {
+		if depth >= MAX_INCLUDE_DEPTH {
+			return Err(format!("Failed to load theme: {file} is included too deeply"));
+		}
+		dir.map(|dir| dir.join(file)).ok_or_else(|| {
+			format!("Failed to load theme: {file} can only be resolved for a theme loaded from a path")
+		})
+	};
+	if let Some(include) = json["include"].as_str() {
+		apply_file(theme, &referenced(include)?, depth + 1)?;
+	}
+	if let Some(name) = json["name"].as_str() {
+		theme.name = Some(name.to_string());
+	}
+	let colors = &json["colors"];
+	if let Some(color) = colors["editor.foreground"].as_str().and_then(parse_color) {
+		theme.settings.foreground = Some(color);
+	}
+	if let Some(color) = colors["editor.background"].as_str().and_then(parse_color) {
+		theme.settings.background = Some(color);
+	}
+	match &json["tokenColors"] {
+		Value::Array(rules) => apply_rules(theme, rules),
+		// A `.tmTheme` holding the rules.
+		Value::String(file) => apply_file(theme, &referenced(file)?, depth + 1)?,
+		_ => {},
+	}
+	Ok(())
+}
+
+/// Apply `{ scope, settings }` rules. A rule without a scope sets the default
+/// colors; rules whose selectors do not parse are skipped.
+fn apply_rules(theme: &mut Theme, rules: &[Value]) {
+	for rule in rules {
+		let settings = &rule["settings"];
+		let style = StyleModifier {
+			foreground: settings["foreground"].as_str().and_then(parse_color),
+			background: settings["background"].as_str().and_then(parse_color),
+			font_style: settings["fontStyle"].as_str().map(parse_font_style),
+		};
+		let scope = match &rule["scope"] {
+			Value::String(scope) => scope.clone(),
+			Value::Array(scopes) => scopes
+				.iter()
+				.filter_map(Value::as_str)
+				.collect::<Vec<_>>()
+				.join(", "),
+			_ => {
+				theme.settings.foreground = style.foreground.or(theme.settings.foreground);
+				theme.settings.background = style.background.or(theme.settings.background);
+				continue;
+			},
+		};
+		if let Ok(scope) = ScopeSelectors::from_str(&scope) {
+			theme.scopes.push(ThemeItem { scope, style });
+		}
+	}
+}
+
+/// Parse `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+fn parse_color(text: &str) -> Option<Color> {
+	let digits: Vec<u8> = text
+		.strip_prefix('#')?
+		.chars()
+		.map(|c| c.to_digit(16).map(|digit| digit as u8))
+		.collect::<Option<_>>()?;
+	let channels: Vec<u8> = match digits.len() {
+		3 | 4 => digits.iter().map(|digit| digit * 17).collect(),
+		6 | 8 => digits
+			.chunks(2)
+			.map(|pair| pair[0] * 16 + pair[1])
+			.collect(),
+		_ => return None,
+	};
+	Some(Color {
+		r: channels[0],
+		g: channels[1],
+		b: channels[2],
+		a: channels.get(3).copied().unwrap_or(u8::MAX),
+	})
+}
+
+/// Parse a `fontStyle` such as `"bold italic"`; an empty one clears the
+/// inherited style.
+fn parse_font_style(text: &str) -> FontStyle {
+	text
+		.split_whitespace()
+		.fold(FontStyle::empty(), |style, word| match word {
+			"bold" => style | FontStyle::BOLD,
+			"italic" => style | FontStyle::ITALIC,
+			"underline" => style | FontStyle::UNDERLINE,
+			_ => style,
+		})
+}
+
+/// Remove `//` and `/* */` comments and trailing commas, which VS Code themes
+/// may contain but JSON does not allow.
+fn strip_jsonc(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	let mut chars = text.chars().peekable();
+	let mut in_string = false;
+	while let Some(c) = chars.next() {
+		if in_string {
+			out.push(c);
+			match c {
+				'\\' => out.extend(chars.next()),
+				'"' => in_string = false,
+				_ => {},
+			}
+			continue;
+		}
+		match c {
+			'"' => {
+				in_string = true;
+				out.push(c);
+			},
+			'/' if chars.peek() == Some(&'/') => while chars.next_if(|&c| c != '\n').is_some() {},
+			'/' if chars.peek() == Some(&'*') => {
+				chars.next();
+				let mut prev = ' ';
+				for c in chars.by_ref() {
+					if prev == '*' && c == '/' {
+						break;
+					}
+					prev = c;
+				}
+			},
+			'}' | ']' => {
+				let end = out.trim_end().len();
+				if out[..end].ends_with(',') {
+					out.truncate(end - 1);
+				}
+				out.push(c);
+			},
+			_ => out.push(c),
+		}
+	}
+	out
+}
+
+/// The theme style of each byte of some code.
+pub struct ByteStyles {
+	/// Index into `styles` per byte.
+	pub bytes:        Vec<u16>,
+	pub styles:       Vec<Style>,
+	default:          Style,
+	/// The theme's default colors, which syntect falls back from to black
+	/// and white.
+	theme_foreground: Option<Color>,
+	theme_background: Option<Color>,
+}
+
+impl ByteStyles {
+	fn intern(&mut self, style: Style) -> u16 {
+		let index = self
+			.styles
+			.iter()
+			.position(|&known| known == style)
+			.unwrap_or_else(|| {
+				self.styles.push(style);
+				self.styles.len() - 1
+			});
+		index as u16
+	}
+
+	/// Foreground and background `style` paints, composited over the theme
+	/// background. Backgrounds matching the theme's are left to the terminal
+	/// or page.
+	fn colors(&self, style: &Style) -> (Option<Color>, Option<Color>) {
+		let foreground = (style.foreground.a > 0
+			&& (self.theme_foreground.is_some() || style.foreground != self.default.foreground))
+			.then(|| blend(style.foreground, self.default.background));
+		let background = (style.background.a > 0 && style.background != self.default.background)
+			.then(|| blend(style.background, self.default.background));
+		(foreground, background)
+	}
+
+	/// Append `text` wrapped in the SGR sequences of style `index`.
+	pub fn push(&self, result: &mut String, text: &str, index: u16, depth: ColorDepth) {
+		let style = self.styles[usize::from(index)];
+		let mut open = Vec::new();
+		let mut close = Vec::new();
+		let (foreground, background) = self.colors(&style);
+		if let Some(color) = foreground {
+			open.push(color_sgr(color, depth, false));
+			close.push("39");
+		}
+		if let Some(color) = background {
+			open.push(color_sgr(color, depth, true));
+			close.push("49");
+		}
+		for (flag, on, off) in [
+			(FontStyle::BOLD, "1", "22"),
+			(FontStyle::ITALIC, "3", "23"),
+			(FontStyle::UNDERLINE, "4", "24"),
+		] {
+			if style.font_style.contains(flag) {
+				open.push(on.to_string());
+				close.push(off);
+			}
+		}
+
+		if open.is_empty() {
+			result.push_str(text);
+			return;
+		}
+		result.push_str("\x1b[");
+		result.push_str(&open.join(";"));
+		result.push('m');
+		result.push_str(text);
+		result.push_str("\x1b[");
+		result.push_str(&close.join(";"));
+		result.push('m');
+	}
+
+	/// The styles as inline colors for HTML and SVG, with the theme's default
+	/// colors for the whole block.
+	pub fn into_markup(self) -> Markup {
+		let css = |color: Color| markup::hex((color.r, color.g, color.b));
+		let styles = self
+			.styles
+			.iter()
+			.map(|style| {
+				let (foreground, background) = self.colors(style);
+				RunStyle {
+					class:      None,
+					color:      foreground.map(css),
+					background: background.map(css),
+					bold:       style.font_style.contains(FontStyle::BOLD),
+					italic:     style.font_style.contains(FontStyle::ITALIC),
+					underline:  style.font_style.contains(FontStyle::UNDERLINE),
+				}
+			})
+			.collect();
+		Markup {
+			bytes: self.bytes,
+			styles,
+			foreground: self.theme_foreground.map(css),
+			background: self.theme_background.map(css),
+		}
+	}
+}
+
+/// Style each byte of `code` with `theme`, using the tree-sitter query for
+/// `lang` when there is one and syntect otherwise.
+pub fn byte_styles(code: &str, lang: Option<&str>, theme: &Theme) -> ByteStyles {
+	let highlighter = Highlighter::new(theme);
+	let default = highlighter.get_default();
+	let mut styles = ByteStyles {
+		bytes: Vec::with_capacity(code.len()),
+		styles: Vec::new(),
+		default,
+		theme_foreground: theme.settings.foreground,
+		theme_background: theme.settings.background,
+	};
+
+	if let Some(captures) = lang.and_then(|lang| treesitter::captures(lang, code)) {
+		// Selectors may name the language's root scope, as TextMate grammars do.
+		let root =
+			lang.and_then(|lang| Scope::new(&format!("source.{}", lang.to_ascii_lowercase())).ok());
+		let mut by_capture = HashMap::new();
+		for &capture in &captures.bytes {
+			let index = *by_capture.entry(capture).or_insert_with(|| {
+				let style = captures.scope(capture).map_or(default, |scope| {
+					let stack: Vec<Scope> = root.into_iter().chain([scope]).collect();
+					highlighter.style_for_stack(&stack)
+				});
+				styles.intern(style)
+			});
+			styles.bytes.push(index);
+		}
+		return styles;
+	}
+
+	let ss = get_syntax_set();
+	let syntax = lang
+		.and_then(|l| find_syntax(ss, l))
+		.unwrap_or_else(|| ss.find_syntax_plain_text());
+	let mut parse_state = ParseState::new(syntax);
+	let mut highlight_state = HighlightState::new(&highlighter, ScopeStack::new());
+	for line in syntect::util::LinesWithEndings::from(code) {
+		let Ok(ops) = parse_state.parse_line(line, ss) else {
+			let index = styles.intern(default);
+			styles.bytes.resize(styles.bytes.len() + line.len(), index);
+			continue;
+		};
+		for (style, text) in HighlightIterator::new(&mut highlight_state, &ops, line, &highlighter) {
+			let index = styles.intern(style);
+			styles.bytes.resize(styles.bytes.len() + text.len(), index);
+		}
+	}
+	styles
+}
+
+/// Composite a translucent color over the background.
+fn blend(color: Color, background: Color) -> Color {
+	if color.a == u8::MAX {
+		return color;
+	}
+	let mix = |fg: u8, bg: u8| {
+		let alpha = u32::from(color.a);
+		((u32::from(fg) * alpha + u32::from(bg) * (255 - alpha)) / 255) as u8
+	};
+	Color {
+		r: mix(color.r, background.r),
+		g: mix(color.g, background.g),
+		b: mix(color.b, background.b),
+		a: u8::MAX,
+	}
+}
+
+/// SGR parameters selecting `color` as the foreground or background.
+fn color_sgr(color: Color, depth: ColorDepth, background: bool) -> String {
+	let base = if background { 48 } else { 38 };
+	match depth {
+		ColorDepth::TrueColor => format!("{base};2;{};{};{}", color.r, color.g, color.b),
+		ColorDepth::Ansi256 => format!("{base};5;{}", ansi256(color)),
+		ColorDepth::Ansi16 => {
+			let index = ansi16(color);
+			let code = if index < 8 {
+				30 + index
+			} else {
+				90 + index - 8
+			};
+			(code + if background { 10 } else { 0 }).to_string()
+		},
+	}
+}
+
+/// Channel levels of the xterm 6×6×6 color cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The 16 basic ANSI colors, with xterm's default palette.
+pub const ANSI16_PALETTE: [(u8, u8, u8); 16] = [
+	(0, 0, 0),
+	(205, 0, 0),
+	(0, 205, 0),
+	(205, 205, 0),
+	(0, 0, 238),
+	(205, 0, 205),
+	(0, 205, 205),
+	(229, 229, 229),
+	(127, 127, 127),
+	(255, 0, 0),
+	(0, 255, 0),
+	(255, 255, 0),
+	(92, 92, 255),
+	(255, 0, 255),
+	(0, 255, 255),
+	(255, 255, 255),
+];
+
+/// Nearest xterm 256-color index, from the 6×6×6 cube or the gray ramp.
+fn ansi256(color: Color) -> u8 {
+	let nearest_level = |value: u8| {
+		(0..CUBE_LEVELS.len())
+			.min_by_key(|&i| CUBE_LEVELS[i].abs_diff(value))
+			.unwrap_or(0)
+	};
+	let (r, g, b) = (nearest_level(color.r), nearest_level(color.g), nearest_level(color.b));
+	let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+	let average = (u32::from(color.r) + u32::from(color.g) + u32::from(color.b)) / 3;
+	let gray_index = (average.saturating_sub(3) / 10).min(23) as u8;
+	let gray = 8 + 10 * gray_index;
+
+	if distance(color, cube) <= distance(color, (gray, gray, gray)) {
+		16 + 36 * r as u8 + 6 * g as u8 + b as u8
+	} else {
+		232 + gray_index
+	}
+}
+
+/// RGB value of an xterm 256-color index.
+pub const fn ansi256_rgb(index: u8) -> (u8, u8, u8) {
+	match index {
+		0..16 => ANSI16_PALETTE[index as usize],
+		16..232 => {
+			let cube = index - 16;
+			(
+				CUBE_LEVELS[(cube / 36) as usize],
+				CUBE_LEVELS[(cube / 6 % 6) as usize],
+				CUBE_LEVELS[(cube % 6) as usize],
+			)
+		},
+		_ => {
+			let gray = 8 + 10 * (index - 232);
+			(gray, gray, gray)
+		},
+	}
+}
+
+/// Nearest of the 16 basic ANSI colors.
+fn ansi16(color: Color) -> u8 {
+	(0..ANSI16_PALETTE.len())
+		.min_by_key(|&i| distance(color, ANSI16_PALETTE[i]))
+		.unwrap_or(0) as u8
+}
+
+fn distance(color: Color, (r, g, b): (u8, u8, u8)) -> u32 {
+	[(color.r, r), (color.g, g), (color.b, b)]
+		.iter()
+		.map(|&(a, b)| u32::from(a.abs_diff(b)).pow(2))
+		.sum()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const VSCODE_THEME: &str = r##"{
+		// Comments and trailing commas are allowed.
+		"name": "Test",
+		"colors": { "editor.foreground": "#cccccc", "editor.background": "#1e1e1e" },
+		"tokenColors": [
+			{ "scope": "comment", "settings": { "foreground": "#6a9955", "fontStyle": "italic" } },
+			{ "scope": ["keyword", "storage.type"], "settings": { "foreground": "#569cd6", "fontStyle": "bold" } },
+			{ "scope": "string", "settings": { "foreground": "#ce9178", "background": "#333" } },
+		],
+	}"##;
+
+	const TM_THEME: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0"><dict>
+	<key>name</key><string>Test &amp; Co</string>
+	<key>settings</key><array>
+		<dict><key>settings</key><dict><key>foreground</key><string>#F8F8F2</string></dict></dict>
+		<dict>
+			<key>scope</key><string>entity.name.function, support.function</string>
+			<key>settings</key><dict><key>foreground</key><string>#A6E22E</string></dict>
+		</dict>
+	</array>
+</dict></plist>"#;
+
+	fn style(theme: &Theme, scope: &str) -> Style {
+		Highlighter::new(theme).style_for_stack(&[Scope::new(scope).unwrap()])
+	}
+
+	#[test]
+	fn loads_vscode_and_textmate_themes() {
+		let theme = load(VSCODE_THEME).expect("VS Code theme should parse");
+		assert_eq!(theme.settings.background, parse_color("#1e1e1e"));
+		let keyword = style(&theme, "keyword.control.rust");
+		assert_eq!(Some(keyword.foreground), parse_color("#569cd6"));
+		assert_eq!(keyword.font_style, FontStyle::BOLD);
+		assert_eq!(Some(style(&theme, "string.quoted").background), parse_color("#333333"));
+		assert!(Arc::ptr_eq(&theme, &load(VSCODE_THEME).unwrap()));
+
+		let theme = load(TM_THEME).expect("tmTheme should parse");
+		assert_eq!(theme.name.as_deref(), Some("Test & Co"));
+		assert_eq!(Some(style(&theme, "support.function").foreground), parse_color("#A6E22E"));
+		assert_eq!(theme.settings.foreground, parse_color("#F8F8F2"));
+	}
+
+	#[test]
+	fn caps_the_theme_cache() {
+		let themes: Vec<String> = (0..=MAX_CACHED_THEMES)
+			.map(|i| format!(r#"{{ "name": "Theme {i}", "tokenColors": [] }}"#))
+			.collect();
+		for theme in &themes {
+			load(theme).expect("theme should parse");
+		}
+		assert!(THEMES.lock().len() <= MAX_CACHED_THEMES);
+		let last = themes.last().unwrap();
+		assert!(Arc::ptr_eq(&load(last).unwrap(), &load(last).unwrap()));
+	}
+
+	#[test]
+	fn renders_styles_at_each_color_depth() {
+		let theme = load(VSCODE_THEME).unwrap();
+		let styles = byte_styles("// hi", Some("rust"), &theme);
+		let render = |depth| {
+			let mut out = String::new();
+			styles.push(&mut out, "// hi", styles.bytes[0], depth);
+			out
+		};
+		assert_eq!(render(ColorDepth::TrueColor), "\x1b[38;2;106;153;85;3m// hi\x1b[39;23m");
+		assert_eq!(render(ColorDepth::Ansi256), "\x1b[38;5;65;3m// hi\x1b[39;23m");
+		assert_eq!(render(ColorDepth::Ansi16), "\x1b[90;3m// hi\x1b[39;23m");
+
+		let red = Color { r: 255, g: 0, b: 0, a: 255 };
+		assert_eq!(ansi256(red), 196);
+		assert_eq!(ansi16(red), 9);
+		assert_eq!(ansi256(Color { r: 128, g: 128, b: 128, a: 255 }), 244);
+	}
+}
@@ -0,0 +1,370 @@
+//! Highlighting with the tree-sitter grammars linked for ast-grep.
+//!
+//! Languages whose grammar ships a highlights query are highlighted from the
+//! syntax tree instead of syntect's regex grammars. Capture names such as
+//! `keyword.return` or `string.special` map onto the same color categories
+//! as syntect scopes, and onto theme scopes; unmatched suffixes fall back to
+//! their prefix.
+
+use std::{
+	borrow::Cow,
+	collections::HashMap,
+	sync::{Arc, LazyLock},
+};
+
+use ast_grep_core::tree_sitter::LanguageExt as _;
+use parking_lot::Mutex;
+use syntect::parsing::Scope;
+use tree_sitter::{Parser, Query, QueryCursor, StreamingIterator as _};
+
+use crate::language::SupportLang;
+
+/// Color index of text no capture colors (or a `@none` capture cleared).
+pub const NO_COLOR: u8 = u8::MAX;
+/// Capture index of bytes no capture covers.
+pub const NO_CAPTURE: u16 = u16::MAX;
+
+/// A compiled highlights query and the color category and theme scope of each
+/// capture.
+struct Highlighter {
+	query:  Query,
+	colors: Vec<Option<u8>>,
+	scopes: Vec<Option<Scope>>,
+}
+
+/// Compiled queries by language; `None` records a query that failed to
+/// compile so it is not retried.
+static HIGHLIGHTERS: LazyLock<Mutex<HashMap<SupportLang, Option<Arc<Highlighter>>>>> =
+	LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Highlights query source for `lang`, when its grammar has one.
+///
+/// Diff stays with syntect, whose `markup.inserted`/`markup.deleted` scopes
+/// drive the `inserted`/`deleted` colors; Markdown needs its inline grammar
+/// injected to be useful.
+fn query_source(lang: SupportLang) -> Option<Cow<'static, str>> {
+	use SupportLang::*;
+	let source = match lang {
+		Astro => include_str!("queries/astro.scm"),
+		Bash => tree_sitter_bash::HIGHLIGHT_QUERY,
+		C => tree_sitter_c::HIGHLIGHT_QUERY,
+		Cpp => {
+			return Some(
+				format!("{}\n{}", tree_sitter_c::HIGHLIGHT_QUERY, tree_sitter_cpp::HIGHLIGHT_QUERY)
+					.into(),
+			);
+		},
+		Css => tree_sitter_css::HIGHLIGHTS_QUERY,
+		Elixir => tree_sitter_elixir::HIGHLIGHTS_QUERY,
+		Erlang => tree_sitter_erlang::HIGHLIGHTS_QUERY,
+		Go => tree_sitter_go::HIGHLIGHTS_QUERY,
+		Haskell => tree_sitter_haskell::HIGHLIGHTS_QUERY,
+		Html => tree_sitter_html::HIGHLIGHTS_QUERY,
+		Ini => tree_sitter_ini::HIGHLIGHTS_QUERY,
+		Java => tree_sitter_java::HIGHLIGHTS_QUERY,
+		JavaScript => {
+			return Some(
+				format!(
+					"{}\n{}",
+					tree_sitter_javascript::HIGHLIGHT_QUERY,
+					tree_sitter_javascript::JSX_HIGHLIGHT_QUERY
+				)
+				.into(),
+			);
+		},
+		Json => tree_sitter_json::HIGHLIGHTS_QUERY,
+		Just => include_str!("queries/just.scm"),
+		Kotlin => tree_sitter_kotlin::HIGHLIGHTS_QUERY,
+		Lua => tree_sitter_lua::HIGHLIGHTS_QUERY,
+		Make => tree_sitter_make::HIGHLIGHTS_QUERY,
+		Nix => tree_sitter_nix::HIGHLIGHTS_QUERY,
+		ObjC => tree_sitter_objc::HIGHLIGHTS_QUERY,
+		Ocaml => tree_sitter_ocaml::HIGHLIGHTS_QUERY,
+		Odin => tree_sitter_odin::HIGHLIGHTS_QUERY,
+		Php => tree_sitter_php::HIGHLIGHTS_QUERY,
+		Powershell => tree_sitter_powershell::HIGHLIGHTS_QUERY,
+		Python => tree_sitter_python::HIGHLIGHTS_QUERY,
+		R => tree_sitter_r::HIGHLIGHTS_QUERY,
+		Regex => tree_sitter_regex::HIGHLIGHTS_QUERY,
+		Ruby => tree_sitter_ruby::HIGHLIGHTS_QUERY,
+		Rust => tree_sitter_rust::HIGHLIGHTS_QUERY,
+		Scala => tree_sitter_scala::HIGHLIGHTS_QUERY,
+		Solidity => tree_sitter_solidity::HIGHLIGHT_QUERY,
+		Sql => tree_sitter_sql::HIGHLIGHTS_QUERY,
+		Starlark => tree_sitter_starlark::HIGHLIGHTS_QUERY,
+		Svelte => include_str!("queries/svelte.scm"),
+		Swift => tree_sitter_swift::HIGHLIGHTS_QUERY,
+		Tlaplus => tree_sitter_tlaplus::HIGHLIGHT_QUERY,
+		Toml => tree_sitter_toml_ng::HIGHLIGHTS_QUERY,
+		// The TypeScript queries only add to the JavaScript ones.
+		Tsx => {
+			return Some(
+				format!(
+					"{}\n{}\n{}",
+					tree_sitter_javascript::HIGHLIGHT_QUERY,
+					tree_sitter_javascript::JSX_HIGHLIGHT_QUERY,
+					tree_sitter_typescript::HIGHLIGHTS_QUERY
+				)
+				.into(),
+			);
+		},
+		TypeScript => {
+			return Some(
+				format!(
+					"{}\n{}",
+					tree_sitter_javascript::HIGHLIGHT_QUERY,
+					tree_sitter_typescript::HIGHLIGHTS_QUERY
+				)
+				.into(),
+			);
+		},
+		Xml => tree_sitter_xml::XML_HIGHLIGHT_QUERY,
+		Yaml => tree_sitter_yaml::HIGHLIGHTS_QUERY,
+		Zig => tree_sitter_zig::HIGHLIGHTS_QUERY,
+		Diff | Markdown => return None,
+		// These grammars ship no query, or only behind a cfg their published
+		// crates never set.
+		Clojure | Cmake | CSharp | Dockerfile | Graphql | Handlebars | Hcl | Julia | Perl | Proto
+		| Verilog | Vue => return None,
+	};
+	Some(Cow::Borrowed(source))
+}
+
+/// Resolve a capture name with `lookup`, falling back to its dotted prefixes.
+fn resolve<T>(name: &str, lookup: impl Fn(&str) -> Option<T>) -> Option<T> {
+	// Captures starting with `_` only feed predicates.
+	if name.starts_with('_') {
+		return None;
+	}
+	let mut name = name;
+	loop {
+		if let Some(found) = lookup(name) {
+			return Some(found);
+		}
+		let (prefix, _) = name.rsplit_once('.')?;
+		name = prefix;
+	}
+}
+
+/// Color category of a capture name: `None` ignores the capture,
+/// [`NO_COLOR`] clears colors of enclosing captures.
+fn capture_color(name: &str) -> Option<u8> {
+	resolve(name, |name| match name {
+		"comment" => Some(0),
+		"keyword" | "conditional" | "repeat" | "include" | "import" | "exception"
+		| "storageclass" | "preproc" => Some(1),
+		"function" | "method" => Some(2),
+		"variable" | "parameter" | "property" | "field" | "label" | "namespace" | "module"
+		| "tag" | "attribute" => Some(3),
+		"string" | "character" | "escape" => Some(4),
+		"number" | "float" | "boolean" | "constant" => Some(5),
+		"type" | "constructor" => Some(6),
+		"operator" => Some(7),
+		"punctuation" | "delimiter" | "tag.delimiter" => Some(8),
+		"none" => Some(NO_COLOR),
+		_ => None,
+	})
+}
+
+/// Theme scope a capture name is styled with.
+fn capture_scope(name: &str) -> Option<&'static str> {
+	resolve(name, |name| match name {
+		"comment" => Some("comment"),
+		"comment.documentation" => Some("comment.block.documentation"),
+		"keyword" => Some("keyword.control"),
+		"keyword.operator" | "operator" => Some("keyword.operator"),
+		"keyword.function" | "keyword.type" | "storageclass" | "keyword.modifier" => {
+			Some("storage.type")
+		},
+		"conditional" | "keyword.conditional" => Some("keyword.control.conditional"),
+		"repeat" | "keyword.repeat" => Some("keyword.control.loop"),
+		"include" | "import" | "keyword.import" => Some("keyword.control.import"),
+		"exception" | "keyword.exception" => Some("keyword.control.exception"),
+		"preproc" | "keyword.directive" => Some("meta.preprocessor"),
+		"function" => Some("entity.name.function"),
+		"function.builtin" => Some("support.function"),
+		"function.macro" => Some("entity.name.function.macro"),
+		"method" | "function.method" => Some("entity.name.function.method"),
+		"variable" => Some("variable.other"),
+		"variable.builtin" => Some("variable.language"),
+		"parameter" | "variable.parameter" => Some("variable.parameter"),
+		"property" | "field" | "variable.member" => Some("variable.other.property"),
+		"label" => Some("entity.name.label"),
+		"namespace" | "module" => Some("entity.name.namespace"),
+		"tag" => Some("entity.name.tag"),
+		"attribute" => Some("entity.other.attribute-name"),
+		"string" => Some("string.quoted"),
+		"string.regex" | "string.regexp" => Some("string.regexp"),
+		"escape" | "string.escape" => Some("constant.character.escape"),
+		"character" => Some("constant.character"),
+		"number" | "float" => Some("constant.numeric"),
+		"boolean" => Some("constant.language.boolean"),
+		"constant" => Some("constant.other"),
+		"constant.builtin" => Some("constant.language"),
+		"type" => Some("entity.name.type"),
+		"type.builtin" => Some("support.type"),
+		"constructor" => Some("entity.name.type.class"),
+		"punctuation" => Some("punctuation"),
+		"punctuation.delimiter" | "delimiter" => Some("punctuation.separator"),
+		"punctuation.bracket" => Some("punctuation.section"),
+		"tag.delimiter" => Some("punctuation.definition.tag"),
+		_ => None,
+	})
+}
+
+fn highlighter(lang: SupportLang) -> Option<Arc<Highlighter>> {
+	if let Some(highlighter) = HIGHLIGHTERS.lock().get(&lang) {
+		return highlighter.clone();
+	}
+	// Compiling a query takes a while; other languages need not wait for it.
+	let compiled = compile(lang);
+	HIGHLIGHTERS.lock().entry(lang).or_insert(compiled).clone()
+}
+
+fn compile(lang: SupportLang) -> Option<Arc<Highlighter>> {
+	let source = query_source(lang)?;
+	let query = Query::new(&lang.get_ts_language(), &source).ok()?;
+	let colors = query
+		.capture_names()
+		.iter()
+		.map(|name| capture_color(name))
+		.collect();
+	let scopes = query
+		.capture_names()
+		.iter()
+		.map(|name| capture_scope(name).and_then(|scope| Scope::new(scope).ok()))
+		.collect();
+	Some(Arc::new(Highlighter { query, colors, scopes }))
+}
+
+/// Whether `lang` names a language with a highlights query.
+pub fn supports(lang: &str) -> bool {
+	SupportLang::from_alias(lang).is_some_and(|lang| query_source(lang).is_some())
+}
+
+/// Canonical names of the languages with a highlights query.
+pub fn languages() -> impl Iterator<Item = &'static str> {
+	SupportLang::all_langs()
+		.iter()
+		.filter(|&&lang| query_source(lang).is_some())
+		.map(|lang| lang.canonical_name())
+}
+
+/// The capture coloring each byte of some code.
+pub struct Captures {
+	highlighter: Arc<Highlighter>,
+	/// Capture index per byte, or [`NO_CAPTURE`].
+	pub bytes:   Vec<u16>,
+}
+
+impl Captures {
+	/// Color category of `capture`.
+	pub fn color(&self, capture: u16) -> u8 {
+		self
+			.highlighter
+			.colors
+			.get(usize::from(capture))
+			.copied()
+			.flatten()
+			.unwrap_or(NO_COLOR)
+	}
+
+	/// Theme scope `capture` is styled with.
+	pub fn scope(&self, capture: u16) -> Option<Scope> {
+		self
+			.highlighter
+			.scopes
+			.get(usize::from(capture))
+			.copied()
+			.flatten()
+	}
+}
+
+/// Capture of each byte of `code`, or `None` when `lang` has no usable
+/// highlights query or its grammar cannot be loaded.
+///
+/// Inner captures override enclosing ones; for captures of the same node the
+/// earliest pattern wins, as in `tree-sitter highlight`. Captures without a
+/// color category are skipped.
+pub fn captures(lang: &str, code: &str) -> Option<Captures> {
+	let lang = SupportLang::from_alias(lang)?;
+	let highlighter = highlighter(lang)?;
+	let mut parser = Parser::new();
+	parser.set_language(&lang.get_ts_language()).ok()?;
+	let tree = parser.parse(code, None)?;
+
+	let mut spans = Vec::new();
+	let mut cursor = QueryCursor::new();
+	{
+		let mut matches = cursor.captures(&highlighter.query, tree.root_node(), code.as_bytes());
+		while let Some((found, index)) = matches.next() {
+			let capture = found.captures[*index];
+			if highlighter.colors[capture.index as usize].is_some() {
+				spans.push((capture.node.byte_range(), found.pattern_index, capture.index as u16));
+			}
+		}
+	}
+	spans.sort_by(|(a, a_pattern, _), (b, b_pattern, _)| {
+		b.len().cmp(&a.len()).then_with(|| b_pattern.cmp(a_pattern))
+	});
+
+	let mut bytes = vec![NO_CAPTURE; code.len()];
+	for (range, _, capture) in spans {
+		bytes[range].fill(capture);
+	}
+	Some(Captures { highlighter, bytes })
+}
+
+/// Color category of each byte of `code`, or `None` when `lang` has no usable
+/// highlights query or its grammar cannot be loaded.
+pub fn byte_colors(lang: &str, code: &str) -> Option<Vec<u8>> {
+	let captures = captures(lang, code)?;
+	Some(
+		captures
+			.bytes
+			.iter()
+			.map(|&capture| captures.color(capture))
+			.collect(),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn maps_captures_onto_categories() {
+		assert_eq!(capture_color("keyword.return"), Some(1));
+		assert_eq!(capture_color("string.special.path"), Some(4));
+		assert_eq!(capture_color("tag.delimiter"), Some(8));
+		assert_eq!(capture_color("none"), Some(NO_COLOR));
+		assert_eq!(capture_color("spell"), None);
+		assert_eq!(capture_color("_name"), None);
+		assert_eq!(capture_scope("function.method.call"), Some("entity.name.function.method"));
+		assert_eq!(capture_scope("string.special"), Some("string.quoted"));
+
+		for lang in ["zig", "nix", "odin", "tlaplus", "astro", "svelte", "just", "rust", "tsx"] {
+			assert!(supports(lang), "{lang} should have a highlights query");
+		}
+		assert!(!supports("diff"));
+	}
+
+	#[test]
+	fn every_query_compiles() {
+		for &lang in SupportLang::all_langs() {
+			if query_source(lang).is_some() {
+				assert!(highlighter(lang).is_some(), "{} query should compile", lang.canonical_name());
+			}
+		}
+	}
+
+	#[test]
+	fn colors_bytes_from_the_syntax_tree() {
+		let code = "const x = 42; // hi";
+		let colors = byte_colors("zig", code).expect("zig should highlight");
+		let at = |text: &str| colors[code.find(text).unwrap()];
+		assert_eq!(at("const"), 1);
+		assert_eq!(at("42"), 5);
+		assert_eq!(at("// hi"), 0);
+	}
+}
@@ -28,6 +28,7 @@ static GLOBAL: MiMalloc = MiMalloc;
 
 pub mod appearance;
 pub mod ast;
+pub mod capture;
 pub mod chunk;
 pub mod clipboard;
 pub mod fff;
@@ -47,6 +48,7 @@ pub mod prof;
 pub mod projfs_overlay;
 pub mod ps;
 pub mod pty;
+pub mod sandbox;
 pub mod search_db;
 pub mod shell;
 pub mod task;
@@ -3,8 +3,12 @@
 //! Provides efficient process tree enumeration and termination without
 //! requiring processes to be spawned with `detached: true`.
 //!
+//! `process_tree` describes a process and its descendants for display; see
+//! [`inspect`]. `terminate_tree` escalates from a polite signal to SIGKILL;
+//! see [`terminate`].
+//!
 //! # Platform Implementation
-//! - **Linux**: Reads `/proc/{pid}/children` recursively
+//! - **Linux**: Reads `/proc/{pid}/task/*/children` recursively
 //! - **macOS**: Uses `libproc` (`proc_listchildpids`)
 //! - **Windows**: Uses `CreateToolhelp32Snapshot` to build parent-child
 //!   relationships
@@ -19,24 +23,71 @@
 
 use napi_derive::napi;
 
+mod inspect;
+mod terminate;
+
+pub use terminate::{TERMINATE_GRACE, terminate_tree, terminate_trees};
+
 #[cfg(target_os = "linux")]
 mod platform {
 	use std::fs;
 
 	/// Collect all descendant PIDs of `pid` into `pids`.
-	/// Skips branches when `/proc/{pid}/children` cannot be read.
+	/// Skips branches whose `/proc/{pid}/task` cannot be read.
 	pub fn collect_descendants(pid: i32, pids: &mut Vec<i32>) {
-		let children_path = format!("/proc/{pid}/task/{pid}/children");
-		let Ok(content) = fs::read_to_string(&children_path) else {
-			return;
+		for child_pid in children(pid) {
+			pids.push(child_pid);
+			collect_descendants(child_pid, pids);
+		}
+	}
+
+	/// Direct children of `pid`, gathered from every thread since each lists
+	/// only the children it forked. Empty when `/proc/{pid}/task` cannot be
+	/// read.
+	pub fn children(pid: i32) -> Vec<i32> {
+		let Ok(tasks) = fs::read_dir(format!("/proc/{pid}/task")) else {
+			return Vec::new();
 		};
+		let mut children: Vec<i32> = tasks
+			.filter_map(|task| fs::read_to_string(task.ok()?.path().join("children")).ok())
+			.flat_map(|content| {
+				content
+					.split_whitespace()
+					.filter_map(|part| part.parse().ok())
+					.collect::<Vec<i32>>()
+			})
+			.collect();
+		children.sort_unstable();
+		children.dedup();
+		children
+	}
 
-		for part in content.split_whitespace() {
-			if let Ok(child_pid) = part.parse::<i32>() {
-				pids.push(child_pid);
-				collect_descendants(child_pid, pids);
-			}
-		}
+	/// Fields of `/proc/{pid}/stat` after the command name, starting with the
+	/// state.
+	fn stat_fields(pid: i32) -> Option<Vec<String>> {
+		let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+		let (_, rest) = stat.rsplit_once(')')?;
+		Some(rest.split_whitespace().map(str::to_string).collect())
+	}
+
+	/// Whether `pid` exists and has not exited; zombies count as exited.
+	pub fn is_running(pid: i32) -> bool {
+		stat_fields(pid)
+			.and_then(|fields| fields.into_iter().next())
+			.is_some_and(|state| state != "Z" && state != "X")
+	}
+
+	/// Processes in the process group `pgid`, found by scanning `/proc`.
+	pub fn group_members(pgid: i32) -> Vec<i32> {
+		let Ok(entries) = fs::read_dir("/proc") else {
+			return Vec::new();
+		};
+		entries
+			.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok())
+			.filter(|&pid| {
+				stat_fields(pid).and_then(|fields| fields.get(2)?.parse::<i32>().ok()) == Some(pgid)
+			})
+			.collect()
 	}
 
 	/// Send `signal` to `pid`.
@@ -69,17 +120,29 @@ mod platform {
 	#[link(name = "proc", kind = "dylib")]
 	unsafe extern "C" {
 		fn proc_listchildpids(ppid: i32, buffer: *mut i32, buffersize: i32) -> i32;
+		fn proc_name(pid: i32, buffer: *mut u8, buffersize: u32) -> i32;
 	}
 
+	/// `proc_listpids` filter selecting one process group.
+	const PROC_PGRP_ONLY: u32 = 2;
+
 	/// Collect all descendant PIDs of `pid` into `pids` using libproc.
 	/// Skips branches when libproc returns no children.
 	pub fn collect_descendants(pid: i32, pids: &mut Vec<i32>) {
+		for child_pid in children(pid) {
+			pids.push(child_pid);
+			collect_descendants(child_pid, pids);
+		}
+	}
+
+	/// Direct children of `pid`; empty when libproc returns none.
+	pub fn children(pid: i32) -> Vec<i32> {
 		// First call to get count
 		// SAFETY: passing null buffer with size 0 to query child count is valid per
 		// libproc API.
 		let count = unsafe { proc_listchildpids(pid, ptr::null_mut(), 0) };
 		if count <= 0 {
-			return;
+			return Vec::new();
 		}
 
 		let mut buffer = vec![0i32; count as usize];
@@ -89,16 +152,60 @@ mod platform {
 		};
 
 		if actual <= 0 {
-			return;
+			return Vec::new();
 		}
 
-		let child_count = actual as usize / size_of::<i32>();
-		for &child_pid in &buffer[..child_count] {
-			if child_pid > 0 {
-				pids.push(child_pid);
-				collect_descendants(child_pid, pids);
-			}
+		buffer.truncate(actual as usize / size_of::<i32>());
+		buffer.retain(|&child_pid| child_pid > 0);
+		buffer
+	}
+
+	/// Short name of `pid`, or `None` when the process does not exist.
+	pub fn process_name(pid: i32) -> Option<String> {
+		let mut buffer = [0u8; 256];
+		// SAFETY: buffer is valid for `buffer.len()` bytes.
+		let len = unsafe { proc_name(pid, buffer.as_mut_ptr(), buffer.len() as u32) };
+		(len > 0).then(|| String::from_utf8_lossy(&buffer[..len as usize]).into_owned())
+	}
+
+	/// Whether `pid` exists and has not exited; zombies count as exited.
+	pub fn is_running(pid: i32) -> bool {
+		// SAFETY: `proc_bsdinfo` is plain data, so a zeroed value is valid.
+		let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+		let size = size_of::<libc::proc_bsdinfo>() as i32;
+		// SAFETY: `info` is valid for `size` bytes.
+		let written = unsafe {
+			libc::proc_pidinfo(pid, libc::PROC_PIDTBSDINFO, 0, (&raw mut info).cast(), size)
+		};
+		written == size && info.pbi_status != libc::SZOMB
+	}
+
+	/// Processes in the process group `pgid`, as reported by libproc.
+	pub fn group_members(pgid: i32) -> Vec<i32> {
+		// SAFETY: passing null buffer with size 0 to query the size is valid per
+		// libproc API.
+		let size = unsafe { libc::proc_listpids(PROC_PGRP_ONLY, pgid as u32, ptr::null_mut(), 0) };
+		if size <= 0 {
+			return Vec::new();
+		}
+
+		let mut buffer = vec![0i32; size as usize / size_of::<i32>()];
+		// SAFETY: buffer is correctly sized and aligned for its i32 elements.
+		let actual = unsafe {
+			libc::proc_listpids(
+				PROC_PGRP_ONLY,
+				pgid as u32,
+				buffer.as_mut_ptr().cast(),
+				(buffer.len() * size_of::<i32>()) as i32,
+			)
+		};
+		if actual <= 0 {
+			return Vec::new();
 		}
+
+		buffer.truncate(actual as usize / size_of::<i32>());
+		buffer.retain(|&pid| pid > 0);
+		buffer
 	}
 
 	/// Send `signal` to `pid`.
@@ -149,6 +256,8 @@ mod platform {
 	const INVALID_HANDLE_VALUE: Handle = -1isize as Handle;
 	const TH32CS_SNAPPROCESS: u32 = 0x00000002;
 	const PROCESS_TERMINATE: u32 = 0x0001;
+	const SYNCHRONIZE: u32 = 0x0010_0000;
+	const WAIT_TIMEOUT: u32 = 0x0000_0102;
 
 	#[link(name = "kernel32")]
 	unsafe extern "system" {
@@ -158,18 +267,17 @@ mod platform {
 		fn CloseHandle(hObject: Handle) -> i32;
 		fn OpenProcess(dwDesiredAccess: u32, bInheritHandle: i32, dwProcessId: u32) -> Handle;
 		fn TerminateProcess(hProcess: Handle, uExitCode: u32) -> i32;
+		fn WaitForSingleObject(hHandle: Handle, dwMilliseconds: u32) -> u32;
 	}
 
-	/// Build a map of `parent_pid` -> [`child_pids`] for all processes.
-	fn build_process_tree() -> HashMap<u32, SmallVec<[u32; 4]>> {
-		let mut tree: HashMap<u32, SmallVec<[u32; 4]>> = HashMap::new();
-
+	/// Call `visit` with every entry of a snapshot of the process table.
+	fn for_each_process(mut visit: impl FnMut(&PROCESSENTRY32W)) {
 		// SAFETY: Toolhelp snapshot APIs are called with initialized structs and valid
 		// handles.
 		unsafe {
 			let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
 			if snapshot == INVALID_HANDLE_VALUE {
-				return tree;
+				return;
 			}
 
 			let mut entry: PROCESSENTRY32W = mem::zeroed();
@@ -177,10 +285,7 @@ mod platform {
 
 			if Process32FirstW(snapshot, &raw mut entry) != 0 {
 				loop {
-					tree
-						.entry(entry.th32ParentProcessID)
-						.or_default()
-						.push(entry.th32ProcessID);
+					visit(&entry);
 
 					if Process32NextW(snapshot, &raw mut entry) == 0 {
 						break;
@@ -190,10 +295,64 @@ mod platform {
 
 			CloseHandle(snapshot);
 		}
+	}
 
+	/// Build a map of `parent_pid` -> [`child_pids`] for all processes.
+	fn build_process_tree() -> HashMap<u32, SmallVec<[u32; 4]>> {
+		let mut tree: HashMap<u32, SmallVec<[u32; 4]>> = HashMap::new();
+		for_each_process(|entry| {
+			tree
+				.entry(entry.th32ParentProcessID)
+				.or_default()
+				.push(entry.th32ProcessID);
+		});
 		tree
 	}
 
+	/// Direct children of `pid`, from a snapshot of the process table.
+	pub fn children(pid: i32) -> Vec<i32> {
+		build_process_tree()
+			.get(&(pid as u32))
+			.map(|children| children.iter().map(|&child_pid| child_pid as i32).collect())
+			.unwrap_or_default()
+	}
+
+	/// Executable name of `pid`, or `None` when the process does not exist.
+	pub fn process_name(pid: i32) -> Option<String> {
+		let mut name = None;
+		for_each_process(|entry| {
+			if entry.th32ProcessID == pid as u32 {
+				let len = entry
+					.szExeFile
+					.iter()
+					.position(|&unit| unit == 0)
+					.unwrap_or(entry.szExeFile.len());
+				name = Some(String::from_utf16_lossy(&entry.szExeFile[..len]));
+			}
+		});
+		name
+	}
+
+	/// Whether `pid` exists and has not exited.
+	pub fn is_running(pid: i32) -> bool {
+		// SAFETY: the handle is checked before use and always closed.
+		unsafe {
+			let handle = OpenProcess(SYNCHRONIZE, 0, pid as u32);
+			if handle.is_null() || handle == INVALID_HANDLE_VALUE {
+				return false;
+			}
+			let running = WaitForSingleObject(handle, 0) == WAIT_TIMEOUT;
+			CloseHandle(handle);
+			running
+		}
+	}
+
+	/// Process groups are not exposed on Windows.
+	/// Always returns an empty list.
+	pub const fn group_members(_pgid: i32) -> Vec<i32> {
+		Vec::new()
+	}
+
 	/// Collect all descendant PIDs of `pid` into `pids`.
 	/// Uses a snapshot of the current process table.
 	pub fn collect_descendants(pid: i32, pids: &mut Vec<i32>) {
@@ -293,3 +452,34 @@ pub fn list_descendants(pid: i32) -> Vec<i32> {
 	platform::collect_descendants(pid, &mut descendants);
 	descendants
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+	use std::{process::Command, sync::mpsc, thread};
+
+	use super::*;
+
+	#[test]
+	fn lists_children_forked_by_any_thread() {
+		let (pid_tx, pid_rx) = mpsc::channel();
+		let (done_tx, done_rx) = mpsc::channel::<()>();
+		// The forking thread stays alive: children of an exited thread are
+		// handed to another one.
+		let forker = thread::spawn(move || {
+			let mut child = Command::new("sleep")
+				.arg("30")
+				.spawn()
+				.expect("sleep should spawn");
+			pid_tx.send(child.id() as i32).expect("pid should send");
+			let _ = done_rx.recv();
+			let _ = child.kill();
+			let _ = child.wait();
+		});
+		let child_pid = pid_rx.recv().expect("forker should report the pid");
+
+		let descendants = list_descendants(std::process::id() as i32);
+		drop(done_tx);
+		forker.join().expect("forker should finish");
+		assert!(descendants.contains(&child_pid), "{descendants:?}");
+	}
+}
@@ -0,0 +1,422 @@
+//! Process tree inspection.
+//!
+//! Describes a process and its descendants (command line, working directory,
+//! state, memory, CPU time, start time and listening TCP ports) so a hung
+//! command can be shown as a tree and a single branch picked for killing.
+//!
+//! Linux reads everything from `/proc`. Other platforms report the tree with
+//! process names only.
+
+use napi_derive::napi;
+
+use super::platform;
+
+/// Scheduling state of a process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ProcessState {
+	/// Running or runnable.
+	#[napi(value = "running")]
+	Running,
+	/// Waiting for an event.
+	#[napi(value = "sleeping")]
+	Sleeping,
+	/// In uninterruptible sleep, usually waiting for I/O.
+	#[napi(value = "diskSleep")]
+	DiskSleep,
+	/// Stopped by a signal or a tracer.
+	#[napi(value = "stopped")]
+	Stopped,
+	/// Exited but not yet reaped by its parent.
+	#[napi(value = "zombie")]
+	Zombie,
+	/// Idle kernel thread.
+	#[napi(value = "idle")]
+	Idle,
+	/// Not reported on this platform.
+	#[default]
+	#[napi(value = "unknown")]
+	Unknown,
+}
+
+/// TCP socket a process is listening on.
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessPort {
+	/// Local address the socket is bound to.
+	pub address: String,
+	/// Local port.
+	pub port:    u16,
+}
+
+/// A process and its descendants.
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct ProcessInfo {
+	/// Process ID.
+	pub pid:             i32,
+	/// Parent process ID.
+	pub ppid:            Option<i32>,
+	/// Short process name.
+	pub name:            String,
+	/// Command line arguments, starting with the program.
+	pub argv:            Vec<String>,
+	/// Working directory, when readable.
+	pub cwd:             Option<String>,
+	/// Scheduling state.
+	pub state:           ProcessState,
+	/// Resident memory in bytes.
+	pub rss_bytes:       Option<f64>,
+	/// User and system CPU time in milliseconds.
+	pub cpu_time_ms:     Option<f64>,
+	/// Start time in milliseconds since the Unix epoch.
+	pub started_at:      Option<f64>,
+	/// TCP sockets the process listens on.
+	pub listening_ports: Vec<ProcessPort>,
+	/// Child processes, each with its own descendants.
+	#[allow(clippy::use_self, reason = "napi derives the TypeScript type from the written name")]
+	pub children:        Vec<ProcessInfo>,
+}
+
+/// Describe `pid` and all its descendants.
+///
+/// Returns `None` when the process does not exist. Details that cannot be
+/// read (another user's process, a process that exits meanwhile) are left
+/// unset rather than failing the whole tree.
+#[napi]
+pub fn process_tree(pid: i32) -> Option<ProcessInfo> {
+	let inspector = Inspector::new();
+	build(&inspector, pid, None)
+}
+
+fn build(inspector: &Inspector, pid: i32, parent: Option<i32>) -> Option<ProcessInfo> {
+	let mut info = inspector.describe(pid)?;
+	info.ppid = info.ppid.or(parent);
+	info.children = platform::children(pid)
+		.into_iter()
+		.filter_map(|child_pid| build(inspector, child_pid, Some(pid)))
+		.collect();
+	Some(info)
+}
+
+#[cfg(target_os = "linux")]
+use linux::Inspector;
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use std::{
+		cell::RefCell,
+		collections::HashMap,
+		fs,
+		net::{Ipv4Addr, Ipv6Addr},
+		path::PathBuf,
+	};
+
+	use super::{ProcessInfo, ProcessPort, ProcessState};
+
+	/// TCP state code of a listening socket in `/proc/{pid}/net/tcp`.
+	const TCP_LISTEN: &str = "0A";
+
+	/// System-wide values needed to interpret `/proc/{pid}` files, read once
+	/// per tree.
+	pub struct Inspector {
+		clock_ticks: f64,
+		page_size:   f64,
+		boot_time:   Option<f64>,
+		/// Listening sockets by inode, per network namespace; each namespace's
+		/// tables are read the first time one of its processes has a socket.
+		listeners:   RefCell<HashMap<PathBuf, HashMap<u64, ProcessPort>>>,
+	}
+
+	impl Inspector {
+		pub fn new() -> Self {
+			// SAFETY: sysconf has no preconditions.
+			let (clock_ticks, page_size) =
+				unsafe { (libc::sysconf(libc::_SC_CLK_TCK), libc::sysconf(libc::_SC_PAGESIZE)) };
+			let boot_time = fs::read_to_string("/proc/stat").ok().and_then(|stat| {
+				stat
+					.lines()
+					.find_map(|line| line.strip_prefix("btime "))
+					.and_then(|value| value.trim().parse().ok())
+			});
+			Self {
+				clock_ticks: if clock_ticks > 0 {
+					clock_ticks as f64
+				} else {
+					100.0
+				},
+				page_size: if page_size > 0 {
+					page_size as f64
+				} else {
+					4096.0
+				},
+				boot_time,
+				listeners: RefCell::default(),
+			}
+		}
+
+		pub fn describe(&self, pid: i32) -> Option<ProcessInfo> {
+			let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+			// The name may itself contain spaces and parentheses.
+			let name_start = stat.find('(')?;
+			let name_end = stat.rfind(')')?;
+			let name = stat[name_start + 1..name_end].to_string();
+			let fields: Vec<&str> = stat[name_end + 1..].split_whitespace().collect();
+			let field = |index: usize| {
+				fields
+					.get(index)
+					.and_then(|value| value.parse::<f64>().ok())
+			};
+
+			let ticks = |index| field(index).map(|ticks| ticks / self.clock_ticks);
+			let cpu_time_ms = ticks(11)
+				.zip(ticks(12))
+				.map(|(user, system)| (user + system) * 1000.0);
+			let started_at = self
+				.boot_time
+				.zip(ticks(19))
+				.map(|(boot, since_boot)| (boot + since_boot) * 1000.0);
+
+			let argv = fs::read(format!("/proc/{pid}/cmdline"))
+				.map(|cmdline| {
+					cmdline
+						.split(|&byte| byte == 0)
+						.filter(|arg| !arg.is_empty())
+						.map(|arg| String::from_utf8_lossy(arg).into_owned())
+						.collect()
+				})
+				.unwrap_or_default();
+			let cwd = fs::read_link(format!("/proc/{pid}/cwd"))
+				.ok()
+				.map(|cwd| cwd.to_string_lossy().into_owned());
+
+			Some(ProcessInfo {
+				pid,
+				ppid: field(1).map(|ppid| ppid as i32),
+				name,
+				argv,
+				cwd,
+				state: fields
+					.first()
+					.map_or(ProcessState::Unknown, |state| parse_state(state)),
+				rss_bytes: field(21).map(|pages| pages * self.page_size),
+				cpu_time_ms,
+				started_at,
+				listening_ports: self.listening_ports(pid),
+				children: Vec::new(),
+			})
+		}
+
+		fn listening_ports(&self, pid: i32) -> Vec<ProcessPort> {
+			let Ok(fds) = fs::read_dir(format!("/proc/{pid}/fd")) else {
+				return Vec::new();
+			};
+			let sockets: Vec<u64> = fds
+				.filter_map(|fd| fs::read_link(fd.ok()?.path()).ok())
+				.filter_map(|target| {
+					let target = target.to_str()?;
+					let inode = target.strip_prefix("socket:[")?.strip_suffix(']')?;
+					inode.parse().ok()
+				})
+				.collect();
+			if sockets.is_empty() {
+				return Vec::new();
+			}
+			// `/proc/net` only shows the sockets of our own namespace.
+			let Ok(namespace) = fs::read_link(format!("/proc/{pid}/ns/net")) else {
+				return Vec::new();
+			};
+			let mut listeners = self.listeners.borrow_mut();
+			let listeners = listeners.entry(namespace).or_insert_with(|| {
+				["tcp", "tcp6"]
+					.into_iter()
+					.filter_map(|table| fs::read_to_string(format!("/proc/{pid}/net/{table}")).ok())
+					.flat_map(|content| parse_listeners(&content).collect::<Vec<_>>())
+					.collect()
+			});
+			let mut ports: Vec<ProcessPort> = sockets
+				.iter()
+				.filter_map(|inode| listeners.get(inode).cloned())
+				.collect();
+			ports.sort_by(|a, b| (a.port, &a.address).cmp(&(b.port, &b.address)));
+			ports.dedup();
+			ports
+		}
+	}
+
+	fn parse_state(state: &str) -> ProcessState {
+		match state {
+			"R" => ProcessState::Running,
+			"S" => ProcessState::Sleeping,
+			"D" => ProcessState::DiskSleep,
+			"T" | "t" => ProcessState::Stopped,
+			"Z" => ProcessState::Zombie,
+			"I" => ProcessState::Idle,
+			_ => ProcessState::Unknown,
+		}
+	}
+
+	/// Listening sockets of a `/proc/{pid}/net/tcp` or `tcp6` table, keyed by
+	/// inode.
+	pub(super) fn parse_listeners(table: &str) -> impl Iterator<Item = (u64, ProcessPort)> + '_ {
+		table.lines().skip(1).filter_map(|line| {
+			let columns: Vec<&str> = line.split_whitespace().collect();
+			if columns.get(3) != Some(&TCP_LISTEN) {
+				return None;
+			}
+			let (address, port) = columns.get(1)?.split_once(':')?;
+			let port = ProcessPort {
+				address: parse_address(address)?,
+				port:    u16::from_str_radix(port, 16).ok()?,
+			};
+			Some((columns.get(9)?.parse().ok()?, port))
+		})
+	}
+
+	/// Decode an address as the kernel prints it: 32-bit words in host byte
+	/// order, as hex.
+	fn parse_address(hex: &str) -> Option<String> {
+		let words = (0..hex.len() / 8)
+			.map(|index| u32::from_str_radix(hex.get(index * 8..index * 8 + 8)?, 16).ok())
+			.collect::<Option<Vec<u32>>>()?;
+		let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+		match bytes.len() {
+			4 => Some(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?).to_string()),
+			16 => Some(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).to_string()),
+			_ => None,
+		}
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Inspector;
+
+#[cfg(not(target_os = "linux"))]
+impl Inspector {
+	const fn new() -> Self {
+		Self
+	}
+
+	fn describe(&self, pid: i32) -> Option<ProcessInfo> {
+		let name = platform::process_name(pid)?;
+		Some(ProcessInfo { pid, name, ..Default::default() })
+	}
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+	use std::{net::TcpListener, process::Command};
+
+	use super::*;
+
+	#[test]
+	fn describes_children_and_listening_ports() {
+		let listener = TcpListener::bind(("127.0.0.1", 0)).expect("listener should bind");
+		let port = listener
+			.local_addr()
+			.expect("listener should have an address")
+			.port();
+		let mut child = Command::new("sleep")
+			.arg("30")
+			.spawn()
+			.expect("sleep should spawn");
+		let child_pid = child.id() as i32;
+
+		let tree = process_tree(std::process::id() as i32).expect("test process should exist");
+		let _ = child.kill();
+		let _ = child.wait();
+
+		assert!(
+			tree
+				.listening_ports
+				.contains(&ProcessPort { address: "127.0.0.1".to_string(), port })
+		);
+		assert_eq!(
+			tree.cwd.as_deref(),
+			std::env::current_dir()
+				.ok()
+				.as_deref()
+				.and_then(|cwd| cwd.to_str())
+		);
+		assert!(tree.rss_bytes.is_some_and(|rss| rss > 0.0));
+		assert!(tree.started_at.is_some_and(|started| started > 0.0));
+
+		let sleep = tree
+			.children
+			.iter()
+			.find(|child| child.pid == child_pid)
+			.expect("sleep should be listed");
+		assert_eq!(sleep.name, "sleep");
+		assert_eq!(sleep.argv, ["sleep", "30"]);
+		assert_eq!(sleep.ppid, Some(tree.pid));
+		assert!(matches!(sleep.state, ProcessState::Sleeping | ProcessState::Running));
+
+		assert!(process_tree(i32::MAX).is_none());
+	}
+
+	#[test]
+	fn reads_ports_from_the_network_namespace_of_each_process() {
+		use std::os::unix::process::CommandExt;
+
+		const PORT: u16 = 47_321;
+		let mut command = Command::new("sleep");
+		command.arg("30");
+		// SAFETY: only async-signal-safe calls run between fork and exec; the
+		// listening socket is inherited by `sleep`.
+		unsafe {
+			command.pre_exec(|| {
+				if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) == -1 {
+					return Err(std::io::Error::last_os_error());
+				}
+				let fd = libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+				let address = libc::sockaddr_in {
+					sin_family: libc::AF_INET as libc::sa_family_t,
+					sin_port:   PORT.to_be(),
+					sin_addr:   libc::in_addr { s_addr: 0 },
+					sin_zero:   [0; 8],
+				};
+				let length = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+				if fd == -1
+					|| libc::bind(fd, (&raw const address).cast(), length) == -1
+					|| libc::listen(fd, 1) == -1
+				{
+					return Err(std::io::Error::last_os_error());
+				}
+				Ok(())
+			});
+		}
+		// Namespaces may be disabled where the tests run.
+		let Ok(mut child) = command.spawn() else {
+			return;
+		};
+
+		let sleep = process_tree(child.id() as i32);
+		let _ = child.kill();
+		let _ = child.wait();
+		assert_eq!(sleep.expect("sleep should exist").listening_ports, [ProcessPort {
+			address: "0.0.0.0".to_string(),
+			port:    PORT,
+		}]);
+	}
+
+	#[test]
+	fn parses_listening_sockets() {
+		let table =
+			"  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  \
+			 timeout inode\n   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 \
+			 00000000  1000        0 4242 1 0000000000000000 100 0 0 10 0\n   1: 0100007F:A2C4 \
+			 0100007F:1F90 01 00000000:00000000 00:00000000 00000000  1000        0 4343 1 \
+			 0000000000000000 20 4 30 10 -1\n";
+		let listeners: Vec<_> = linux::parse_listeners(table).collect();
+		assert_eq!(listeners, [(4242, ProcessPort {
+			address: "127.0.0.1".to_string(),
+			port:    8080,
+		})]);
+
+		let table6 = "header\n   0: 00000000000000000000000001000000:0050 \
+		              00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 \
+		              00000000 0 0 77 1\n";
+		let listeners: Vec<_> = linux::parse_listeners(table6).collect();
+		assert_eq!(listeners, [(77, ProcessPort { address: "::1".to_string(), port: 80 })]);
+	}
+}
@@ -0,0 +1,238 @@
+//! Escalating termination of process trees.
+//!
+//! Sends a polite signal to a tree and the process groups it leads, waits for
+//! it to exit while signalling processes forked in the meantime, then kills
+//! whatever is left. Windows has no polite signal, so there the first stage
+//! already terminates.
+
+use std::{
+	thread,
+	time::{Duration, Instant},
+};
+
+use napi_derive::napi;
+
+use super::platform;
+use crate::task;
+
+/// Signal sent first when `signal` is not set (SIGTERM).
+const DEFAULT_SIGNAL: i32 = 15;
+const KILL_SIGNAL: i32 = 9;
+/// Grace period used when `grace_ms` is not set.
+const DEFAULT_GRACE_MS: u32 = 2000;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long killed processes get to disappear before they count as
+/// survivors.
+const KILL_WAIT: Duration = Duration::from_millis(500);
+/// How long commands cancelled through `Shell` or killed through `PtySession`
+/// get to exit after SIGTERM before SIGKILL.
+pub const TERMINATE_GRACE: Duration = Duration::from_millis(500);
+
+/// Options for `terminate_tree`.
+#[napi(object)]
+pub struct TerminateTreeOptions {
+	/// Signal sent first (default: 15, SIGTERM). Pass 2 for SIGINT.
+	pub signal:        Option<i32>,
+	/// Milliseconds to wait for the tree to exit before sending SIGKILL
+	/// (default: 2000).
+	pub grace_ms:      Option<u32>,
+	/// Also signal the members of the process group `pid` leads (default:
+	/// true). The caller's own group is never signalled.
+	pub process_group: Option<bool>,
+}
+
+/// Which processes exited at each stage of `terminate_tree`. Each list is
+/// sorted by PID.
+#[napi(object)]
+#[derive(Debug, Default)]
+pub struct TerminateTreeResult {
+	/// Processes sent the first signal, including ones forked during the grace
+	/// period.
+	pub signalled: Vec<i32>,
+	/// Processes that exited within the grace period.
+	pub exited:    Vec<i32>,
+	/// Processes still running after the grace period, sent SIGKILL.
+	pub killed:    Vec<i32>,
+	/// Processes still running shortly after SIGKILL, usually stuck in
+	/// uninterruptible sleep.
+	pub survivors: Vec<i32>,
+}
+
+/// Terminate `pid`, its descendants and the process group it leads.
+///
+/// Sends `signal` bottom-up, then polls until every process has exited or the
+/// grace period ends, signalling descendants and group members that appear
+/// meanwhile. Whatever is still running gets SIGKILL. Zombies count as exited.
+#[napi]
+pub fn terminate_tree(
+	pid: i32,
+	options: Option<TerminateTreeOptions>,
+) -> task::Promise<TerminateTreeResult> {
+	let signal = options
+		.as_ref()
+		.and_then(|options| options.signal)
+		.unwrap_or(DEFAULT_SIGNAL);
+	let grace_ms = options
+		.as_ref()
+		.and_then(|options| options.grace_ms)
+		.unwrap_or(DEFAULT_GRACE_MS);
+	let process_group = options
+		.and_then(|options| options.process_group)
+		.unwrap_or(true);
+	task::blocking("ps.terminate_tree", (), move |_| {
+		let groups: Vec<i32> = process_group
+			.then(|| platform::process_group_id(pid))
+			.flatten()
+			.filter(|&pgid| pgid == pid)
+			.into_iter()
+			.collect();
+		Ok(terminate_trees(&[pid], &groups, signal, Duration::from_millis(grace_ms.into())))
+	})
+}
+
+/// Terminate the trees rooted at `pids` and every member of `groups`,
+/// escalating from `signal` to SIGKILL after `grace`. Blocks until the
+/// processes exit or the escalation gives up on them.
+pub fn terminate_trees(
+	pids: &[i32],
+	groups: &[i32],
+	signal: i32,
+	grace: Duration,
+) -> TerminateTreeResult {
+	let own_pid = std::process::id() as i32;
+	let own_group = platform::process_group_id(own_pid);
+	let mut groups: Vec<i32> = groups
+		.iter()
+		.copied()
+		.filter(|&pgid| pgid > 1 && Some(pgid) != own_group)
+		.collect();
+	groups.sort_unstable();
+	groups.dedup();
+
+	let mut tracker = Tracker { roots: pids, groups: &groups, own_pid, seen: Vec::new() };
+	let mut result = TerminateTreeResult::default();
+
+	let mut running = tracker.discover(&[]);
+	send(&running, &groups, signal);
+	result.signalled.clone_from(&running);
+	let deadline = Instant::now() + grace;
+	loop {
+		let (still, exited): (Vec<i32>, Vec<i32>) = running
+			.into_iter()
+			.partition(|&pid| platform::is_running(pid));
+		running = still;
+		result.exited.extend(exited);
+		let forked = tracker.discover(&running);
+		send(&forked, &[], signal);
+		result.signalled.extend_from_slice(&forked);
+		running.extend(forked);
+		if running.is_empty() || Instant::now() >= deadline {
+			break;
+		}
+		thread::sleep(POLL_INTERVAL);
+	}
+
+	if !running.is_empty() {
+		let forked = tracker.discover(&running);
+		running.extend(forked);
+		send(&running, &groups, KILL_SIGNAL);
+		result.killed.clone_from(&running);
+		let deadline = Instant::now() + KILL_WAIT;
+		while !running.is_empty() && Instant::now() < deadline {
+			thread::sleep(POLL_INTERVAL);
+			running.retain(|&pid| platform::is_running(pid));
+		}
+		result.survivors = running;
+	}
+
+	for pids in
+		[&mut result.signalled, &mut result.exited, &mut result.killed, &mut result.survivors]
+	{
+		pids.sort_unstable();
+	}
+	result
+}
+
+/// Finds the running processes a termination covers.
+struct Tracker<'a> {
+	roots:   &'a [i32],
+	groups:  &'a [i32],
+	own_pid: i32,
+	seen:    Vec<i32>,
+}
+
+impl Tracker<'_> {
+	/// Running processes not seen before: the roots, descendants of the roots
+	/// and of `running`, and group members. Descendants come before their
+	/// ancestors so signals go bottom-up.
+	fn discover(&mut self, running: &[i32]) -> Vec<i32> {
+		let mut candidates = Vec::new();
+		for &pid in self.roots.iter().chain(running) {
+			let mut tree = vec![pid];
+			platform::collect_descendants(pid, &mut tree);
+			candidates.extend(tree.into_iter().rev());
+		}
+		for &pgid in self.groups {
+			candidates.extend(platform::group_members(pgid));
+		}
+
+		let mut found = Vec::new();
+		for pid in candidates {
+			if pid > 0 && pid != self.own_pid && !self.seen.contains(&pid) && platform::is_running(pid)
+			{
+				self.seen.push(pid);
+				found.push(pid);
+			}
+		}
+		found
+	}
+}
+
+/// Send `signal` to the groups first, so members cannot fork past it, then to
+/// each process in order.
+fn send(pids: &[i32], groups: &[i32], signal: i32) {
+	for &pgid in groups {
+		let _ = platform::kill_process_group(pgid, signal);
+	}
+	for &pid in pids {
+		let _ = platform::kill_pid(pid, signal);
+	}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use std::process::{Command, Stdio};
+
+	use super::*;
+
+	#[test]
+	fn escalates_to_kill_for_processes_ignoring_the_signal() {
+		let mut child = Command::new("sh")
+			.args(["-c", "trap '' TERM; sleep 30 & sleep 30 & wait"])
+			.stdin(Stdio::null())
+			.spawn()
+			.expect("spawn sh");
+		let pid = child.id() as i32;
+		let deadline = Instant::now() + Duration::from_secs(5);
+		let mut descendants = Vec::new();
+		while descendants.len() < 2 && Instant::now() < deadline {
+			thread::sleep(POLL_INTERVAL);
+			descendants.clear();
+			platform::collect_descendants(pid, &mut descendants);
+		}
+		assert_eq!(descendants.len(), 2, "both sleeps should start");
+
+		let result = terminate_trees(&[pid], &[], DEFAULT_SIGNAL, Duration::from_millis(200));
+		let _ = child.wait();
+
+		let mut all = descendants.clone();
+		all.push(pid);
+		all.sort_unstable();
+		assert_eq!(result.signalled, all);
+		// The backgrounded sleeps inherit the ignored TERM, so nothing exits
+		// politely.
+		assert_eq!(result.exited, Vec::<i32>::new());
+		assert_eq!(result.killed, all);
+		assert!(result.survivors.is_empty());
+	}
+}
@@ -12,52 +12,102 @@ use std::{
 	time::{Duration, Instant},
 };
 
+mod expect;
+mod ready;
+mod recording;
+mod screen;
+
+use expect::{PtyExpectOptions, PtyExpectResult, Script, Transcript};
 use napi::{
 	bindgen_prelude::*,
 	threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
 };
 use napi_derive::napi;
-use portable_pty::{Child, CommandBuilder, PtySize, native_pty_system};
+use portable_pty::{
+	Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtySize, native_pty_system,
+};
+use ready::{PtyReadyOptions, PtyReadyResult};
+use recording::{PtyRecordOptions, Recording};
+use screen::{PtyScreenSnapshot, PtySnapshotOptions, Screen};
+use tokio_util::sync::CancellationToken;
 
-use crate::task;
+use crate::{
+	capture::{OutputCapture, OutputLimit},
+	ps::TERMINATE_GRACE,
+	sandbox::{ProcessJail, ResourceLimits, SandboxOptions},
+	shell::{Readiness, ShellBlockedCommand, ShellOptions, ShellRunConfig},
+	task,
+};
 
 /// Options for running a command in a PTY session.
 #[napi(object)]
 pub struct PtyStartOptions<'env> {
 	/// Command string to execute.
-	pub command:    String,
+	pub command:      String,
 	/// Working directory for command execution.
-	pub cwd:        Option<String>,
+	pub cwd:          Option<String>,
 	/// Environment variables for this command.
-	pub env:        Option<HashMap<String, String>>,
+	pub env:          Option<HashMap<String, String>>,
 	/// Timeout in milliseconds before cancelling.
-	pub timeout_ms: Option<u32>,
+	pub timeout_ms:   Option<u32>,
 	/// Abort signal for cancelling the operation.
-	pub signal:     Option<Unknown<'env>>,
+	pub signal:       Option<Unknown<'env>>,
 	/// PTY column count.
-	pub cols:       Option<u16>,
+	pub cols:         Option<u16>,
 	/// PTY row count.
-	pub rows:       Option<u16>,
+	pub rows:         Option<u16>,
+	/// Byte budget and head/tail retention applied before output reaches the
+	/// callback.
+	pub output_limit: Option<OutputLimit>,
+	/// Resource limits for the command and everything it spawns.
+	pub limits:       Option<ResourceLimits>,
+	/// Run the command in an isolated sandbox (Linux only). With `shell`,
+	/// builtins run unconfined in this process, except that files the shell
+	/// opens for output redirections must lie in the writable paths.
+	pub sandbox:      Option<SandboxOptions>,
+	/// Run the command through a fresh native shell session configured like
+	/// `Shell` (session env, snapshot, policy, history) instead of `sh -lc`
+	/// (Unix only). The PTY is the session's stdin, stdout and stderr, and the
+	/// controlling terminal of the foreground programs it spawns.
+	pub shell:        Option<ShellOptions>,
+	/// Record output, input and resizes for export as an asciicast.
+	pub record:       Option<PtyRecordOptions>,
 }
 
 /// Result of a PTY command run.
 #[napi(object)]
 pub struct PtyRunResult {
 	/// Exit code when the command completes.
-	pub exit_code: Option<i32>,
+	pub exit_code:    Option<i32>,
 	/// Whether command was cancelled by signal/user kill.
-	pub cancelled: bool,
+	pub cancelled:    bool,
 	/// Whether command timed out.
-	pub timed_out: bool,
+	pub timed_out:    bool,
+	/// Bytes of output produced by the command.
+	pub total_bytes:  f64,
+	/// Bytes dropped from the middle of the output by `output_limit`.
+	pub elided_bytes: f64,
+	/// Temp file holding the full output when `output_limit.spill` was set and
+	/// output was elided.
+	pub output_path:  Option<String>,
+	/// Command refused by the `shell` session's policy; the run stops at the
+	/// first refusal.
+	pub blocked:      Option<ShellBlockedCommand>,
 }
 
-#[derive(Clone)]
+/// Yields the command a `shell` run's policy refused.
+type BlockedReceiver = tokio::sync::oneshot::Receiver<ShellBlockedCommand>;
+
 struct PtyRunConfig {
-	command: String,
-	cwd:     Option<String>,
-	env:     Option<HashMap<String, String>>,
-	cols:    u16,
-	rows:    u16,
+	command:      String,
+	cwd:          Option<String>,
+	env:          Option<HashMap<String, String>>,
+	cols:         u16,
+	rows:         u16,
+	output_limit: Option<OutputLimit>,
+	limits:       Option<ResourceLimits>,
+	sandbox:      Option<SandboxOptions>,
+	shell:        Option<ShellOptions>,
 }
 
 enum ReaderEvent {
@@ -77,7 +127,6 @@ const POST_CANCEL_DRAIN_TIMEOUT: Duration = Duration::from_millis(300);
 const POST_EXIT_DRAIN_TIMEOUT: Duration = Duration::from_millis(300);
 const FINAL_READER_DRAIN_TIMEOUT: Duration = Duration::from_millis(50);
 const TERM_SIGNAL: i32 = 15;
-const KILL_SIGNAL: i32 = 9;
 
 struct PtySessionCore {
 	control_tx: mpsc::Sender<ControlMessage>,
@@ -86,7 +135,31 @@ struct PtySessionCore {
 /// Stateful PTY session for interactive stdin/stdout passthrough.
 #[napi]
 pub struct PtySession {
-	core: Arc<Mutex<Option<PtySessionCore>>>,
+	core:     Arc<Mutex<Option<PtySessionCore>>>,
+	/// Output views of the current or most recent command.
+	terminal: Arc<Mutex<Option<Terminal>>>,
+}
+
+/// What a session keeps of a command's output: the emulated screen, output
+/// awaiting expect steps, and the recording when one was requested. `cwd`
+/// resolves the paths of readiness conditions.
+#[derive(Clone)]
+struct Terminal {
+	screen:     Screen,
+	transcript: Transcript,
+	recording:  Option<Recording>,
+	cwd:        std::path::PathBuf,
+}
+
+impl Terminal {
+	fn new(rows: u16, cols: u16, recording: Option<Recording>) -> Self {
+		Self {
+			screen: Screen::new(rows, cols),
+			transcript: Transcript::default(),
+			recording,
+			cwd: std::env::current_dir().unwrap_or_default(),
+		}
+	}
 }
 
 impl Default for PtySession {
@@ -99,10 +172,13 @@ impl Default for PtySession {
 impl PtySession {
 	#[napi(constructor)]
 	pub fn new() -> Self {
-		Self { core: Arc::new(Mutex::new(None)) }
+		Self { core: Arc::new(Mutex::new(None)), terminal: Arc::new(Mutex::new(None)) }
 	}
 
 	/// Start a PTY command and stream output chunks via callback.
+	///
+	/// The command's stdout and stderr share the PTY slave, so chunks arrive as
+	/// one untagged terminal stream.
 	#[napi]
 	pub fn start<'env>(
 		&self,
@@ -112,11 +188,15 @@ impl PtySession {
 		on_chunk: Option<ThreadsafeFunction<String>>,
 	) -> Result<PromiseRaw<'env, PtyRunResult>> {
 		let run_config = PtyRunConfig {
-			command: options.command,
-			cwd:     options.cwd,
-			env:     options.env,
-			cols:    options.cols.unwrap_or(120).clamp(20, 400),
-			rows:    options.rows.unwrap_or(40).clamp(5, 200),
+			command:      options.command,
+			cwd:          options.cwd,
+			env:          options.env,
+			cols:         options.cols.unwrap_or(120).clamp(20, 400),
+			rows:         options.rows.unwrap_or(40).clamp(5, 200),
+			output_limit: options.output_limit,
+			limits:       options.limits,
+			sandbox:      options.sandbox,
+			shell:        options.shell,
 		};
 		let ct = task::CancelToken::new(options.timeout_ms, options.signal);
 		let core = Arc::clone(&self.core);
@@ -132,10 +212,24 @@ impl PtySession {
 			}
 			*guard = Some(PtySessionCore { control_tx });
 		}
+		let recording = options.record.map(|record| {
+			Recording::new(&record, &run_config.command, run_config.cols, run_config.rows)
+		});
+		let mut terminal = Terminal::new(run_config.rows, run_config.cols, recording);
+		if let Some(cwd) = run_config.cwd.as_deref() {
+			terminal.cwd = terminal.cwd.join(cwd);
+		}
+		*self
+			.terminal
+			.lock()
+			.map_err(|_| Error::from_reason("PTY session lock poisoned"))? = Some(terminal.clone());
 		task::future(env, "pty.start", async move {
-			let run_result =
-				tokio::task::spawn_blocking(move || run_pty_sync(run_config, on_chunk, control_rx, ct))
-					.await;
+			let run_result = tokio::task::spawn_blocking(move || {
+				let result = run_pty_sync(run_config, on_chunk, control_rx, &terminal, ct);
+				terminal.transcript.close();
+				result
+			})
+			.await;
 
 			// Always clear core regardless of result
 			let mut guard = core
@@ -171,9 +265,96 @@ impl PtySession {
 	pub fn kill(&self) -> Result<()> {
 		self.send_control(ControlMessage::Kill)
 	}
+
+	/// What the terminal currently shows, as interpreted by a VT100/xterm
+	/// emulator fed with the command's output.
+	///
+	/// Remains available after the command exits, until the next `start`.
+	#[napi]
+	pub fn snapshot(&self, options: Option<PtySnapshotOptions>) -> Result<PtyScreenSnapshot> {
+		let attributes = options
+			.and_then(|options| options.attributes)
+			.unwrap_or(false);
+		Ok(self.terminal()?.screen.snapshot(attributes))
+	}
+
+	/// The recording of the current or most recent command as an asciicast v2
+	/// document. Available once `start` was called with `record`, including
+	/// after the command was killed or timed out.
+	#[napi]
+	pub fn recording(&self) -> Result<String> {
+		self
+			.terminal()?
+			.recording
+			.map(|recording| recording.to_asciicast())
+			.ok_or_else(|| Error::from_reason("PTY session is not recording"))
+	}
+
+	/// Write the recording to `path` as an asciicast v2 file.
+	#[napi]
+	pub fn save_recording(&self, path: String) -> Result<()> {
+		std::fs::write(&path, self.recording()?)
+			.map_err(|err| Error::from_reason(format!("Failed to write recording {path}: {err}")))
+	}
+
+	/// Run an expect script: wait for output or the screen to match, and send
+	/// input once it does, step by step.
+	///
+	/// Output that arrived before the call but was not matched by an earlier
+	/// script is searched too, so a prompt printed right after `start` is not
+	/// missed.
+	#[napi]
+	pub fn expect(&self, options: PtyExpectOptions) -> Result<task::Promise<PtyExpectResult>> {
+		let script = Script::new(options.steps)?;
+		let ct = task::CancelToken::new(options.timeout_ms, options.signal);
+		let Terminal { screen, transcript, .. } = self.terminal()?;
+		let control_tx = self
+			.core
+			.lock()
+			.map_err(|_| Error::from_reason("PTY session lock poisoned"))?
+			.as_ref()
+			.map(|core| core.control_tx.clone());
+		Ok(task::blocking("pty.expect", ct, move |ct| {
+			let send = |data| {
+				control_tx
+					.as_ref()
+					.is_some_and(|tx| tx.send(ControlMessage::Input(data)).is_ok())
+			};
+			Ok(script.run(&transcript, &screen, send, &ct))
+		}))
+	}
+
+	/// Wait until the command is ready: its output matches `ready.pattern`, a
+	/// port on 127.0.0.1 accepts connections, and/or a file exists. Resolves
+	/// early when the command's output ends or the wait times out; the
+	/// command keeps running either way.
+	///
+	/// Output is only inspected, so a later expect script still sees it.
+	#[napi]
+	pub fn wait_ready<'env>(
+		&self,
+		env: &'env Env,
+		options: PtyReadyOptions<'env>,
+	) -> Result<PromiseRaw<'env, PtyReadyResult>> {
+		let Terminal { transcript, cwd, .. } = self.terminal()?;
+		let readiness = Readiness::new(options.ready, &cwd)?;
+		let ct = task::CancelToken::new(options.timeout_ms, options.signal);
+		task::future(env, "pty.waitReady", async move {
+			Ok(ready::wait(&readiness, &transcript, ct).await)
+		})
+	}
 }
 
 impl PtySession {
+	fn terminal(&self) -> Result<Terminal> {
+		self
+			.terminal
+			.lock()
+			.map_err(|_| Error::from_reason("PTY session lock poisoned"))?
+			.clone()
+			.ok_or_else(|| Error::from_reason("PTY session has not been started"))
+	}
+
 	fn send_control(&self, message: ControlMessage) -> Result<()> {
 		let guard = self
 			.core
@@ -189,52 +370,237 @@ impl PtySession {
 	}
 }
 
-#[cfg(unix)]
+/// Start terminating the command in the background: SIGTERM to its tree and
+/// process group, SIGKILL to whatever is left after [`TERMINATE_GRACE`].
 fn terminate_pty_processes(
 	child: &mut Box<dyn Child + Send + Sync>,
 	child_pid: Option<i32>,
 	process_group_id: Option<i32>,
 ) {
-	if let Some(pgid) = process_group_id {
-		let _ = crate::ps::kill_process_group(pgid, TERM_SIGNAL);
+	// Commands run through a shell session have no process of their own; the
+	// session terminates what it spawned.
+	let Some(pid) = child_pid else {
+		let _ = child.kill();
+		return;
+	};
+	let groups: Vec<i32> = process_group_id.into_iter().collect();
+	std::thread::spawn(move || {
+		crate::ps::terminate_trees(&[pid], &groups, TERM_SIGNAL, TERMINATE_GRACE)
+	});
+}
+
+/// Directory `cmd` starts in: its cwd when that is a directory, otherwise
+/// `HOME`, as portable-pty resolves it.
+fn command_dir(cmd: &CommandBuilder) -> std::path::PathBuf {
+	cmd.get_cwd()
+		.map(std::path::PathBuf::from)
+		.filter(|dir| dir.is_dir())
+		.or_else(|| cmd.get_env("HOME").map(std::path::PathBuf::from))
+		.unwrap_or_else(|| "/".into())
+}
+
+/// Path of the PTY's slave side.
+#[cfg(unix)]
+fn tty_name(master: &dyn MasterPty) -> Result<std::path::PathBuf> {
+	master
+		.tty_name()
+		.ok_or_else(|| Error::from_reason("Failed to spawn PTY command: PTY has no name"))
+}
+
+/// Open the PTY's slave side without making it our controlling terminal.
+#[cfg(unix)]
+fn open_tty(tty_name: &std::path::Path) -> Result<std::fs::File> {
+	use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt};
+
+	OpenOptions::new()
+		.read(true)
+		.write(true)
+		.custom_flags(libc::O_NOCTTY)
+		.open(tty_name)
+		.map_err(|err| Error::from_reason(format!("Failed to spawn PTY command: {err}")))
+}
+
+/// Spawn `cmd` on the PTY like `SlavePty::spawn_command`, with `jail` applied
+/// before exec. portable-pty has no pre-exec hook, so this repeats its setup:
+/// default signal handling, a new session and the PTY as controlling terminal.
+#[cfg(unix)]
+fn spawn_jailed(
+	master: &dyn MasterPty,
+	cmd: &CommandBuilder,
+	jail: &ProcessJail,
+) -> Result<Box<dyn Child + Send + Sync>> {
+	use std::{
+		os::unix::process::CommandExt,
+		process::{Command, Stdio},
+	};
+
+	let spawn_err =
+		|err: std::io::Error| Error::from_reason(format!("Failed to spawn PTY command: {err}"));
+	let tty = open_tty(&tty_name(master)?)?;
+	let stdio = || tty.try_clone().map(Stdio::from).map_err(spawn_err);
+
+	let argv = cmd.get_argv();
+	let mut command = Command::new(&argv[0]);
+	command
+		.args(&argv[1..])
+		.current_dir(command_dir(cmd))
+		.env_clear()
+		.env("SHELL", cmd.get_shell())
+		.envs(cmd.iter_full_env_as_str())
+		.stdin(stdio()?)
+		.stdout(stdio()?)
+		.stderr(stdio()?);
+	// SAFETY: only async-signal-safe calls between fork and exec.
+	unsafe {
+		command.pre_exec(|| {
+			for signo in
+				[libc::SIGCHLD, libc::SIGHUP, libc::SIGINT, libc::SIGQUIT, libc::SIGTERM, libc::SIGALRM]
+			{
+				libc::signal(signo, libc::SIG_DFL);
+			}
+			let empty_set: libc::sigset_t = std::mem::zeroed();
+			libc::sigprocmask(libc::SIG_SETMASK, &raw const empty_set, std::ptr::null_mut());
+			if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+				return Err(std::io::Error::last_os_error());
+			}
+			Ok(())
+		});
 	}
+	jail.attach(&mut command);
+	Ok(Box::new(command.spawn().map_err(spawn_err)?))
+}
+
+#[cfg(not(unix))]
+fn spawn_jailed(
+	_master: &dyn MasterPty,
+	_cmd: &CommandBuilder,
+	_jail: &ProcessJail,
+) -> Result<Box<dyn Child + Send + Sync>> {
+	// `ProcessJail::new` refuses every option off unix.
+	Err(Error::from_reason("Resource limits are not supported on this platform"))
+}
+
+/// Command running in an in-process shell session, exposed as a [`Child`] so
+/// the PTY loop can poll and kill it like a spawned process.
+#[derive(Debug)]
+struct ShellChild {
+	exit:   tokio::sync::oneshot::Receiver<Result<i32>>,
+	status: Option<ExitStatus>,
+	cancel: CancellationToken,
+}
 
-	if let Some(pid) = child_pid {
-		let _ = crate::ps::kill_tree(pid, TERM_SIGNAL);
+impl ShellChild {
+	fn finish(&mut self, result: Result<i32>) -> std::io::Result<ExitStatus> {
+		let code = result.map_err(|err| std::io::Error::other(err.reason.clone()))?;
+		let status = ExitStatus::with_exit_code(code.unsigned_abs());
+		self.status = Some(status.clone());
+		Ok(status)
+	}
+}
+
+impl Child for ShellChild {
+	fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+		if self.status.is_some() {
+			return Ok(self.status.clone());
+		}
+		match self.exit.try_recv() {
+			Ok(result) => self.finish(result).map(Some),
+			Err(tokio::sync::oneshot::error::TryRecvError::Empty) => Ok(None),
+			Err(tokio::sync::oneshot::error::TryRecvError::Closed) => {
+				Err(std::io::Error::other("shell session ended without an exit status"))
+			},
+		}
 	}
 
-	let _ = child.kill();
+	fn wait(&mut self) -> std::io::Result<ExitStatus> {
+		loop {
+			if let Some(status) = self.try_wait()? {
+				return Ok(status);
+			}
+			std::thread::sleep(Duration::from_millis(16));
+		}
+	}
 
-	if let Some(pgid) = process_group_id {
-		let _ = crate::ps::kill_process_group(pgid, KILL_SIGNAL);
+	fn process_id(&self) -> Option<u32> {
+		None
 	}
 
-	if let Some(pid) = child_pid {
-		let _ = crate::ps::kill_tree(pid, KILL_SIGNAL);
+	#[cfg(windows)]
+	fn as_raw_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+		None
 	}
 }
 
-#[cfg(not(unix))]
-fn terminate_pty_processes(
-	child: &mut Box<dyn Child + Send + Sync>,
-	child_pid: Option<i32>,
-	_process_group_id: Option<i32>,
-) {
-	if let Some(pid) = child_pid {
-		let _ = crate::ps::kill_tree(pid, TERM_SIGNAL);
+impl ChildKiller for ShellChild {
+	fn kill(&mut self) -> std::io::Result<()> {
+		self.cancel.cancel();
+		Ok(())
+	}
+
+	fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+		Box::new(ShellKiller(self.cancel.clone()))
 	}
+}
+
+#[derive(Debug)]
+struct ShellKiller(CancellationToken);
 
-	let _ = child.kill();
+impl ChildKiller for ShellKiller {
+	fn kill(&mut self) -> std::io::Result<()> {
+		self.0.cancel();
+		Ok(())
+	}
 
-	if let Some(pid) = child_pid {
-		let _ = crate::ps::kill_tree(pid, KILL_SIGNAL);
+	fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+		Box::new(Self(self.0.clone()))
 	}
 }
 
+/// Run `run_config` through a native shell session attached to the PTY. The
+/// receiver yields the command the policy refused, before the child exits.
+#[cfg(unix)]
+fn spawn_in_shell(
+	master: &dyn MasterPty,
+	options: ShellOptions,
+	run_config: ShellRunConfig,
+) -> Result<(Box<dyn Child + Send + Sync>, BlockedReceiver)> {
+	let tty_path = tty_name(master)?;
+	let tty = open_tty(&tty_path)?;
+	let cancel = CancellationToken::new();
+	let (exit_tx, exit) = tokio::sync::oneshot::channel();
+	let (blocked_tx, blocked) = tokio::sync::oneshot::channel();
+	tokio::spawn({
+		let cancel = cancel.clone();
+		async move {
+			let result =
+				crate::shell::run_on_terminal(options, run_config, tty, tty_path, cancel).await;
+			let _ = exit_tx.send(result.map(|(code, refused)| {
+				if let Some(refused) = refused {
+					let _ = blocked_tx.send(refused);
+				}
+				code
+			}));
+		}
+	});
+	Ok((Box::new(ShellChild { exit, status: None, cancel }), blocked))
+}
+
+#[cfg(not(unix))]
+fn spawn_in_shell(
+	_master: &dyn MasterPty,
+	_options: ShellOptions,
+	_run_config: ShellRunConfig,
+) -> Result<(Box<dyn Child + Send + Sync>, BlockedReceiver)> {
+	Err(Error::from_reason(
+		"Running PTY commands through the shell is not supported on this platform",
+	))
+}
+
 fn run_pty_sync(
 	config: PtyRunConfig,
 	on_chunk: Option<ThreadsafeFunction<String>>,
 	control_rx: mpsc::Receiver<ControlMessage>,
+	terminal: &Terminal,
 	ct: task::CancelToken,
 ) -> Result<PtyRunResult> {
 	let pty_system = native_pty_system();
@@ -247,22 +613,45 @@ fn run_pty_sync(
 		})
 		.map_err(|err| Error::from_reason(format!("Failed to open PTY: {err}")))?;
 
-	let mut cmd = CommandBuilder::new("sh");
-	cmd.arg("-lc");
-	cmd.arg(&config.command);
-	if let Some(cwd) = config.cwd.as_ref() {
-		cmd.cwd(cwd);
-	}
-	if let Some(env) = config.env.as_ref() {
-		for (key, value) in env {
-			cmd.env(key, value);
+	let mut blocked = None;
+	let mut child = if let Some(shell) = config.shell {
+		let run_config = ShellRunConfig {
+			command:   config.command,
+			cwd:       config.cwd,
+			env:       config.env,
+			confirmed: false,
+			tracer:    None,
+			limits:    config.limits,
+			sandbox:   config.sandbox,
+		};
+		let (child, refused) = spawn_in_shell(pair.master.as_ref(), shell, run_config)?;
+		blocked = Some(refused);
+		child
+	} else {
+		let mut cmd = CommandBuilder::new("sh");
+		cmd.arg("-lc");
+		cmd.arg(&config.command);
+		if let Some(cwd) = config.cwd.as_ref() {
+			cmd.cwd(cwd);
+		}
+		if let Some(env) = config.env.as_ref() {
+			for (key, value) in env {
+				cmd.env(key, value);
+			}
 		}
-	}
 
-	let mut child = pair
-		.slave
-		.spawn_command(cmd)
-		.map_err(|err| Error::from_reason(format!("Failed to spawn PTY command: {err}")))?;
+		let jail = ProcessJail::new(config.limits, config.sandbox.as_ref(), &command_dir(&cmd))?;
+		match jail {
+			Some(jail) => {
+				jail.probe()?;
+				spawn_jailed(pair.master.as_ref(), &cmd, &jail)?
+			},
+			None => pair
+				.slave
+				.spawn_command(cmd)
+				.map_err(|err| Error::from_reason(format!("Failed to spawn PTY command: {err}")))?,
+		}
+	};
 	drop(pair.slave);
 
 	let master = pair.master;
@@ -274,6 +663,7 @@ fn run_pty_sync(
 		.map_err(|err| Error::from_reason(format!("Failed to create PTY reader: {err}")))?;
 
 	let (reader_tx, reader_rx) = mpsc::channel::<ReaderEvent>();
+	let reader_screen = terminal.screen.clone();
 	let reader_thread = std::thread::spawn(move || {
 		const REPLACEMENT: &str = "\u{FFFD}";
 		const BUF: usize = 65536;
@@ -285,6 +675,7 @@ fn run_pty_sync(
 					break;
 				},
 				Ok(n) => {
+					reader_screen.feed(&buf[it..it + n]);
 					it += n;
 					while it > 0 {
 						let pending = &buf[..it];
@@ -341,6 +732,14 @@ fn run_pty_sync(
 	let process_group_id = master.process_group_leader().filter(|pgid| *pgid > 0);
 	#[cfg(not(unix))]
 	let process_group_id: Option<i32> = None;
+	let mut capture = OutputCapture::new(config.output_limit);
+	let mut emit = |chunk: &str| {
+		terminal.transcript.push(chunk);
+		if let Some(recording) = &terminal.recording {
+			recording.output(chunk);
+		}
+		capture.push((), chunk, |(), text| emit_chunk(text, on_chunk.as_ref()));
+	};
 	let mut timed_out = false;
 	let mut cancelled = false;
 	let mut reader_done = false;
@@ -354,24 +753,32 @@ fn run_pty_sync(
 			cancelled = !timed_out;
 			terminate_pty_processes(&mut child, child_pid, process_group_id);
 			terminate_requested = true;
-			reader_drain_deadline = Some(Instant::now() + POST_CANCEL_DRAIN_TIMEOUT);
+			reader_drain_deadline = Some(Instant::now() + TERMINATE_GRACE + POST_CANCEL_DRAIN_TIMEOUT);
 		}
 
 		for _ in 0..CONTROL_MESSAGES_PER_TICK {
 			match control_rx.try_recv() {
 				Ok(ControlMessage::Input(data)) => {
+					if let Some(recording) = &terminal.recording {
+						recording.input(&data);
+					}
 					let _ = writer.write_all(data.as_bytes());
 					let _ = writer.flush();
 				},
 				Ok(ControlMessage::Resize { cols, rows }) => {
 					let _ = master.resize(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 });
+					terminal.screen.resize(rows, cols);
+					if let Some(recording) = &terminal.recording {
+						recording.resize(cols, rows);
+					}
 				},
 				Ok(ControlMessage::Kill) => {
 					cancelled = true;
 					if !terminate_requested {
 						terminate_pty_processes(&mut child, child_pid, process_group_id);
 						terminate_requested = true;
-						reader_drain_deadline = Some(Instant::now() + POST_CANCEL_DRAIN_TIMEOUT);
+						reader_drain_deadline =
+							Some(Instant::now() + TERMINATE_GRACE + POST_CANCEL_DRAIN_TIMEOUT);
 					}
 				},
 				Err(mpsc::TryRecvError::Empty) => break,
@@ -381,7 +788,7 @@ fn run_pty_sync(
 
 		for _ in 0..READER_EVENTS_PER_TICK {
 			match reader_rx.try_recv() {
-				Ok(ReaderEvent::Chunk(chunk)) => emit_chunk(&chunk, on_chunk.as_ref()),
+				Ok(ReaderEvent::Chunk(chunk)) => emit(&chunk),
 				Ok(ReaderEvent::Done) => {
 					reader_done = true;
 					break;
@@ -416,7 +823,7 @@ fn run_pty_sync(
 					.min(Duration::from_millis(16))
 			});
 			match reader_rx.recv_timeout(wait_duration) {
-				Ok(ReaderEvent::Chunk(chunk)) => emit_chunk(&chunk, on_chunk.as_ref()),
+				Ok(ReaderEvent::Chunk(chunk)) => emit(&chunk),
 				Ok(ReaderEvent::Done) => reader_done = true,
 				Err(mpsc::RecvTimeoutError::Timeout) => {},
 				Err(mpsc::RecvTimeoutError::Disconnected) => {
@@ -453,7 +860,7 @@ fn run_pty_sync(
 			let remaining = finalize_deadline.saturating_duration_since(Instant::now());
 			let wait_duration = remaining.min(Duration::from_millis(5));
 			match reader_rx.recv_timeout(wait_duration) {
-				Ok(ReaderEvent::Chunk(chunk)) => emit_chunk(&chunk, on_chunk.as_ref()),
+				Ok(ReaderEvent::Chunk(chunk)) => emit(&chunk),
 				Ok(ReaderEvent::Done) => {
 					reader_done = true;
 					break;
@@ -472,7 +879,16 @@ fn run_pty_sync(
 	if reader_done {
 		let _ = reader_thread.join();
 	}
-	Ok(PtyRunResult { exit_code, cancelled, timed_out })
+	let summary = capture.finish(|(), text| emit_chunk(text, on_chunk.as_ref()));
+	Ok(PtyRunResult {
+		exit_code,
+		cancelled,
+		timed_out,
+		total_bytes: summary.total_bytes as f64,
+		elided_bytes: summary.elided_bytes as f64,
+		output_path: summary.output_path,
+		blocked: blocked.and_then(|mut blocked| blocked.try_recv().ok()),
+	})
 }
 
 fn emit_chunk(text: &str, callback: Option<&ThreadsafeFunction<String>>) {
@@ -480,3 +896,198 @@ fn emit_chunk(text: &str, callback: Option<&ThreadsafeFunction<String>>) {
 		callback.call(Ok(text.to_string()), ThreadsafeFunctionCallMode::NonBlocking);
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(unix)]
+	#[test]
+	fn limits_apply_to_pty_commands() {
+		let config = PtyRunConfig {
+			command:      "[ \"$(ulimit -n)\" = 64 ] && [ -t 0 ] && [ -t 1 ]".to_string(),
+			cwd:          None,
+			env:          None,
+			cols:         80,
+			rows:         24,
+			output_limit: None,
+			limits:       Some(ResourceLimits { open_files: Some(64.0), ..Default::default() }),
+			sandbox:      None,
+			shell:        None,
+		};
+		let (_control_tx, control_rx) = mpsc::channel();
+		let result = run_pty_sync(
+			config,
+			None,
+			control_rx,
+			&Terminal::new(24, 80, None),
+			task::CancelToken::new(None, None),
+		)
+		.expect("PTY command should run");
+		assert_eq!(result.exit_code, Some(0));
+	}
+
+	#[cfg(unix)]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn shell_option_runs_commands_through_a_session() {
+		let snapshot =
+			std::env::temp_dir().join(format!("pi-pty-snapshot-test-{}.sh", std::process::id()));
+		std::fs::write(&snapshot, "greet() { printf '%s, %s\\n' \"$GREETING\" \"$1\"; }\n")
+			.expect("snapshot should be written");
+		let config = PtyRunConfig {
+			command:      "greet \"$NAME\"; [ -t 0 ] && [ -t 1 ] && read line && echo \"got $line\"; \
+			               exit 7"
+				.to_string(),
+			cwd:          None,
+			env:          Some(HashMap::from([("NAME".to_string(), "pty".to_string())])),
+			cols:         80,
+			rows:         24,
+			output_limit: None,
+			limits:       None,
+			sandbox:      None,
+			shell:        Some(ShellOptions {
+				session_env:   Some(HashMap::from([("GREETING".to_string(), "hello".to_string())])),
+				snapshot_path: Some(snapshot.to_string_lossy().into_owned()),
+				policy:        None,
+				history:       None,
+			}),
+		};
+		let (control_tx, control_rx) = mpsc::channel();
+		control_tx
+			.send(ControlMessage::Input("typed\r".to_string()))
+			.expect("input should queue");
+		let terminal = Terminal::new(24, 80, None);
+		let result = tokio::task::spawn_blocking({
+			let terminal = terminal.clone();
+			move || run_pty_sync(config, None, control_rx, &terminal, task::CancelToken::default())
+		})
+		.await
+		.expect("PTY thread should finish")
+		.expect("PTY command should run");
+		let _ = std::fs::remove_file(&snapshot);
+
+		assert_eq!(result.exit_code, Some(7));
+		let output = Script::new(Vec::new())
+			.expect("script should be valid")
+			.run(&terminal.transcript, &terminal.screen, |_| false, &task::CancelToken::default())
+			.output;
+		// The terminal echoes the queued input whenever it arrives.
+		assert_eq!(output.replacen("typed\r\n", "", 1), "hello, pty\r\ngot typed\r\n");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn records_output_input_and_resizes() {
+		let config = PtyRunConfig {
+			command:      "read line && echo \"got $line\"".to_string(),
+			cwd:          None,
+			env:          None,
+			cols:         80,
+			rows:         24,
+			output_limit: None,
+			limits:       None,
+			sandbox:      None,
+			shell:        None,
+		};
+		let (control_tx, control_rx) = mpsc::channel();
+		control_tx
+			.send(ControlMessage::Resize { cols: 100, rows: 30 })
+			.expect("resize should queue");
+		control_tx
+			.send(ControlMessage::Input("typed\r".to_string()))
+			.expect("input should queue");
+		let options = PtyRecordOptions { max_bytes: None, input: None };
+		let terminal = Terminal::new(24, 80, Some(Recording::new(&options, &config.command, 80, 24)));
+		let result =
+			run_pty_sync(config, None, control_rx, &terminal, task::CancelToken::new(None, None))
+				.expect("PTY command should run");
+		assert_eq!(result.exit_code, Some(0));
+
+		let cast = terminal
+			.recording
+			.expect("recording was requested")
+			.to_asciicast();
+		let events: Vec<serde_json::Value> = cast
+			.lines()
+			.skip(1)
+			.map(|line| serde_json::from_str(line).expect("event should be JSON"))
+			.collect();
+		assert_eq!((&events[0][1], &events[0][2]), (&"r".into(), &"100x30".into()));
+		assert_eq!((&events[1][1], &events[1][2]), (&"i".into(), &"typed\r".into()));
+		let recorded: String = events
+			.iter()
+			.filter(|event| event[1] == "o")
+			.filter_map(|event| event[2].as_str())
+			.collect();
+		assert_eq!(recorded, terminal.transcript.peek().0);
+		assert!(recorded.ends_with("got typed\r\n"), "{recorded:?}");
+	}
+
+	/// Run `command` through a shell session on a PTY, sending `message` once
+	/// the output shows `ready`; returns the exit code and the output.
+	#[cfg(unix)]
+	async fn run_in_shell_after_ready(
+		command: &str,
+		message: ControlMessage,
+	) -> (Option<i32>, String) {
+		let config = PtyRunConfig {
+			command:      command.to_string(),
+			cwd:          None,
+			env:          None,
+			cols:         80,
+			rows:         24,
+			output_limit: None,
+			limits:       None,
+			sandbox:      None,
+			shell:        Some(ShellOptions {
+				session_env:   None,
+				snapshot_path: None,
+				policy:        None,
+				history:       None,
+			}),
+		};
+		let (control_tx, control_rx) = mpsc::channel();
+		let terminal = Terminal::new(24, 80, None);
+		let run = tokio::task::spawn_blocking({
+			let terminal = terminal.clone();
+			move || run_pty_sync(config, None, control_rx, &terminal, task::CancelToken::default())
+		});
+		let deadline = Instant::now() + Duration::from_secs(10);
+		while !terminal.transcript.peek().0.contains("ready") && Instant::now() < deadline {
+			tokio::time::sleep(Duration::from_millis(20)).await;
+		}
+		control_tx.send(message).expect("message should queue");
+		let result = run
+			.await
+			.expect("PTY thread should finish")
+			.expect("PTY command should run");
+		(result.exit_code, terminal.transcript.peek().0)
+	}
+
+	#[cfg(unix)]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn ctrl_c_interrupts_the_foreground_program() {
+		let started = Instant::now();
+		let (exit_code, output) = run_in_shell_after_ready(
+			"sh -c 'echo ready; exec sleep 10'; echo \"status $?\"",
+			ControlMessage::Input("\x03".to_string()),
+		)
+		.await;
+		assert_eq!(exit_code, Some(0));
+		assert!(output.contains("status 130"), "{output:?}");
+		assert!(started.elapsed() < Duration::from_secs(5));
+	}
+
+	#[cfg(unix)]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn resizes_reach_the_foreground_program() {
+		let (exit_code, output) = run_in_shell_after_ready(
+			"sh -c 'trap \"stty size < /dev/tty; exit 3\" WINCH; echo ready; for i in $(seq 100); do \
+			 sleep 0.05; done'",
+			ControlMessage::Resize { cols: 100, rows: 30 },
+		)
+		.await;
+		assert_eq!(exit_code, Some(3));
+		assert!(output.contains("30 100"), "{output:?}");
+	}
+}
@@ -0,0 +1,451 @@
+//! Scripted interaction with PTY sessions.
+//!
+//! Interactive programs print a prompt and then block on input, so answering
+//! with `PtySession::write` means guessing when the prompt has appeared. An
+//! expect script instead alternates steps that wait for output (or for the
+//! emulated screen) to match with steps that send input, all in one call.
+
+use std::{
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use napi::{Error, Result, bindgen_prelude::Unknown};
+use napi_derive::napi;
+use parking_lot::{Condvar, Mutex};
+use regex::Regex;
+
+use super::screen::Screen;
+use crate::task;
+
+/// Most unmatched output retained for expect steps; older output is dropped
+/// first.
+const PENDING_LIMIT: usize = 1024 * 1024;
+/// How often the screen and cancellation are rechecked while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+/// What an expect step matches against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum PtyExpectTarget {
+	/// Output received since the previous output match; a match consumes the
+	/// output up to its end.
+	#[napi(value = "output")]
+	Output,
+	/// Text currently visible on the emulated screen.
+	#[napi(value = "screen")]
+	Screen,
+}
+
+/// One step of an expect script: either `send`, or one of `pattern` and
+/// `literal`.
+#[napi(object)]
+pub struct PtyExpectStep {
+	/// Text to write to the command's stdin.
+	pub send:       Option<String>,
+	/// Regex to wait for.
+	pub pattern:    Option<String>,
+	/// Exact text to wait for.
+	pub literal:    Option<String>,
+	/// Where to look for `pattern` or `literal` (default: output).
+	pub target:     Option<PtyExpectTarget>,
+	/// Milliseconds to wait for this step's match.
+	pub timeout_ms: Option<u32>,
+}
+
+/// Options for running an expect script.
+#[napi(object)]
+pub struct PtyExpectOptions<'env> {
+	/// Steps to run in order.
+	pub steps:      Vec<PtyExpectStep>,
+	/// Milliseconds the whole script may take.
+	pub timeout_ms: Option<u32>,
+	/// Abort signal for stopping the script.
+	pub signal:     Option<Unknown<'env>>,
+}
+
+/// Match made by an expect step.
+#[napi(object)]
+#[derive(Debug)]
+pub struct PtyExpectMatch {
+	/// Index of the step in the script.
+	pub step:    u32,
+	/// Text that matched.
+	pub matched: String,
+	/// Text before the match: the skipped output, or the screen above it.
+	pub before:  String,
+	/// Capture groups of a `pattern`, unset when a group did not participate.
+	pub groups:  Vec<Option<String>>,
+}
+
+/// Outcome of an expect script. The script stops at the first step that does
+/// not complete.
+#[napi(object)]
+#[derive(Debug)]
+pub struct PtyExpectResult {
+	/// Matches of the expect steps that completed, in order.
+	pub matches:     Vec<PtyExpectMatch>,
+	/// Whether every step completed.
+	pub completed:   bool,
+	/// Index of the step the script stopped at.
+	pub failed_step: Option<u32>,
+	/// Whether a step's or the script's timeout elapsed.
+	pub timed_out:   bool,
+	/// Whether the abort signal fired.
+	pub cancelled:   bool,
+	/// Whether the command's output ended before a match, or input could not be
+	/// sent because it exited.
+	pub exited:      bool,
+	/// Output received since the last output match.
+	pub output:      String,
+}
+
+#[derive(Default)]
+struct TranscriptState {
+	pending: String,
+	closed:  bool,
+}
+
+/// Output of a PTY command that expect steps have not consumed yet.
+#[derive(Clone, Default)]
+pub struct Transcript(Arc<(Mutex<TranscriptState>, Condvar)>);
+
+impl Transcript {
+	pub fn push(&self, text: &str) {
+		let (state, changed) = &*self.0;
+		let mut state = state.lock();
+		state.pending.push_str(text);
+		if state.pending.len() > PENDING_LIMIT {
+			let mut start = state.pending.len() - PENDING_LIMIT;
+			while !state.pending.is_char_boundary(start) {
+				start += 1;
+			}
+			state.pending.drain(..start);
+		}
+		changed.notify_all();
+	}
+
+	/// Output not consumed yet, and whether the output has ended.
+	pub fn peek(&self) -> (String, bool) {
+		let state = self.0.0.lock();
+		(state.pending.clone(), state.closed)
+	}
+
+	/// Marks the output as ended; waiting steps stop once nothing matches.
+	pub fn close(&self) {
+		let (state, changed) = &*self.0;
+		state.lock().closed = true;
+		changed.notify_all();
+	}
+}
+
+enum Matcher {
+	Pattern(Regex),
+	Literal(String),
+}
+
+impl Matcher {
+	/// Byte range of the first match in `text` and its capture groups.
+	fn find(&self, text: &str) -> Option<(usize, usize, Vec<Option<String>>)> {
+		match self {
+			Self::Pattern(regex) => {
+				let captures = regex.captures(text)?;
+				let whole = captures.get(0)?;
+				let groups = captures
+					.iter()
+					.skip(1)
+					.map(|group| group.map(|group| group.as_str().to_string()))
+					.collect();
+				Some((whole.start(), whole.end(), groups))
+			},
+			Self::Literal(literal) => {
+				let start = text.find(literal.as_str())?;
+				Some((start, start + literal.len(), Vec::new()))
+			},
+		}
+	}
+}
+
+enum Action {
+	Send(String),
+	Expect { matcher: Matcher, target: PtyExpectTarget, timeout: Option<Duration> },
+}
+
+impl Action {
+	fn new(step: PtyExpectStep, index: usize) -> Result<Self> {
+		let matcher = match (step.send, step.pattern, step.literal) {
+			(Some(data), None, None) => return Ok(Self::Send(data)),
+			(None, Some(pattern), None) => Matcher::Pattern(Regex::new(&pattern).map_err(|err| {
+				Error::from_reason(format!(
+					"Failed to compile expect pattern {pattern:?} of step {index}: {err}"
+				))
+			})?),
+			(None, None, Some(literal)) => Matcher::Literal(literal),
+			_ => {
+				return Err(Error::from_reason(format!(
+					"Expect step {index} needs exactly one of send, pattern or literal"
+				)));
+			},
+		};
+		Ok(Self::Expect {
+			matcher,
+			target: step.target.unwrap_or(PtyExpectTarget::Output),
+			timeout: step
+				.timeout_ms
+				.map(|timeout_ms| Duration::from_millis(timeout_ms.into())),
+		})
+	}
+}
+
+/// Why a step did not complete.
+enum Stop {
+	TimedOut,
+	Cancelled,
+	Exited,
+}
+
+/// Validated expect script, ready to run against a session's output.
+pub struct Script(Vec<Action>);
+
+impl Script {
+	pub fn new(steps: Vec<PtyExpectStep>) -> Result<Self> {
+		steps
+			.into_iter()
+			.enumerate()
+			.map(|(index, step)| Action::new(step, index))
+			.collect::<Result<_>>()
+			.map(Self)
+	}
+
+	/// Run the steps in order, writing `send` steps through `send`, which
+	/// returns `false` once the command can no longer receive input.
+	pub fn run(
+		self,
+		transcript: &Transcript,
+		screen: &Screen,
+		send: impl Fn(String) -> bool,
+		ct: &task::CancelToken,
+	) -> PtyExpectResult {
+		let mut result = PtyExpectResult {
+			matches:     Vec::new(),
+			completed:   false,
+			failed_step: None,
+			timed_out:   false,
+			cancelled:   false,
+			exited:      false,
+			output:      String::new(),
+		};
+		let mut stop = None;
+		for (index, action) in self.0.into_iter().enumerate() {
+			let outcome = match action {
+				Action::Send(data) => {
+					if send(data) {
+						Ok(None)
+					} else {
+						Err(Stop::Exited)
+					}
+				},
+				Action::Expect { matcher, target, timeout } => {
+					let deadline = timeout.map(|timeout| Instant::now() + timeout);
+					expect(transcript, screen, &matcher, target, deadline, ct).map(Some)
+				},
+			};
+			match outcome {
+				Ok(Some((matched, before, groups))) => {
+					result
+						.matches
+						.push(PtyExpectMatch { step: index as u32, matched, before, groups });
+				},
+				Ok(None) => {},
+				Err(reason) => {
+					result.failed_step = Some(index as u32);
+					stop = Some(reason);
+					break;
+				},
+			}
+		}
+		match stop {
+			None => result.completed = true,
+			Some(Stop::TimedOut) => result.timed_out = true,
+			Some(Stop::Cancelled) => result.cancelled = true,
+			Some(Stop::Exited) => result.exited = true,
+		}
+		result.output.clone_from(&transcript.0.0.lock().pending);
+		result
+	}
+}
+
+/// Wait until `matcher` matches `target`, returning the match, the text before
+/// it and its capture groups.
+fn expect(
+	transcript: &Transcript,
+	screen: &Screen,
+	matcher: &Matcher,
+	target: PtyExpectTarget,
+	deadline: Option<Instant>,
+	ct: &task::CancelToken,
+) -> std::result::Result<(String, String, Vec<Option<String>>), Stop> {
+	let (state, changed) = &*transcript.0;
+	let mut state = state.lock();
+	loop {
+		if let Err(err) = ct.heartbeat() {
+			return Err(if err.to_string().contains("Timeout") {
+				Stop::TimedOut
+			} else {
+				Stop::Cancelled
+			});
+		}
+		match target {
+			PtyExpectTarget::Output => {
+				if let Some((start, end, groups)) = matcher.find(&state.pending) {
+					let consumed: String = state.pending.drain(..end).collect();
+					return Ok((consumed[start..].to_string(), consumed[..start].to_string(), groups));
+				}
+			},
+			PtyExpectTarget::Screen => {
+				let text = screen.snapshot(false).text;
+				if let Some((start, end, groups)) = matcher.find(&text) {
+					return Ok((text[start..end].to_string(), text[..start].to_string(), groups));
+				}
+			},
+		}
+		if state.closed {
+			return Err(Stop::Exited);
+		}
+		let wait = match deadline {
+			Some(deadline) => {
+				let remaining = deadline.saturating_duration_since(Instant::now());
+				if remaining.is_zero() {
+					return Err(Stop::TimedOut);
+				}
+				remaining.min(POLL_INTERVAL)
+			},
+			None => POLL_INTERVAL,
+		};
+		changed.wait_for(&mut state, wait);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::mpsc;
+
+	use super::{super::*, *};
+
+	fn step() -> PtyExpectStep {
+		PtyExpectStep {
+			send:       None,
+			pattern:    None,
+			literal:    None,
+			target:     None,
+			timeout_ms: None,
+		}
+	}
+
+	fn expect_literal(literal: &str) -> PtyExpectStep {
+		PtyExpectStep { literal: Some(literal.to_string()), ..step() }
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn answers_prompts_of_a_running_command() {
+		let config = PtyRunConfig {
+			command:      "printf 'name? '; read name; printf 'hi %s\\n' \"$name\"; printf \
+			               '\\033[2J\\033[Hdone %s' \"$name\"; read _"
+				.to_string(),
+			cwd:          None,
+			env:          None,
+			cols:         80,
+			rows:         24,
+			output_limit: None,
+			limits:       None,
+			sandbox:      None,
+			shell:        None,
+		};
+		let (control_tx, control_rx) = mpsc::channel();
+		let terminal = Terminal::new(24, 80, None);
+		let run = std::thread::spawn({
+			let terminal = terminal.clone();
+			move || run_pty_sync(config, None, control_rx, &terminal, task::CancelToken::default())
+		});
+
+		let script = Script::new(vec![
+			PtyExpectStep { pattern: Some(r"(\w+)\? $".to_string()), ..step() },
+			PtyExpectStep { send: Some("bob\r".to_string()), ..step() },
+			expect_literal("hi bob"),
+			PtyExpectStep {
+				literal: Some("done bob".to_string()),
+				target: Some(PtyExpectTarget::Screen),
+				..step()
+			},
+			PtyExpectStep { timeout_ms: Some(200), ..expect_literal("never printed") },
+		])
+		.expect("script should be valid");
+		let send = |data| control_tx.send(ControlMessage::Input(data)).is_ok();
+		let result = script.run(
+			&terminal.transcript,
+			&terminal.screen,
+			send,
+			&task::CancelToken::new(Some(5000), None),
+		);
+
+		assert_eq!(result.matches.len(), 3);
+		assert_eq!(result.matches[0].groups, vec![Some("name".to_string())]);
+		assert_eq!(result.matches[1].before, "bob\r\n");
+		assert_eq!(result.matches[2].before, "");
+		assert!(result.timed_out && !result.completed);
+		assert_eq!(result.failed_step, Some(4));
+
+		control_tx
+			.send(ControlMessage::Input("\r".to_string()))
+			.expect("command should run");
+		let run = run.join().expect("PTY thread should finish");
+		assert_eq!(run.expect("PTY command should run").exit_code, Some(0));
+	}
+
+	#[test]
+	fn stops_when_output_ends_or_step_times_out() {
+		let screen = Screen::new(24, 80);
+		let transcript = Transcript::default();
+		transcript.push("ready> ");
+		let script = |steps| Script::new(steps).expect("script should be valid");
+		let ct = task::CancelToken::default();
+
+		let timed = script(vec![PtyExpectStep { timeout_ms: Some(20), ..expect_literal("$") }]).run(
+			&transcript,
+			&screen,
+			|_| true,
+			&ct,
+		);
+		assert!(timed.timed_out && !timed.exited);
+		assert_eq!(timed.output, "ready> ");
+
+		transcript.close();
+		let ended = script(vec![expect_literal("ready>"), expect_literal("$")]).run(
+			&transcript,
+			&screen,
+			|_| true,
+			&ct,
+		);
+		assert!(ended.exited && !ended.timed_out);
+		assert_eq!((ended.matches.len(), ended.failed_step), (1, Some(1)));
+		assert_eq!(ended.output, " ");
+
+		let unsent = script(vec![PtyExpectStep { send: Some("x".to_string()), ..step() }]).run(
+			&transcript,
+			&screen,
+			|_| false,
+			&ct,
+		);
+		assert!(unsent.exited);
+	}
+
+	#[test]
+	fn rejects_ambiguous_or_invalid_steps() {
+		assert!(Script::new(vec![step()]).is_err());
+		let both = PtyExpectStep { send: Some("y".to_string()), ..expect_literal("?") };
+		assert!(Script::new(vec![both]).is_err());
+		let invalid = PtyExpectStep { pattern: Some("(".to_string()), ..step() };
+		assert!(Script::new(vec![invalid]).is_err());
+	}
+}
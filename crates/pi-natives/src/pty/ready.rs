@@ -0,0 +1,121 @@
+//! Waiting for a PTY command to become ready.
+//!
+//! The conditions are those of `Shell::start_job`: an output pattern, a port
+//! on 127.0.0.1 accepting connections, and/or a file appearing. The pattern
+//! is matched against the output expect scripts have not consumed, without
+//! consuming it.
+
+use std::time::Duration;
+
+use napi::{
+	bindgen_prelude::Unknown,
+	tokio::{self, time},
+};
+use napi_derive::napi;
+
+use super::expect::Transcript;
+use crate::{
+	shell::{Readiness, ShellReadyCondition},
+	task,
+};
+
+/// How often readiness is rechecked.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Options for waiting until a PTY command is ready.
+#[napi(object)]
+pub struct PtyReadyOptions<'env> {
+	/// When the command counts as ready. A relative `path` resolves against
+	/// the `cwd` passed to `start`, or the current directory without one.
+	pub ready:      ShellReadyCondition,
+	/// Milliseconds to wait for readiness.
+	pub timeout_ms: Option<u32>,
+	/// Abort signal for giving up on the wait.
+	pub signal:     Option<Unknown<'env>>,
+}
+
+/// Outcome of waiting for a PTY command to become ready.
+#[napi(object)]
+#[derive(Debug)]
+pub struct PtyReadyResult {
+	/// Whether the readiness condition held.
+	pub ready:     bool,
+	/// Whether the command's output ended first.
+	pub exited:    bool,
+	/// Whether the timeout elapsed first.
+	pub timed_out: bool,
+	/// Whether the abort signal fired first.
+	pub cancelled: bool,
+	/// Output not consumed by expect scripts, up to this point.
+	pub output:    String,
+}
+
+/// Poll `readiness` against `transcript` until it holds, the output ends, or
+/// `ct` fires. The command keeps running in every case.
+pub async fn wait(
+	readiness: &Readiness,
+	transcript: &Transcript,
+	ct: task::CancelToken,
+) -> PtyReadyResult {
+	let mut result = PtyReadyResult {
+		ready:     false,
+		exited:    false,
+		timed_out: false,
+		cancelled: false,
+		output:    String::new(),
+	};
+	let cancelled = ct.wait();
+	tokio::pin!(cancelled);
+	loop {
+		let (output, closed) = transcript.peek();
+		if readiness.holds(&output).await {
+			result.ready = true;
+			break;
+		}
+		if closed {
+			result.exited = true;
+			break;
+		}
+		tokio::select! {
+			() = time::sleep(POLL_INTERVAL) => {},
+			reason = &mut cancelled => {
+				result.timed_out = matches!(reason, task::AbortReason::Timeout);
+				result.cancelled = !result.timed_out;
+				break;
+			}
+		}
+	}
+	result.output = transcript.peek().0;
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use super::*;
+
+	fn pattern(pattern: &str) -> Readiness {
+		let condition =
+			ShellReadyCondition { pattern: Some(pattern.to_string()), port: None, path: None };
+		Readiness::new(condition, Path::new("/")).expect("condition should compile")
+	}
+
+	#[tokio::test]
+	async fn resolves_on_output_exit_and_timeout() {
+		let transcript = Transcript::default();
+		transcript.push("booting\r\nlistening on 4000\r\n");
+		let ready =
+			wait(&pattern(r"listening on \d+"), &transcript, task::CancelToken::default()).await;
+		assert!(ready.ready);
+		assert_eq!(ready.output, "booting\r\nlistening on 4000\r\n");
+
+		let waiting =
+			wait(&pattern("never"), &transcript, task::CancelToken::new(Some(100), None)).await;
+		assert!(!waiting.ready && waiting.timed_out && !waiting.cancelled);
+
+		transcript.close();
+		let exited = wait(&pattern("never"), &transcript, task::CancelToken::default()).await;
+		assert!(!exited.ready && exited.exited);
+	}
+}
@@ -0,0 +1,161 @@
+//! Recording of PTY sessions as asciicast v2.
+//!
+//! The recording keeps every output chunk, input write and resize with its
+//! time since the command started, so a reviewer can replay exactly what a
+//! terminal program drew rather than reading the flattened output.
+
+use std::{
+	fmt::Write as _,
+	sync::Arc,
+	time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use napi_derive::napi;
+use parking_lot::Mutex;
+use serde_json::json;
+
+/// Recording size used when `max_bytes` is not set.
+const DEFAULT_MAX_BYTES: u32 = 8 * 1024 * 1024;
+
+/// Options for recording a PTY session.
+#[napi(object)]
+pub struct PtyRecordOptions {
+	/// Most bytes of event data to keep (default: 8 MiB). Events past the cap
+	/// are dropped and a marker records where.
+	pub max_bytes: Option<u32>,
+	/// Record input written to the command (default: true).
+	pub input:     Option<bool>,
+}
+
+struct Event {
+	time: f64,
+	code: &'static str,
+	data: String,
+}
+
+struct RecordingState {
+	command:   String,
+	cols:      u16,
+	rows:      u16,
+	timestamp: u64,
+	started:   Instant,
+	events:    Vec<Event>,
+	bytes:     usize,
+	max_bytes: usize,
+	input:     bool,
+	truncated: bool,
+}
+
+/// Events of one PTY command, shared between the run loop and the session.
+#[derive(Clone)]
+pub struct Recording(Arc<Mutex<RecordingState>>);
+
+impl Recording {
+	pub fn new(options: &PtyRecordOptions, command: &str, cols: u16, rows: u16) -> Self {
+		Self(Arc::new(Mutex::new(RecordingState {
+			command: command.to_string(),
+			cols,
+			rows,
+			timestamp: SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map_or(0, |elapsed| elapsed.as_secs()),
+			started: Instant::now(),
+			events: Vec::new(),
+			bytes: 0,
+			max_bytes: options.max_bytes.unwrap_or(DEFAULT_MAX_BYTES) as usize,
+			input: options.input.unwrap_or(true),
+			truncated: false,
+		})))
+	}
+
+	pub fn output(&self, text: &str) {
+		self.push("o", text.to_string());
+	}
+
+	pub fn input(&self, text: &str) {
+		if self.0.lock().input {
+			self.push("i", text.to_string());
+		}
+	}
+
+	pub fn resize(&self, cols: u16, rows: u16) {
+		self.push("r", format!("{cols}x{rows}"));
+	}
+
+	fn push(&self, code: &'static str, data: String) {
+		let mut state = self.0.lock();
+		if state.truncated {
+			return;
+		}
+		let time = state.started.elapsed().as_secs_f64();
+		if state.bytes + data.len() > state.max_bytes {
+			state.truncated = true;
+			state
+				.events
+				.push(Event { time, code: "m", data: "recording truncated".to_string() });
+			return;
+		}
+		state.bytes += data.len();
+		state.events.push(Event { time, code, data });
+	}
+
+	/// The recording as an asciicast v2 document: a header line followed by
+	/// one `[time, code, data]` line per event.
+	pub fn to_asciicast(&self) -> String {
+		let state = self.0.lock();
+		let header = json!({
+			"version": 2,
+			"width": state.cols,
+			"height": state.rows,
+			"timestamp": state.timestamp,
+			"command": state.command,
+		});
+		let mut cast = format!("{header}\n");
+		for event in &state.events {
+			let _ = writeln!(cast, "{}", json!([round_time(event.time), event.code, event.data]));
+		}
+		cast
+	}
+}
+
+/// Event time rounded to microseconds, as asciinema writes it.
+fn round_time(time: f64) -> f64 {
+	(time * 1_000_000.0).round() / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn writes_asciicast_and_stops_at_the_cap() {
+		let options = PtyRecordOptions { max_bytes: Some(16), input: None };
+		let recording = Recording::new(&options, "vim", 80, 24);
+		recording.output("\x1b[2Jhi\r\n");
+		recording.input("q");
+		recording.resize(100, 30);
+		recording.output("too much output");
+		recording.output("dropped");
+
+		let cast = recording.to_asciicast();
+		let lines: Vec<serde_json::Value> = cast
+			.lines()
+			.map(|line| serde_json::from_str(line).expect("line should be JSON"))
+			.collect();
+		assert_eq!(lines.len(), 5);
+		assert_eq!(lines[0]["version"], 2);
+		assert_eq!((lines[0]["width"].as_u64(), lines[0]["height"].as_u64()), (Some(80), Some(24)));
+		assert_eq!(lines[0]["command"], "vim");
+		assert_eq!(lines[1][1], "o");
+		assert_eq!(lines[1][2], "\x1b[2Jhi\r\n");
+		assert_eq!((&lines[2][1], &lines[2][2]), (&json!("i"), &json!("q")));
+		assert_eq!((&lines[3][1], &lines[3][2]), (&json!("r"), &json!("100x30")));
+		assert_eq!((&lines[4][1], &lines[4][2]), (&json!("m"), &json!("recording truncated")));
+		assert!(lines[4][0].as_f64() >= lines[1][0].as_f64());
+
+		let silent =
+			Recording::new(&PtyRecordOptions { max_bytes: None, input: Some(false) }, "", 80, 24);
+		silent.input("secret");
+		assert_eq!(silent.to_asciicast().lines().count(), 1);
+	}
+}
@@ -0,0 +1,187 @@
+//! Virtual terminal screen for PTY sessions.
+//!
+//! Full-screen programs, progress bars and prompts redraw the terminal with
+//! cursor movement and erase sequences, which makes the raw byte stream hard
+//! to read. The screen replays that stream through a VT100/xterm emulator so
+//! the currently visible contents can be read back as text.
+
+use std::{fmt::Write as _, sync::Arc};
+
+use napi_derive::napi;
+use parking_lot::Mutex;
+
+/// Options for `PtySession::snapshot`.
+#[napi(object)]
+pub struct PtySnapshotOptions {
+	/// Also render the screen with SGR escape sequences for colors and text
+	/// attributes.
+	pub attributes: Option<bool>,
+}
+
+/// Visible contents of a PTY session's terminal.
+#[napi(object)]
+#[derive(Debug)]
+pub struct PtyScreenSnapshot {
+	/// One line per screen row, joined with `\n`, without trailing blanks.
+	pub text:             String,
+	/// `text` with SGR escape sequences, when attributes were requested.
+	pub formatted:        Option<String>,
+	/// Number of rows on the screen.
+	pub rows:             u16,
+	/// Number of columns on the screen.
+	pub cols:             u16,
+	/// Zero-based row of the cursor.
+	pub cursor_row:       u16,
+	/// Zero-based column of the cursor.
+	pub cursor_col:       u16,
+	/// Whether the program has hidden the cursor.
+	pub cursor_hidden:    bool,
+	/// Whether the alternate screen (used by full-screen programs) is active.
+	pub alternate_screen: bool,
+}
+
+/// Terminal emulator fed with everything a PTY command writes.
+#[derive(Clone)]
+pub struct Screen(Arc<Mutex<vt100::Parser>>);
+
+impl Screen {
+	pub fn new(rows: u16, cols: u16) -> Self {
+		Self(Arc::new(Mutex::new(vt100::Parser::new(rows, cols, 0))))
+	}
+
+	/// Apply raw output bytes to the screen.
+	pub fn feed(&self, bytes: &[u8]) {
+		self.0.lock().process(bytes);
+	}
+
+	pub fn resize(&self, rows: u16, cols: u16) {
+		self.0.lock().screen_mut().set_size(rows, cols);
+	}
+
+	pub fn snapshot(&self, attributes: bool) -> PtyScreenSnapshot {
+		let parser = self.0.lock();
+		let screen = parser.screen();
+		let (rows, cols) = screen.size();
+		let (cursor_row, cursor_col) = screen.cursor_position();
+		PtyScreenSnapshot {
+			text: screen.rows(0, cols).collect::<Vec<_>>().join("\n"),
+			formatted: attributes.then(|| {
+				(0..rows)
+					.map(|row| formatted_row(screen, row, cols))
+					.collect::<Vec<_>>()
+					.join("\n")
+			}),
+			rows,
+			cols,
+			cursor_row,
+			cursor_col,
+			cursor_hidden: screen.hide_cursor(),
+			alternate_screen: screen.alternate_screen(),
+		}
+	}
+}
+
+/// Render one row, switching SGR state only where it changes and resetting
+/// it at the end of the row. Trailing blanks without attributes are dropped.
+fn formatted_row(screen: &vt100::Screen, row: u16, cols: u16) -> String {
+	let mut line = String::new();
+	let mut active = String::new();
+	let mut blanks = 0;
+	for cell in (0..cols).filter_map(|col| screen.cell(row, col)) {
+		if cell.is_wide_continuation() {
+			continue;
+		}
+		let params = sgr_params(cell);
+		let text = if cell.has_contents() {
+			cell.contents()
+		} else {
+			" "
+		};
+		if params.is_empty() && text == " " {
+			blanks += 1;
+			continue;
+		}
+		if blanks > 0 {
+			if !active.is_empty() {
+				line.push_str("\x1b[0m");
+				active.clear();
+			}
+			line.extend(std::iter::repeat_n(' ', blanks));
+			blanks = 0;
+		}
+		if params != active {
+			if params.is_empty() {
+				line.push_str("\x1b[0m");
+			} else {
+				let _ = write!(line, "\x1b[0;{params}m");
+			}
+			active = params;
+		}
+		line.push_str(text);
+	}
+	if !active.is_empty() {
+		line.push_str("\x1b[0m");
+	}
+	line
+}
+
+/// SGR parameters for a cell's attributes, empty for the defaults.
+fn sgr_params(cell: &vt100::Cell) -> String {
+	let mut params = Vec::new();
+	for (enabled, code) in [
+		(cell.bold(), "1"),
+		(cell.dim(), "2"),
+		(cell.italic(), "3"),
+		(cell.underline(), "4"),
+		(cell.inverse(), "7"),
+	] {
+		if enabled {
+			params.push(code.to_string());
+		}
+	}
+	push_color(&mut params, cell.fgcolor(), 30, 90, 38);
+	push_color(&mut params, cell.bgcolor(), 40, 100, 48);
+	params.join(";")
+}
+
+fn push_color(params: &mut Vec<String>, color: vt100::Color, base: u8, bright: u8, extended: u8) {
+	match color {
+		vt100::Color::Default => {},
+		vt100::Color::Idx(idx @ 0..8) => params.push((base + idx).to_string()),
+		vt100::Color::Idx(idx @ 8..16) => params.push((bright + idx - 8).to_string()),
+		vt100::Color::Idx(idx) => params.push(format!("{extended};5;{idx}")),
+		vt100::Color::Rgb(r, g, b) => params.push(format!("{extended};2;{r};{g};{b}")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tracks_redraws_cursor_and_alternate_screen() {
+		let screen = Screen::new(4, 20);
+		screen.feed(b"downloading 10%\r\x1b[Kdownloading 100%\r\n\x1b[1;31mfail\x1b[0m ok");
+
+		let snapshot = screen.snapshot(true);
+		assert_eq!(snapshot.text, "downloading 100%\nfail ok\n\n");
+		assert_eq!(
+			snapshot.formatted.as_deref(),
+			Some("downloading 100%\n\x1b[0;1;31mfail\x1b[0m ok\n\n")
+		);
+		assert_eq!((snapshot.cursor_row, snapshot.cursor_col), (1, 7));
+		assert!(!snapshot.alternate_screen);
+
+		screen.feed(b"\x1b[?1049h\x1b[?25l\x1b[2;3Hmenu");
+		let snapshot = screen.snapshot(false);
+		assert_eq!(snapshot.text, "\n  menu\n\n");
+		assert!(snapshot.alternate_screen && snapshot.cursor_hidden);
+		assert_eq!(snapshot.formatted, None);
+
+		screen.feed(b"\x1b[?1049l");
+		screen.resize(6, 30);
+		let snapshot = screen.snapshot(false);
+		assert_eq!(snapshot.text, "downloading 100%\nfail ok\n\n\n\n");
+		assert_eq!((snapshot.rows, snapshot.cols), (6, 30));
+	}
+}
@@ -0,0 +1,555 @@
+//! Resource limits and namespace sandboxing for spawned commands.
+//!
+//! # Overview
+//! A [`ProcessJail`] is compiled in the parent from the JS options and applied
+//! in each child between `fork` and `exec`, so it covers the whole command
+//! tree: limits and namespaces are inherited by everything the child spawns.
+//! Limits are set through the resource table of brush's `ulimit` builtin. The
+//! sandbox moves the child into fresh user, mount and (unless
+//! network access is allowed) network namespaces, remounts the filesystem
+//! read-only, and keeps only the configured paths writable.
+//!
+//! Everything the child runs is prepared up front; the pre-exec step itself
+//! only issues system calls, as required after `fork` in a threaded process.
+
+use std::{path::Path, sync::Arc};
+
+#[cfg(unix)]
+use brush_builtins::LimitResource;
+use napi::Result;
+use napi_derive::napi;
+
+/// Resource limits applied to each process a command spawns.
+///
+/// Each limit sets both the soft and the hard value, so commands cannot raise
+/// it again. Values are non-negative integers; `Infinity` removes the limit.
+#[napi(object)]
+#[derive(Clone, Copy, Default)]
+pub struct ResourceLimits {
+	/// CPU time in seconds (`RLIMIT_CPU`).
+	pub cpu_seconds:         Option<f64>,
+	/// Virtual address space in bytes (`RLIMIT_AS`).
+	pub address_space_bytes: Option<f64>,
+	/// Open file descriptors (`RLIMIT_NOFILE`).
+	pub open_files:          Option<f64>,
+	/// Processes for the user (`RLIMIT_NPROC`). Counts every process of the
+	/// user, not just the command's.
+	pub processes:           Option<f64>,
+	/// Size in bytes of files the command may write (`RLIMIT_FSIZE`).
+	pub file_size_bytes:     Option<f64>,
+}
+
+/// Namespace sandbox for spawned processes (Linux only).
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct SandboxOptions {
+	/// Directories that stay writable. Defaults to the command's working
+	/// directory.
+	pub writable_paths: Option<Vec<String>>,
+	/// Keep the host network instead of an isolated network namespace with
+	/// only a loopback device.
+	pub allow_network:  Option<bool>,
+}
+
+/// Limits and sandbox applied to child processes before they exec.
+#[derive(Clone)]
+pub struct ProcessJail(Arc<Plan>);
+
+struct Plan {
+	#[cfg(unix)]
+	limits:     Vec<(LimitResource, u64)>,
+	#[cfg(target_os = "linux")]
+	namespaces: Option<linux::Namespaces>,
+}
+
+impl ProcessJail {
+	/// Compile the options; `None` when neither asks for anything.
+	///
+	/// `cwd` is the working directory the command starts in, which is the
+	/// default writable path of the sandbox.
+	pub fn new(
+		limits: Option<ResourceLimits>,
+		sandbox: Option<&SandboxOptions>,
+		cwd: &Path,
+	) -> Result<Option<Self>> {
+		let limits = limits.unwrap_or_default();
+		// Field name and `ulimit` option of each limit.
+		let requested = [
+			("cpuSeconds", 't', limits.cpu_seconds),
+			("addressSpaceBytes", 'v', limits.address_space_bytes),
+			("openFiles", 'n', limits.open_files),
+			("processes", 'u', limits.processes),
+			("fileSizeBytes", 'f', limits.file_size_bytes),
+		];
+		if sandbox.is_none() && requested.iter().all(|(.., value)| value.is_none()) {
+			return Ok(None);
+		}
+
+		#[cfg(not(unix))]
+		{
+			let _ = cwd;
+			Err(napi::Error::from_reason(
+				"Resource limits and sandboxing are not supported on this platform",
+			))
+		}
+
+		#[cfg(unix)]
+		{
+			let limits = requested
+				.into_iter()
+				.filter_map(|(name, option, value)| Some((name, option, value?)))
+				.map(|(name, option, value)| {
+					let resource = LimitResource::from_option(option)
+						.filter(|resource| resource.is_supported())
+						.ok_or_else(|| {
+							napi::Error::from_reason(format!(
+								"Resource limit {name} is not supported on this platform"
+							))
+						})?;
+					Ok((resource, limit_value(name, value)?))
+				})
+				.collect::<Result<_>>()?;
+
+			#[cfg(target_os = "linux")]
+			let namespaces = sandbox
+				.map(|sandbox| linux::Namespaces::new(sandbox, cwd))
+				.transpose()?;
+			#[cfg(not(target_os = "linux"))]
+			if sandbox.is_some() {
+				let _ = cwd;
+				return Err(napi::Error::from_reason("Sandboxing requires Linux namespaces"));
+			}
+
+			Ok(Some(Self(Arc::new(Plan {
+				limits,
+				#[cfg(target_os = "linux")]
+				namespaces,
+			}))))
+		}
+	}
+
+	/// Check that the sandbox can be set up, by setting it up in a short-lived
+	/// forked child. Fails with an explanation when namespaces are
+	/// unavailable.
+	pub fn probe(&self) -> Result<()> {
+		#[cfg(target_os = "linux")]
+		if let Some(namespaces) = &self.0.namespaces {
+			return namespaces.probe();
+		}
+		Ok(())
+	}
+
+	/// Refuse writes to `path` that the sandbox would refuse, for files the
+	/// shell opens in its own process (output redirections).
+	pub fn check_write(&self, path: &Path) -> std::io::Result<()> {
+		#[cfg(target_os = "linux")]
+		if let Some(namespaces) = &self.0.namespaces
+			&& !namespaces.allows_write(path)
+		{
+			return Err(std::io::Error::from_raw_os_error(libc::EROFS));
+		}
+		#[cfg(not(target_os = "linux"))]
+		let _ = path;
+		Ok(())
+	}
+
+	/// Arrange for the plan to be applied in the child of `command`.
+	#[cfg(unix)]
+	pub fn attach(&self, command: &mut std::process::Command) {
+		use std::os::unix::process::CommandExt;
+
+		let plan = Arc::clone(&self.0);
+		// SAFETY: `Plan::apply` only issues system calls on data prepared in
+		// the parent, which is safe between fork and exec.
+		unsafe {
+			command.pre_exec(move || plan.apply());
+		}
+	}
+}
+
+#[cfg(unix)]
+impl Plan {
+	fn apply(&self) -> std::io::Result<()> {
+		// Namespaces first: the hard process limit would otherwise count
+		// against the unshare.
+		#[cfg(target_os = "linux")]
+		if let Some(namespaces) = &self.namespaces {
+			namespaces.enter().map_err(|(_, err)| err)?;
+		}
+		for &(resource, value) in &self.limits {
+			resource.set(value)?;
+		}
+		Ok(())
+	}
+}
+
+/// Convert a limit from JavaScript, where `Infinity` means unlimited.
+#[cfg(unix)]
+fn limit_value(name: &str, value: f64) -> Result<u64> {
+	if value.is_infinite() && value.is_sign_positive() {
+		return Ok(libc::RLIM_INFINITY);
+	}
+	if !value.is_finite() || value < 0.0 || value.fract() != 0.0 || value >= u64::MAX as f64 {
+		return Err(napi::Error::from_reason(format!(
+			"Invalid resource limit {name}: {value} (expected a non-negative integer or Infinity)"
+		)));
+	}
+	Ok(value as u64)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+	use std::{
+		ffi::{CStr, CString},
+		fs, io,
+		os::unix::ffi::OsStrExt,
+		path::{Path, PathBuf},
+		ptr,
+	};
+
+	use libc::{c_char, c_int, c_ulong};
+	use napi::{Error, Result};
+
+	use super::SandboxOptions;
+
+	/// Pseudo filesystems that stay as they are; `/dev/null` and friends must
+	/// remain writable.
+	const UNTOUCHED_ROOTS: [&str; 3] = ["/proc", "/sys", "/dev"];
+
+	/// Mount flags the kernel locks when a mount is copied into a less
+	/// privileged namespace; a remount has to repeat them.
+	const LOCKED_FLAGS: [(&str, c_ulong); 6] = [
+		("nosuid", libc::MS_NOSUID),
+		("nodev", libc::MS_NODEV),
+		("noexec", libc::MS_NOEXEC),
+		("noatime", libc::MS_NOATIME),
+		("nodiratime", libc::MS_NODIRATIME),
+		("relatime", libc::MS_RELATIME),
+	];
+
+	/// Step of the sandbox setup, reported when it fails.
+	#[derive(Clone, Copy)]
+	pub enum Stage {
+		Unshare = 1,
+		IdMap,
+		Mount,
+	}
+
+	pub struct Namespaces {
+		flags:          c_int,
+		uid_map:        Vec<u8>,
+		gid_map:        Vec<u8>,
+		writable:       Vec<CString>,
+		writable_paths: Vec<PathBuf>,
+		read_only:      Vec<(CString, c_ulong)>,
+	}
+
+	impl Namespaces {
+		pub fn new(options: &SandboxOptions, cwd: &Path) -> Result<Self> {
+			let writable = match &options.writable_paths {
+				Some(paths) => paths.iter().map(PathBuf::from).collect(),
+				None => vec![cwd.to_path_buf()],
+			};
+			let writable = writable
+				.iter()
+				.map(|path| {
+					fs::canonicalize(path).map_err(|err| {
+						Error::from_reason(format!(
+							"Failed to resolve sandbox path {}: {err}",
+							path.display()
+						))
+					})
+				})
+				.collect::<Result<Vec<_>>>()?;
+
+			let mountinfo = fs::read_to_string("/proc/self/mountinfo")
+				.map_err(|err| Error::from_reason(format!("Failed to read mount table: {err}")))?;
+			let read_only = read_only_mounts(&mountinfo, &writable)
+				.into_iter()
+				.map(|(path, flags)| Ok((c_path(&path)?, flags)))
+				.collect::<Result<_>>()?;
+
+			let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+			if !options.allow_network.unwrap_or(false) {
+				flags |= libc::CLONE_NEWNET;
+			}
+			// SAFETY: getuid and getgid cannot fail.
+			let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+			Ok(Self {
+				flags,
+				uid_map: format!("{uid} {uid} 1\n").into_bytes(),
+				gid_map: format!("{gid} {gid} 1\n").into_bytes(),
+				writable: writable
+					.iter()
+					.map(|path| c_path(path))
+					.collect::<Result<_>>()?,
+				writable_paths: writable,
+				read_only,
+			})
+		}
+
+		/// Whether `path` stays writable inside the sandbox. A file that does
+		/// not exist yet is judged by its directory.
+		pub fn allows_write(&self, path: &Path) -> bool {
+			let resolved = fs::canonicalize(path).ok().or_else(|| {
+				let parent = fs::canonicalize(path.parent()?).ok()?;
+				Some(parent.join(path.file_name()?))
+			});
+			// Without a directory the open fails on its own.
+			let Some(resolved) = resolved else {
+				return true;
+			};
+			UNTOUCHED_ROOTS
+				.iter()
+				.map(Path::new)
+				.chain(self.writable_paths.iter().map(PathBuf::as_path))
+				.any(|root| resolved.starts_with(root))
+		}
+
+		/// Enter the namespaces and set up the mounts. Only issues system
+		/// calls, so it is safe to run between fork and exec.
+		pub fn enter(&self) -> std::result::Result<(), (Stage, io::Error)> {
+			// SAFETY: all pointers come from live, NUL-terminated buffers.
+			unsafe {
+				check(libc::unshare(self.flags)).map_err(|err| (Stage::Unshare, err))?;
+
+				write_file(c"/proc/self/setgroups", b"deny")
+					.and_then(|()| write_file(c"/proc/self/uid_map", &self.uid_map))
+					.and_then(|()| write_file(c"/proc/self/gid_map", &self.gid_map))
+					.map_err(|err| (Stage::IdMap, err))?;
+
+				self.mount().map_err(|err| (Stage::Mount, err))
+			}
+		}
+
+		unsafe fn mount(&self) -> io::Result<()> {
+			// SAFETY: see `enter`.
+			unsafe {
+				check(libc::mount(
+					ptr::null(),
+					c"/".as_ptr(),
+					ptr::null(),
+					libc::MS_REC | libc::MS_PRIVATE,
+					ptr::null(),
+				))?;
+				// Binding each writable path onto itself makes it a mount of its
+				// own that keeps its write access when its parent turns read-only.
+				for path in &self.writable {
+					check(libc::mount(
+						path.as_ptr(),
+						path.as_ptr(),
+						ptr::null(),
+						libc::MS_BIND | libc::MS_REC,
+						ptr::null(),
+					))?;
+				}
+				for (path, flags) in &self.read_only {
+					check(libc::mount(
+						ptr::null(),
+						path.as_ptr(),
+						ptr::null(),
+						libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | flags,
+						ptr::null(),
+					))?;
+				}
+
+				// The working directory still points into the old mount; look it up
+				// again so it resolves to the writable bind mount.
+				let mut cwd = [0 as c_char; libc::PATH_MAX as usize];
+				if !libc::getcwd(cwd.as_mut_ptr(), cwd.len()).is_null() {
+					check(libc::chdir(cwd.as_ptr()))?;
+				}
+			}
+			Ok(())
+		}
+
+		/// Run [`Self::enter`] in a forked child and report how it went.
+		pub fn probe(&self) -> Result<()> {
+			let mut fds = [0 as c_int; 2];
+			// SAFETY: `fds` has room for the two descriptors.
+			unsafe { check(libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC)) }
+				.map_err(|err| Error::from_reason(format!("Failed to probe sandbox: {err}")))?;
+			let [read_fd, write_fd] = fds;
+
+			// SAFETY: the child only issues system calls before `_exit`.
+			let pid = unsafe { libc::fork() };
+			if pid == 0 {
+				let mut report = [0u8; 5];
+				if let Err((stage, err)) = self.enter() {
+					report[0] = stage as u8;
+					report[1..].copy_from_slice(&err.raw_os_error().unwrap_or(0).to_ne_bytes());
+				}
+				// SAFETY: plain writes on a pipe we own, then exit without running
+				// the parent's destructors.
+				unsafe {
+					libc::write(write_fd, report.as_ptr().cast(), report.len());
+					libc::_exit(0);
+				}
+			}
+
+			// SAFETY: closing our copy of the write end and reading a fixed-size
+			// report from the read end.
+			let (pid, report, read) = unsafe {
+				libc::close(write_fd);
+				let mut report = [0u8; 5];
+				let read = if pid > 0 {
+					libc::read(read_fd, report.as_mut_ptr().cast(), report.len())
+				} else {
+					-1
+				};
+				libc::close(read_fd);
+				(pid, report, read)
+			};
+			if pid < 0 {
+				let err = io::Error::last_os_error();
+				return Err(Error::from_reason(format!("Failed to probe sandbox: {err}")));
+			}
+			reap(pid);
+
+			if read != 5 {
+				return Err(Error::from_reason("Failed to probe sandbox: setup did not report back"));
+			}
+			let errno = i32::from_ne_bytes([report[1], report[2], report[3], report[4]]);
+			let err = io::Error::from_raw_os_error(errno);
+			match report[0] {
+				0 => Ok(()),
+				x if x == Stage::Unshare as u8 => Err(Error::from_reason(format!(
+					"Sandbox unavailable: cannot create user, mount and network namespaces ({err}); \
+					 unprivileged user namespaces may be disabled"
+				))),
+				x if x == Stage::IdMap as u8 => Err(Error::from_reason(format!(
+					"Sandbox unavailable: failed to map user and group IDs ({err})"
+				))),
+				_ => Err(Error::from_reason(format!(
+					"Sandbox unavailable: failed to set up read-only mounts ({err})"
+				))),
+			}
+		}
+	}
+
+	/// Mount points to remount read-only, with the locked flags to preserve.
+	pub fn read_only_mounts(mountinfo: &str, writable: &[PathBuf]) -> Vec<(PathBuf, c_ulong)> {
+		mountinfo
+			.lines()
+			.filter_map(|line| {
+				let mut fields = line.split(' ');
+				let mount_point = PathBuf::from(unescape(fields.nth(4)?));
+				let options = fields.next()?;
+				let untouched = UNTOUCHED_ROOTS
+					.iter()
+					.any(|root| mount_point.starts_with(root))
+					|| writable.iter().any(|path| mount_point.starts_with(path));
+				if untouched {
+					return None;
+				}
+				let flags = options
+					.split(',')
+					.filter_map(|option| {
+						LOCKED_FLAGS
+							.iter()
+							.find(|(name, _)| *name == option)
+							.map(|(_, flag)| *flag)
+					})
+					.fold(0, |acc, flag| acc | flag);
+				Some((mount_point, flags))
+			})
+			.collect()
+	}
+
+	/// Decode the octal escapes (`\040` for space, ...) of mountinfo paths.
+	fn unescape(field: &str) -> String {
+		let bytes = field.as_bytes();
+		let mut out = Vec::with_capacity(bytes.len());
+		let mut i = 0;
+		while i < bytes.len() {
+			let octal = bytes
+				.get(i + 1..i + 4)
+				.filter(|_| bytes[i] == b'\\')
+				.and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+			if let Some(byte) = octal {
+				out.push(byte);
+				i += 4;
+			} else {
+				out.push(bytes[i]);
+				i += 1;
+			}
+		}
+		String::from_utf8_lossy(&out).into_owned()
+	}
+
+	fn c_path(path: &Path) -> Result<CString> {
+		CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+			Error::from_reason(format!("Sandbox path contains a NUL byte: {}", path.display()))
+		})
+	}
+
+	fn check(ret: c_int) -> io::Result<()> {
+		if ret == -1 {
+			Err(io::Error::last_os_error())
+		} else {
+			Ok(())
+		}
+	}
+
+	unsafe fn write_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+		// SAFETY: `path` is NUL-terminated and `contents` is a live buffer.
+		unsafe {
+			let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+			check(fd)?;
+			let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+			libc::close(fd);
+			if written < 0 {
+				return Err(io::Error::last_os_error());
+			}
+		}
+		Ok(())
+	}
+
+	fn reap(pid: libc::pid_t) {
+		let mut status = 0;
+		// SAFETY: waiting on our own child.
+		while unsafe { libc::waitpid(pid, &raw mut status, 0) } == -1
+			&& io::Error::last_os_error().kind() == io::ErrorKind::Interrupted
+		{}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn empty_options_need_no_jail() {
+		let jail = ProcessJail::new(Some(ResourceLimits::default()), None, Path::new("/"))
+			.expect("options should compile");
+		assert!(jail.is_none());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn limits_must_be_integers_or_infinity() {
+		assert_eq!(limit_value("openFiles", 64.0).ok(), Some(64));
+		assert_eq!(limit_value("cpuSeconds", f64::INFINITY).ok(), Some(libc::RLIM_INFINITY));
+		for value in [f64::NAN, f64::NEG_INFINITY, -1.0, 1.5] {
+			let err = limit_value("openFiles", value).expect_err("value should be rejected");
+			assert!(err.reason.starts_with("Invalid resource limit openFiles"), "{}", err.reason);
+		}
+	}
+
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn read_only_mounts_skip_pseudo_and_writable_paths() {
+		let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+23 22 0:5 / /proc rw,nosuid,nodev,noexec,relatime shared:2 - proc proc rw
+24 22 0:6 / /dev rw,nosuid shared:3 - devtmpfs udev rw
+25 22 8:2 / /home/me/my\\040project rw,nodev,noatime shared:4 - ext4 /dev/sda2 rw
+26 22 8:3 / /srv rw,nosuid,nodev shared:5 - ext4 /dev/sda3 rw";
+		let mounts =
+			linux::read_only_mounts(mountinfo, &[std::path::PathBuf::from("/home/me/my project")]);
+		assert_eq!(mounts, [
+			(std::path::PathBuf::from("/"), libc::MS_RELATIME),
+			(std::path::PathBuf::from("/srv"), libc::MS_NOSUID | libc::MS_NODEV),
+		]);
+	}
+}
@@ -20,9 +20,16 @@ use std::{
 	io::{self, Write},
 	str,
 	sync::Arc,
-	time::Duration,
+	time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+mod analyze;
+mod completion;
+mod history;
+mod jobs;
+mod policy;
+mod ready;
+mod snapshot;
 #[cfg(windows)]
 mod windows;
 
@@ -31,32 +38,59 @@ use brush_core::{
 	CreateOptions, ExecutionContext, ExecutionControlFlow, ExecutionExitCode, ExecutionResult,
 	ProcessGroupPolicy, Shell as BrushShell, ShellValue, ShellVariable, builtins,
 	env::EnvironmentScope,
+	interfaces::{CommandKind, CommandTrace, CommandTracer},
+	namedoptions,
 	openfiles::{self, OpenFile, OpenFiles},
+	options::RuntimeOptions,
 };
 use clap::Parser;
+use completion::ShellCompletion;
+use history::{ShellHistoryEntry, ShellHistoryOptions, ShellHistorySearch};
+use jobs::{JobOutputCallback, JobOutputs, ShellJob, ShellJobWaitResult};
 use napi::{
 	bindgen_prelude::*,
 	threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
 	tokio::{
 		self,
-		sync::{Mutex as TokioMutex, mpsc},
+		sync::{MappedMutexGuard, Mutex as TokioMutex, MutexGuard, mpsc},
 		time,
 	},
 };
 use napi_derive::napi;
+pub(crate) use policy::ShellBlockedCommand;
+use policy::{PolicyGate, PolicyRules, ShellPolicyRule};
+pub(crate) use ready::{Readiness, ShellReadyCondition};
+use ready::{ShellStartOptions, ShellStartResult, StartConfig};
 #[cfg(not(unix))]
 use tokio::io::AsyncReadExt as _;
 use tokio_util::sync::CancellationToken;
 #[cfg(windows)]
 use windows::configure_windows_path;
 
-use crate::task;
+use crate::{
+	capture::{CaptureSummary, OutputCapture, OutputLimit},
+	ps::TERMINATE_GRACE,
+	sandbox::{ProcessJail, ResourceLimits, SandboxOptions},
+	task,
+};
 
 const TERM_SIGNAL: i32 = 15;
-const KILL_SIGNAL: i32 = 9;
 
 struct ShellSessionCore {
-	shell: BrushShell,
+	shell:      BrushShell,
+	policy:     Option<Arc<PolicyRules>>,
+	/// Buffers of `&` jobs; absent for terminal sessions, whose jobs write to
+	/// the terminal.
+	job_output: Option<Arc<JobOutputs>>,
+}
+
+impl ShellSessionCore {
+	/// Install a fresh policy gate for the next run and return it.
+	fn begin_policy(&mut self, confirmed: bool) -> Option<Arc<PolicyGate>> {
+		let gate = self.policy.as_ref()?.begin(confirmed);
+		self.shell.set_command_policy(Some(gate.clone()));
+		Some(gate)
+	}
 }
 
 #[derive(Clone, Default)]
@@ -83,6 +117,36 @@ impl ShellAbortState {
 struct ShellConfig {
 	session_env:   Option<HashMap<String, String>>,
 	snapshot_path: Option<String>,
+	policy:        Option<Arc<PolicyRules>>,
+	history:       Option<ShellHistoryOptions>,
+	job_output:    Arc<JobOutputs>,
+}
+
+impl ShellConfig {
+	fn new(
+		session_env: Option<HashMap<String, String>>,
+		snapshot_path: Option<String>,
+		policy: Option<Vec<ShellPolicyRule>>,
+	) -> Result<Self> {
+		let policy = policy.map(PolicyRules::new).transpose()?.map(Arc::new);
+		Ok(Self {
+			session_env,
+			snapshot_path,
+			policy,
+			history: None,
+			job_output: Arc::new(JobOutputs::default()),
+		})
+	}
+
+	fn from_options(options: Option<ShellOptions>) -> Result<Self> {
+		Ok(match options {
+			Some(opt) => Self {
+				history: opt.history,
+				..Self::new(opt.session_env, opt.snapshot_path, opt.policy)?
+			},
+			None => Self::new(None, None, None)?,
+		})
+	}
 }
 
 /// Options for configuring a persistent shell session.
@@ -92,42 +156,210 @@ pub struct ShellOptions {
 	pub session_env:   Option<HashMap<String, String>>,
 	/// Optional snapshot file to source on session creation.
 	pub snapshot_path: Option<String>,
+	/// Rules checked before each simple command runs, after expansion.
+	pub policy:        Option<Vec<ShellPolicyRule>>,
+	/// Record each `run` command line in the session's history.
+	pub history:       Option<ShellHistoryOptions>,
 }
 
 /// Options for running a shell command (internal, lifetime-free).
-struct ShellRunConfig {
+pub(crate) struct ShellRunConfig {
 	/// Command string to execute in the shell.
-	command: String,
+	pub(crate) command:   String,
 	/// Working directory for the command.
-	cwd:     Option<String>,
+	pub(crate) cwd:       Option<String>,
 	/// Environment variables to apply for this command only.
-	env:     Option<HashMap<String, String>>,
+	pub(crate) env:       Option<HashMap<String, String>>,
+	/// Whether `confirm` policy rules are lifted for this run.
+	pub(crate) confirmed: bool,
+	/// Receiver for per-command trace records.
+	pub(crate) tracer:    Option<Arc<dyn CommandTracer>>,
+	/// Resource limits for spawned processes.
+	pub(crate) limits:    Option<ResourceLimits>,
+	/// Namespace sandbox for spawned processes.
+	pub(crate) sandbox:   Option<SandboxOptions>,
 }
 
 /// Options for running a shell command.
 #[napi(object)]
 pub struct ShellRunOptions<'env> {
 	/// Command string to execute in the shell.
-	pub command:    String,
+	pub command:      String,
 	/// Working directory for the command.
-	pub cwd:        Option<String>,
+	pub cwd:          Option<String>,
 	/// Environment variables to apply for this command only.
-	pub env:        Option<HashMap<String, String>>,
+	pub env:          Option<HashMap<String, String>>,
 	/// Timeout in milliseconds before cancelling the command.
-	pub timeout_ms: Option<u32>,
+	pub timeout_ms:   Option<u32>,
 	/// Abort signal for cancelling the operation.
-	pub signal:     Option<Unknown<'env>>,
+	pub signal:       Option<Unknown<'env>>,
+	/// Byte budget and head/tail retention applied before output reaches the
+	/// callbacks.
+	pub output_limit: Option<OutputLimit>,
+	/// Allow commands matched by `confirm` policy rules for this run.
+	pub confirmed:    Option<bool>,
+	/// Resource limits for the external processes the command spawns.
+	pub limits:       Option<ResourceLimits>,
+	/// Run external processes in an isolated sandbox (Linux only). Files the
+	/// shell opens for output redirections must lie in the writable paths too;
+	/// builtins otherwise run unconfined in this process.
+	pub sandbox:      Option<SandboxOptions>,
+}
+
+/// Output stream a chunk was read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellStream {
+	/// Standard output.
+	#[napi(value = "stdout")]
+	Stdout,
+	/// Standard error.
+	#[napi(value = "stderr")]
+	Stderr,
+}
+
+/// Output chunk tagged with the stream it was read from.
+///
+/// Stdout and stderr are read from separate pipes that are drained after
+/// every command, so chunks follow the order of writes; only one command's
+/// writes to both streams may arrive out of order with each other.
+#[napi(object)]
+pub struct ShellOutputChunk {
+	/// Stream that produced the chunk.
+	pub stream: ShellStream,
+	/// Decoded chunk text.
+	pub text:   String,
+}
+
+/// How a traced command was carried out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellCommandKind {
+	/// Shell builtin run in-process.
+	#[napi(value = "builtin")]
+	Builtin,
+	/// Shell function.
+	#[napi(value = "function")]
+	Function,
+	/// External program.
+	#[napi(value = "external")]
+	External,
+	/// Refused by the command policy; nothing ran.
+	#[napi(value = "blocked")]
+	Blocked,
+}
+
+/// Record of one simple command executed by the interpreter.
+#[napi(object)]
+pub struct ShellCommandTrace {
+	/// Expanded command name and arguments.
+	pub argv:        Vec<String>,
+	/// Working directory the command ran in.
+	pub cwd:         String,
+	/// How the command was carried out.
+	pub kind:        ShellCommandKind,
+	/// Process ID for external commands.
+	pub pid:         Option<i32>,
+	/// Dispatch time in milliseconds since the Unix epoch.
+	pub started_at:  f64,
+	/// Completion time in milliseconds since the Unix epoch.
+	pub finished_at: f64,
+	/// Exit status of the command.
+	pub exit_code:   i32,
+}
+
+impl From<CommandTrace> for ShellCommandTrace {
+	fn from(trace: CommandTrace) -> Self {
+		let millis = |time: SystemTime| {
+			time
+				.duration_since(UNIX_EPOCH)
+				.map_or(0.0, |elapsed| elapsed.as_secs_f64() * 1000.0)
+		};
+		Self {
+			argv:        trace.argv,
+			cwd:         trace.cwd.to_string_lossy().into_owned(),
+			kind:        match trace.kind {
+				CommandKind::Builtin => ShellCommandKind::Builtin,
+				CommandKind::Function => ShellCommandKind::Function,
+				CommandKind::External => ShellCommandKind::External,
+				CommandKind::Blocked => ShellCommandKind::Blocked,
+			},
+			pid:         trace.pid,
+			started_at:  millis(trace.started_at),
+			finished_at: millis(trace.finished_at),
+			exit_code:   i32::from(trace.exit_code),
+		}
+	}
+}
+
+/// Forwards interpreter trace records to a JS callback.
+struct TraceForwarder(ThreadsafeFunction<ShellCommandTrace>);
+
+impl TraceForwarder {
+	fn shared(callback: ThreadsafeFunction<ShellCommandTrace>) -> Arc<dyn CommandTracer> {
+		Arc::new(Self(callback))
+	}
+}
+
+impl CommandTracer for TraceForwarder {
+	fn on_command(&self, trace: CommandTrace) {
+		self
+			.0
+			.call(Ok(trace.into()), ThreadsafeFunctionCallMode::NonBlocking);
+	}
+}
+
+/// Tracer installed for one run. Background jobs keep it after the run
+/// returns, so it is closed then: their later commands go unreported and the
+/// run's callback is released.
+struct RunTracer(parking_lot::Mutex<Option<Arc<dyn CommandTracer>>>);
+
+impl RunTracer {
+	fn new(tracer: Arc<dyn CommandTracer>) -> Arc<Self> {
+		Arc::new(Self(parking_lot::Mutex::new(Some(tracer))))
+	}
+
+	fn close(&self) {
+		self.0.lock().take();
+	}
+}
+
+impl CommandTracer for RunTracer {
+	fn on_command(&self, trace: CommandTrace) {
+		let tracer = self.0.lock().clone();
+		if let Some(tracer) = tracer {
+			tracer.on_command(trace);
+		}
+	}
 }
 
 /// Result of running a shell command.
 #[napi(object)]
 pub struct ShellRunResult {
 	/// Exit code when the command completes normally.
-	pub exit_code: Option<i32>,
+	pub exit_code:    Option<i32>,
 	/// Whether the command was cancelled via abort.
-	pub cancelled: bool,
+	pub cancelled:    bool,
 	/// Whether the command timed out before completion.
-	pub timed_out: bool,
+	pub timed_out:    bool,
+	/// Bytes of output produced by the command.
+	pub total_bytes:  f64,
+	/// Bytes dropped from the middle of the output by `output_limit`.
+	pub elided_bytes: f64,
+	/// Temp file holding the full output when `output_limit.spill` was set and
+	/// output was elided.
+	pub output_path:  Option<String>,
+	/// Command refused by the policy; the run stops at the first refusal.
+	pub blocked:      Option<ShellBlockedCommand>,
+}
+
+/// Option states of a shell session.
+#[napi(object)]
+pub struct ShellOptionFlags {
+	/// Options controlled by `set -o`, keyed by long name.
+	pub set_o: HashMap<String, bool>,
+	/// Options controlled by `shopt`.
+	pub shopt: HashMap<String, bool>,
 }
 
 /// Persistent brush-core shell session.
@@ -143,24 +375,29 @@ impl Shell {
 	#[napi(constructor)]
 	/// Create a new shell session from optional configuration.
 	///
-	/// The options set session-scoped environment variables and a snapshot path.
-	pub fn new(options: Option<ShellOptions>) -> Self {
-		let config = options.map_or_else(
-			|| ShellConfig { session_env: None, snapshot_path: None },
-			|opt| ShellConfig { session_env: opt.session_env, snapshot_path: opt.snapshot_path },
-		);
-		Self {
-			session: Arc::new(TokioMutex::new(None)),
+	/// The options set session-scoped environment variables, a snapshot path,
+	/// the command policy, and history recording. Fails when a policy pattern
+	/// is not a valid regex.
+	pub fn new(options: Option<ShellOptions>) -> Result<Self> {
+		Ok(Self {
+			session:     Arc::new(TokioMutex::new(None)),
 			abort_state: ShellAbortState::default(),
-			config,
-		}
+			config:      ShellConfig::from_options(options)?,
+		})
 	}
 
 	/// Run a shell command using the provided options.
 	///
-	/// The `on_chunk` callback receives streamed stdout/stderr output. Returns
-	/// the exit code when the command completes, or flags when cancelled or
-	/// timed out.
+	/// The `on_chunk` callback receives streamed stdout/stderr output. When
+	/// `on_output` is provided, stdout and stderr are read from separate pipes
+	/// and each chunk is also delivered tagged with its stream; both callbacks
+	/// then see the output of each command before that of the next, so only
+	/// one command's writes to stdout and stderr can be reordered with each
+	/// other. Without `on_output`, `on_chunk` sees the exact write order. When
+	/// `on_trace` is provided, it receives a record for every simple command
+	/// the interpreter executes or the policy refuses, until the run returns;
+	/// background jobs' later commands are not reported. Returns the exit code
+	/// when the command completes, or flags when cancelled or timed out.
 	#[napi]
 	pub fn run<'e>(
 		&self,
@@ -168,17 +405,31 @@ impl Shell {
 		options: ShellRunOptions<'e>,
 		#[napi(ts_arg_type = "((error: Error | null, chunk: string) => void) | undefined | null")]
 		on_chunk: Option<ThreadsafeFunction<String>>,
+		#[napi(ts_arg_type = "((error: Error | null, chunk: ShellOutputChunk) => void) | \
+		                      undefined | null")]
+		on_output: Option<ThreadsafeFunction<ShellOutputChunk>>,
+		#[napi(ts_arg_type = "((error: Error | null, trace: ShellCommandTrace) => void) | \
+		                      undefined | null")]
+		on_trace: Option<ThreadsafeFunction<ShellCommandTrace>>,
 	) -> Result<PromiseRaw<'e, ShellRunResult>> {
 		let ct = task::CancelToken::new(options.timeout_ms, options.signal);
 		let session = self.session.clone();
 		let abort_state = self.abort_state.clone();
 		let config = self.config.clone();
 
-		let run_config =
-			ShellRunConfig { command: options.command, cwd: options.cwd, env: options.env };
+		let run_config = ShellRunConfig {
+			command:   options.command,
+			cwd:       options.cwd,
+			env:       options.env,
+			confirmed: options.confirmed.unwrap_or(false),
+			tracer:    on_trace.map(TraceForwarder::shared),
+			limits:    options.limits,
+			sandbox:   options.sandbox,
+		};
+		let sink = OutputSink::new(on_chunk, on_output, options.output_limit);
 
 		task::future(env, "shell.run", async move {
-			run_shell_session(session, abort_state, config, run_config, on_chunk, ct).await
+			run_shell_session(session, abort_state, config, run_config, sink, ct).await
 		})
 	}
 
@@ -190,6 +441,227 @@ impl Shell {
 		self.abort_state.abort().await;
 		Ok(())
 	}
+
+	/// Current working directory of the session.
+	///
+	/// Like the other state accessors, this starts the session if no command
+	/// has run yet and waits for a running command to finish.
+	#[napi]
+	pub async fn cwd(&self) -> Result<String> {
+		self
+			.with_session(|shell| shell.working_dir().to_string_lossy().into_owned())
+			.await
+	}
+
+	/// Exported variables visible to commands run in the session.
+	#[napi]
+	pub async fn env(&self) -> Result<HashMap<String, String>> {
+		self.with_session(exported_env).await
+	}
+
+	/// Names of the shell functions defined in the session, sorted.
+	#[napi]
+	pub async fn functions(&self) -> Result<Vec<String>> {
+		self.with_session(function_names).await
+	}
+
+	/// Aliases defined in the session, keyed by name.
+	#[napi]
+	pub async fn aliases(&self) -> Result<HashMap<String, String>> {
+		self.with_session(|shell| shell.aliases.clone()).await
+	}
+
+	/// `set -o` and `shopt` option states of the session.
+	#[napi]
+	pub async fn options(&self) -> Result<ShellOptionFlags> {
+		self.with_session(option_flags).await
+	}
+
+	/// Write the session's variables, functions, aliases and changed options
+	/// to `path` as a script that can be passed back as `snapshot_path`.
+	#[napi]
+	pub async fn export_snapshot(&self, path: String) -> Result<()> {
+		let defaults = RuntimeOptions::defaults_from(&session_create_options());
+		let script = self
+			.with_session(|shell| snapshot::render(shell, &defaults))
+			.await??;
+		fs::write(&path, script)
+			.map_err(|err| Error::from_reason(format!("Failed to write snapshot: {err}")))
+	}
+
+	/// Completion candidates for `line` with the cursor at `cursor`, a
+	/// JavaScript string index.
+	///
+	/// Uses compspecs registered with `complete` (e.g. `complete -F` functions
+	/// from the snapshot) and otherwise completes command names, files, and
+	/// `$` variable references.
+	#[napi]
+	pub async fn complete(&self, line: String, cursor: u32) -> Result<ShellCompletion> {
+		let mut session = self.session().await?;
+		completion::complete(&mut session.shell, &line, cursor).await
+	}
+
+	/// The last `limit` history entries (all when omitted), oldest first.
+	///
+	/// Empty unless the session was created with the `history` option.
+	#[napi]
+	pub async fn history(&self, limit: Option<u32>) -> Result<Vec<ShellHistoryEntry>> {
+		self
+			.with_session(|shell| history::entries(shell, limit))
+			.await
+	}
+
+	/// History entries matching `query`, newest first.
+	#[napi]
+	pub async fn search_history(&self, query: ShellHistorySearch) -> Result<Vec<ShellHistoryEntry>> {
+		self
+			.with_session(|shell| history::search(shell, &query))
+			.await
+	}
+
+	/// Start a command in the background and resolve once its `ready`
+	/// condition holds, it finishes, or the timeout or abort signal fires.
+	///
+	/// The command runs as a job of the session and keeps running when the
+	/// wait gives up. The result carries its job ID, PID and the output it
+	/// wrote while starting up.
+	#[napi]
+	pub fn start_job<'e>(
+		&self,
+		env: &'e Env,
+		options: ShellStartOptions<'e>,
+	) -> Result<PromiseRaw<'e, ShellStartResult>> {
+		let ct = task::CancelToken::new(options.timeout_ms, options.signal);
+		let session = self.session.clone();
+		let config = self.config.clone();
+		let start_config = StartConfig {
+			command: options.command,
+			cwd:     options.cwd,
+			env:     options.env,
+			ready:   options.ready,
+		};
+
+		task::future(env, "shell.startJob", async move {
+			ready::start(session, config, start_config, ct).await
+		})
+	}
+
+	/// Background jobs of the session, oldest first.
+	///
+	/// Finished jobs stay listed with their exit code until `wait_job`
+	/// collects them.
+	#[napi]
+	pub async fn jobs(&self) -> Result<Vec<ShellJob>> {
+		let mut session = self.session().await?;
+		jobs::list(&mut session.shell)
+	}
+
+	/// The last `max_bytes` of a background job's combined stdout and stderr;
+	/// all retained output (up to 1 MiB) when omitted.
+	///
+	/// Doesn't wait for a running command, so a job can be tailed while the
+	/// foreground is busy.
+	#[napi]
+	pub fn job_output(&self, id: u32, max_bytes: Option<u32>) -> Result<String> {
+		self.config.job_output.tail(id, max_bytes)
+	}
+
+	/// Stream a background job's output to `on_chunk`: the retained output
+	/// first, then each chunk as the job writes it, until its output closes.
+	///
+	/// The callback doesn't keep the process alive.
+	#[napi]
+	pub fn stream_job_output(
+		&self,
+		id: u32,
+		#[napi(ts_arg_type = "(error: Error | null, chunk: string) => void")]
+		on_chunk: JobOutputCallback,
+	) -> Result<()> {
+		self.config.job_output.subscribe(id, on_chunk)
+	}
+
+	/// Send `signal` (a name such as `INT` or `SIGKILL`, or a number; `TERM`
+	/// when omitted) to a background job's processes.
+	#[napi]
+	pub async fn signal_job(&self, id: u32, signal: Option<String>) -> Result<()> {
+		let session = self.session().await?;
+		jobs::signal(&session.shell, id, signal.as_deref())
+	}
+
+	/// Wait up to `timeout_ms` (indefinitely when omitted) for a background job
+	/// to finish, then remove it from the job table.
+	///
+	/// The session is only locked while checking, so commands can run while a
+	/// wait is pending.
+	#[napi]
+	pub async fn wait_job(&self, id: u32, timeout_ms: Option<u32>) -> Result<ShellJobWaitResult> {
+		const POLL_INTERVAL: Duration = Duration::from_millis(50);
+		let deadline =
+			timeout_ms.map(|ms| time::Instant::now() + Duration::from_millis(u64::from(ms)));
+		loop {
+			let acquired = match deadline {
+				Some(deadline) => time::timeout_at(deadline, self.session()).await.ok(),
+				None => Some(self.session().await),
+			};
+			let Some(session) = acquired else {
+				break;
+			};
+			let collected = jobs::collect(&mut session?.shell, id)?;
+			if let Some(result) = collected {
+				return Ok(result);
+			}
+			if deadline.is_some_and(|deadline| time::Instant::now() >= deadline) {
+				break;
+			}
+			let next = time::Instant::now() + POLL_INTERVAL;
+			time::sleep_until(deadline.map_or(next, |deadline| deadline.min(next))).await;
+		}
+		Ok(ShellJobWaitResult { exit_code: None, timed_out: true })
+	}
+}
+
+impl Shell {
+	/// Lock the session, creating it if no command has run yet.
+	async fn session(&self) -> Result<MappedMutexGuard<'_, ShellSessionCore>> {
+		let mut session_guard = self.session.lock().await;
+		if session_guard.is_none() {
+			*session_guard = Some(create_session(&self.config).await?);
+		}
+		Ok(MutexGuard::map(session_guard, |session| {
+			session.as_mut().expect("session was just created")
+		}))
+	}
+
+	async fn with_session<T>(&self, read: impl FnOnce(&BrushShell) -> T) -> Result<T> {
+		Ok(read(&self.session().await?.shell))
+	}
+}
+
+fn exported_env(shell: &BrushShell) -> HashMap<String, String> {
+	shell
+		.env
+		.iter_exported()
+		.map(|(name, var)| (name.clone(), var.value().to_cow_str(shell).into_owned()))
+		.collect()
+}
+
+fn function_names(shell: &BrushShell) -> Vec<String> {
+	let mut names: Vec<String> = shell.funcs().iter().map(|(name, _)| name.clone()).collect();
+	names.sort_unstable();
+	names
+}
+
+fn option_flags(shell: &BrushShell) -> ShellOptionFlags {
+	let read = |kind| {
+		namedoptions::options(kind)
+			.iter()
+			.map(|option| (option.name.to_string(), option.definition.get(&shell.options)))
+			.collect()
+	};
+	ShellOptionFlags {
+		set_o: read(namedoptions::ShellOptionKind::SetO),
+		shopt: read(namedoptions::ShellOptionKind::Shopt),
+	}
 }
 
 /// Run a shell command within a persistent session.
@@ -198,12 +670,13 @@ async fn run_shell_session(
 	abort_state: ShellAbortState,
 	config: ShellConfig,
 	run_config: ShellRunConfig,
-	on_chunk: Option<ThreadsafeFunction<String>>,
+	sink: OutputSink,
 	mut ct: task::CancelToken,
 ) -> Result<ShellRunResult> {
 	let tokio_cancel = CancellationToken::new();
 
 	let mut run_task = tokio::spawn({
+		let sink = sink.clone();
 		let session = session.clone();
 		let abort_state = abort_state.clone();
 		let tokio_cancel = tokio_cancel.clone();
@@ -216,7 +689,7 @@ async fn run_shell_session(
 				None => session_guard.insert(create_session(&config).await?),
 			};
 			abort_state.set(at).await;
-			run_shell_command(session, &run_config, on_chunk, tokio_cancel).await
+			run_shell_command(session, &run_config, sink, tokio_cancel).await
 		}
 	});
 
@@ -236,10 +709,15 @@ async fn run_shell_session(
 			if let Ok(mut guard) = session.try_lock() {
 				*guard = None;
 			}
+			let summary = sink.finish();
 			return Ok(ShellRunResult {
-				exit_code: None,
-				cancelled: matches!(reason, task::AbortReason::Signal),
-				timed_out: matches!(reason, task::AbortReason::Timeout),
+				exit_code:    None,
+				cancelled:    matches!(reason, task::AbortReason::Signal),
+				timed_out:    matches!(reason, task::AbortReason::Timeout),
+				total_bytes:  summary.total_bytes as f64,
+				elided_bytes: summary.elided_bytes as f64,
+				output_path:  summary.output_path,
+				blocked:      None,
 			});
 		}
 	};
@@ -247,11 +725,23 @@ async fn run_shell_session(
 		res.unwrap_or_else(|e| Err(Error::from_reason(format!("Shell execution task failed: {e}"))));
 	abort_state.clear().await;
 
-	let keepalive = res.as_ref().is_ok_and(session_keepalive);
+	let keepalive = res
+		.as_ref()
+		.is_ok_and(|(result, _)| session_keepalive(result));
 	if !keepalive {
 		*session.lock().await = None;
 	}
-	Ok(ShellRunResult { exit_code: Some(exit_code(&res?)), cancelled: false, timed_out: false })
+	let summary = sink.finish();
+	let (result, blocked) = res?;
+	Ok(ShellRunResult {
+		exit_code: Some(exit_code(&result)),
+		cancelled: false,
+		timed_out: false,
+		total_bytes: summary.total_bytes as f64,
+		elided_bytes: summary.elided_bytes as f64,
+		output_path: summary.output_path,
+		blocked,
+	})
 }
 
 /// Options for executing a shell command via brush-core.
@@ -271,39 +761,80 @@ pub struct ShellExecuteOptions<'env> {
 	pub snapshot_path: Option<String>,
 	/// Abort signal for cancelling the operation.
 	pub signal:        Option<Unknown<'env>>,
+	/// Byte budget and head/tail retention applied before output reaches the
+	/// callbacks.
+	pub output_limit:  Option<OutputLimit>,
+	/// Rules checked before each simple command runs, after expansion.
+	pub policy:        Option<Vec<ShellPolicyRule>>,
+	/// Allow commands matched by `confirm` policy rules.
+	pub confirmed:     Option<bool>,
+	/// Resource limits for the external processes the command spawns.
+	pub limits:        Option<ResourceLimits>,
+	/// Run external processes in an isolated sandbox (Linux only). Files the
+	/// shell opens for output redirections must lie in the writable paths too;
+	/// builtins otherwise run unconfined in this process.
+	pub sandbox:       Option<SandboxOptions>,
 }
 
 /// Result of executing a shell command via brush-core.
 #[napi(object)]
 pub struct ShellExecuteResult {
 	/// Exit code when the command completes normally.
-	pub exit_code: Option<i32>,
+	pub exit_code:    Option<i32>,
 	/// Whether the command was cancelled via abort.
-	pub cancelled: bool,
+	pub cancelled:    bool,
 	/// Whether the command timed out before completion.
-	pub timed_out: bool,
+	pub timed_out:    bool,
+	/// Bytes of output produced by the command.
+	pub total_bytes:  f64,
+	/// Bytes dropped from the middle of the output by `output_limit`.
+	pub elided_bytes: f64,
+	/// Temp file holding the full output when `output_limit.spill` was set and
+	/// output was elided.
+	pub output_path:  Option<String>,
+	/// Command refused by the policy; the run stops at the first refusal.
+	pub blocked:      Option<ShellBlockedCommand>,
 }
 
 /// Execute a brush shell command.
 ///
 /// Creates a fresh session for each call. The `on_chunk` callback receives
-/// streamed stdout/stderr output. Returns the exit code when the command
-/// completes, or flags when cancelled or timed out.
+/// streamed stdout/stderr output; `on_output` additionally receives chunks
+/// tagged by stream (with the same ordering caveat) and `on_trace`
+/// per-command records, as in [`Shell::run`].
+/// Returns the exit code when the command completes, or flags when cancelled
+/// or timed out.
 #[napi]
 pub fn execute_shell<'env>(
 	env: &'env Env,
 	options: ShellExecuteOptions<'env>,
 	#[napi(ts_arg_type = "((error: Error | null, chunk: string) => void) | undefined | null")]
 	on_chunk: Option<ThreadsafeFunction<String>>,
+	#[napi(
+		ts_arg_type = "((error: Error | null, chunk: ShellOutputChunk) => void) | undefined | null"
+	)]
+	on_output: Option<ThreadsafeFunction<ShellOutputChunk>>,
+	#[napi(
+		ts_arg_type = "((error: Error | null, trace: ShellCommandTrace) => void) | undefined | null"
+	)]
+	on_trace: Option<ThreadsafeFunction<ShellCommandTrace>>,
 ) -> Result<PromiseRaw<'env, ShellExecuteResult>> {
-	let config =
-		ShellConfig { session_env: options.session_env, snapshot_path: options.snapshot_path };
-	let run_config =
-		ShellRunConfig { command: options.command, cwd: options.cwd, env: options.env };
+	let config = ShellConfig::new(options.session_env, options.snapshot_path, options.policy)?;
+	let run_config = ShellRunConfig {
+		command:   options.command,
+		cwd:       options.cwd,
+		env:       options.env,
+		confirmed: options.confirmed.unwrap_or(false),
+		tracer:    on_trace.map(TraceForwarder::shared),
+		limits:    options.limits,
+		sandbox:   options.sandbox,
+	};
+
+	let sink = OutputSink::new(on_chunk, on_output, options.output_limit);
 
 	let ct = task::CancelToken::new(options.timeout_ms, options.signal);
 	task::future(env, "shell.execute", async move {
-		run_shell_oneshot(config, run_config, on_chunk, ct).await
+		run_shell_oneshot(config, run_config, sink, ct).await
 	})
 }
 
@@ -311,16 +842,17 @@ pub fn execute_shell<'env>(
 async fn run_shell_oneshot(
 	config: ShellConfig,
 	run_config: ShellRunConfig,
-	on_chunk: Option<ThreadsafeFunction<String>>,
+	sink: OutputSink,
 	ct: task::CancelToken,
 ) -> Result<ShellExecuteResult> {
 	let tokio_cancel = CancellationToken::new();
 
 	let mut task = tokio::spawn({
+		let sink = sink.clone();
 		let tokio_cancel = tokio_cancel.clone();
 		async move {
 			let mut session = create_session(&config).await?;
-			run_shell_command(&mut session, &run_config, on_chunk, tokio_cancel).await
+			run_shell_command(&mut session, &run_config, sink, tokio_cancel).await
 		}
 	});
 
@@ -333,10 +865,15 @@ async fn run_shell_oneshot(
 				task.abort();
 				let _ = task.await;
 			}
+			let summary = sink.finish();
 			return Ok(ShellExecuteResult {
-				exit_code: None,
-				cancelled: matches!(reason, task::AbortReason::Signal),
-				timed_out: matches!(reason, task::AbortReason::Timeout),
+				exit_code:    None,
+				cancelled:    matches!(reason, task::AbortReason::Signal),
+				timed_out:    matches!(reason, task::AbortReason::Timeout),
+				total_bytes:  summary.total_bytes as f64,
+				elided_bytes: summary.elided_bytes as f64,
+				output_path:  summary.output_path,
+				blocked:      None,
 			})
 		},
 	};
@@ -344,7 +881,17 @@ async fn run_shell_oneshot(
 	let res = run_result
 		.unwrap_or_else(|e| Err(Error::from_reason(format!("Shell execution task failed: {e}"))));
 
-	Ok(ShellExecuteResult { exit_code: Some(exit_code(&res?)), cancelled: false, timed_out: false })
+	let summary = sink.finish();
+	let (result, blocked) = res?;
+	Ok(ShellExecuteResult {
+		exit_code: Some(exit_code(&result)),
+		cancelled: false,
+		timed_out: false,
+		total_bytes: summary.total_bytes as f64,
+		elided_bytes: summary.elided_bytes as f64,
+		output_path: summary.output_path,
+		blocked,
+	})
 }
 
 fn null_file() -> Result<OpenFile> {
@@ -423,15 +970,34 @@ fn merge_path_values(_existing: &str, incoming: &str) -> String {
 	incoming.to_string()
 }
 
-async fn create_session(config: &ShellConfig) -> Result<ShellSessionCore> {
-	let create_options = CreateOptions {
+fn session_create_options() -> CreateOptions {
+	CreateOptions {
 		interactive: false,
 		login: false,
 		no_profile: true,
 		no_rc: true,
 		do_not_inherit_env: true,
-		builtins: default_builtins(BuiltinSet::BashMode),
 		..Default::default()
+	}
+}
+
+async fn create_session(config: &ShellConfig) -> Result<ShellSessionCore> {
+	let mut session = create_terminal_session(config).await?;
+	// Job numbering starts over with the session.
+	config.job_output.reset();
+	session
+		.shell
+		.set_job_output(Some(config.job_output.clone()));
+	session.job_output = Some(config.job_output.clone());
+	Ok(session)
+}
+
+/// Session whose background jobs share the output of the run that started
+/// them.
+async fn create_terminal_session(config: &ShellConfig) -> Result<ShellSessionCore> {
+	let create_options = CreateOptions {
+		builtins: default_builtins(BuiltinSet::BashMode),
+		..session_create_options()
 	};
 
 	let mut shell = BrushShell::new(create_options)
@@ -506,7 +1072,14 @@ async fn create_session(config: &ShellConfig) -> Result<ShellSessionCore> {
 		source_snapshot(&mut shell, snapshot_path).await?;
 	}
 
-	Ok(ShellSessionCore { shell })
+	if let Some(options) = config.history.as_ref() {
+		history::configure(&mut shell, options)?;
+	}
+
+	// Installed after the snapshot so the user's own setup is never refused.
+	let mut session = ShellSessionCore { shell, policy: config.policy.clone(), job_output: None };
+	session.begin_policy(false);
+	Ok(session)
 }
 
 async fn source_snapshot(shell: &mut BrushShell, snapshot_path: &str) -> Result<()> {
@@ -524,27 +1097,160 @@ async fn source_snapshot(shell: &mut BrushShell, snapshot_path: &str) -> Result<
 	Ok(())
 }
 
-async fn run_shell_command(
-	session: &mut ShellSessionCore,
-	options: &ShellRunConfig,
-	on_chunk: Option<ThreadsafeFunction<String>>,
-	cancel_token: CancellationToken,
-) -> Result<ExecutionResult> {
+/// Apply the run's working directory and build the jail for the processes it
+/// spawns.
+fn enter_run(shell: &mut BrushShell, options: &ShellRunConfig) -> Result<Option<ProcessJail>> {
 	if let Some(cwd) = options.cwd.as_deref() {
-		session
-			.shell
+		shell
 			.set_working_dir(cwd)
 			.map_err(|err| Error::from_reason(format!("Failed to set cwd: {err}")))?;
 	}
 
+	// Builtins run in-process: only spawned programs and the files the shell
+	// opens for redirections are confined.
+	let jail = ProcessJail::new(options.limits, options.sandbox.as_ref(), shell.working_dir())?;
+	if let Some(jail) = jail.as_ref() {
+		jail.probe()?;
+	}
+	Ok(jail)
+}
+
+/// Process setup for one run: confines spawned programs to the run's jail,
+/// hands them the run's terminal as controlling terminal and, when the run is
+/// cancelled, terminates each program's tree with SIGTERM before resorting
+/// to SIGKILL.
+#[cfg(unix)]
+struct RunSetup {
+	jail:     Option<ProcessJail>,
+	terminal: Option<std::path::PathBuf>,
+}
+
+#[cfg(unix)]
+impl brush_core::interfaces::ProcessSetup for RunSetup {
+	fn before_spawn(
+		&self,
+		command: &mut std::process::Command,
+	) -> std::result::Result<(), brush_core::Error> {
+		if let Some(jail) = self.jail.as_ref() {
+			jail.attach(command);
+		}
+		Ok(())
+	}
+
+	fn check_write(&self, path: &std::path::Path) -> std::io::Result<()> {
+		self
+			.jail
+			.as_ref()
+			.map_or(Ok(()), |jail| jail.check_write(path))
+	}
+
+	fn controlling_terminal(&self) -> Option<&std::path::Path> {
+		self.terminal.as_deref()
+	}
+
+	fn terminate(&self, pid: i32) -> bool {
+		// Foreground commands lead their own process group, or session on a
+		// terminal.
+		std::thread::spawn(move || {
+			crate::ps::terminate_trees(&[pid], &[pid], TERM_SIGNAL, TERMINATE_GRACE)
+		});
+		true
+	}
+}
+
+/// Push a command scope holding `env`; returns whether a scope was pushed.
+fn push_command_env(shell: &mut BrushShell, env: Option<&HashMap<String, String>>) -> Result<bool> {
+	let Some(env) = env else {
+		return Ok(false);
+	};
+	shell.env.push_scope(EnvironmentScope::Command);
+	for (key, value) in env {
+		let normalized_key = normalize_env_key(key);
+		if should_skip_env_var(normalized_key) {
+			continue;
+		}
+		let mut var = ShellVariable::new(ShellValue::String(value.clone()));
+		var.export();
+		if let Err(err) = shell
+			.env
+			.add(normalized_key, var, EnvironmentScope::Command)
+		{
+			let _ = shell.env.pop_scope(EnvironmentScope::Command);
+			return Err(Error::from_reason(format!("Failed to set env: {err}")));
+		}
+	}
+	Ok(true)
+}
+
+/// Run a command in a fresh session whose stdin, stdout and stderr are the
+/// terminal `tty` at `tty_path`; used by `PtySession` when `shell` options are
+/// given. Returns the exit code and the command the policy refused, if any.
+///
+/// The session stays inside the Node.js process, so each foreground program
+/// the command spawns leads a session of its own with the terminal as its
+/// controlling terminal, which delivers Ctrl-C and window size changes to it.
+#[cfg(unix)]
+pub(crate) async fn run_on_terminal(
+	options: ShellOptions,
+	run_config: ShellRunConfig,
+	tty: fs::File,
+	tty_path: std::path::PathBuf,
+	cancel_token: CancellationToken,
+) -> Result<(i32, Option<ShellBlockedCommand>)> {
+	let mut session = create_terminal_session(&ShellConfig::from_options(Some(options))?).await?;
+	let jail = enter_run(&mut session.shell, &run_config)?;
+
+	let stream = |tty: &fs::File| {
+		tty.try_clone()
+			.map(OpenFile::from)
+			.map_err(|err| Error::from_reason(format!("Failed to clone terminal: {err}")))
+	};
+	let mut params = session.shell.default_exec_params();
+	params.set_fd(OpenFiles::STDIN_FD, stream(&tty)?);
+	params.set_fd(OpenFiles::STDOUT_FD, stream(&tty)?);
+	params.set_fd(OpenFiles::STDERR_FD, OpenFile::from(tty));
+	params.process_group_policy = ProcessGroupPolicy::NewProcessGroup;
+	params.set_cancel_token(cancel_token.clone());
+
+	// The session ends with this command, so the scope is never popped.
+	push_command_env(&mut session.shell, run_config.env.as_ref())?;
+	let gate = session.begin_policy(run_config.confirmed);
+	history::record(&mut session.shell, &run_config.command);
+	session
+		.shell
+		.set_process_setup(Some(Arc::new(RunSetup { jail, terminal: Some(tty_path) })));
+	let result = session.shell.run_string(run_config.command, &params).await;
+	if cancel_token.is_cancelled() {
+		terminate_background_jobs(&session.shell);
+	}
+	let blocked = gate.and_then(|gate| gate.take_blocked());
+	let result =
+		result.map_err(|err| Error::from_reason(format!("Shell execution failed: {err}")))?;
+	Ok((exit_code(&result), blocked))
+}
+
+async fn run_shell_command(
+	session: &mut ShellSessionCore,
+	options: &ShellRunConfig,
+	sink: OutputSink,
+	cancel_token: CancellationToken,
+) -> Result<(ExecutionResult, Option<ShellBlockedCommand>)> {
+	let jail = enter_run(&mut session.shell, options)?;
+
 	let (reader_file, writer_file) = pipe_to_files("output")?;
 
-	let stdout_file = OpenFile::from(
-		writer_file
+	// Without a tagged consumer both fds share one pipe, which keeps the exact
+	// interleaving the kernel saw; splitting is only worth it when asked for.
+	let (stderr_reader, stderr_file) = if sink.is_split() {
+		let (reader, writer) = pipe_to_files("stderr")?;
+		(Some(reader), OpenFile::from(writer))
+	} else {
+		let writer = writer_file
 			.try_clone()
-			.map_err(|err| Error::from_reason(format!("Failed to clone pipe: {err}")))?,
-	);
-	let stderr_file = OpenFile::from(writer_file);
+			.map_err(|err| Error::from_reason(format!("Failed to clone pipe: {err}")))?;
+		(None, OpenFile::from(writer))
+	};
+	let stdout_file = OpenFile::from(writer_file);
 
 	let mut params = session.shell.default_exec_params();
 	params.set_fd(OpenFiles::STDIN_FD, null_file()?);
@@ -553,49 +1259,107 @@ async fn run_shell_command(
 	params.process_group_policy = ProcessGroupPolicy::NewProcessGroup;
 	params.set_cancel_token(cancel_token.clone());
 
-	let mut env_scope_pushed = false;
-	if let Some(env) = options.env.as_ref() {
-		session.shell.env.push_scope(EnvironmentScope::Command);
-		env_scope_pushed = true;
-		for (key, value) in env {
-			let normalized_key = normalize_env_key(key);
-			if should_skip_env_var(normalized_key) {
-				continue;
-			}
-			let mut var = ShellVariable::new(ShellValue::String(value.clone()));
-			var.export();
-			if let Err(err) = session
-				.shell
-				.env
-				.add(normalized_key, var, EnvironmentScope::Command)
-			{
-				let _ = session.shell.env.pop_scope(EnvironmentScope::Command);
-				return Err(Error::from_reason(format!("Failed to set env: {err}")));
-			}
-		}
-	}
+	let env_scope_pushed = push_command_env(&mut session.shell, options.env.as_ref())?;
 
 	let reader_cancel = CancellationToken::new();
 	let (activity_tx, mut activity_rx) = mpsc::channel::<()>(1);
-	let mut reader_handle = tokio::spawn({
-		let reader_cancel = reader_cancel.clone();
-		async move {
-			Box::pin(read_output(reader_file, on_chunk, reader_cancel, activity_tx)).await;
-			Result::<()>::Ok(())
-		}
-	});
+	#[cfg(unix)]
+	let (mut reader_handle, tracer) = {
+		let open = |reader, stream| {
+			let sink = sink.clone();
+			OutputPipe::new(reader, move |text| sink.emit(stream, text))
+				.map(Arc::new)
+				.map_err(|err| Error::from_reason(format!("Failed to read output pipe: {err}")))
+		};
+		let stdout = open(reader_file, ShellStream::Stdout)?;
+		let stderr = stderr_reader
+			.map(|reader| open(reader, ShellStream::Stderr))
+			.transpose()?;
+		let tracer = match &stderr {
+			Some(stderr) => Some(Arc::new(OutputBarrier {
+				pipes:  vec![stdout.clone(), stderr.clone()],
+				tracer: options.tracer.clone(),
+			}) as Arc<dyn CommandTracer>),
+			None => options.tracer.clone(),
+		};
+		let handle = tokio::spawn({
+			let reader_cancel = reader_cancel.clone();
+			async move {
+				let stdout = read_pipe(stdout, reader_cancel.clone(), activity_tx.clone());
+				let stderr = async move {
+					if let Some(stderr) = stderr {
+						read_pipe(stderr, reader_cancel, activity_tx).await;
+					}
+				};
+				tokio::join!(Box::pin(stdout), Box::pin(stderr));
+				Result::<()>::Ok(())
+			}
+		});
+		(handle, tracer)
+	};
+	#[cfg(not(unix))]
+	let (mut reader_handle, tracer) = {
+		let handle = tokio::spawn({
+			let reader_cancel = reader_cancel.clone();
+			async move {
+				let stdout = read_output(reader_file, reader_cancel.clone(), activity_tx.clone(), {
+					let sink = sink.clone();
+					move |text| sink.emit(ShellStream::Stdout, text)
+				});
+				let stderr = async move {
+					if let Some(reader) = stderr_reader {
+						read_output(reader, reader_cancel, activity_tx, move |text| {
+							sink.emit(ShellStream::Stderr, text);
+						})
+						.await;
+					}
+				};
+				tokio::join!(Box::pin(stdout), Box::pin(stderr));
+				Result::<()>::Ok(())
+			}
+		});
+		(handle, options.tracer.clone())
+	};
 	let cancel_bridge = tokio::spawn({
 		let cancel_token = cancel_token.clone();
 		let reader_cancel = reader_cancel.clone();
 		async move {
 			cancel_token.cancelled().await;
+			// Keep reading while cancelled commands clean up: closing the pipe
+			// would kill them with SIGPIPE on their last words.
+			time::sleep(TERMINATE_GRACE).await;
 			reader_cancel.cancel();
 		}
 	});
+	let gate = session.begin_policy(options.confirmed);
+	history::record(&mut session.shell, &options.command);
+	let tracer = tracer.map(RunTracer::new);
+	session.shell.set_command_tracer(
+		tracer
+			.clone()
+			.map(|tracer| tracer as Arc<dyn CommandTracer>),
+	);
+	#[cfg(unix)]
+	session
+		.shell
+		.set_process_setup(Some(Arc::new(RunSetup { jail, terminal: None })));
+	if let Some(job_output) = &session.job_output {
+		job_output.begin_run(sink.clone());
+	}
 	let result = session
 		.shell
 		.run_string(options.command.clone(), &params)
 		.await;
+	if let Some(job_output) = &session.job_output {
+		job_output.end_run();
+	}
+	session.shell.set_command_tracer(None);
+	if let Some(tracer) = tracer {
+		tracer.close();
+	}
+	#[cfg(unix)]
+	session.shell.set_process_setup(None);
+	let blocked = gate.and_then(|gate| gate.take_blocked());
 
 	if cancel_token.is_cancelled() {
 		terminate_background_jobs(&session.shell);
@@ -652,7 +1416,9 @@ async fn run_shell_command(
 	cancel_bridge.abort();
 	let _ = cancel_bridge.await;
 
-	result.map_err(|err| Error::from_reason(format!("Shell execution failed: {err}")))
+	let result =
+		result.map_err(|err| Error::from_reason(format!("Shell execution failed: {err}")))?;
+	Ok((result, blocked))
 }
 
 #[cfg(unix)]
@@ -662,7 +1428,8 @@ fn terminate_background_jobs(shell: &BrushShell) {
 	}
 	let mut pgids = Vec::new();
 	let mut pids = Vec::new();
-	for job in &shell.jobs.jobs {
+	// `disown -h` exempts a job from being hung up with the session.
+	for job in shell.jobs.jobs.iter().filter(|job| !job.no_hangup) {
 		if let Some(pgid) = job.process_group_id()
 			&& !pgids.contains(&pgid)
 		{
@@ -678,21 +1445,8 @@ fn terminate_background_jobs(shell: &BrushShell) {
 		return;
 	}
 
-	for &pgid in &pgids {
-		let _ = crate::ps::kill_process_group(pgid, TERM_SIGNAL);
-	}
-	for &pid in &pids {
-		let _ = crate::ps::kill_tree(pid, TERM_SIGNAL);
-	}
-
-	tokio::spawn(async move {
-		time::sleep(Duration::from_millis(500)).await;
-		for pid in pgids {
-			let _ = crate::ps::kill_process_group(pid, KILL_SIGNAL);
-		}
-		for pid in pids {
-			let _ = crate::ps::kill_tree(pid, KILL_SIGNAL);
-		}
+	tokio::task::spawn_blocking(move || {
+		crate::ps::terminate_trees(&pids, &pgids, TERM_SIGNAL, TERMINATE_GRACE)
 	});
 }
 
@@ -702,7 +1456,8 @@ fn terminate_background_jobs(shell: &BrushShell) {
 		return;
 	}
 	let mut pids = Vec::new();
-	for job in &shell.jobs.jobs {
+	// `disown -h` exempts a job from being hung up with the session.
+	for job in shell.jobs.jobs.iter().filter(|job| !job.no_hangup) {
 		if let Some(pid) = job.representative_pid()
 			&& !pids.contains(&pid)
 		{
@@ -713,15 +1468,8 @@ fn terminate_background_jobs(shell: &BrushShell) {
 		return;
 	}
 
-	for &pid in &pids {
-		let _ = crate::ps::kill_tree(pid, TERM_SIGNAL);
-	}
-
-	tokio::spawn(async move {
-		time::sleep(Duration::from_millis(500)).await;
-		for pid in pids {
-			let _ = crate::ps::kill_tree(pid, KILL_SIGNAL);
-		}
+	tokio::task::spawn_blocking(move || {
+		crate::ps::terminate_trees(&pids, &[], TERM_SIGNAL, TERMINATE_GRACE)
 	});
 }
 
@@ -793,69 +1541,35 @@ const fn session_keepalive(result: &ExecutionResult) -> bool {
 	}
 }
 
-async fn read_output(
-	reader: fs::File,
-	on_chunk: Option<ThreadsafeFunction<String>>,
-	cancel_token: CancellationToken,
-	activity: mpsc::Sender<()>,
-) {
+/// Decodes UTF-8 across reads, holding back a sequence split between them.
+struct Utf8Decoder {
+	buf: Vec<u8>,
+	len: usize,
+}
+
+impl Utf8Decoder {
+	const CAPACITY: usize = 65536;
 	const REPLACEMENT: &str = "\u{FFFD}";
-	const BUF: usize = 65536;
-	let mut buf = vec![0u8; BUF + 4]; // +4 for max UTF-8 char
-	let mut it = 0;
 
-	#[cfg(unix)]
-	let Ok(reader) = register_nonblocking_pipe(reader) else {
-		return;
-	};
-	#[cfg(not(unix))]
-	let reader = tokio::fs::File::from_std(reader);
-	#[cfg(not(unix))]
-	tokio::pin!(reader);
+	fn new() -> Self {
+		// +4 for max UTF-8 char
+		Self { buf: vec![0u8; Self::CAPACITY + 4], len: 0 }
+	}
 
-	loop {
-		#[cfg(unix)]
-		let n = {
-			let Ok(mut readiness) = (tokio::select! {
-				ready = reader.readable() => ready,
-				() = cancel_token.cancelled() => break,
-			}) else {
-				break;
-			};
-			match readiness.try_io(|inner| read_nonblocking(inner.get_ref(), &mut buf[it..BUF])) {
-				Ok(Ok(0)) => break,
-				Ok(Ok(n)) => n,
-				Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => continue,
-				Ok(Err(_)) => break,
-				Err(_would_block) => continue,
-			}
-		};
-		#[cfg(not(unix))]
-		let n = {
-			let read_future = reader.read(&mut buf[it..BUF]);
-			tokio::pin!(read_future);
-			match tokio::select! {
-				res = &mut read_future => res,
-				() = cancel_token.cancelled() => break,
-			} {
-				Ok(0) => break, // EOF
-				Ok(n) => n,
-				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
-				Err(_) => break,
-			}
-		};
-		if n > 0 {
-			let _ = activity.try_send(());
-		}
-		it += n;
+	/// Room for the next read.
+	fn spare(&mut self) -> &mut [u8] {
+		&mut self.buf[self.len..Self::CAPACITY]
+	}
 
-		// Consume as much of `pending` as is decodable *right now*.
-		while it > 0 {
-			let pending = &buf[..it];
+	/// Take `n` bytes read into `spare` and emit as much as is decodable.
+	fn push(&mut self, n: usize, emit: &(impl Fn(&str) + ?Sized)) {
+		self.len += n;
+		while self.len > 0 {
+			let pending = &self.buf[..self.len];
 			match str::from_utf8(pending) {
 				Ok(text) => {
-					emit_chunk(text, on_chunk.as_ref());
-					it = 0;
+					emit(text);
+					self.len = 0;
 					break;
 				},
 				Err(err) => {
@@ -863,21 +1577,18 @@ async fn read_output(
 					if p > 0 {
 						// SAFETY: [..p] is guaranteed valid UTF-8 by valid_up_to().
 						let text = unsafe { str::from_utf8_unchecked(&pending[..p]) };
-						emit_chunk(text, on_chunk.as_ref());
-						// copy p..it to the beginning of the buffer
-						buf.copy_within(p..it, 0);
-						it -= p;
+						emit(text);
+						// copy p..len to the beginning of the buffer
+						self.buf.copy_within(p..self.len, 0);
+						self.len -= p;
 					}
 
 					match err.error_len() {
 						Some(p) => {
 							// Invalid byte sequence: emit replacement and drop those bytes.
-							emit_chunk(REPLACEMENT, on_chunk.as_ref());
-							// copy p..it to the beginning of the buffer
-							buf.copy_within(p..it, 0);
-							it -= p;
-							// continue loop in case more bytes remain after the
-							// invalid sequence
+							emit(Self::REPLACEMENT);
+							self.buf.copy_within(p..self.len, 0);
+							self.len -= p;
 						},
 						None => {
 							// Incomplete UTF-8 sequence at end: keep bytes for next read.
@@ -889,14 +1600,147 @@ async fn read_output(
 		}
 	}
 
-	// Flush whatever is left at EOF (including an incomplete final sequence).
-	for chunk in buf[..it].utf8_chunks() {
-		let valid = chunk.valid();
-		if !valid.is_empty() {
-			emit_chunk(valid, on_chunk.as_ref());
+	/// Emit whatever is left at EOF, including an incomplete final sequence.
+	fn flush(&mut self, emit: &(impl Fn(&str) + ?Sized)) {
+		for chunk in self.buf[..self.len].utf8_chunks() {
+			let valid = chunk.valid();
+			if !valid.is_empty() {
+				emit(valid);
+			}
+			if !chunk.invalid().is_empty() {
+				emit(Self::REPLACEMENT);
+			}
+		}
+		self.len = 0;
+	}
+}
+
+/// Read end of an output pipe. The reader task drains it as data arrives,
+/// and [`OutputBarrier`] drains it between commands.
+#[cfg(unix)]
+struct OutputPipe {
+	file:    tokio::io::unix::AsyncFd<fs::File>,
+	decoder: parking_lot::Mutex<Utf8Decoder>,
+	emit:    Box<dyn Fn(&str) + Send + Sync>,
+}
+
+#[cfg(unix)]
+impl OutputPipe {
+	fn new(reader: fs::File, emit: impl Fn(&str) + Send + Sync + 'static) -> io::Result<Self> {
+		Ok(Self {
+			file:    register_nonblocking_pipe(reader)?,
+			decoder: parking_lot::Mutex::new(Utf8Decoder::new()),
+			emit:    Box::new(emit),
+		})
+	}
+
+	/// Read and emit everything the pipe holds; returns the bytes read and
+	/// whether the pipe reached EOF or failed.
+	fn drain(&self) -> (usize, bool) {
+		let mut decoder = self.decoder.lock();
+		let mut total = 0;
+		loop {
+			match read_nonblocking(self.file.get_ref(), decoder.spare()) {
+				Ok(0) => return (total, true),
+				Ok(n) => {
+					decoder.push(n, &*self.emit);
+					total += n;
+				},
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => return (total, false),
+				Err(_) => return (total, true),
+			}
+		}
+	}
+
+	fn flush(&self) {
+		self.decoder.lock().flush(&*self.emit);
+	}
+}
+
+/// Drains `pipe` until EOF or cancellation.
+#[cfg(unix)]
+async fn read_pipe(
+	pipe: Arc<OutputPipe>,
+	cancel_token: CancellationToken,
+	activity: mpsc::Sender<()>,
+) {
+	while let Some(mut readiness) = tokio::select! {
+		ready = pipe.file.readable() => ready.ok(),
+		() = cancel_token.cancelled() => None,
+	} {
+		let (read, done) = pipe.drain();
+		if read > 0 {
+			let _ = activity.try_send(());
+		}
+		if done {
+			break;
+		}
+		readiness.clear_ready();
+	}
+	pipe.flush();
+}
+
+#[cfg(all(unix, test))]
+async fn read_output(
+	reader: fs::File,
+	cancel_token: CancellationToken,
+	activity: mpsc::Sender<()>,
+	emit: impl Fn(&str) + Send + Sync + 'static,
+) {
+	if let Ok(pipe) = OutputPipe::new(reader, emit) {
+		read_pipe(Arc::new(pipe), cancel_token, activity).await;
+	}
+}
+
+#[cfg(not(unix))]
+async fn read_output(
+	reader: fs::File,
+	cancel_token: CancellationToken,
+	activity: mpsc::Sender<()>,
+	emit: impl Fn(&str) + Send + Sync + 'static,
+) {
+	let mut decoder = Utf8Decoder::new();
+	let reader = tokio::fs::File::from_std(reader);
+	tokio::pin!(reader);
+
+	loop {
+		let read_future = reader.read(decoder.spare());
+		tokio::pin!(read_future);
+		let n = match tokio::select! {
+			res = &mut read_future => res,
+			() = cancel_token.cancelled() => break,
+		} {
+			Ok(0) => break, // EOF
+			Ok(n) => n,
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			Err(_) => break,
+		};
+		let _ = activity.try_send(());
+		decoder.push(n, &emit);
+	}
+	decoder.flush(&emit);
+}
+
+/// Drains the stdout and stderr pipes of a split run after each simple
+/// command, so output of one command is delivered before the next one
+/// writes: the combined view then keeps the order of writes across
+/// commands, and only writes of a single command to both streams can come
+/// out of order. Forwards each trace to the run's tracer, if any.
+#[cfg(unix)]
+struct OutputBarrier {
+	pipes:  Vec<Arc<OutputPipe>>,
+	tracer: Option<Arc<dyn CommandTracer>>,
+}
+
+#[cfg(unix)]
+impl CommandTracer for OutputBarrier {
+	fn on_command(&self, trace: CommandTrace) {
+		for pipe in &self.pipes {
+			pipe.drain();
 		}
-		if !chunk.invalid().is_empty() {
-			emit_chunk(REPLACEMENT, on_chunk.as_ref());
+		if let Some(tracer) = &self.tracer {
+			tracer.on_command(trace);
 		}
 	}
 }
@@ -941,9 +1785,92 @@ fn read_nonblocking<T: std::os::fd::AsRawFd>(file: &T, buf: &mut [u8]) -> io::Re
 	}
 }
 
-fn emit_chunk(text: &str, callback: Option<&ThreadsafeFunction<String>>) {
-	if let Some(callback) = callback {
-		callback.call(Ok(text.to_string()), ThreadsafeFunctionCallMode::NonBlocking);
+/// Fan-out for decoded command output.
+///
+/// `on_chunk` receives the combined view and `on_output` the stream-tagged
+/// view. Both are fed under the capture lock so they agree on the order of
+/// chunks. When split, stdout and stderr are drained by separate readers that
+/// meet at an [`OutputBarrier`] after every command, so chunks follow the
+/// order of writes except between the two streams within a single command.
+#[derive(Clone)]
+struct OutputSink(Arc<OutputSinkInner>);
+
+type ChunkCallback = Box<dyn Fn(&str) + Send + Sync>;
+type OutputCallback = Box<dyn Fn(ShellStream, &str) + Send + Sync>;
+
+struct OutputSinkInner {
+	on_chunk:  Option<ChunkCallback>,
+	on_output: Option<OutputCallback>,
+	capture:   parking_lot::Mutex<OutputCapture<ShellStream>>,
+}
+
+impl OutputSinkInner {
+	fn deliver(&self, stream: ShellStream, text: &str) {
+		if let Some(callback) = &self.on_chunk {
+			callback(text);
+		}
+		if let Some(callback) = &self.on_output {
+			callback(stream, text);
+		}
+	}
+}
+
+impl OutputSink {
+	fn new(
+		on_chunk: Option<ThreadsafeFunction<String>>,
+		on_output: Option<ThreadsafeFunction<ShellOutputChunk>>,
+		limit: Option<OutputLimit>,
+	) -> Self {
+		Self::with_callbacks(
+			on_chunk.map(|callback| -> ChunkCallback {
+				Box::new(move |text| {
+					callback.call(Ok(text.to_string()), ThreadsafeFunctionCallMode::NonBlocking);
+				})
+			}),
+			on_output.map(|callback| -> OutputCallback {
+				Box::new(move |stream, text| {
+					callback.call(
+						Ok(ShellOutputChunk { stream, text: text.to_string() }),
+						ThreadsafeFunctionCallMode::NonBlocking,
+					);
+				})
+			}),
+			limit,
+		)
+	}
+
+	fn with_callbacks(
+		on_chunk: Option<ChunkCallback>,
+		on_output: Option<OutputCallback>,
+		limit: Option<OutputLimit>,
+	) -> Self {
+		Self(Arc::new(OutputSinkInner {
+			on_chunk,
+			on_output,
+			capture: parking_lot::Mutex::new(OutputCapture::new(limit)),
+		}))
+	}
+
+	/// Whether stdout and stderr should be read from separate pipes.
+	fn is_split(&self) -> bool {
+		self.0.on_output.is_some()
+	}
+
+	fn emit(&self, stream: ShellStream, text: &str) {
+		let inner = &*self.0;
+		inner
+			.capture
+			.lock()
+			.push(stream, text, |stream, text| inner.deliver(stream, text));
+	}
+
+	/// Flush any retained tail output and return the capture totals.
+	fn finish(&self) -> CaptureSummary {
+		let inner = &*self.0;
+		inner
+			.capture
+			.lock()
+			.finish(|stream, text| inner.deliver(stream, text))
 	}
 }
 
@@ -1153,7 +2080,7 @@ mod tests {
 		let (reader, _writer) = pipe_to_files("test").expect("test pipe should be created");
 		let cancel = CancellationToken::new();
 		let (activity_tx, _activity_rx) = mpsc::channel(1);
-		let handle = tokio::spawn(read_output(reader, None, cancel.clone(), activity_tx));
+		let handle = tokio::spawn(read_output(reader, cancel.clone(), activity_tx, |_| {}));
 
 		time::sleep(Duration::from_millis(10)).await;
 		cancel.cancel();
@@ -1163,4 +2090,629 @@ mod tests {
 			.expect("reader task should stop after cancellation")
 			.expect("reader task should not panic");
 	}
+
+	#[tokio::test]
+	async fn read_output_keeps_split_utf8_sequences_together() {
+		let (reader, mut writer) = pipe_to_files("test").expect("test pipe should be created");
+		let (activity_tx, _activity_rx) = mpsc::channel(1);
+		let chunks = Arc::new(parking_lot::Mutex::new(String::new()));
+		let handle = tokio::spawn(read_output(reader, CancellationToken::new(), activity_tx, {
+			let chunks = chunks.clone();
+			move |text| chunks.lock().push_str(text)
+		}));
+
+		let bytes = "err: \u{00e9}\n".as_bytes();
+		writer
+			.write_all(&bytes[..6])
+			.expect("first write should succeed");
+		time::sleep(Duration::from_millis(10)).await;
+		writer
+			.write_all(&bytes[6..])
+			.expect("second write should succeed");
+		drop(writer);
+
+		time::timeout(Duration::from_millis(500), handle)
+			.await
+			.expect("reader task should reach EOF")
+			.expect("reader task should not panic");
+		assert_eq!(chunks.lock().as_str(), "err: \u{00e9}\n");
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn tagged_output_keeps_the_order_of_writes_across_commands() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut session = create_session(&config).await.expect("session should start");
+		let combined = Arc::new(parking_lot::Mutex::new(String::new()));
+		let tagged = Arc::new(parking_lot::Mutex::new(Vec::new()));
+		let sink = OutputSink::with_callbacks(
+			Some(Box::new({
+				let combined = combined.clone();
+				move |text| combined.lock().push_str(text)
+			})),
+			Some(Box::new({
+				let tagged = tagged.clone();
+				move |stream, text| tagged.lock().push((stream, text.to_string()))
+			})),
+			None,
+		);
+
+		let (result, _) = run_shell_command(
+			&mut session,
+			&run_config("echo out; echo err >&2; sh -c 'echo ext >&2'; echo out2"),
+			sink,
+			CancellationToken::new(),
+		)
+		.await
+		.expect("run should complete");
+
+		assert_eq!(exit_code(&result), 0);
+		assert_eq!(combined.lock().as_str(), "out\nerr\next\nout2\n");
+		assert_eq!(*tagged.lock(), vec![
+			(ShellStream::Stdout, "out\n".to_string()),
+			(ShellStream::Stderr, "err\n".to_string()),
+			(ShellStream::Stderr, "ext\n".to_string()),
+			(ShellStream::Stdout, "out2\n".to_string()),
+		]);
+	}
+
+	fn run_config(command: &str) -> ShellRunConfig {
+		ShellRunConfig {
+			command:   command.to_string(),
+			cwd:       None,
+			env:       None,
+			confirmed: false,
+			tracer:    None,
+			limits:    None,
+			sandbox:   None,
+		}
+	}
+
+	async fn run_in(
+		session: &mut ShellSessionCore,
+		command: &str,
+	) -> (ExecutionResult, Option<ShellBlockedCommand>) {
+		run_with(session, &run_config(command), CancellationToken::new())
+			.await
+			.expect("run should complete")
+	}
+
+	/// Run `run_config` without collecting output, cancelled by `cancel`.
+	async fn run_with(
+		session: &mut ShellSessionCore,
+		run_config: &ShellRunConfig,
+		cancel: CancellationToken,
+	) -> Result<(ExecutionResult, Option<ShellBlockedCommand>)> {
+		run_shell_command(session, run_config, OutputSink::new(None, None, None), cancel).await
+	}
+
+	#[tokio::test]
+	async fn policy_blocks_expanded_command_and_stops_the_run() {
+		let rules = vec![ShellPolicyRule {
+			command: "echo".to_string(),
+			args:    Some("--force".to_string()),
+			action:  policy::ShellPolicyAction::Deny,
+			reason:  Some("no forcing".to_string()),
+		}];
+		let config = ShellConfig::new(None, None, Some(rules)).expect("policy should compile");
+		let mut session = create_session(&config).await.expect("session should start");
+
+		let (result, blocked) = run_in(&mut session, "flag=--force; echo $flag; marker=ran").await;
+
+		assert_eq!(exit_code(&result), 126);
+		assert_eq!(
+			blocked,
+			Some(ShellBlockedCommand {
+				argv:   vec!["echo".to_string(), "--force".to_string()],
+				action: policy::ShellPolicyAction::Deny,
+				reason: "no forcing".to_string(),
+			})
+		);
+		assert!(session.shell.env.get("marker").is_none());
+	}
+
+	#[tokio::test]
+	async fn background_job_output_reaches_the_run_that_started_it() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut session = create_session(&config).await.expect("session should start");
+		let output = Arc::new(parking_lot::Mutex::new(String::new()));
+		let sink = OutputSink::with_callbacks(
+			Some(Box::new({
+				let output = output.clone();
+				move |text| output.lock().push_str(text)
+			})),
+			None,
+			None,
+		);
+
+		run_shell_command(&mut session, &run_config("echo a & wait"), sink, CancellationToken::new())
+			.await
+			.expect("run should complete");
+
+		assert_eq!(output.lock().as_str(), "a\n");
+		assert_eq!(
+			config
+				.job_output
+				.tail(1, None)
+				.expect("job output should be kept"),
+			"a\n"
+		);
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn background_jobs_keep_the_policy_of_the_run_that_started_them() {
+		let rules = vec![ShellPolicyRule {
+			command: "touch".to_string(),
+			args:    None,
+			action:  policy::ShellPolicyAction::Confirm,
+			reason:  None,
+		}];
+		let config = ShellConfig::new(None, None, Some(rules)).expect("policy should compile");
+		let mut session = create_session(&config).await.expect("session should start");
+		let marker = std::env::temp_dir().join(format!("pi-policy-job-{}", std::process::id()));
+		let _ = std::fs::remove_file(&marker);
+
+		run_in(&mut session, &format!("{{ sleep 0.2; touch {}; }} &", marker.display())).await;
+		let confirmed = ShellRunConfig { confirmed: true, ..run_config("wait") };
+		let (_, blocked) = run_with(&mut session, &confirmed, CancellationToken::new())
+			.await
+			.expect("run should complete");
+
+		assert!(!marker.exists(), "the job should still need confirmation");
+		assert_eq!(blocked, None);
+		let _ = std::fs::remove_file(&marker);
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn cancelled_command_can_clean_up_before_the_run_stops() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut session = create_session(&config).await.expect("session should start");
+		let marker = std::env::temp_dir().join(format!("pi-terminate-{}", std::process::id()));
+		let _ = std::fs::remove_file(&marker);
+		let command = format!(
+			"sh -c 'trap \"echo cleaned > {0}; exit 0\" TERM; while :; do sleep 0.05; done'; echo \
+			 after > {0}",
+			marker.display()
+		);
+		let cancel = CancellationToken::new();
+		tokio::spawn({
+			let cancel = cancel.clone();
+			async move {
+				time::sleep(Duration::from_millis(300)).await;
+				cancel.cancel();
+			}
+		});
+
+		run_with(&mut session, &run_config(&command), cancel)
+			.await
+			.expect("run should complete");
+
+		let contents = std::fs::read_to_string(&marker).expect("trap should write the marker");
+		let _ = std::fs::remove_file(&marker);
+		assert_eq!(contents, "cleaned\n");
+	}
+
+	#[tokio::test]
+	async fn state_accessors_read_session_structures() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut session = create_session(&config).await.expect("session should start");
+
+		run_in(
+			&mut session,
+			"export PI_STATE_TEST=1; greet() { :; }; alias ll='ls -l'; set -o noglob; shopt -s \
+			 extglob",
+		)
+		.await;
+
+		let shell = &session.shell;
+		assert_eq!(exported_env(shell).get("PI_STATE_TEST").map(String::as_str), Some("1"));
+		assert!(function_names(shell).contains(&"greet".to_string()));
+		assert_eq!(shell.aliases.get("ll").map(String::as_str), Some("ls -l"));
+		let flags = option_flags(shell);
+		assert_eq!(flags.set_o.get("noglob"), Some(&true));
+		assert_eq!(flags.shopt.get("extglob"), Some(&true));
+	}
+
+	#[tokio::test]
+	async fn completes_functions_variables_and_files() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut session = create_session(&config).await.expect("session should start");
+		let dir = std::env::temp_dir().join(format!("pi-complete-test-{}", std::process::id()));
+		fs::create_dir_all(dir.join("sub dir")).expect("temp dir should be created");
+		run_in(
+			&mut session,
+			&format!(
+				"cd '{}'; PI_COMPLETE_TEST=1; _deploy() {{ COMPREPLY=(staging prod); echo noise; }}; \
+				 complete -F _deploy deploy",
+				dir.display()
+			),
+		)
+		.await;
+
+		let shell = &mut session.shell;
+		let custom = completion::complete(shell, "deploy st", 9).await;
+		let variable = completion::complete(shell, "echo $PI_COMPLETE_T", 19).await;
+		let file = completion::complete(shell, "ls su", 5).await;
+		let _ = fs::remove_dir_all(&dir);
+
+		let custom = custom.expect("completion should succeed");
+		assert_eq!(custom.candidates, ["prod", "staging"]);
+		assert_eq!((custom.start, custom.end), (7, 9));
+		let variable = variable.expect("completion should succeed");
+		assert_eq!(variable.candidates, ["$PI_COMPLETE_TEST"]);
+		assert!(!variable.filenames);
+		let file = file.expect("completion should succeed");
+		assert_eq!(file.candidates, ["sub\\ dir/"]);
+		assert!(!file.append_space);
+	}
+
+	#[tokio::test]
+	async fn completion_keeps_the_session_open_files() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut session = create_session(&config).await.expect("session should start");
+		let path = std::env::temp_dir().join(format!("pi-complete-fd-test-{}", std::process::id()));
+		let file = fs::File::create(&path).expect("file should be created");
+		let mut files = session.shell.persistent_open_files().clone();
+		files.set_fd(3, OpenFile::from(file));
+		session.shell.set_persistent_open_files(files);
+
+		let completion = completion::complete(&mut session.shell, "ech", 3).await;
+		let (result, _) = run_in(&mut session, "echo kept >&3").await;
+		let written = fs::read_to_string(&path);
+		let _ = fs::remove_file(&path);
+		completion.expect("completion should succeed");
+		assert_eq!(exit_code(&result), 0);
+		assert_eq!(written.expect("file should be readable"), "kept\n");
+	}
+
+	#[tokio::test]
+	async fn history_persists_with_limit_and_dedupe() {
+		let path = std::env::temp_dir().join(format!("pi-history-test-{}", std::process::id()));
+		let config = ShellConfig {
+			history: Some(ShellHistoryOptions {
+				path:       Some(path.to_string_lossy().into_owned()),
+				max_items:  Some(3),
+				timestamps: None,
+				dedupe:     Some(history::ShellHistoryDedupe::All),
+			}),
+			..ShellConfig::new(None, None, None).expect("config should build")
+		};
+		let mut session = create_session(&config).await.expect("session should start");
+		for command in ["echo one", "echo two", "echo one", "echo three", "echo four"] {
+			run_in(&mut session, command).await;
+		}
+		let commands = |entries: Vec<ShellHistoryEntry>| -> Vec<String> {
+			entries.into_iter().map(|entry| entry.command).collect()
+		};
+		assert_eq!(commands(history::entries(&session.shell, None)), [
+			"echo one",
+			"echo three",
+			"echo four"
+		]);
+
+		let restored = create_session(&config).await;
+		let _ = fs::remove_file(&path);
+		let restored = restored.expect("session should start");
+		let entries = history::entries(&restored.shell, Some(2));
+		assert_eq!(entries[0].index, 2);
+		assert!(entries[0].timestamp.is_some());
+		assert_eq!(commands(entries), ["echo three", "echo four"]);
+		let query = ShellHistorySearch {
+			text:  "echo t".to_string(),
+			mode:  Some(history::ShellHistoryMatch::Prefix),
+			limit: None,
+		};
+		assert_eq!(commands(history::search(&restored.shell, &query)), ["echo three"]);
+	}
+
+	#[tokio::test]
+	async fn history_appends_entries_of_sessions_sharing_a_file() {
+		let path = std::env::temp_dir().join(format!("pi-history-shared-{}", std::process::id()));
+		let _ = fs::remove_file(&path);
+		let config = ShellConfig {
+			history: Some(ShellHistoryOptions {
+				path:       Some(path.to_string_lossy().into_owned()),
+				max_items:  None,
+				timestamps: Some(false),
+				dedupe:     None,
+			}),
+			..ShellConfig::new(None, None, None).expect("config should build")
+		};
+		let mut first = create_session(&config).await.expect("session should start");
+		let mut second = create_session(&config).await.expect("session should start");
+		run_in(&mut first, "echo a1").await;
+		run_in(&mut second, "echo b1").await;
+		run_in(&mut first, "echo a2").await;
+
+		let written = fs::read_to_string(&path);
+		let _ = fs::remove_file(&path);
+		assert_eq!(written.expect("history should be saved"), "echo a1\necho b1\necho a2\n");
+	}
+
+	#[tokio::test]
+	async fn history_keeps_the_snapshot_histfile() {
+		let dir = std::env::temp_dir().join(format!("pi-history-snapshot-{}", std::process::id()));
+		fs::create_dir_all(&dir).expect("temp dir should be created");
+		let history = dir.join("history");
+		let snapshot = dir.join("snapshot.sh");
+		fs::write(&snapshot, format!("HISTFILE='{}'\n", history.display()))
+			.expect("snapshot should be written");
+		let config = ShellConfig {
+			history: Some(ShellHistoryOptions {
+				path:       None,
+				max_items:  None,
+				timestamps: None,
+				dedupe:     None,
+			}),
+			..ShellConfig::new(None, Some(snapshot.to_string_lossy().into_owned()), None)
+				.expect("config should build")
+		};
+		let mut session = create_session(&config).await.expect("session should start");
+		run_in(&mut session, "echo kept").await;
+
+		let written = fs::read_to_string(&history);
+		let _ = fs::remove_dir_all(&dir);
+		let written = written.expect("history should be saved");
+		assert_eq!(written.lines().last(), Some("echo kept"), "{written}");
+	}
+
+	#[cfg(target_os = "linux")]
+	#[tokio::test]
+	async fn limits_and_sandbox_confine_external_commands() {
+		let dir = std::env::temp_dir().join(format!("pi-sandbox-test-{}", std::process::id()));
+		fs::create_dir_all(&dir).expect("temp dir should be created");
+		let outside = std::env::temp_dir().join(format!("pi-sandbox-outside-{}", std::process::id()));
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut session = create_session(&config).await.expect("session should start");
+		let mut run_config = ShellRunConfig {
+			cwd: Some(dir.to_string_lossy().into_owned()),
+			limits: Some(ResourceLimits { open_files: Some(64.0), ..Default::default() }),
+			..run_config("sh -c 'ulimit -n > limit'")
+		};
+
+		run_with(&mut session, &run_config, CancellationToken::new())
+			.await
+			.expect("run should complete");
+		assert_eq!(
+			fs::read_to_string(dir.join("limit"))
+				.unwrap_or_default()
+				.trim(),
+			"64"
+		);
+
+		run_config.limits = None;
+		run_config.sandbox = Some(SandboxOptions::default());
+		run_config.command = format!(
+			"sh -c 'echo in > inside; echo out > {0}'; echo builtin > {0}; echo builtin >> inside",
+			outside.display()
+		);
+		let sandboxed = run_with(&mut session, &run_config, CancellationToken::new()).await;
+		let inside = fs::read_to_string(dir.join("inside"));
+		let _ = fs::remove_dir_all(&dir);
+		if let Err(err) = sandboxed {
+			// Namespaces may be disabled where the tests run.
+			assert!(err.reason.starts_with("Sandbox unavailable"), "{}", err.reason);
+			return;
+		}
+		assert_eq!(inside.expect("writable path should accept writes"), "in\nbuiltin\n");
+		// Redirections are opened by the shell itself and checked separately.
+		assert!(!outside.exists(), "writes outside the sandbox should fail");
+	}
+
+	#[tokio::test]
+	async fn exported_snapshot_restores_session_state() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut source = create_session(&config).await.expect("session should start");
+		run_in(
+			&mut source,
+			"declare -a list=(one 'two words'); declare -A map=([key]=value); export QUOTED='a\"b'; \
+			 greet() { echo \"hi $1\"; }; alias ll='ls -l'; shopt -s extglob",
+		)
+		.await;
+
+		let defaults = RuntimeOptions::defaults_from(&session_create_options());
+		let script = snapshot::render(&source.shell, &defaults).expect("snapshot should render");
+		let path = std::env::temp_dir().join(format!("pi-snapshot-test-{}.sh", std::process::id()));
+		fs::write(&path, script).expect("snapshot should be written");
+		let restored_config = ShellConfig::new(None, Some(path.to_string_lossy().into_owned()), None)
+			.expect("config should build");
+		let restored = create_session(&restored_config).await;
+		let _ = fs::remove_file(&path);
+		let mut restored = restored.expect("snapshot should source");
+
+		let (result, _) = run_in(
+			&mut restored,
+			"[[ ${list[1]} == 'two words' && ${map[key]} == value && $(greet x) == 'hi x' ]]",
+		)
+		.await;
+		assert_eq!(exit_code(&result), 0);
+		let shell = &restored.shell;
+		assert_eq!(exported_env(shell).get("QUOTED").map(String::as_str), Some("a\"b"));
+		assert!(function_names(shell).contains(&"greet".to_string()));
+		assert_eq!(shell.aliases.get("ll").map(String::as_str), Some("ls -l"));
+		assert_eq!(option_flags(shell).shopt.get("extglob"), Some(&true));
+	}
+
+	#[tokio::test]
+	async fn snapshot_keeps_readonly_variables_and_skips_shell_managed_ones() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut source = create_session(&config).await.expect("session should start");
+		let elsewhere = std::env::temp_dir();
+		run_in(&mut source, &format!("readonly FROZEN=kept; cd '{}'", elsewhere.display())).await;
+
+		let defaults = RuntimeOptions::defaults_from(&session_create_options());
+		let script = snapshot::render(&source.shell, &defaults).expect("snapshot should render");
+		assert!(!script.contains(" PWD="), "{script}");
+		let path = std::env::temp_dir().join(format!("pi-readonly-test-{}.sh", std::process::id()));
+		fs::write(&path, script).expect("snapshot should be written");
+		let restored_config = ShellConfig::new(None, Some(path.to_string_lossy().into_owned()), None)
+			.expect("config should build");
+		let restored = create_session(&restored_config).await;
+		let mut restored = restored.expect("snapshot should source");
+
+		// Sourcing again must not trip over the now-readonly variable.
+		let (result, _) = run_in(
+			&mut restored,
+			&format!("source '{}' && [[ ${{FROZEN@a}} == *r* ]]", path.display()),
+		)
+		.await;
+		let _ = fs::remove_file(&path);
+		assert_eq!(exit_code(&result), 0);
+		let shell = &restored.shell;
+		assert_eq!(shell.env_str("FROZEN").as_deref(), Some("kept"));
+		assert_ne!(shell.env_str("PWD").as_deref(), Some(&*elsewhere.to_string_lossy()));
+	}
+
+	#[tokio::test]
+	async fn errors_in_snapshot_functions_report_file_and_line() {
+		let path = std::env::temp_dir().join(format!("pi-lineno-test-{}.sh", std::process::id()));
+		let errors = path.with_extension("err");
+		fs::write(&path, "fail() {\n  true\n  pi-missing-command\n}\n")
+			.expect("snapshot should be written");
+		let config = ShellConfig::new(None, Some(path.to_string_lossy().into_owned()), None)
+			.expect("config should build");
+		let session = create_session(&config).await;
+		let _ = fs::remove_file(&path);
+		let mut session = session.expect("snapshot should source");
+
+		run_in(&mut session, &format!("\nfail 2> {}", errors.display())).await;
+		let stderr = fs::read_to_string(&errors).unwrap_or_default();
+		let _ = fs::remove_file(&errors);
+		assert!(stderr.starts_with(&format!("{}:3: error:", path.display())), "{stderr}");
+	}
+
+	#[derive(Default)]
+	struct CollectTraces(parking_lot::Mutex<Vec<CommandTrace>>);
+
+	impl CommandTracer for CollectTraces {
+		fn on_command(&self, trace: CommandTrace) {
+			self.0.lock().push(trace);
+		}
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn tracer_reports_each_simple_command() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let mut session = create_session(&config).await.expect("session should start");
+		let traces = Arc::new(CollectTraces::default());
+		let run_config = ShellRunConfig {
+			tracer: Some(traces.clone()),
+			..run_config("f() { true; }; cd / && f && sh -c 'exit 3' | cat")
+		};
+		run_with(&mut session, &run_config, CancellationToken::new())
+			.await
+			.expect("run should complete");
+
+		let traces = traces.0.lock();
+		let find = |name: &str| {
+			traces
+				.iter()
+				.find(|trace| trace.argv[0] == name)
+				.unwrap_or_else(|| panic!("{name} should be traced"))
+		};
+		assert_eq!(find("cd").kind, CommandKind::Builtin);
+		assert_eq!(find("f").kind, CommandKind::Function);
+		let sh = find("sh");
+		assert_eq!(sh.kind, CommandKind::External);
+		assert_eq!(sh.argv, ["sh", "-c", "exit 3"]);
+		assert_eq!(sh.exit_code, 3);
+		assert_eq!(sh.cwd, std::path::Path::new("/"));
+		assert!(sh.pid.is_some());
+		assert!(sh.finished_at >= sh.started_at);
+		assert!(session.shell.command_tracer().is_none());
+	}
+
+	#[tokio::test]
+	async fn tracer_reports_blocked_commands_and_closes_with_the_run() {
+		let rules = vec![ShellPolicyRule {
+			command: "rm".to_string(),
+			args:    None,
+			action:  policy::ShellPolicyAction::Deny,
+			reason:  None,
+		}];
+		let config = ShellConfig::new(None, None, Some(rules)).expect("policy should compile");
+		let mut session = create_session(&config).await.expect("session should start");
+		let traces = Arc::new(CollectTraces::default());
+		let run_config = ShellRunConfig {
+			tracer: Some(traces.clone()),
+			..run_config("sleep 0.2 & rm -rf nowhere")
+		};
+		run_with(&mut session, &run_config, CancellationToken::new())
+			.await
+			.expect("run should complete");
+		drop(run_config);
+		time::sleep(Duration::from_millis(400)).await;
+
+		let traces = Arc::into_inner(traces).expect("the run should release its tracer");
+		let traces = traces.0.into_inner();
+		assert_eq!(traces.len(), 1, "the job's sleep ends after the run");
+		assert_eq!(traces[0].argv, ["rm", "-rf", "nowhere"]);
+		assert_eq!(traces[0].kind, CommandKind::Blocked);
+		assert_eq!(traces[0].exit_code, 126);
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn background_jobs_are_tailed_signalled_and_collected() {
+		let shell = Shell::new(None).expect("shell should build");
+		{
+			let mut session = shell.session().await.expect("session should start");
+			let (result, _) = run_in(&mut session, "sh -c 'echo started; exec sleep 30' &").await;
+			assert_eq!(exit_code(&result), 0);
+		}
+
+		let mut output = String::new();
+		for _ in 0..100 {
+			output = shell
+				.job_output(1, None)
+				.expect("job output should be recorded");
+			if !output.is_empty() {
+				break;
+			}
+			time::sleep(Duration::from_millis(20)).await;
+		}
+		assert_eq!(output, "started\n");
+		assert_eq!(shell.job_output(1, Some(3)).expect("tail should be read"), "ed\n");
+
+		let jobs = shell.jobs().await.expect("jobs should be listed");
+		assert_eq!(jobs.len(), 1);
+		assert_eq!(jobs[0].id, 1);
+		assert_eq!(jobs[0].state, jobs::ShellJobState::Running);
+		assert!(jobs[0].command.contains("exec sleep 30"));
+		assert!(jobs[0].pid.is_some());
+		assert_eq!(jobs[0].pgid, jobs[0].pid);
+
+		let pending = shell
+			.wait_job(1, Some(50))
+			.await
+			.expect("wait should time out");
+		assert_eq!(pending, ShellJobWaitResult { exit_code: None, timed_out: true });
+
+		assert!(
+			shell
+				.signal_job(1, Some("BOGUS".to_string()))
+				.await
+				.is_err()
+		);
+		shell
+			.signal_job(1, Some("KILL".to_string()))
+			.await
+			.expect("job should be signalled");
+		let finished = shell
+			.wait_job(1, Some(5000))
+			.await
+			.expect("job should finish");
+		assert_eq!(finished, ShellJobWaitResult { exit_code: Some(137), timed_out: false });
+		assert!(
+			shell
+				.jobs()
+				.await
+				.expect("jobs should be listed")
+				.is_empty()
+		);
+		assert!(shell.wait_job(1, None).await.is_err());
+	}
 }
@@ -0,0 +1,813 @@
+//! Static analysis of shell command strings.
+//!
+//! Parses a command with brush's parser and walks the AST without executing
+//! anything, reporting every simple command with its arguments and
+//! redirections. Words are unquoted where that can be done statically; parts
+//! that depend on runtime state (variables, substitutions, globs, ...) are
+//! flagged instead so callers can decide how cautious to be.
+
+use std::io::Cursor;
+
+use brush_core::escape::{self, EscapeExpansionMode};
+use brush_parser::{
+	Parser, ParserOptions, SourceInfo, ast,
+	word::{self, ParameterExpr, WordPiece, WordPieceWithSource},
+};
+use napi::{Error, Result};
+use napi_derive::napi;
+
+/// Where a simple command sits relative to the top-level script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellCommandContext {
+	/// Top-level list of the script.
+	#[napi(value = "main")]
+	Main,
+	/// Inside a `( ... )` subshell.
+	#[napi(value = "subshell")]
+	Subshell,
+	/// Inside a `$( ... )` or backquoted command substitution.
+	#[napi(value = "commandSubstitution")]
+	CommandSubstitution,
+	/// Inside a `<( ... )` or `>( ... )` process substitution.
+	#[napi(value = "processSubstitution")]
+	ProcessSubstitution,
+	/// Inside a function body, which only runs when the function is called.
+	#[napi(value = "function")]
+	Function,
+}
+
+/// Part of a word that can only be resolved at runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellUnresolvedPart {
+	/// Parameter or variable expansion.
+	#[napi(value = "variable")]
+	Variable,
+	/// Command substitution.
+	#[napi(value = "commandSubstitution")]
+	CommandSubstitution,
+	/// Process substitution.
+	#[napi(value = "processSubstitution")]
+	ProcessSubstitution,
+	/// Arithmetic expansion.
+	#[napi(value = "arithmetic")]
+	Arithmetic,
+	/// Unquoted glob pattern.
+	#[napi(value = "glob")]
+	Glob,
+	/// Unquoted brace expansion.
+	#[napi(value = "brace")]
+	Brace,
+	/// Tilde prefix.
+	#[napi(value = "tilde")]
+	Tilde,
+}
+
+/// Kind of redirection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellRedirectKind {
+	/// `<`
+	#[napi(value = "read")]
+	Read,
+	/// `>`
+	#[napi(value = "write")]
+	Write,
+	/// `>>`
+	#[napi(value = "append")]
+	Append,
+	/// `<>`
+	#[napi(value = "readWrite")]
+	ReadWrite,
+	/// `>|`
+	#[napi(value = "clobber")]
+	Clobber,
+	/// `<&`
+	#[napi(value = "duplicateInput")]
+	DuplicateInput,
+	/// `>&`
+	#[napi(value = "duplicateOutput")]
+	DuplicateOutput,
+	/// `&>`
+	#[napi(value = "outputAndError")]
+	OutputAndError,
+	/// `&>>`
+	#[napi(value = "appendOutputAndError")]
+	AppendOutputAndError,
+	/// `<<` or `<<-`
+	#[napi(value = "hereDocument")]
+	HereDocument,
+	/// `<<<`
+	#[napi(value = "hereString")]
+	HereString,
+}
+
+/// Word as written in the source, with its static value when it has one.
+#[napi(object)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShellWord {
+	/// Source text of the word.
+	pub raw:        String,
+	/// Value after quote removal; absent when any part is unresolved.
+	pub value:      Option<String>,
+	/// Parts that depend on runtime state.
+	pub unresolved: Vec<ShellUnresolvedPart>,
+}
+
+/// Redirection attached to a command.
+#[napi(object)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShellRedirect {
+	/// Explicit file descriptor, e.g. `2` in `2>err.log`.
+	pub fd:     Option<i32>,
+	/// Redirection operator.
+	pub kind:   ShellRedirectKind,
+	/// File, descriptor or here-string word; absent for here-documents and
+	/// process substitutions.
+	pub target: Option<ShellWord>,
+	/// Here-document body.
+	pub body:   Option<String>,
+}
+
+/// Simple command found in the script.
+#[napi(object)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShellAnalyzedCommand {
+	/// Command name; absent for assignment-only commands, bare redirections,
+	/// and redirections applied to compound commands.
+	pub name:              Option<ShellWord>,
+	/// Arguments after the command name.
+	pub args:              Vec<ShellWord>,
+	/// Variables assigned for the command (`FOO=bar cmd`).
+	pub assignments:       Vec<String>,
+	/// Redirections applied to the command.
+	pub redirects:         Vec<ShellRedirect>,
+	/// Innermost construct the command is nested in.
+	pub context:           ShellCommandContext,
+	/// Number of nested subshells, substitutions and functions around it.
+	pub depth:             u32,
+	/// Position in its pipeline, starting at 0.
+	pub pipeline_position: u32,
+	/// Number of commands in its pipeline.
+	pub pipeline_length:   u32,
+	/// Whether it runs asynchronously (`&`).
+	pub background:        bool,
+}
+
+/// Result of statically analyzing a shell command string.
+#[napi(object)]
+pub struct ShellAnalysis {
+	/// Simple commands in execution order; commands inside substitutions come
+	/// before the command whose words contain them.
+	pub commands:   Vec<ShellAnalyzedCommand>,
+	/// Whether any word could not be resolved statically.
+	pub unresolved: bool,
+}
+
+/// Parse a shell command string and report what it would run without
+/// executing it.
+///
+/// Fails when the string is not valid shell syntax.
+#[napi]
+pub fn analyze_shell_command(command: String) -> Result<ShellAnalysis> {
+	let mut analyzer = Analyzer::default();
+	let program = analyzer
+		.parse(&command)
+		.map_err(|err| Error::from_reason(format!("Failed to parse shell command: {err}")))?;
+	analyzer.program(&program, Scope::default());
+	Ok(ShellAnalysis { commands: analyzer.commands, unresolved: analyzer.unresolved })
+}
+
+#[derive(Clone, Copy)]
+struct Scope {
+	context:    ShellCommandContext,
+	depth:      u32,
+	background: bool,
+}
+
+impl Default for Scope {
+	fn default() -> Self {
+		Self { context: ShellCommandContext::Main, depth: 0, background: false }
+	}
+}
+
+impl Scope {
+	const fn enter(self, context: ShellCommandContext) -> Self {
+		Self { context, depth: self.depth + 1, ..self }
+	}
+}
+
+#[derive(Clone, Copy)]
+struct Position {
+	index: usize,
+	len:   usize,
+}
+
+const STANDALONE: Position = Position { index: 0, len: 1 };
+
+#[derive(Default)]
+struct Analyzer {
+	options:    ParserOptions,
+	commands:   Vec<ShellAnalyzedCommand>,
+	unresolved: bool,
+}
+
+impl Analyzer {
+	fn parse(&self, source: &str) -> std::result::Result<ast::Program, brush_parser::ParseError> {
+		let source_info = SourceInfo { source: "analyze".to_string() };
+		Parser::new(Cursor::new(source), &self.options, &source_info).parse_program()
+	}
+
+	fn program(&mut self, program: &ast::Program, scope: Scope) {
+		for list in &program.complete_commands {
+			self.compound_list(list, scope);
+		}
+	}
+
+	fn compound_list(&mut self, list: &ast::CompoundList, scope: Scope) {
+		for ast::CompoundListItem(and_or, separator) in &list.0 {
+			let background = matches!(separator, ast::SeparatorOperator::Async);
+			let scope = Scope { background: scope.background || background, ..scope };
+			self.pipeline(&and_or.first, scope);
+			for next in &and_or.additional {
+				let (ast::AndOr::And(pipeline) | ast::AndOr::Or(pipeline)) = next;
+				self.pipeline(pipeline, scope);
+			}
+		}
+	}
+
+	fn pipeline(&mut self, pipeline: &ast::Pipeline, scope: Scope) {
+		let len = pipeline.seq.len();
+		for (index, command) in pipeline.seq.iter().enumerate() {
+			self.command(command, scope, Position { index, len });
+		}
+	}
+
+	fn command(&mut self, command: &ast::Command, scope: Scope, position: Position) {
+		match command {
+			ast::Command::Simple(simple) => self.simple(simple, scope, position),
+			ast::Command::Compound(compound, redirects) => {
+				self.compound(compound, scope);
+				self.redirect_only(redirects.as_ref(), scope, position);
+			},
+			ast::Command::Function(definition) => {
+				let scope = scope.enter(ShellCommandContext::Function);
+				self.compound(&definition.body.0, scope);
+				self.redirect_only(definition.body.1.as_ref(), scope, STANDALONE);
+			},
+			ast::Command::ExtendedTest(test) => self.extended_test(&test.expr, scope),
+		}
+	}
+
+	fn compound(&mut self, compound: &ast::CompoundCommand, scope: Scope) {
+		match compound {
+			ast::CompoundCommand::Arithmetic(command) => {
+				self.expanded_text(&command.expr.value, scope);
+			},
+			ast::CompoundCommand::ArithmeticForClause(clause) => {
+				for expr in [&clause.initializer, &clause.condition, &clause.updater]
+					.into_iter()
+					.flatten()
+				{
+					self.expanded_text(&expr.value, scope);
+				}
+				self.compound_list(&clause.body.list, scope);
+			},
+			ast::CompoundCommand::BraceGroup(group) => self.compound_list(&group.list, scope),
+			ast::CompoundCommand::Subshell(subshell) => {
+				self.compound_list(&subshell.list, scope.enter(ShellCommandContext::Subshell));
+			},
+			ast::CompoundCommand::ForClause(clause) => {
+				for value in clause.values.iter().flatten() {
+					self.word(value, scope);
+				}
+				self.compound_list(&clause.body.list, scope);
+			},
+			ast::CompoundCommand::CaseClause(clause) => {
+				self.word(&clause.value, scope);
+				for item in &clause.cases {
+					for pattern in &item.patterns {
+						self.word(pattern, scope);
+					}
+					if let Some(list) = &item.cmd {
+						self.compound_list(list, scope);
+					}
+				}
+			},
+			ast::CompoundCommand::IfClause(clause) => {
+				self.compound_list(&clause.condition, scope);
+				self.compound_list(&clause.then, scope);
+				for branch in clause.elses.iter().flatten() {
+					if let Some(condition) = &branch.condition {
+						self.compound_list(condition, scope);
+					}
+					self.compound_list(&branch.body, scope);
+				}
+			},
+			ast::CompoundCommand::WhileClause(clause) | ast::CompoundCommand::UntilClause(clause) => {
+				self.compound_list(&clause.0, scope);
+				self.compound_list(&clause.1.list, scope);
+			},
+		}
+	}
+
+	fn extended_test(&mut self, expr: &ast::ExtendedTestExpr, scope: Scope) {
+		match expr {
+			ast::ExtendedTestExpr::And(left, right) | ast::ExtendedTestExpr::Or(left, right) => {
+				self.extended_test(left, scope);
+				self.extended_test(right, scope);
+			},
+			ast::ExtendedTestExpr::Not(inner) | ast::ExtendedTestExpr::Parenthesized(inner) => {
+				self.extended_test(inner, scope);
+			},
+			ast::ExtendedTestExpr::UnaryTest(_, operand) => {
+				self.word(operand, scope);
+			},
+			ast::ExtendedTestExpr::BinaryTest(_, left, right) => {
+				self.word(left, scope);
+				self.word(right, scope);
+			},
+		}
+	}
+
+	fn simple(&mut self, simple: &ast::SimpleCommand, scope: Scope, position: Position) {
+		let mut assignments = Vec::new();
+		let mut redirects = Vec::new();
+		let mut args = Vec::new();
+
+		for item in simple.prefix.iter().flat_map(|prefix| &prefix.0) {
+			match item {
+				ast::CommandPrefixOrSuffixItem::AssignmentWord(assignment, _) => {
+					assignments.push(self.assignment(assignment, scope));
+				},
+				other => self.item(other, scope, &mut args, &mut redirects),
+			}
+		}
+		let name = simple
+			.word_or_name
+			.as_ref()
+			.map(|word| self.word(word, scope));
+		for item in simple.suffix.iter().flat_map(|suffix| &suffix.0) {
+			self.item(item, scope, &mut args, &mut redirects);
+		}
+
+		self.commands.push(ShellAnalyzedCommand {
+			name,
+			args,
+			assignments,
+			redirects,
+			context: scope.context,
+			depth: scope.depth,
+			pipeline_position: position.index as u32,
+			pipeline_length: position.len as u32,
+			background: scope.background,
+		});
+	}
+
+	fn item(
+		&mut self,
+		item: &ast::CommandPrefixOrSuffixItem,
+		scope: Scope,
+		args: &mut Vec<ShellWord>,
+		redirects: &mut Vec<ShellRedirect>,
+	) {
+		match item {
+			ast::CommandPrefixOrSuffixItem::IoRedirect(redirect) => {
+				redirects.push(self.redirect(redirect, scope));
+			},
+			// Declaration builtins (`export A=b`) receive assignments as arguments.
+			ast::CommandPrefixOrSuffixItem::Word(word)
+			| ast::CommandPrefixOrSuffixItem::AssignmentWord(_, word) => {
+				args.push(self.word(word, scope));
+			},
+			ast::CommandPrefixOrSuffixItem::ProcessSubstitution(kind, subshell) => {
+				self.compound_list(
+					&subshell.list,
+					scope.enter(ShellCommandContext::ProcessSubstitution),
+				);
+				self.unresolved = true;
+				let sigil = match kind {
+					ast::ProcessSubstitutionKind::Read => '<',
+					ast::ProcessSubstitutionKind::Write => '>',
+				};
+				args.push(ShellWord {
+					raw:        format!("{sigil}({})", subshell.list),
+					value:      None,
+					unresolved: vec![ShellUnresolvedPart::ProcessSubstitution],
+				});
+			},
+		}
+	}
+
+	fn assignment(&mut self, assignment: &ast::Assignment, scope: Scope) -> String {
+		match &assignment.value {
+			ast::AssignmentValue::Scalar(value) => {
+				self.word(value, scope);
+			},
+			ast::AssignmentValue::Array(elements) => {
+				for (key, value) in elements {
+					if let Some(key) = key {
+						self.word(key, scope);
+					}
+					self.word(value, scope);
+				}
+			},
+		}
+		match &assignment.name {
+			ast::AssignmentName::VariableName(name)
+			| ast::AssignmentName::ArrayElementName(name, _) => name.clone(),
+		}
+	}
+
+	/// Record redirections that are not attached to a simple command.
+	fn redirect_only(
+		&mut self,
+		redirects: Option<&ast::RedirectList>,
+		scope: Scope,
+		position: Position,
+	) {
+		let Some(list) = redirects.filter(|list| !list.0.is_empty()) else {
+			return;
+		};
+		let redirects = list
+			.0
+			.iter()
+			.map(|redirect| self.redirect(redirect, scope))
+			.collect();
+		self.commands.push(ShellAnalyzedCommand {
+			name: None,
+			args: Vec::new(),
+			assignments: Vec::new(),
+			redirects,
+			context: scope.context,
+			depth: scope.depth,
+			pipeline_position: position.index as u32,
+			pipeline_length: position.len as u32,
+			background: scope.background,
+		});
+	}
+
+	fn redirect(&mut self, redirect: &ast::IoRedirect, scope: Scope) -> ShellRedirect {
+		match redirect {
+			ast::IoRedirect::File(fd, kind, target) => {
+				let kind = match kind {
+					ast::IoFileRedirectKind::Read => ShellRedirectKind::Read,
+					ast::IoFileRedirectKind::Write => ShellRedirectKind::Write,
+					ast::IoFileRedirectKind::Append => ShellRedirectKind::Append,
+					ast::IoFileRedirectKind::ReadAndWrite => ShellRedirectKind::ReadWrite,
+					ast::IoFileRedirectKind::Clobber => ShellRedirectKind::Clobber,
+					ast::IoFileRedirectKind::DuplicateInput => ShellRedirectKind::DuplicateInput,
+					ast::IoFileRedirectKind::DuplicateOutput => ShellRedirectKind::DuplicateOutput,
+				};
+				let target = match target {
+					ast::IoFileRedirectTarget::Filename(word)
+					| ast::IoFileRedirectTarget::Duplicate(word) => Some(self.word(word, scope)),
+					ast::IoFileRedirectTarget::Fd(target_fd) => Some(ShellWord {
+						raw:        target_fd.to_string(),
+						value:      Some(target_fd.to_string()),
+						unresolved: Vec::new(),
+					}),
+					ast::IoFileRedirectTarget::ProcessSubstitution(_, subshell) => {
+						self.compound_list(
+							&subshell.list,
+							scope.enter(ShellCommandContext::ProcessSubstitution),
+						);
+						self.unresolved = true;
+						None
+					},
+				};
+				ShellRedirect { fd: *fd, kind, target, body: None }
+			},
+			ast::IoRedirect::HereDocument(fd, doc) => {
+				if doc.requires_expansion {
+					self.heredoc_body(&doc.doc.value, scope);
+				}
+				ShellRedirect {
+					fd:     *fd,
+					kind:   ShellRedirectKind::HereDocument,
+					target: None,
+					body:   Some(doc.doc.value.clone()),
+				}
+			},
+			ast::IoRedirect::HereString(fd, word) => ShellRedirect {
+				fd:     *fd,
+				kind:   ShellRedirectKind::HereString,
+				target: Some(self.word(word, scope)),
+				body:   None,
+			},
+			ast::IoRedirect::OutputAndError(word, append) => ShellRedirect {
+				fd:     None,
+				kind:   if *append {
+					ShellRedirectKind::AppendOutputAndError
+				} else {
+					ShellRedirectKind::OutputAndError
+				},
+				target: Some(self.word(word, scope)),
+				body:   None,
+			},
+		}
+	}
+
+	/// Collect substitutions from an expanding here-document body.
+	fn heredoc_body(&mut self, body: &str, scope: Scope) {
+		self.expanded_text(body, scope);
+		self.unresolved = true;
+	}
+
+	/// Collect substitutions from text that is expanded before use:
+	/// here-document bodies, arithmetic, and the words inside `${...}`. Quotes
+	/// are treated as literal, so the text is only scanned for `$` and
+	/// backquotes.
+	fn expanded_text(&mut self, text: &str, scope: Scope) {
+		if !text.contains(['$', '`']) {
+			return;
+		}
+		let quoted = format!("\"{}\"", text.replace('"', "\\\""));
+		if let Ok(pieces) = word::parse(&quoted, &self.options) {
+			let mut value = Some(String::new());
+			let mut unresolved = Vec::new();
+			self.pieces(&pieces, true, scope, &mut value, &mut unresolved);
+		}
+	}
+
+	/// Collect substitutions from the parts of a parameter expansion that are
+	/// expanded along with it, e.g. the default in `${x:-$(cmd)}`.
+	fn parameter(&mut self, expr: &ParameterExpr, scope: Scope) {
+		let (parameter, words) = match expr {
+			ParameterExpr::Parameter { parameter, .. }
+			| ParameterExpr::ParameterLength { parameter, .. }
+			| ParameterExpr::Transform { parameter, .. } => (Some(parameter), [None, None]),
+			ParameterExpr::UseDefaultValues { parameter, default_value: word, .. }
+			| ParameterExpr::AssignDefaultValues { parameter, default_value: word, .. }
+			| ParameterExpr::IndicateErrorIfNullOrUnset { parameter, error_message: word, .. }
+			| ParameterExpr::UseAlternativeValue { parameter, alternative_value: word, .. }
+			| ParameterExpr::RemoveSmallestSuffixPattern { parameter, pattern: word, .. }
+			| ParameterExpr::RemoveLargestSuffixPattern { parameter, pattern: word, .. }
+			| ParameterExpr::RemoveSmallestPrefixPattern { parameter, pattern: word, .. }
+			| ParameterExpr::RemoveLargestPrefixPattern { parameter, pattern: word, .. }
+			| ParameterExpr::UppercaseFirstChar { parameter, pattern: word, .. }
+			| ParameterExpr::UppercasePattern { parameter, pattern: word, .. }
+			| ParameterExpr::LowercaseFirstChar { parameter, pattern: word, .. }
+			| ParameterExpr::LowercasePattern { parameter, pattern: word, .. } => {
+				(Some(parameter), [word.as_deref(), None])
+			},
+			ParameterExpr::ReplaceSubstring { parameter, pattern, replacement, .. } => {
+				(Some(parameter), [Some(pattern.as_str()), replacement.as_deref()])
+			},
+			ParameterExpr::Substring { parameter, offset, length, .. } => (Some(parameter), [
+				Some(offset.value.as_str()),
+				length.as_ref().map(|l| l.value.as_str()),
+			]),
+			ParameterExpr::VariableNames { .. } | ParameterExpr::MemberKeys { .. } => {
+				(None, [None, None])
+			},
+		};
+		if let Some(word::Parameter::NamedWithIndex { index, .. }) = parameter {
+			self.expanded_text(index, scope);
+		}
+		for word in words.into_iter().flatten() {
+			self.expanded_text(word, scope);
+		}
+	}
+
+	fn word(&mut self, word: &ast::Word, scope: Scope) -> ShellWord {
+		let mut value = Some(String::new());
+		let mut unresolved = Vec::new();
+		match word::parse(&word.value, &self.options) {
+			Ok(pieces) => self.pieces(&pieces, false, scope, &mut value, &mut unresolved),
+			Err(_) => value = None,
+		}
+		if !unresolved.is_empty() {
+			value = None;
+		}
+		if value.is_none() {
+			self.unresolved = true;
+		}
+		ShellWord { raw: word.value.clone(), value, unresolved }
+	}
+
+	fn pieces(
+		&mut self,
+		pieces: &[WordPieceWithSource],
+		quoted: bool,
+		scope: Scope,
+		value: &mut Option<String>,
+		unresolved: &mut Vec<ShellUnresolvedPart>,
+	) {
+		for piece in pieces {
+			match &piece.piece {
+				WordPiece::Text(text) => {
+					if !quoted {
+						if text.contains(['*', '?', '[']) {
+							flag(unresolved, ShellUnresolvedPart::Glob);
+						}
+						if has_brace_expansion(text) {
+							flag(unresolved, ShellUnresolvedPart::Brace);
+						}
+					}
+					push(value, text);
+				},
+				WordPiece::SingleQuotedText(text) => push(value, text),
+				WordPiece::AnsiCQuotedText(text) => {
+					match escape::expand_backslash_escapes(text, EscapeExpansionMode::AnsiCQuotes) {
+						Ok((bytes, _)) => push(value, &String::from_utf8_lossy(&bytes)),
+						Err(_) => *value = None,
+					}
+				},
+				WordPiece::DoubleQuotedSequence(inner)
+				| WordPiece::GettextDoubleQuotedSequence(inner) => {
+					self.pieces(inner, true, scope, value, unresolved);
+				},
+				WordPiece::EscapeSequence(sequence) => {
+					let escaped = sequence.strip_prefix('\\').unwrap_or(sequence);
+					// Inside double quotes a backslash only escapes a few characters.
+					if quoted && !escaped.starts_with(['$', '`', '"', '\\', '\n']) {
+						push(value, sequence);
+					} else if escaped != "\n" {
+						push(value, escaped);
+					}
+				},
+				WordPiece::TildePrefix(_) => flag(unresolved, ShellUnresolvedPart::Tilde),
+				WordPiece::ParameterExpansion(expr) => {
+					flag(unresolved, ShellUnresolvedPart::Variable);
+					self.parameter(expr, scope);
+				},
+				WordPiece::ArithmeticExpression(expr) => {
+					flag(unresolved, ShellUnresolvedPart::Arithmetic);
+					self.expanded_text(&expr.value, scope);
+				},
+				WordPiece::CommandSubstitution(source)
+				| WordPiece::BackquotedCommandSubstitution(source) => {
+					flag(unresolved, ShellUnresolvedPart::CommandSubstitution);
+					if let Ok(program) = self.parse(source) {
+						self.program(&program, scope.enter(ShellCommandContext::CommandSubstitution));
+					}
+				},
+			}
+		}
+	}
+}
+
+fn flag(unresolved: &mut Vec<ShellUnresolvedPart>, part: ShellUnresolvedPart) {
+	if !unresolved.contains(&part) {
+		unresolved.push(part);
+	}
+}
+
+fn push(value: &mut Option<String>, text: &str) {
+	if let Some(value) = value {
+		value.push_str(text);
+	}
+}
+
+/// Whether unquoted text contains a `{a,b}` or `{1..3}` expansion.
+fn has_brace_expansion(text: &str) -> bool {
+	text.match_indices('{').any(|(open, _)| {
+		text[open..].find('}').is_some_and(|close| {
+			let inner = &text[open + 1..open + close];
+			inner.contains(',') || inner.contains("..")
+		})
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn analyze(command: &str) -> ShellAnalysis {
+		analyze_shell_command(command.to_string()).expect("command should parse")
+	}
+
+	fn values(words: &[ShellWord]) -> Vec<Option<&str>> {
+		words.iter().map(|word| word.value.as_deref()).collect()
+	}
+
+	#[test]
+	fn reports_literal_arguments_after_quote_removal() {
+		let analysis = analyze(r#"git commit -m "fix: it's done" 'a b' c\ d"#);
+		assert!(!analysis.unresolved);
+		let [command] = analysis.commands.as_slice() else {
+			panic!("expected one command");
+		};
+		assert_eq!(command.name.as_ref().and_then(|name| name.value.as_deref()), Some("git"));
+		assert_eq!(values(&command.args), [
+			Some("commit"),
+			Some("-m"),
+			Some("fix: it's done"),
+			Some("a b"),
+			Some("c d")
+		]);
+	}
+
+	#[test]
+	fn flags_parts_that_need_runtime_state() {
+		let analysis = analyze("rm -rf $HOME/*.log ~/x {a,b} \"$(pwd)\"");
+		assert!(analysis.unresolved);
+		let rm = analysis.commands.last().expect("rm should be reported");
+		assert_eq!(rm.args[0].value.as_deref(), Some("-rf"));
+		assert_eq!(rm.args[1].unresolved, [ShellUnresolvedPart::Variable, ShellUnresolvedPart::Glob]);
+		assert_eq!(rm.args[2].unresolved, [ShellUnresolvedPart::Tilde]);
+		assert_eq!(rm.args[3].unresolved, [ShellUnresolvedPart::Brace]);
+		assert_eq!(rm.args[4].unresolved, [ShellUnresolvedPart::CommandSubstitution]);
+		assert_eq!(rm.args[4].value, None);
+	}
+
+	#[test]
+	fn walks_substitutions_subshells_and_pipelines() {
+		let analysis = analyze("curl -s $(cat url.txt) | sudo sh; (cd /tmp && make) &");
+		let names: Vec<_> = analysis
+			.commands
+			.iter()
+			.map(|command| {
+				let name = command.name.as_ref().and_then(|name| name.value.as_deref());
+				(name, command.context, command.pipeline_position, command.background)
+			})
+			.collect();
+		assert_eq!(names, [
+			(Some("cat"), ShellCommandContext::CommandSubstitution, 0, false),
+			(Some("curl"), ShellCommandContext::Main, 0, false),
+			(Some("sudo"), ShellCommandContext::Main, 1, false),
+			(Some("cd"), ShellCommandContext::Subshell, 0, true),
+			(Some("make"), ShellCommandContext::Subshell, 0, true),
+		]);
+	}
+
+	#[test]
+	fn walks_case_patterns() {
+		let analysis = analyze("case $x in $(id -un)|root) echo admin ;; esac");
+		let names: Vec<_> = analysis
+			.commands
+			.iter()
+			.filter_map(|command| command.name.as_ref().and_then(|name| name.value.as_deref()))
+			.collect();
+		assert_eq!(names, ["id", "echo"]);
+		assert_eq!(analysis.commands[0].context, ShellCommandContext::CommandSubstitution);
+	}
+
+	#[test]
+	fn reports_redirections_and_heredocs() {
+		let analysis = analyze("cat <<EOF > out.txt 2>&1\nhello $(whoami)\nEOF\n");
+		let cat = analysis
+			.commands
+			.iter()
+			.find(|command| command.name.as_ref().is_some_and(|name| name.raw == "cat"))
+			.expect("cat should be reported");
+		let kinds: Vec<_> = cat.redirects.iter().map(|redirect| redirect.kind).collect();
+		assert_eq!(kinds, [
+			ShellRedirectKind::HereDocument,
+			ShellRedirectKind::Write,
+			ShellRedirectKind::DuplicateOutput
+		]);
+		assert_eq!(cat.redirects[0].body.as_deref(), Some("hello $(whoami)\n"));
+		assert_eq!(
+			cat.redirects[1]
+				.target
+				.as_ref()
+				.and_then(|target| target.value.as_deref()),
+			Some("out.txt")
+		);
+		assert_eq!(cat.redirects[2].fd, Some(2));
+		assert!(analysis.commands.iter().any(|command| {
+			command.context == ShellCommandContext::CommandSubstitution
+				&& command
+					.name
+					.as_ref()
+					.is_some_and(|name| name.raw == "whoami")
+		}));
+	}
+
+	#[test]
+	fn walks_substitutions_inside_expansions_and_arithmetic() {
+		let cases = [
+			("echo $(( $(curl x|sh) ))", &["curl", "sh"][..]),
+			("echo ${x:-$(rm -rf ~)}", &["rm"]),
+			("echo ${a[$(id)]} ${x/$(pat)/`rep`}", &["id", "pat", "rep"]),
+			("(( $(cmd) ))", &["cmd"]),
+			("for (( i = $(init); i < $(max); i++ )); do :; done", &["init", "max"]),
+		];
+		for (command, expected) in cases {
+			let analysis = analyze(command);
+			let substituted: Vec<_> = analysis
+				.commands
+				.iter()
+				.filter(|command| command.context == ShellCommandContext::CommandSubstitution)
+				.filter_map(|command| command.name.as_ref().and_then(|name| name.value.as_deref()))
+				.collect();
+			assert_eq!(substituted, expected, "{command}");
+		}
+	}
+
+	#[test]
+	fn flags_brace_expansions_after_other_braces() {
+		let analysis = analyze("echo x{a}{b,c} {a}");
+		let echo = &analysis.commands[0];
+		assert_eq!(echo.args[0].unresolved, [ShellUnresolvedPart::Brace]);
+		assert_eq!(echo.args[1].value.as_deref(), Some("{a}"));
+	}
+
+	#[test]
+	fn rejects_invalid_syntax() {
+		assert!(analyze_shell_command("if then".to_string()).is_err());
+	}
+}
@@ -0,0 +1,120 @@
+//! Tab completion backed by brush's programmable completion.
+//!
+//! Candidates come from compspecs registered with `complete` (including
+//! `complete -F` functions sourced from a snapshot) and otherwise from brush's
+//! basic lookup of commands, files and variables. Positions cross the N-API
+//! boundary as UTF-16 offsets so they index JavaScript strings directly.
+
+use brush_core::{
+	Shell as BrushShell,
+	escape::{self, QuoteMode},
+	openfiles::{self, OpenFiles},
+};
+use napi::{Error, Result};
+use napi_derive::napi;
+
+/// Completion candidates for an input line.
+#[napi(object)]
+#[derive(Debug)]
+pub struct ShellCompletion {
+	/// Replacement texts for the range `start..end`, in display order.
+	/// Filenames are escaped for the shell and directories end in `/`.
+	pub candidates:   Vec<String>,
+	/// Start of the range to replace, as a UTF-16 offset into the line.
+	pub start:        u32,
+	/// End of the range to replace (the cursor), as a UTF-16 offset.
+	pub end:          u32,
+	/// Whether the candidates are filenames.
+	pub filenames:    bool,
+	/// Whether a space should follow a candidate once it is accepted.
+	pub append_space: bool,
+}
+
+/// Complete `line` at `cursor`, a UTF-16 offset into it.
+///
+/// Completion functions run in the session itself, so their side effects
+/// persist; their output is discarded.
+pub async fn complete(shell: &mut BrushShell, line: &str, cursor: u32) -> Result<ShellCompletion> {
+	let position = byte_offset(line, cursor as usize);
+	let null = openfiles::null()
+		.map_err(|err| Error::from_reason(format!("Failed to create null file: {err}")))?;
+	// Silence the standard streams without losing the session's own fds.
+	let mut silenced = shell.persistent_open_files().clone();
+	silenced.update_from(
+		[OpenFiles::STDIN_FD, OpenFiles::STDOUT_FD, OpenFiles::STDERR_FD]
+			.into_iter()
+			.map(|fd| (fd, null.clone())),
+	);
+	let saved = shell.set_persistent_open_files(silenced);
+	let completions = shell.complete(line, position).await;
+	shell.set_persistent_open_files(saved);
+	let completions =
+		completions.map_err(|err| Error::from_reason(format!("Failed to complete: {err}")))?;
+
+	let options = &completions.options;
+	let candidates = completions
+		.candidates
+		.into_iter()
+		.map(|candidate| {
+			if !options.treat_as_filenames {
+				return candidate;
+			}
+			let is_dir = !candidate.ends_with('/') && shell.absolute_path(&candidate).is_dir();
+			let mut candidate = if options.no_autoquote_filenames {
+				candidate
+			} else {
+				escape::quote_if_needed(&candidate, QuoteMode::BackslashEscape).into_owned()
+			};
+			if is_dir {
+				candidate.push('/');
+			}
+			candidate
+		})
+		.collect::<Vec<_>>();
+
+	let start = completions.insertion_index;
+	let end = (start + completions.delete_count).min(line.len());
+	let append_space = !options.no_trailing_space_at_end_of_line
+		&& !candidates.iter().any(|candidate| candidate.ends_with('/'));
+	Ok(ShellCompletion {
+		start: utf16_offset(line, start),
+		end: utf16_offset(line, end),
+		filenames: options.treat_as_filenames,
+		append_space,
+		candidates,
+	})
+}
+
+/// Byte offset of the UTF-16 offset `cursor`, clamped to the line.
+fn byte_offset(line: &str, cursor: usize) -> usize {
+	let mut units = 0;
+	for (index, ch) in line.char_indices() {
+		if units >= cursor {
+			return index;
+		}
+		units += ch.len_utf16();
+	}
+	line.len()
+}
+
+fn utf16_offset(line: &str, byte: usize) -> u32 {
+	line
+		.get(..byte)
+		.map_or(0, |prefix| prefix.encode_utf16().count()) as u32
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn converts_between_utf16_and_byte_offsets() {
+		let line = "echo é🎉 x";
+		assert_eq!(byte_offset(line, 5), 5);
+		assert_eq!(byte_offset(line, 6), 7);
+		assert_eq!(byte_offset(line, 8), 11);
+		assert_eq!(byte_offset(line, 100), line.len());
+		assert_eq!(utf16_offset(line, 11), 8);
+		assert_eq!(utf16_offset(line, line.len()), 10);
+	}
+}
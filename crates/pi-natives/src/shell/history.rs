@@ -0,0 +1,183 @@
+//! Command history for persistent shell sessions.
+//!
+//! History is configured through bash's own variables (`HISTFILE`, `HISTSIZE`,
+//! `HISTCONTROL`, `HISTTIMEFORMAT`), so `history`, `fc` and a sourced snapshot
+//! see the same settings. Each `Shell.run` command line is recorded as one
+//! entry and appended to the file (`histappend`), so sessions sharing a file
+//! keep each other's entries.
+
+use brush_core::{Shell as BrushShell, ShellValue, ShellVariable, history::Item};
+use napi::{Error, Result};
+use napi_derive::napi;
+
+/// How repeated command lines are recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellHistoryDedupe {
+	/// Record every command line.
+	#[napi(value = "none")]
+	None,
+	/// Skip a command line identical to the previous one (`ignoredups`).
+	#[napi(value = "consecutive")]
+	Consecutive,
+	/// Remove earlier copies of a command line before recording it
+	/// (`erasedups`).
+	#[napi(value = "all")]
+	All,
+}
+
+/// History settings for a shell session.
+#[napi(object)]
+#[derive(Clone)]
+pub struct ShellHistoryOptions {
+	/// File history is loaded from and saved to. Defaults to a `HISTFILE` set
+	/// by the session snapshot; without either, history only lives as long as
+	/// the session.
+	pub path:       Option<String>,
+	/// Maximum number of entries kept; the oldest are dropped first. Defaults
+	/// to the snapshot's `HISTSIZE`.
+	pub max_items:  Option<u32>,
+	/// Write a timestamp comment before each entry in the file. Defaults to
+	/// the snapshot's `HISTTIMEFORMAT`, or true.
+	pub timestamps: Option<bool>,
+	/// How repeated command lines are recorded. Defaults to the snapshot's
+	/// `HISTCONTROL`, or `none`.
+	pub dedupe:     Option<ShellHistoryDedupe>,
+}
+
+/// Recorded command line.
+#[napi(object)]
+#[derive(Debug, PartialEq)]
+pub struct ShellHistoryEntry {
+	/// 1-based position in the history, as shown by the `history` builtin.
+	pub index:     u32,
+	/// Command line as it was run.
+	pub command:   String,
+	/// When the command was recorded, in milliseconds since the Unix epoch;
+	/// absent for entries loaded from a file without timestamps.
+	pub timestamp: Option<f64>,
+}
+
+/// How `ShellHistorySearch.text` is matched against command lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellHistoryMatch {
+	/// Command line starts with the text.
+	#[napi(value = "prefix")]
+	Prefix,
+	/// Command line contains the text.
+	#[napi(value = "contains")]
+	Contains,
+	/// Command line equals the text.
+	#[napi(value = "exact")]
+	Exact,
+}
+
+/// Query for searching history.
+#[napi(object)]
+pub struct ShellHistorySearch {
+	/// Text to look for.
+	pub text:  String,
+	/// How the text is matched. Defaults to `contains`.
+	pub mode:  Option<ShellHistoryMatch>,
+	/// Maximum number of entries returned.
+	pub limit: Option<u32>,
+}
+
+/// Enable history in `shell` and load any existing history file.
+///
+/// Options left unset keep what the session snapshot configured.
+pub fn configure(shell: &mut BrushShell, options: &ShellHistoryOptions) -> Result<()> {
+	let control = options.dedupe.map(|dedupe| match dedupe {
+		ShellHistoryDedupe::None => None,
+		ShellHistoryDedupe::Consecutive => Some("ignoredups".to_string()),
+		ShellHistoryDedupe::All => Some("erasedups".to_string()),
+	});
+	let time_format = match options.timestamps {
+		Some(timestamps) => Some(timestamps.then(|| "%F %T ".to_string())),
+		None if shell.env.is_set("HISTTIMEFORMAT") => None,
+		None => Some(Some("%F %T ".to_string())),
+	};
+	// `None` leaves a variable alone, `Some(None)` unsets it.
+	let vars = [
+		("HISTFILE", options.path.clone().map(Some)),
+		("HISTSIZE", options.max_items.map(|max| Some(max.to_string()))),
+		("HISTCONTROL", control),
+		("HISTTIMEFORMAT", time_format),
+	];
+	for (name, value) in vars {
+		match value {
+			Some(Some(value)) => shell
+				.env
+				.set_global(name, ShellVariable::new(ShellValue::String(value)))
+				.map_err(|err| Error::from_reason(format!("Failed to set {name}: {err}")))?,
+			Some(None) => {
+				shell
+					.env
+					.unset(name)
+					.map_err(|err| Error::from_reason(format!("Failed to unset {name}: {err}")))?;
+			},
+			None => {},
+		}
+	}
+
+	shell.options.enable_command_history = true;
+	shell.options.append_to_history_file = true;
+	shell
+		.load_history()
+		.map_err(|err| Error::from_reason(format!("Failed to load history: {err}")))
+}
+
+/// Record `command` and save the history file, when history is enabled.
+///
+/// Best effort: an unwritable history file must not keep commands from
+/// running.
+pub fn record(shell: &mut BrushShell, command: &str) {
+	if shell.options.enable_command_history && shell.add_to_history(command).is_ok() {
+		let _ = shell.save_history();
+	}
+}
+
+/// The last `limit` entries (all when absent), oldest first.
+pub fn entries(shell: &BrushShell, limit: Option<u32>) -> Vec<ShellHistoryEntry> {
+	let Some(history) = shell.history() else {
+		return Vec::new();
+	};
+	let skip = limit.map_or(0, |limit| history.count().saturating_sub(limit as usize));
+	history
+		.iter()
+		.enumerate()
+		.skip(skip)
+		.map(|(index, item)| entry(index, item))
+		.collect()
+}
+
+/// Entries matching `query`, newest first.
+pub fn search(shell: &BrushShell, query: &ShellHistorySearch) -> Vec<ShellHistoryEntry> {
+	let Some(history) = shell.history() else {
+		return Vec::new();
+	};
+	let text = query.text.as_str();
+	let matches = |command: &str| match query.mode.unwrap_or(ShellHistoryMatch::Contains) {
+		ShellHistoryMatch::Prefix => command.starts_with(text),
+		ShellHistoryMatch::Contains => command.contains(text),
+		ShellHistoryMatch::Exact => command == text,
+	};
+	(0..history.count())
+		.rev()
+		.filter_map(|index| history.get(index).map(|item| (index, item)))
+		.filter(|(_, item)| matches(&item.command_line))
+		.take(query.limit.map_or(usize::MAX, |limit| limit as usize))
+		.map(|(index, item)| entry(index, item))
+		.collect()
+}
+
+fn entry(index: usize, item: &Item) -> ShellHistoryEntry {
+	ShellHistoryEntry {
+		index:     index as u32 + 1,
+		command:   item.command_line.clone(),
+		timestamp: item
+			.timestamp
+			.map(|timestamp| timestamp.timestamp_millis() as f64),
+	}
+}
@@ -0,0 +1,396 @@
+//! Background job control for persistent shell sessions.
+//!
+//! Jobs started with `&` get their own output pipes instead of sharing the
+//! ones of the `Shell.run` call that launched them, so a long-running job (a
+//! dev server, a watcher) can be tailed or streamed while other commands run
+//! in the foreground. Until the launching run returns, its output is also
+//! passed on to that run, so `echo a & wait` still prints `a`. Finished jobs
+//! stay listed until `Shell.waitJob` collects them; their output stays
+//! readable until the job ID is reused.
+
+use std::{collections::HashMap, sync::Arc, time::UNIX_EPOCH};
+
+use brush_core::{
+	Shell as BrushShell,
+	interfaces::JobOutput,
+	jobs::{Job, JobState},
+	openfiles::OpenFile,
+	traps::TrapSignal,
+};
+use napi::{
+	Error, Result, Status,
+	bindgen_prelude::Unknown,
+	threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+	tokio::{self, sync::mpsc},
+};
+use napi_derive::napi;
+use parking_lot::Mutex;
+use tokio_util::sync::CancellationToken;
+
+#[cfg(not(unix))]
+use super::read_output;
+#[cfg(unix)]
+use super::{OutputPipe, read_pipe};
+use super::{OutputSink, ShellStream, pipe_to_files};
+
+/// Most output retained per job; older output is dropped first.
+const TAIL_LIMIT: usize = 1024 * 1024;
+
+/// Callback receiving a job's output. Weak so that an unfinished job doesn't
+/// keep the Node.js process alive.
+pub type JobOutputCallback =
+	ThreadsafeFunction<String, Unknown<'static>, String, Status, true, true>;
+
+/// Execution state of a background job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellJobState {
+	/// The job is still running.
+	#[napi(value = "running")]
+	Running,
+	/// The job was stopped by a signal.
+	#[napi(value = "stopped")]
+	Stopped,
+	/// The job has finished.
+	#[napi(value = "done")]
+	Done,
+}
+
+/// Background job of a shell session.
+#[napi(object)]
+#[derive(Debug)]
+pub struct ShellJob {
+	/// Job number, as used in `%N` job specs.
+	pub id:         u32,
+	/// Process ID of the job's most recently spawned external process.
+	pub pid:        Option<i32>,
+	/// Process group the job's external processes run in.
+	pub pgid:       Option<i32>,
+	/// Command line of the job.
+	pub command:    String,
+	/// Execution state of the job.
+	pub state:      ShellJobState,
+	/// Start time in milliseconds since the Unix epoch.
+	pub started_at: f64,
+	/// Exit status once the job is done.
+	pub exit_code:  Option<i32>,
+}
+
+/// Outcome of waiting for a background job.
+#[napi(object)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShellJobWaitResult {
+	/// Exit status when the job finished.
+	pub exit_code: Option<i32>,
+	/// Whether the timeout elapsed before the job finished.
+	pub timed_out: bool,
+}
+
+/// Lists the session's jobs, refreshing their states first.
+pub fn list(shell: &mut BrushShell) -> Result<Vec<ShellJob>> {
+	shell.jobs.reap_disowned();
+	shell
+		.jobs
+		.jobs
+		.iter_mut()
+		.map(|job| {
+			poll(job)?;
+			Ok(describe(job))
+		})
+		.collect()
+}
+
+/// Job `id` with its state refreshed, if the session still has it.
+pub fn get(shell: &mut BrushShell, id: u32) -> Result<Option<ShellJob>> {
+	let Some(job) = shell.jobs.jobs.iter_mut().find(|job| job.id == id as usize) else {
+		return Ok(None);
+	};
+	poll(job)?;
+	Ok(Some(describe(job)))
+}
+
+/// Removes job `id` from the session if it has finished.
+///
+/// Returns `None` while the job is still running.
+pub fn collect(shell: &mut BrushShell, id: u32) -> Result<Option<ShellJobWaitResult>> {
+	let jobs = &mut shell.jobs.jobs;
+	let index = jobs
+		.iter()
+		.position(|job| job.id == id as usize)
+		.ok_or_else(|| no_such_job(id))?;
+	poll(&mut jobs[index])?;
+	if !matches!(jobs[index].state, JobState::Done) {
+		return Ok(None);
+	}
+	let job = jobs.remove(index);
+	Ok(Some(ShellJobWaitResult { exit_code: job.exit_status.map(i32::from), timed_out: false }))
+}
+
+/// Sends `signal` (a name such as `INT` or `SIGKILL`, or a number; `TERM`
+/// when omitted) to job `id`.
+///
+/// The whole process group is signalled when the job leads its own; otherwise
+/// the job's latest process and its descendants are.
+pub fn signal(shell: &BrushShell, id: u32, signal: Option<&str>) -> Result<()> {
+	let name = signal.unwrap_or("TERM");
+	let number = name
+		.parse::<TrapSignal>()
+		.ok()
+		.and_then(|signal| i32::try_from(signal).ok())
+		.ok_or_else(|| Error::from_reason(format!("Invalid signal: {name}")))?;
+
+	let job = shell
+		.jobs
+		.jobs
+		.iter()
+		.find(|job| job.id == id as usize)
+		.ok_or_else(|| no_such_job(id))?;
+	let delivered = if let Some(pgid) = own_process_group(job) {
+		crate::ps::kill_process_group(pgid, number)
+	} else if let Some(pid) = job.representative_pid() {
+		crate::ps::kill_tree(pid, number) > 0
+	} else {
+		return Err(Error::from_reason(format!("Job {id} has no running process")));
+	};
+	if delivered {
+		Ok(())
+	} else {
+		Err(Error::from_reason(format!("Failed to signal job {id}")))
+	}
+}
+
+fn poll(job: &mut Job) -> Result<()> {
+	job.poll_done()
+		.map(drop)
+		.map_err(|err| Error::from_reason(format!("Failed to poll job {}: {err}", job.id)))
+}
+
+fn describe(job: &Job) -> ShellJob {
+	ShellJob {
+		id:         job.id as u32,
+		pid:        job.representative_pid(),
+		pgid:       own_process_group(job),
+		command:    job.command_line.clone(),
+		state:      match job.state {
+			JobState::Stopped => ShellJobState::Stopped,
+			JobState::Done => ShellJobState::Done,
+			JobState::Running | JobState::Unknown => ShellJobState::Running,
+		},
+		started_at: job
+			.started_at
+			.duration_since(UNIX_EPOCH)
+			.map_or(0.0, |elapsed| elapsed.as_secs_f64() * 1000.0),
+		exit_code:  job.exit_status.map(i32::from),
+	}
+}
+
+/// The job's process group, if it leads one; jobs that share the session's
+/// group must never be signalled by group.
+fn own_process_group(job: &Job) -> Option<i32> {
+	job.process_group_id()
+		.filter(|&pgid| crate::ps::process_group_id(pgid) == Some(pgid))
+}
+
+fn no_such_job(id: u32) -> Error {
+	Error::from_reason(format!("No such job: {id}"))
+}
+
+/// Output captured from one background job.
+struct JobBuffer {
+	tail:        String,
+	subscribers: Vec<JobOutputCallback>,
+	/// Output of the run that launched the job, while that run is active.
+	tee:         Option<OutputSink>,
+	/// Streams not yet read to the end.
+	open:        u8,
+}
+
+impl JobBuffer {
+	const fn new(tee: Option<OutputSink>) -> Self {
+		Self { tail: String::new(), subscribers: Vec::new(), tee, open: 2 }
+	}
+
+	fn push(&mut self, stream: ShellStream, text: &str) {
+		if let Some(tee) = &self.tee {
+			tee.emit(stream, text);
+		}
+		for callback in &self.subscribers {
+			callback.call(Ok(text.to_string()), ThreadsafeFunctionCallMode::NonBlocking);
+		}
+		self.tail.push_str(text);
+		if self.tail.len() > TAIL_LIMIT {
+			let start = tail_start(&self.tail, TAIL_LIMIT);
+			self.tail.drain(..start);
+		}
+	}
+
+	const fn closed(&self) -> bool {
+		self.open == 0
+	}
+
+	fn close(&mut self) {
+		self.open = self.open.saturating_sub(1);
+		if self.closed() {
+			self.subscribers.clear();
+		}
+	}
+}
+
+/// Byte offset of the last `max_bytes` of `text`, moved forward to a
+/// character boundary.
+const fn tail_start(text: &str, max_bytes: usize) -> usize {
+	let mut start = text.len().saturating_sub(max_bytes);
+	while !text.is_char_boundary(start) {
+		start += 1;
+	}
+	start
+}
+
+/// Job started by the active run, whose output is still passed on to it.
+struct TeedJob {
+	buffer: Arc<Mutex<JobBuffer>>,
+	#[cfg(unix)]
+	pipes:  Vec<Arc<OutputPipe>>,
+}
+
+#[derive(Default)]
+struct JobOutputsState {
+	buffers: HashMap<usize, Arc<Mutex<JobBuffer>>>,
+	cancel:  CancellationToken,
+	/// Output of the active run.
+	run:     Option<OutputSink>,
+	teed:    Vec<TeedJob>,
+}
+
+/// Output of a session's background jobs, keyed by job ID.
+///
+/// Installed as the shell's [`JobOutput`], so every job started with `&`
+/// writes to pipes drained into its own buffer.
+#[derive(Default)]
+pub struct JobOutputs(Mutex<JobOutputsState>);
+
+impl JobOutputs {
+	/// Forgets all jobs and stops reading their output; used when a new session
+	/// starts numbering jobs from 1 again.
+	pub fn reset(&self) {
+		let mut state = self.0.lock();
+		state.cancel.cancel();
+		*state = JobOutputsState::default();
+	}
+
+	/// The last `max_bytes` of job `id`'s output (all retained output when
+	/// omitted).
+	pub fn tail(&self, id: u32, max_bytes: Option<u32>) -> Result<String> {
+		let buffer = self.buffer(id)?;
+		let buffer = buffer.lock();
+		let start = max_bytes.map_or(0, |max| tail_start(&buffer.tail, max as usize));
+		Ok(buffer.tail[start..].to_string())
+	}
+
+	/// Delivers the retained output of job `id` to `callback`, followed by
+	/// everything the job writes until its output is closed.
+	pub fn subscribe(&self, id: u32, callback: JobOutputCallback) -> Result<()> {
+		let buffer = self.buffer(id)?;
+		let mut buffer = buffer.lock();
+		if !buffer.tail.is_empty() {
+			callback.call(Ok(buffer.tail.clone()), ThreadsafeFunctionCallMode::NonBlocking);
+		}
+		if !buffer.closed() {
+			buffer.subscribers.push(callback);
+		}
+		Ok(())
+	}
+
+	/// Whether job `id`'s output has been read to the end.
+	pub fn is_closed(&self, id: u32) -> Result<bool> {
+		Ok(self.buffer(id)?.lock().closed())
+	}
+
+	/// Passes the output of jobs started from now on to `sink` as well, until
+	/// [`Self::end_run`].
+	pub fn begin_run(&self, sink: OutputSink) {
+		self.0.lock().run = Some(sink);
+	}
+
+	/// Stops passing job output on to the run that just returned, after
+	/// handing it what its jobs have written so far. Jobs still running keep
+	/// writing to their own buffers only.
+	pub fn end_run(&self) {
+		let teed = {
+			let mut state = self.0.lock();
+			state.run = None;
+			std::mem::take(&mut state.teed)
+		};
+		for job in teed {
+			#[cfg(unix)]
+			for pipe in &job.pipes {
+				pipe.drain();
+			}
+			job.buffer.lock().tee = None;
+		}
+	}
+
+	fn buffer(&self, id: u32) -> Result<Arc<Mutex<JobBuffer>>> {
+		self
+			.0
+			.lock()
+			.buffers
+			.get(&(id as usize))
+			.cloned()
+			.ok_or_else(|| no_such_job(id))
+	}
+}
+
+impl JobOutput for JobOutputs {
+	fn open(&self, job_id: usize, _command_line: &str) -> Option<(OpenFile, OpenFile)> {
+		let (stdout_reader, stdout_writer) = pipe_to_files("job output").ok()?;
+		let (stderr_reader, stderr_writer) = pipe_to_files("job error").ok()?;
+		let mut state = self.0.lock();
+		let buffer = Arc::new(Mutex::new(JobBuffer::new(state.run.clone())));
+		state.buffers.insert(job_id, buffer.clone());
+		let cancel = state.cancel.clone();
+
+		let readers = [(stdout_reader, ShellStream::Stdout), (stderr_reader, ShellStream::Stderr)];
+		#[cfg(unix)]
+		{
+			let pipes = readers
+				.into_iter()
+				.map(|(reader, stream)| {
+					let buffer = buffer.clone();
+					OutputPipe::new(reader, move |text| buffer.lock().push(stream, text)).map(Arc::new)
+				})
+				.collect::<std::io::Result<Vec<_>>>()
+				.ok()?;
+			for pipe in &pipes {
+				let (pipe, cancel, buffer) = (pipe.clone(), cancel.clone(), buffer.clone());
+				let (activity_tx, _activity_rx) = mpsc::channel(1);
+				tokio::spawn(async move {
+					read_pipe(pipe, cancel, activity_tx).await;
+					buffer.lock().close();
+				});
+			}
+			if state.run.is_some() {
+				state.teed.push(TeedJob { buffer, pipes });
+			}
+		}
+		#[cfg(not(unix))]
+		{
+			for (reader, stream) in readers {
+				let (cancel, buffer) = (cancel.clone(), buffer.clone());
+				let (activity_tx, _activity_rx) = mpsc::channel(1);
+				tokio::spawn(async move {
+					read_output(reader, cancel, activity_tx, {
+						let buffer = buffer.clone();
+						move |text| buffer.lock().push(stream, text)
+					})
+					.await;
+					buffer.lock().close();
+				});
+			}
+			if state.run.is_some() {
+				state.teed.push(TeedJob { buffer });
+			}
+		}
+		Some((OpenFile::from(stdout_writer), OpenFile::from(stderr_writer)))
+	}
+}
@@ -0,0 +1,236 @@
+//! Command allow/deny policy enforced inside the brush interpreter.
+//!
+//! Rules are checked by brush-core right before each simple command is
+//! dispatched, after alias and word expansion, so `x=rm; $x -rf /` is judged
+//! the same as `rm -rf /`. The first refusal aborts the run and is reported
+//! back to JavaScript as a structured result.
+
+use std::{path::Path, sync::Arc};
+
+use brush_core::{
+	Shell as BrushShell,
+	interfaces::{CommandPolicy, PolicyDecision},
+};
+use napi::{Error, Result};
+use napi_derive::napi;
+use parking_lot::Mutex;
+use regex::Regex;
+
+/// What to do when a policy rule matches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum ShellPolicyAction {
+	/// Never run the command.
+	#[napi(value = "deny")]
+	Deny,
+	/// Only run the command when the run was explicitly confirmed.
+	#[napi(value = "confirm")]
+	Confirm,
+}
+
+/// Rule matching simple commands by name, path, and arguments.
+#[napi(object)]
+pub struct ShellPolicyRule {
+	/// Command name (`rm`) or path (`/usr/bin/rm`). Names match any path with
+	/// that basename; paths also match bare names that resolve to them via
+	/// `PATH`.
+	pub command: String,
+	/// Regex searched in the expanded arguments joined by single spaces. When
+	/// omitted the rule matches any arguments.
+	pub args:    Option<String>,
+	/// Action taken when the rule matches.
+	pub action:  ShellPolicyAction,
+	/// Explanation reported with the blocked command.
+	pub reason:  Option<String>,
+}
+
+/// Simple command refused by the policy.
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShellBlockedCommand {
+	/// Expanded command name and arguments.
+	pub argv:   Vec<String>,
+	/// Action of the rule that matched.
+	pub action: ShellPolicyAction,
+	/// Explanation from the rule, or a default for its action.
+	pub reason: String,
+}
+
+struct CompiledRule {
+	command: String,
+	args:    Option<Regex>,
+	action:  ShellPolicyAction,
+	reason:  Option<String>,
+}
+
+impl CompiledRule {
+	fn compile(rule: ShellPolicyRule) -> Result<Self> {
+		let args = rule
+			.args
+			.map(|pattern| {
+				Regex::new(&pattern).map_err(|err| {
+					Error::from_reason(format!("Failed to compile policy pattern {pattern:?}: {err}"))
+				})
+			})
+			.transpose()?;
+		Ok(Self { command: rule.command, args, action: rule.action, reason: rule.reason })
+	}
+
+	/// `resolved` is where a bare command name was found on `PATH`, if any.
+	fn matches(&self, argv: &[String], resolved: Option<&Path>) -> bool {
+		let Some(name) = argv.first() else {
+			return false;
+		};
+		let command_matches = if self.command.contains('/') {
+			*name == self.command || resolved.is_some_and(|path| path == Path::new(&self.command))
+		} else {
+			Path::new(name)
+				.file_name()
+				.is_some_and(|base| base == self.command.as_str())
+		};
+		command_matches
+			&& self
+				.args
+				.as_ref()
+				.is_none_or(|pattern| pattern.is_match(&argv[1..].join(" ")))
+	}
+}
+
+/// Compiled rule set of a session.
+pub struct PolicyRules(Arc<[CompiledRule]>);
+
+impl PolicyRules {
+	pub fn new(rules: Vec<ShellPolicyRule>) -> Result<Self> {
+		let rules = rules
+			.into_iter()
+			.map(CompiledRule::compile)
+			.collect::<Result<_>>()?;
+		Ok(Self(rules))
+	}
+
+	/// Gate for a new run. Background jobs keep the gate of the run that
+	/// started them, so a later confirmed run never lifts their rules.
+	pub fn begin(&self, confirmed: bool) -> Arc<PolicyGate> {
+		Arc::new(PolicyGate { rules: self.0.clone(), confirmed, blocked: Mutex::default() })
+	}
+}
+
+/// Rules as applied to one run, plus the state brush reports back through.
+pub struct PolicyGate {
+	rules:     Arc<[CompiledRule]>,
+	confirmed: bool,
+	blocked:   Mutex<Option<ShellBlockedCommand>>,
+}
+
+impl PolicyGate {
+	/// First command refused by this gate.
+	pub fn take_blocked(&self) -> Option<ShellBlockedCommand> {
+		self.blocked.lock().take()
+	}
+
+	fn evaluate(&self, argv: &[String], resolved: Option<&Path>) -> Option<&CompiledRule> {
+		self.rules.iter().find(|rule| {
+			(rule.action == ShellPolicyAction::Deny || !self.confirmed) && rule.matches(argv, resolved)
+		})
+	}
+}
+
+impl CommandPolicy for PolicyGate {
+	fn check(&self, args: &[String], shell: &BrushShell) -> PolicyDecision {
+		let resolved = args
+			.first()
+			.filter(|name| !name.contains('/'))
+			.and_then(|name| shell.find_first_executable_in_path(name));
+		let Some(rule) = self.evaluate(args, resolved.as_deref()) else {
+			return PolicyDecision::Allow;
+		};
+
+		let reason = rule.reason.clone().unwrap_or_else(|| {
+			match rule.action {
+				ShellPolicyAction::Deny => "denied",
+				ShellPolicyAction::Confirm => "requires confirmation",
+			}
+			.to_string()
+		});
+		self
+			.blocked
+			.lock()
+			.get_or_insert_with(|| ShellBlockedCommand {
+				argv:   args.to_vec(),
+				action: rule.action,
+				reason: reason.clone(),
+			});
+		PolicyDecision::Deny(reason)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rules(command: &str, args: Option<&str>, action: ShellPolicyAction) -> PolicyRules {
+		PolicyRules::new(vec![ShellPolicyRule {
+			command: command.to_string(),
+			args: args.map(str::to_string),
+			action,
+			reason: None,
+		}])
+		.expect("rule should compile")
+	}
+
+	fn argv(words: &[&str]) -> Vec<String> {
+		words.iter().map(|word| (*word).to_string()).collect()
+	}
+
+	#[test]
+	fn matches_names_by_basename_and_args_by_pattern() {
+		let gate = rules("rm", Some(r"-\w*r\w*f\w* /$"), ShellPolicyAction::Deny).begin(false);
+		assert!(gate.evaluate(&argv(&["rm", "-rf", "/"]), None).is_some());
+		assert!(
+			gate
+				.evaluate(&argv(&["/bin/rm", "-rf", "/"]), None)
+				.is_some()
+		);
+		assert!(
+			gate
+				.evaluate(&argv(&["rm", "-rf", "/tmp/x"]), None)
+				.is_none()
+		);
+		assert!(gate.evaluate(&argv(&["rmdir", "-rf", "/"]), None).is_none());
+	}
+
+	#[test]
+	fn matches_paths_against_resolved_names() {
+		let gate = rules("/usr/bin/git", None, ShellPolicyAction::Deny).begin(false);
+		let resolved = Path::new("/usr/bin/git");
+		assert!(
+			gate
+				.evaluate(&argv(&["git", "push"]), Some(resolved))
+				.is_some()
+		);
+		assert!(gate.evaluate(&argv(&["/usr/bin/git"]), None).is_some());
+		assert!(gate.evaluate(&argv(&["git", "push"]), None).is_none());
+	}
+
+	#[test]
+	fn confirmation_only_lifts_confirm_rules() {
+		let confirm = rules("git", Some("push .*--force"), ShellPolicyAction::Confirm);
+		let command = argv(&["git", "push", "origin", "--force"]);
+		assert!(confirm.begin(false).evaluate(&command, None).is_some());
+		assert!(confirm.begin(true).evaluate(&command, None).is_none());
+
+		let deny = rules("git", None, ShellPolicyAction::Deny);
+		assert!(deny.begin(true).evaluate(&command, None).is_some());
+	}
+
+	#[test]
+	fn rejects_invalid_patterns() {
+		let result = PolicyRules::new(vec![ShellPolicyRule {
+			command: "rm".to_string(),
+			args:    Some("(".to_string()),
+			action:  ShellPolicyAction::Deny,
+			reason:  None,
+		}]);
+		assert!(result.is_err());
+	}
+}
@@ -0,0 +1,387 @@
+//! Starting background commands and waiting for them to become ready.
+//!
+//! A command started this way runs as a regular job of the session (see
+//! [`super::jobs`]), so once it is ready it can be tailed, signalled and
+//! waited for like any job started with `&`. `PtySession::wait_ready` checks
+//! the same conditions against a PTY command's output.
+
+use std::{
+	collections::HashMap,
+	net::{IpAddr, Ipv4Addr, Ipv6Addr},
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+
+use napi::{
+	Error, Result,
+	bindgen_prelude::Unknown,
+	tokio::{self, net::TcpStream, sync::Mutex as TokioMutex, time},
+};
+use napi_derive::napi;
+use regex::Regex;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+	OutputSink, ShellConfig, ShellRunConfig, ShellSessionCore, create_session,
+	jobs::{self, ShellJob, ShellJobState},
+	run_shell_command,
+};
+use crate::task;
+
+/// How often readiness and job completion are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a single port probe may take.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+/// Polls to wait for a finished job's output to be drained.
+const DRAIN_POLLS: usize = 5;
+
+/// Conditions that mark a background command as ready. All conditions that
+/// are set must hold; at least one is required.
+#[napi(object)]
+pub struct ShellReadyCondition {
+	/// Regex matched against the command's combined stdout and stderr.
+	pub pattern: Option<String>,
+	/// TCP port on localhost (`127.0.0.1` or `::1`) that must accept
+	/// connections.
+	pub port:    Option<u16>,
+	/// File that must exist, relative to the command's working directory.
+	pub path:    Option<String>,
+}
+
+/// Options for starting a background command.
+#[napi(object)]
+pub struct ShellStartOptions<'env> {
+	/// Command string to run in the background.
+	pub command:    String,
+	/// Working directory for the command.
+	pub cwd:        Option<String>,
+	/// Environment variables to apply for this command only.
+	pub env:        Option<HashMap<String, String>>,
+	/// When the command counts as ready.
+	pub ready:      ShellReadyCondition,
+	/// Milliseconds to wait for readiness.
+	pub timeout_ms: Option<u32>,
+	/// Abort signal for giving up on the wait.
+	pub signal:     Option<Unknown<'env>>,
+}
+
+/// Result of starting a background command.
+#[napi(object)]
+pub struct ShellStartResult {
+	/// Job number of the started command.
+	pub job_id:    u32,
+	/// Process ID of the job's most recently spawned external process.
+	pub pid:       Option<i32>,
+	/// Whether the readiness condition held.
+	pub ready:     bool,
+	/// Exit status when the command finished before becoming ready.
+	pub exit_code: Option<i32>,
+	/// Whether the timeout elapsed first.
+	pub timed_out: bool,
+	/// Whether the abort signal fired first.
+	pub cancelled: bool,
+	/// Output written by the command up to this point.
+	pub output:    String,
+}
+
+/// Options for starting a background command (internal, lifetime-free).
+pub struct StartConfig {
+	/// Command string to run in the background.
+	pub command: String,
+	/// Working directory for the command.
+	pub cwd:     Option<String>,
+	/// Environment variables to apply for this command only.
+	pub env:     Option<HashMap<String, String>>,
+	/// When the command counts as ready.
+	pub ready:   ShellReadyCondition,
+}
+
+/// Compiled form of a [`ShellReadyCondition`].
+pub struct Readiness {
+	pattern: Option<Regex>,
+	port:    Option<u16>,
+	path:    Option<PathBuf>,
+}
+
+impl Readiness {
+	/// Validate `condition`; a relative `path` is resolved against `cwd`.
+	pub fn new(condition: ShellReadyCondition, cwd: &Path) -> Result<Self> {
+		if condition.pattern.is_none() && condition.port.is_none() && condition.path.is_none() {
+			return Err(Error::from_reason("Readiness condition needs a pattern, port or path"));
+		}
+		let pattern = condition
+			.pattern
+			.map(|pattern| {
+				Regex::new(&pattern).map_err(|err| {
+					Error::from_reason(format!("Failed to compile ready pattern {pattern:?}: {err}"))
+				})
+			})
+			.transpose()?;
+		Ok(Self { pattern, port: condition.port, path: condition.path.map(|path| cwd.join(path)) })
+	}
+
+	/// Whether every condition holds, with `output` being what the command
+	/// has written so far.
+	pub async fn holds(&self, output: &str) -> bool {
+		if let Some(pattern) = &self.pattern
+			&& !pattern.is_match(output)
+		{
+			return false;
+		}
+		if let Some(path) = &self.path
+			&& !path.exists()
+		{
+			return false;
+		}
+		if let Some(port) = self.port {
+			for host in [IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)] {
+				let probe = time::timeout(PROBE_TIMEOUT, TcpStream::connect((host, port)));
+				if probe.await.is_ok_and(|connected| connected.is_ok()) {
+					return true;
+				}
+			}
+			return false;
+		}
+		true
+	}
+}
+
+/// Start `options.command` as a background job of the session and wait until
+/// it is ready, finishes, or `ct` fires. The job is left running when `ct`
+/// fires; signal or wait for it through the job API.
+pub async fn start(
+	session: Arc<TokioMutex<Option<ShellSessionCore>>>,
+	config: ShellConfig,
+	options: StartConfig,
+	ct: task::CancelToken,
+) -> Result<ShellStartResult> {
+	let (job_id, readiness) = {
+		let mut session_guard = session.lock().await;
+		let session = match &mut *session_guard {
+			Some(session) => session,
+			None => session_guard.insert(create_session(&config).await?),
+		};
+		// Checked before the job starts so an invalid condition leaves nothing
+		// running.
+		let cwd = match options.cwd.as_deref() {
+			Some(cwd) => session.shell.absolute_path(Path::new(cwd)),
+			None => session.shell.working_dir().to_path_buf(),
+		};
+		let readiness = Readiness::new(options.ready, &cwd)?;
+		let run_config = ShellRunConfig {
+			// Grouped so that `&` applies to the whole command line.
+			command:   format!("{{ {}\n}} &", options.command),
+			cwd:       options.cwd,
+			env:       options.env,
+			confirmed: false,
+			tracer:    None,
+			limits:    None,
+			sandbox:   None,
+		};
+		run_shell_command(
+			session,
+			&run_config,
+			OutputSink::new(None, None, None),
+			CancellationToken::new(),
+		)
+		.await?;
+		let job_id = session
+			.shell
+			.jobs
+			.current_job()
+			.map(|job| job.id as u32)
+			.ok_or_else(|| Error::from_reason("Failed to start background job"))?;
+		(job_id, readiness)
+	};
+
+	let mut result = ShellStartResult {
+		job_id,
+		pid: None,
+		ready: false,
+		exit_code: None,
+		timed_out: false,
+		cancelled: false,
+		output: String::new(),
+	};
+	let cancelled = ct.wait();
+	tokio::pin!(cancelled);
+	loop {
+		let output = config.job_output.tail(job_id, None)?;
+		if readiness.holds(&output).await {
+			result.ready = true;
+			break;
+		}
+		match job_state(&session, job_id).await? {
+			Some(job) if job.state != ShellJobState::Done => {},
+			job => {
+				result.exit_code = job.and_then(|job| job.exit_code);
+				// Let the reader catch up with what the job wrote before exiting.
+				for _ in 0..DRAIN_POLLS {
+					if config.job_output.is_closed(job_id)? {
+						break;
+					}
+					time::sleep(POLL_INTERVAL).await;
+				}
+				break;
+			},
+		}
+		tokio::select! {
+			() = time::sleep(POLL_INTERVAL) => {},
+			reason = &mut cancelled => {
+				result.timed_out = matches!(reason, task::AbortReason::Timeout);
+				result.cancelled = !result.timed_out;
+				break;
+			}
+		}
+	}
+
+	result.output = config.job_output.tail(job_id, None)?;
+	result.pid = job_state(&session, job_id).await?.and_then(|job| job.pid);
+	Ok(result)
+}
+
+async fn job_state(
+	session: &TokioMutex<Option<ShellSessionCore>>,
+	id: u32,
+) -> Result<Option<ShellJob>> {
+	match session.lock().await.as_mut() {
+		Some(session) => jobs::get(&mut session.shell, id),
+		None => Ok(None),
+	}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use std::net::TcpListener;
+
+	use super::*;
+
+	async fn start_in(
+		session: &Arc<TokioMutex<Option<ShellSessionCore>>>,
+		config: &ShellConfig,
+		command: &str,
+		ready: ShellReadyCondition,
+		timeout_ms: u32,
+	) -> ShellStartResult {
+		let options = StartConfig { command: command.to_string(), cwd: None, env: None, ready };
+		start(
+			session.clone(),
+			config.clone(),
+			options,
+			task::CancelToken::new(Some(timeout_ms), None),
+		)
+		.await
+		.expect("command should start")
+	}
+
+	#[tokio::test]
+	async fn port_condition_accepts_ipv6_only_listeners() {
+		let Ok(listener) = TcpListener::bind((Ipv6Addr::LOCALHOST, 0)) else {
+			eprintln!("skipping: IPv6 loopback unavailable");
+			return;
+		};
+		let port = listener
+			.local_addr()
+			.expect("listener should have an address")
+			.port();
+		let condition = ShellReadyCondition { port: Some(port), ..condition() };
+		let readiness = Readiness::new(condition, Path::new(".")).expect("condition should compile");
+		assert!(readiness.holds("").await);
+
+		drop(listener);
+		assert!(!readiness.holds("").await);
+	}
+
+	const fn condition() -> ShellReadyCondition {
+		ShellReadyCondition { pattern: None, port: None, path: None }
+	}
+
+	#[tokio::test]
+	async fn resolves_on_output_port_exit_and_timeout() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let session = Arc::new(TokioMutex::new(None));
+
+		let logged = start_in(
+			&session,
+			&config,
+			"echo booting; sh -c 'echo listening on 4000; exec sleep 30'",
+			ShellReadyCondition { pattern: Some(r"listening on \d+".to_string()), ..condition() },
+			5000,
+		)
+		.await;
+		assert!(logged.ready);
+		assert_eq!(logged.output, "booting\nlistening on 4000\n");
+		assert!(logged.pid.is_some());
+
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("listener should bind");
+		let port = listener
+			.local_addr()
+			.expect("listener should have an address")
+			.port();
+		let listening = start_in(
+			&session,
+			&config,
+			"sh -c 'exec sleep 30'",
+			ShellReadyCondition { port: Some(port), ..condition() },
+			5000,
+		)
+		.await;
+		assert!(listening.ready);
+		assert_eq!(listening.job_id, logged.job_id + 1);
+
+		let exited = start_in(
+			&session,
+			&config,
+			"echo oops; exit 3",
+			ShellReadyCondition { path: Some("never-created".to_string()), ..condition() },
+			5000,
+		)
+		.await;
+		assert!(!exited.ready && !exited.timed_out);
+		assert_eq!(exited.exit_code, Some(3));
+		assert_eq!(exited.output, "oops\n");
+
+		let waiting = start_in(
+			&session,
+			&config,
+			"sh -c 'exec sleep 30'",
+			ShellReadyCondition { path: Some("never-created".to_string()), ..condition() },
+			200,
+		)
+		.await;
+		assert!(!waiting.ready && waiting.timed_out && !waiting.cancelled);
+		assert_eq!(waiting.exit_code, None);
+
+		let mut guard = session.lock().await;
+		let shell = &mut guard.as_mut().expect("session should exist").shell;
+		for job in [logged.job_id, listening.job_id, waiting.job_id] {
+			jobs::signal(shell, job, Some("KILL")).expect("job should be signalled");
+		}
+	}
+
+	#[tokio::test]
+	async fn invalid_conditions_start_nothing() {
+		let config = ShellConfig::new(None, None, None).expect("config should build");
+		let session = Arc::new(TokioMutex::new(None));
+		let options = StartConfig {
+			command: "sleep 30".to_string(),
+			cwd:     None,
+			env:     None,
+			ready:   ShellReadyCondition { pattern: Some("(".to_string()), ..condition() },
+		};
+		let started = start(session.clone(), config, options, task::CancelToken::default()).await;
+		assert!(started.is_err());
+		let mut guard = session.lock().await;
+		let shell = &mut guard.as_mut().expect("session should exist").shell;
+		assert!(jobs::list(shell).expect("jobs should list").is_empty());
+	}
+
+	#[test]
+	fn rejects_missing_or_invalid_conditions() {
+		let cwd = Path::new("/");
+		assert!(Readiness::new(condition(), cwd).is_err());
+		let invalid = ShellReadyCondition { pattern: Some("(".to_string()), ..condition() };
+		assert!(Readiness::new(invalid, cwd).is_err());
+	}
+}
@@ -0,0 +1,97 @@
+//! Serializes a live brush session into a sourceable snapshot script.
+//!
+//! The output is the inverse of `ShellOptions::snapshot_path`: sourcing it in
+//! a fresh session restores variables (with their array, export and readonly
+//! attributes), functions, aliases and the options that differ from a fresh
+//! session's defaults.
+
+use std::fmt::Write as _;
+
+use brush_core::{
+	Shell as BrushShell, ShellValue,
+	escape::{self, QuoteMode},
+	namedoptions::{self, ShellOptionKind},
+	options::RuntimeOptions,
+	variables::FormatStyle,
+};
+use napi::{Error, Result};
+
+/// Variables the shell maintains for its own process; restoring them would
+/// describe the exporting session instead of the one sourcing the snapshot.
+const SHELL_MANAGED: &[&str] = &[
+	"BASHOPTS",
+	"BASHPID",
+	"BASH_VERSINFO",
+	"EUID",
+	"OLDPWD",
+	"PPID",
+	"PWD",
+	"SHELLOPTS",
+	"SHLVL",
+	"UID",
+];
+
+/// Render `shell` as a script; `defaults` are the options of a fresh session.
+pub fn render(shell: &BrushShell, defaults: &RuntimeOptions) -> Result<String> {
+	let mut script = String::from("# Shell snapshot exported by pi-natives\n");
+
+	let mut vars: Vec<_> = shell.env.iter().collect();
+	vars.sort_unstable_by_key(|(name, _)| *name);
+	for (name, var) in vars {
+		// Dynamic values (RANDOM, SECONDS, ...) are recomputed by the shell.
+		if matches!(var.value(), ShellValue::Dynamic { .. }) || SHELL_MANAGED.contains(&name.as_str())
+		{
+			continue;
+		}
+		let flags = var.attribute_flags(shell);
+		let value = var
+			.value()
+			.format(FormatStyle::DeclarePrint, shell)
+			.map_err(|err| Error::from_reason(format!("Failed to format {name}: {err}")))?;
+		let assignment = if matches!(var.value(), ShellValue::Unset(_)) {
+			String::new()
+		} else {
+			format!("={value}")
+		};
+		// A readonly variable cannot be redeclared, so skip it when the sourcing
+		// session already has it readonly (e.g. the snapshot is sourced twice).
+		let guard = if var.is_readonly() {
+			format!("[[ ${{{name}@a}} == *r* ]] || ")
+		} else {
+			String::new()
+		};
+		let _ = writeln!(script, "{guard}declare -g{flags} {name}{assignment}");
+	}
+
+	let mut funcs: Vec<_> = shell.funcs().iter().collect();
+	funcs.sort_unstable_by_key(|(name, _)| *name);
+	for (name, registration) in funcs {
+		let _ = writeln!(script, "{}", registration.definition());
+		if registration.is_exported() {
+			let _ = writeln!(script, "declare -fx {name}");
+		}
+	}
+
+	let mut aliases: Vec<_> = shell.aliases.iter().collect();
+	aliases.sort_unstable();
+	for (name, value) in aliases {
+		let _ =
+			writeln!(script, "alias {name}={}", escape::force_quote(value, QuoteMode::SingleQuote));
+	}
+
+	for (kind, on, off) in [
+		(ShellOptionKind::SetO, "set -o", "set +o"),
+		(ShellOptionKind::Shopt, "shopt -s", "shopt -u"),
+	] {
+		let mut options: Vec<_> = namedoptions::options(kind).iter().collect();
+		options.sort_unstable_by_key(|option| option.name);
+		for option in options {
+			let value = option.definition.get(&shell.options);
+			if value != option.definition.get(defaults) {
+				let _ = writeln!(script, "{} {}", if value { on } else { off }, option.name);
+			}
+		}
+	}
+
+	Ok(script)
+}
@@ -367,7 +367,7 @@ export async function runInteractiveBashPty(
 				.then(finalize)
 				.catch(error => {
 					sink.push(`PTY error: ${error instanceof Error ? error.message : String(error)}\n`);
-					finalize({ exitCode: undefined, cancelled: false, timedOut: false });
+					finalize({ exitCode: undefined, cancelled: false, timedOut: false, totalBytes: 0, elidedBytes: 0 });
 				});
 			return component;
 		},
@@ -127,7 +127,12 @@ export declare class PhotonImage {
 /** Stateful PTY session for interactive stdin/stdout passthrough. */
 export declare class PtySession {
   constructor()
-  /** Start a PTY command and stream output chunks via callback. */
+  /**
+   * Start a PTY command and stream output chunks via callback.
+   *
+   * The command's stdout and stderr share the PTY slave, so chunks arrive as
+   * one untagged terminal stream.
+   */
   start(options: PtyStartOptions, onChunk?: ((error: Error | null, chunk: string) => void) | undefined | null): Promise<PtyRunResult>
   /** Write raw input bytes to PTY stdin. */
   write(data: string): void
@@ -135,6 +140,39 @@ export declare class PtySession {
   resize(cols: number, rows: number): void
   /** Force-kill the active PTY command. */
   kill(): void
+  /**
+   * What the terminal currently shows, as interpreted by a VT100/xterm
+   * emulator fed with the command's output.
+   *
+   * Remains available after the command exits, until the next `start`.
+   */
+  snapshot(options?: PtySnapshotOptions | undefined | null): PtyScreenSnapshot
+  /**
+   * Run an expect script: wait for output or the screen to match, and send
+   * input once it does, step by step.
+   *
+   * Output that arrived before the call but was not matched by an earlier
+   * script is searched too, so a prompt printed right after `start` is not
+   * missed.
+   */
+  expect(options: PtyExpectOptions): Promise<PtyExpectResult>
+  /**
+   * Wait until the command is ready: its output matches `ready.pattern`, a
+   * port on 127.0.0.1 accepts connections, and/or a file exists. Resolves
+   * early when the command's output ends or the wait times out; the
+   * command keeps running either way.
+   *
+   * Output is only inspected, so a later expect script still sees it.
+   */
+  waitReady(options: PtyReadyOptions): Promise<PtyReadyResult>
+  /**
+   * The recording of the current or most recent command as an asciicast v2
+   * document. Available once `start` was called with `record`, including
+   * after the command was killed or timed out.
+   */
+  recording(): string
+  /** Write the recording to `path` as an asciicast v2 file. */
+  saveRecording(path: string): void
 }
 
 /**
@@ -159,25 +197,123 @@ export declare class Shell {
   /**
    * Create a new shell session from optional configuration.
    *
-   * The options set session-scoped environment variables and a snapshot path.
+   * The options set session-scoped environment variables, a snapshot path,
+   * the command policy, and history recording. Fails when a policy pattern
+   * is not a valid regex.
    */
   constructor(options?: ShellOptions | undefined | null)
   /**
    * Run a shell command using the provided options.
    *
-   * The `on_chunk` callback receives streamed stdout/stderr output. Returns
-   * the exit code when the command completes, or flags when cancelled or
-   * timed out.
+   * The `on_chunk` callback receives streamed stdout/stderr output. When
+   * `on_output` is provided, stdout and stderr are read from separate pipes
+   * and each chunk is also delivered tagged with its stream; both callbacks
+   * then see the output of each command before that of the next, so only
+   * one command's writes to stdout and stderr can be reordered with each
+   * other. Without `on_output`, `on_chunk` sees the exact write order. When `on_trace`
+   * is provided, it receives a record for every simple command the
+   * interpreter executes or the policy refuses, until the run returns;
+   * background jobs' later commands are not reported. Returns the exit code
+   * when the command completes, or flags when cancelled or timed out.
    */
-  run(options: ShellRunOptions, onChunk?: ((error: Error | null, chunk: string) => void) | undefined | null): Promise<ShellRunResult>
+  run(options: ShellRunOptions, onChunk?: ((error: Error | null, chunk: string) => void) | undefined | null, onOutput?: ((error: Error | null, chunk: ShellOutputChunk) => void) | undefined | null, onTrace?: ((error: Error | null, trace: ShellCommandTrace) => void) | undefined | null): Promise<ShellRunResult>
   /**
    * Abort all running commands for this shell session.
    *
    * Returns `Ok(())` even when no commands are running.
    */
   abort(): Promise<void>
+  /**
+   * Current working directory of the session.
+   *
+   * Like the other state accessors, this starts the session if no command
+   * has run yet and waits for a running command to finish.
+   */
+  cwd(): Promise<string>
+  /** Exported variables visible to commands run in the session. */
+  env(): Promise<Record<string, string>>
+  /** Names of the shell functions defined in the session, sorted. */
+  functions(): Promise<Array<string>>
+  /** Aliases defined in the session, keyed by name. */
+  aliases(): Promise<Record<string, string>>
+  /** `set -o` and `shopt` option states of the session. */
+  options(): Promise<ShellOptionFlags>
+  /**
+   * Write the session's variables, functions, aliases and changed options
+   * to `path` as a script that can be passed back as `snapshot_path`.
+   */
+  exportSnapshot(path: string): Promise<void>
+  /**
+   * Completion candidates for `line` with the cursor at `cursor`, a
+   * JavaScript string index.
+   *
+   * Uses compspecs registered with `complete` (e.g. `complete -F` functions
+   * from the snapshot) and otherwise completes command names, files, and
+   * `$` variable references.
+   */
+  complete(line: string, cursor: number): Promise<ShellCompletion>
+  /**
+   * The last `limit` history entries (all when omitted), oldest first.
+   *
+   * Empty unless the session was created with the `history` option.
+   */
+  history(limit?: number | undefined | null): Promise<Array<ShellHistoryEntry>>
+  /** History entries matching `query`, newest first. */
+  searchHistory(query: ShellHistorySearch): Promise<Array<ShellHistoryEntry>>
+  /**
+   * Start a command in the background and resolve once its `ready`
+   * condition holds, it finishes, or the timeout or abort signal fires.
+   *
+   * The command runs as a job of the session and keeps running when the
+   * wait gives up. The result carries its job ID, PID and the output it
+   * wrote while starting up.
+   */
+  startJob(options: ShellStartOptions): Promise<ShellStartResult>
+  /**
+   * Background jobs of the session, oldest first.
+   *
+   * Finished jobs stay listed with their exit code until `wait_job`
+   * collects them.
+   */
+  jobs(): Promise<Array<ShellJob>>
+  /**
+   * The last `max_bytes` of a background job's combined stdout and stderr;
+   * all retained output (up to 1 MiB) when omitted.
+   *
+   * Doesn't wait for a running command, so a job can be tailed while the
+   * foreground is busy.
+   */
+  jobOutput(id: number, maxBytes?: number | undefined | null): string
+  /**
+   * Stream a background job's output to `on_chunk`: the retained output
+   * first, then each chunk as the job writes it, until its output closes.
+   *
+   * The callback doesn't keep the process alive.
+   */
+  streamJobOutput(id: number, onChunk: (error: Error | null, chunk: string) => void): void
+  /**
+   * Send `signal` (a name such as `INT` or `SIGKILL`, or a number; `TERM`
+   * when omitted) to a background job's processes.
+   */
+  signalJob(id: number, signal?: string | undefined | null): Promise<void>
+  /**
+   * Wait up to `timeout_ms` (indefinitely when omitted) for a background job
+   * to finish, then remove it from the job table.
+   *
+   * The session is only locked while checking, so commands can run while a
+   * wait is pending.
+   */
+  waitJob(id: number, timeoutMs?: number | undefined | null): Promise<ShellJobWaitResult>
 }
 
+/**
+ * Parse a shell command string and report what it would run without
+ * executing it.
+ *
+ * Fails when the string is not valid shell syntax.
+ */
+export declare function analyzeShellCommand(command: string): ShellAnalysis
+
 /**
  * Apply ast-grep rewrite rules to matching files; honors `dryRun` and returns
  * a promise.
@@ -459,6 +595,16 @@ export interface ClipboardImage {
   mimeType: string
 }
 
+/** Color depth of themed output. */
+export declare enum ColorDepth {
+  /** 24-bit colors. */
+  TrueColor = 'truecolor',
+  /** The xterm 256-color palette. */
+  Ansi256 = '256',
+  /** The 16 basic ANSI colors. */
+  Ansi16 = '16'
+}
+
 /** A context line (before or after a match). */
 export interface ContextLine {
   /** 1-indexed line number in the source file. */
@@ -484,6 +630,29 @@ export declare function copyToClipboard(text: string): void
  */
 export declare function detectMacOSAppearance(): MacOSAppearance | null
 
+/** Options for `highlight_diff`. */
+export interface DiffHighlightOptions {
+  /** SGR sequence tinting added lines, usually a background color. */
+  insertedLine?: string
+  /** SGR sequence tinting removed lines, usually a background color. */
+  deletedLine?: string
+  /**
+   * Mark the words that changed between paired removed and added lines
+   * (default: false).
+   */
+  wordDiff?: boolean
+  /**
+   * SGR sequence marking changed words in added lines (default: reverse
+   * video).
+   */
+  insertedWord?: string
+  /**
+   * SGR sequence marking changed words in removed lines (default: reverse
+   * video).
+   */
+  deletedWord?: string
+}
+
 /**
  * One edit in a batch; targets a chunk via `sel`/`crc` (with params-level
  * defaults).
@@ -581,10 +750,13 @@ export declare function encodeSixel(bytes: Uint8Array, targetWidthPx: number, ta
  * Execute a brush shell command.
  *
  * Creates a fresh session for each call. The `on_chunk` callback receives
- * streamed stdout/stderr output. Returns the exit code when the command
- * completes, or flags when cancelled or timed out.
+ * streamed stdout/stderr output; `on_output` additionally receives chunks
+ * tagged by stream (with the same ordering caveat) and `on_trace`
+ * per-command records, as in [`Shell::run`].
+ * Returns the exit code when the command completes, or flags when cancelled
+ * or timed out.
  */
-export declare function executeShell(options: ShellExecuteOptions, onChunk?: ((error: Error | null, chunk: string) => void) | undefined | null): Promise<ShellExecuteResult>
+export declare function executeShell(options: ShellExecuteOptions, onChunk?: ((error: Error | null, chunk: string) => void) | undefined | null, onOutput?: ((error: Error | null, chunk: ShellOutputChunk) => void) | undefined | null, onTrace?: ((error: Error | null, trace: ShellCommandTrace) => void) | undefined | null): Promise<ShellExecuteResult>
 
 /**
  * Extract the before/after slices around an overlay region.
@@ -868,18 +1040,22 @@ export interface GrepResult {
 export declare function hasMatch(content: string | Uint8Array, pattern: string | Uint8Array, ignoreCase?: boolean | undefined | null, multiline?: boolean | undefined | null): boolean
 
 /**
- * Highlight code and return ANSI-colored lines.
+ * Highlight code and return ANSI-colored lines, HTML or SVG.
  *
  * # Arguments
  * * `code` - The source code to highlight
  * * `lang` - Language identifier (e.g., "rust", "typescript", "python")
  * * `colors` - Theme colors as ANSI escape sequences
+ * * `options` - A `.tmTheme` or VS Code theme that replaces `colors`, and the
+ *   output format
  *
  * # Returns
  * Highlighted code with ANSI color codes, or the original code if highlighting
- * fails.
+ * fails or neither `colors` nor a theme is given. HTML and SVG output color
+ * runs from the same categories or theme styles, reading colors back from
+ * the ANSI sequences of `colors`.
  */
-export declare function highlightCode(code: string, lang: string | undefined | null, colors: HighlightColors): string
+export declare function highlightCode(code: string, lang?: string | undefined | null, colors?: HighlightColors | undefined | null, options?: HighlightOptions | undefined | null): string
 
 /**
  * Theme colors for syntax highlighting.
@@ -910,6 +1086,57 @@ export interface HighlightColors {
   deleted?: string
 }
 
+/**
+ * Highlight a unified diff, coloring the code in each hunk as `lang`.
+ *
+ * # Arguments
+ * * `diff` - Unified diff text, as produced by `git diff` or `diff -u`
+ * * `lang` - Language of the diffed file (e.g., "rust", "typescript")
+ * * `colors` - Theme colors as ANSI escape sequences; `inserted`/`deleted`
+ *   color the `+`/`-` markers
+ * * `options` - Line tints and word-level change marks
+ *
+ * # Returns
+ * The diff with ANSI color codes. Headers and hunk ranges use the keyword
+ * color.
+ */
+export declare function highlightDiff(diff: string, lang: string | undefined | null, colors: HighlightColors, options?: DiffHighlightOptions | undefined | null): string
+
+/** Output format of `highlight_code`. */
+export declare enum HighlightFormat {
+  /** ANSI escape sequences for terminals. */
+  Ansi = 'ansi',
+  /** A `<pre><code>` block. */
+  Html = 'html',
+  /** A standalone SVG image. */
+  Svg = 'svg'
+}
+
+/** Options for `highlight_code`. */
+export interface HighlightOptions {
+  /**
+   * Theme to color with instead of `colors`: a `.tmTheme` or VS Code JSON
+   * theme, given as a file path or the theme's text. Parsed themes are
+   * cached.
+   */
+  theme?: string
+  /** Color depth of themed ANSI output (default: "truecolor"). */
+  colorDepth?: ColorDepth
+  /** Output format (default: "ansi"). */
+  format?: HighlightFormat
+  /**
+   * Style HTML runs with `style` attributes instead of `hl-<category>`
+   * classes such as `hl-keyword` (default: false). Themes apply to inline
+   * styles only; SVG is always styled inline.
+   */
+  inlineStyles?: boolean
+  /**
+   * Number the lines of HTML and SVG output (default: true for SVG, false
+   * for HTML).
+   */
+  lineNumbers?: boolean
+}
+
 /**
  * Convert HTML source to Markdown with optional preprocessing.
  *
@@ -1030,6 +1257,19 @@ export declare function matchesKittySequence(data: string, expectedCodepoint: nu
  */
 export declare function matchesLegacySequence(data: string, keyName: string): boolean
 
+/** Byte budget and head/tail retention policy for command output. */
+export interface OutputLimit {
+  /** Total bytes of output forwarded to the callback. */
+  maxBytes: number
+  /**
+   * Portion of `max_bytes` kept from the end of the output; the remainder is
+   * kept from the start. Defaults to half of `max_bytes`.
+   */
+  tailBytes?: number
+  /** Write the full output to a temp file when anything is elided. */
+  spill?: boolean
+}
+
 /** Parsed Kitty keyboard protocol sequence result for a Kitty input sequence. */
 export interface ParsedKittyResult {
   /** Primary codepoint associated with the key. */
@@ -1058,6 +1298,67 @@ export declare function parseKey(data: string, kittyProtocolActive: boolean): st
  */
 export declare function parseKittySequence(data: string): ParsedKittyResult | null
 
+/** A process and its descendants. */
+export interface ProcessInfo {
+  /** Process ID. */
+  pid: number
+  /** Parent process ID. */
+  ppid?: number
+  /** Short process name. */
+  name: string
+  /** Command line arguments, starting with the program. */
+  argv: Array<string>
+  /** Working directory, when readable. */
+  cwd?: string
+  /** Scheduling state. */
+  state: ProcessState
+  /** Resident memory in bytes. */
+  rssBytes?: number
+  /** User and system CPU time in milliseconds. */
+  cpuTimeMs?: number
+  /** Start time in milliseconds since the Unix epoch. */
+  startedAt?: number
+  /** TCP sockets the process listens on. */
+  listeningPorts: Array<ProcessPort>
+  /** Child processes, each with its own descendants. */
+  children: Array<ProcessInfo>
+}
+
+/** TCP socket a process is listening on. */
+export interface ProcessPort {
+  /** Local address the socket is bound to. */
+  address: string
+  /** Local port. */
+  port: number
+}
+
+/** Scheduling state of a process. */
+export declare enum ProcessState {
+  /** Running or runnable. */
+  Running = 'running',
+  /** Waiting for an event. */
+  Sleeping = 'sleeping',
+  /** In uninterruptible sleep, usually waiting for I/O. */
+  DiskSleep = 'diskSleep',
+  /** Stopped by a signal or a tracer. */
+  Stopped = 'stopped',
+  /** Exited but not yet reaped by its parent. */
+  Zombie = 'zombie',
+  /** Idle kernel thread. */
+  Idle = 'idle',
+  /** Not reported on this platform. */
+  Unknown = 'unknown'
+}
+
+/**
+ * Describe `pid` and all its descendants.
+ *
+ * Returns `None` when the process does not exist. Details that cannot be
+ * read (another user's process, a process that exits meanwhile) are left
+ * unset rather than failing the whole tree.
+ */
+export declare function processTree(pid: number): ProcessInfo | null
+
 /** Probe whether `ProjFS` overlay virtualization can be started on this system. */
 export declare function projfsOverlayProbe(): ProjfsOverlayProbeResult
 
@@ -1084,6 +1385,118 @@ export declare function projfsOverlayStart(lowerRoot: string, projectionRoot: st
 /** Stop `ProjFS` virtualization for an active `projection_root` session. */
 export declare function projfsOverlayStop(projectionRoot: string): void
 
+/** Match made by an expect step. */
+export interface PtyExpectMatch {
+  /** Index of the step in the script. */
+  step: number
+  /** Text that matched. */
+  matched: string
+  /** Text before the match: the skipped output, or the screen above it. */
+  before: string
+  /** Capture groups of a `pattern`, unset when a group did not participate. */
+  groups: Array<string | undefined | null>
+}
+
+/** Options for running an expect script. */
+export interface PtyExpectOptions {
+  /** Steps to run in order. */
+  steps: Array<PtyExpectStep>
+  /** Milliseconds the whole script may take. */
+  timeoutMs?: number
+  /** Abort signal for stopping the script. */
+  signal?: unknown
+}
+
+/**
+ * Outcome of an expect script. The script stops at the first step that does
+ * not complete.
+ */
+export interface PtyExpectResult {
+  /** Matches of the expect steps that completed, in order. */
+  matches: Array<PtyExpectMatch>
+  /** Whether every step completed. */
+  completed: boolean
+  /** Index of the step the script stopped at. */
+  failedStep?: number
+  /** Whether a step's or the script's timeout elapsed. */
+  timedOut: boolean
+  /** Whether the abort signal fired. */
+  cancelled: boolean
+  /**
+   * Whether the command's output ended before a match, or input could not be
+   * sent because it exited.
+   */
+  exited: boolean
+  /** Output received since the last output match. */
+  output: string
+}
+
+/**
+ * One step of an expect script: either `send`, or one of `pattern` and
+ * `literal`.
+ */
+export interface PtyExpectStep {
+  /** Text to write to the command's stdin. */
+  send?: string
+  /** Regex to wait for. */
+  pattern?: string
+  /** Exact text to wait for. */
+  literal?: string
+  /** Where to look for `pattern` or `literal` (default: output). */
+  target?: PtyExpectTarget
+  /** Milliseconds to wait for this step's match. */
+  timeoutMs?: number
+}
+
+/** What an expect step matches against. */
+export declare enum PtyExpectTarget {
+  /**
+   * Output received since the previous output match; a match consumes the
+   * output up to its end.
+   */
+  Output = 'output',
+  /** Text currently visible on the emulated screen. */
+  Screen = 'screen'
+}
+
+/** Options for waiting until a PTY command is ready. */
+export interface PtyReadyOptions {
+  /**
+   * When the command counts as ready. A relative `path` resolves against
+   * the `cwd` passed to `start`, or the current directory without one.
+   */
+  ready: ShellReadyCondition
+  /** Milliseconds to wait for readiness. */
+  timeoutMs?: number
+  /** Abort signal for giving up on the wait. */
+  signal?: unknown
+}
+
+/** Outcome of waiting for a PTY command to become ready. */
+export interface PtyReadyResult {
+  /** Whether the readiness condition held. */
+  ready: boolean
+  /** Whether the command's output ended first. */
+  exited: boolean
+  /** Whether the timeout elapsed first. */
+  timedOut: boolean
+  /** Whether the abort signal fired first. */
+  cancelled: boolean
+  /** Output not consumed by expect scripts, up to this point. */
+  output: string
+}
+
+/** Options for recording a PTY session. */
+export interface PtyRecordOptions {
+  /**
+   * Most bytes of event data to keep (default: 8 MiB). Events past the cap
+   * are dropped and a marker records where.
+   */
+  maxBytes?: number
+  /** Record input written to the command (default: true). */
+  input?: boolean
+}
+
 /** Result of a PTY command run. */
 export interface PtyRunResult {
   /** Exit code when the command completes. */
@@ -1092,6 +1505,49 @@ export interface PtyRunResult {
   cancelled: boolean
   /** Whether command timed out. */
   timedOut: boolean
+  /** Bytes of output produced by the command. */
+  totalBytes: number
+  /** Bytes dropped from the middle of the output by `output_limit`. */
+  elidedBytes: number
+  /**
+   * Temp file holding the full output when `output_limit.spill` was set and
+   * output was elided.
+   */
+  outputPath?: string
+  /**
+   * Command refused by the `shell` session's policy; the run stops at the
+   * first refusal.
+   */
+  blocked?: ShellBlockedCommand
+}
+
+/** Visible contents of a PTY session's terminal. */
+export interface PtyScreenSnapshot {
+  /** One line per screen row, joined with `\n`, without trailing blanks. */
+  text: string
+  /** `text` with SGR escape sequences, when attributes were requested. */
+  formatted?: string
+  /** Number of rows on the screen. */
+  rows: number
+  /** Number of columns on the screen. */
+  cols: number
+  /** Zero-based row of the cursor. */
+  cursorRow: number
+  /** Zero-based column of the cursor. */
+  cursorCol: number
+  /** Whether the program has hidden the cursor. */
+  cursorHidden: boolean
+  /** Whether the alternate screen (used by full-screen programs) is active. */
+  alternateScreen: boolean
+}
+
+/** Options for `PtySession::snapshot`. */
+export interface PtySnapshotOptions {
+  /**
+   * Also render the screen with SGR escape sequences for colors and text
+   * attributes.
+   */
+  attributes?: boolean
 }
 
 /** Options for running a command in a PTY session. */
@@ -1110,6 +1566,28 @@ export interface PtyStartOptions {
   cols?: number
   /** PTY row count. */
   rows?: number
+  /**
+   * Byte budget and head/tail retention applied before output reaches the
+   * callback.
+   */
+  outputLimit?: OutputLimit
+  /** Resource limits for the command and everything it spawns. */
+  limits?: ResourceLimits
+  /**
+   * Run the command in an isolated sandbox (Linux only). With `shell`,
+   * builtins run unconfined in this process, except that files the shell
+   * opens for output redirections must lie in the writable paths.
+   */
+  sandbox?: SandboxOptions
+  /**
+   * Run the command through a fresh native shell session configured like
+   * `Shell` (session env, snapshot, policy, history) instead of `sh -lc`
+   * (Unix only). The PTY is the session's stdin, stdout and stderr, and the
+   * controlling terminal of the foreground programs it spawns.
+   */
+  shell?: ShellOptions
+  /** Record output, input and resizes for export as an asciicast. */
+  record?: PtyRecordOptions
 }
 
 /**
@@ -1185,6 +1663,28 @@ export interface RenderParams {
   focusedPaths?: Array<FocusedPath>
 }
 
+/**
+ * Resource limits applied to each process a command spawns.
+ *
+ * Each limit sets both the soft and the hard value, so commands cannot raise
+ * it again. Values are non-negative integers; `Infinity` removes the limit.
+ */
+export interface ResourceLimits {
+  /** CPU time in seconds (`RLIMIT_CPU`). */
+  cpuSeconds?: number
+  /** Virtual address space in bytes (`RLIMIT_AS`). */
+  addressSpaceBytes?: number
+  /** Open file descriptors (`RLIMIT_NOFILE`). */
+  openFiles?: number
+  /**
+   * Processes for the user (`RLIMIT_NPROC`). Counts every process of the
+   * user, not just the command's.
+   */
+  processes?: number
+  /** Size in bytes of files the command may write (`RLIMIT_FSIZE`). */
+  fileSizeBytes?: number
+}
+
 /** Sampling filter for resize operations. */
 export declare enum SamplingFilter {
   /** Nearest-neighbor sampling (fast, low quality). */
@@ -1199,6 +1699,20 @@ export declare enum SamplingFilter {
   Lanczos3 = 5
 }
 
+/** Namespace sandbox for spawned processes (Linux only). */
+export interface SandboxOptions {
+  /**
+   * Directories that stay writable. Defaults to the command's working
+   * directory.
+   */
+  writablePaths?: Array<string>
+  /**
+   * Keep the host network instead of an isolated network namespace with
+   * only a loopback device.
+   */
+  allowNetwork?: boolean
+}
+
 /**
  * Strip ANSI escape sequences, remove control characters / lone surrogates,
  * and normalize line endings.
@@ -1254,6 +1768,113 @@ export interface SearchResult {
   error?: string
 }
 
+/** Result of statically analyzing a shell command string. */
+export interface ShellAnalysis {
+  /**
+   * Simple commands in execution order; commands inside substitutions come
+   * before the command whose words contain them.
+   */
+  commands: Array<ShellAnalyzedCommand>
+  /** Whether any word could not be resolved statically. */
+  unresolved: boolean
+}
+
+/** Simple command found in the script. */
+export interface ShellAnalyzedCommand {
+  /**
+   * Command name; absent for assignment-only commands, bare redirections,
+   * and redirections applied to compound commands.
+   */
+  name?: ShellWord
+  /** Arguments after the command name. */
+  args: Array<ShellWord>
+  /** Variables assigned for the command (`FOO=bar cmd`). */
+  assignments: Array<string>
+  /** Redirections applied to the command. */
+  redirects: Array<ShellRedirect>
+  /** Innermost construct the command is nested in. */
+  context: ShellCommandContext
+  /** Number of nested subshells, substitutions and functions around it. */
+  depth: number
+  /** Position in its pipeline, starting at 0. */
+  pipelinePosition: number
+  /** Number of commands in its pipeline. */
+  pipelineLength: number
+  /** Whether it runs asynchronously (`&`). */
+  background: boolean
+}
+
+/** Simple command refused by the policy. */
+export interface ShellBlockedCommand {
+  /** Expanded command name and arguments. */
+  argv: Array<string>
+  /** Action of the rule that matched. */
+  action: ShellPolicyAction
+  /** Explanation from the rule, or a default for its action. */
+  reason: string
+}
+
+/** Where a simple command sits relative to the top-level script. */
+export declare enum ShellCommandContext {
+  /** Top-level list of the script. */
+  Main = 'main',
+  /** Inside a `( ... )` subshell. */
+  Subshell = 'subshell',
+  /** Inside a `$( ... )` or backquoted command substitution. */
+  CommandSubstitution = 'commandSubstitution',
+  /** Inside a `<( ... )` or `>( ... )` process substitution. */
+  ProcessSubstitution = 'processSubstitution',
+  /** Inside a function body, which only runs when the function is called. */
+  Function = 'function'
+}
+
+/** How a traced command was carried out. */
+export declare enum ShellCommandKind {
+  /** Shell builtin run in-process. */
+  Builtin = 'builtin',
+  /** Shell function. */
+  Function = 'function',
+  /** External program. */
+  External = 'external',
+  /** Refused by the command policy; nothing ran. */
+  Blocked = 'blocked'
+}
+
+/** Record of one simple command executed by the interpreter. */
+export interface ShellCommandTrace {
+  /** Expanded command name and arguments. */
+  argv: Array<string>
+  /** Working directory the command ran in. */
+  cwd: string
+  /** How the command was carried out. */
+  kind: ShellCommandKind
+  /** Process ID for external commands. */
+  pid?: number
+  /** Dispatch time in milliseconds since the Unix epoch. */
+  startedAt: number
+  /** Completion time in milliseconds since the Unix epoch. */
+  finishedAt: number
+  /** Exit status of the command. */
+  exitCode: number
+}
+
+/** Completion candidates for an input line. */
+export interface ShellCompletion {
+  /**
+   * Replacement texts for the range `start..end`, in display order.
+   * Filenames are escaped for the shell and directories end in `/`.
+   */
+  candidates: Array<string>
+  /** Start of the range to replace, as a UTF-16 offset into the line. */
+  start: number
+  /** End of the range to replace (the cursor), as a UTF-16 offset. */
+  end: number
+  /** Whether the candidates are filenames. */
+  filenames: boolean
+  /** Whether a space should follow a candidate once it is accepted. */
+  appendSpace: boolean
+}
+
 /** Options for executing a shell command via brush-core. */
 export interface ShellExecuteOptions {
   /** Command string to execute in the shell. */
@@ -1270,6 +1891,23 @@ export interface ShellExecuteOptions {
   snapshotPath?: string
   /** Abort signal for cancelling the operation. */
   signal?: unknown
+  /**
+   * Byte budget and head/tail retention applied before output reaches the
+   * callbacks.
+   */
+  outputLimit?: OutputLimit
+  /** Rules checked before each simple command runs, after expansion. */
+  policy?: Array<ShellPolicyRule>
+  /** Allow commands matched by `confirm` policy rules. */
+  confirmed?: boolean
+  /** Resource limits for the external processes the command spawns. */
+  limits?: ResourceLimits
+  /**
+   * Run external processes in an isolated sandbox (Linux only). Files the
+   * shell opens for output redirections must lie in the writable paths too;
+   * builtins otherwise run unconfined in this process.
+   */
+  sandbox?: SandboxOptions
 }
 
 /** Result of executing a shell command via brush-core. */
@@ -1280,6 +1918,132 @@ export interface ShellExecuteResult {
   cancelled: boolean
   /** Whether the command timed out before completion. */
   timedOut: boolean
+  /** Bytes of output produced by the command. */
+  totalBytes: number
+  /** Bytes dropped from the middle of the output by `output_limit`. */
+  elidedBytes: number
+  /**
+   * Temp file holding the full output when `output_limit.spill` was set and
+   * output was elided.
+   */
+  outputPath?: string
+  /** Command refused by the policy; the run stops at the first refusal. */
+  blocked?: ShellBlockedCommand
+}
+
+/** How repeated command lines are recorded. */
+export declare enum ShellHistoryDedupe {
+  /** Record every command line. */
+  None = 'none',
+  /** Skip a command line identical to the previous one (`ignoredups`). */
+  Consecutive = 'consecutive',
+  /**
+   * Remove earlier copies of a command line before recording it
+   * (`erasedups`).
+   */
+  All = 'all'
+}
+
+/** Recorded command line. */
+export interface ShellHistoryEntry {
+  /** 1-based position in the history, as shown by the `history` builtin. */
+  index: number
+  /** Command line as it was run. */
+  command: string
+  /**
+   * When the command was recorded, in milliseconds since the Unix epoch;
+   * absent for entries loaded from a file without timestamps.
+   */
+  timestamp?: number
+}
+
+/** How `ShellHistorySearch.text` is matched against command lines. */
+export declare enum ShellHistoryMatch {
+  /** Command line starts with the text. */
+  Prefix = 'prefix',
+  /** Command line contains the text. */
+  Contains = 'contains',
+  /** Command line equals the text. */
+  Exact = 'exact'
+}
+
+/** History settings for a shell session. */
+export interface ShellHistoryOptions {
+  /**
+   * File history is loaded from and saved to. Defaults to a `HISTFILE` set
+   * by the session snapshot; without either, history only lives as long as
+   * the session.
+   */
+  path?: string
+  /**
+   * Maximum number of entries kept; the oldest are dropped first. Defaults
+   * to the snapshot's `HISTSIZE`.
+   */
+  maxItems?: number
+  /**
+   * Write a timestamp comment before each entry in the file. Defaults to
+   * the snapshot's `HISTTIMEFORMAT`, or true.
+   */
+  timestamps?: boolean
+  /**
+   * How repeated command lines are recorded. Defaults to the snapshot's
+   * `HISTCONTROL`, or `none`.
+   */
+  dedupe?: ShellHistoryDedupe
+}
+
+/** Query for searching history. */
+export interface ShellHistorySearch {
+  /** Text to look for. */
+  text: string
+  /** How the text is matched. Defaults to `contains`. */
+  mode?: ShellHistoryMatch
+  /** Maximum number of entries returned. */
+  limit?: number
+}
+
+/** Background job of a shell session. */
+export interface ShellJob {
+  /** Job number, as used in `%N` job specs. */
+  id: number
+  /** Process ID of the job's most recently spawned external process. */
+  pid?: number
+  /** Process group the job's external processes run in. */
+  pgid?: number
+  /** Command line of the job. */
+  command: string
+  /** Execution state of the job. */
+  state: ShellJobState
+  /** Start time in milliseconds since the Unix epoch. */
+  startedAt: number
+  /** Exit status once the job is done. */
+  exitCode?: number
+}
+
+/** Execution state of a background job. */
+export declare enum ShellJobState {
+  /** The job is still running. */
+  Running = 'running',
+  /** The job was stopped by a signal. */
+  Stopped = 'stopped',
+  /** The job has finished. */
+  Done = 'done'
+}
+
+/** Outcome of waiting for a background job. */
+export interface ShellJobWaitResult {
+  /** Exit status when the job finished. */
+  exitCode?: number
+  /** Whether the timeout elapsed before the job finished. */
+  timedOut: boolean
+}
+
+/** Option states of a shell session. */
+export interface ShellOptionFlags {
+  /** Options controlled by `set -o`, keyed by long name. */
+  setO: Record<string, boolean>
+  /** Options controlled by `shopt`. */
+  shopt: Record<string, boolean>
 }
 
 /** Options for configuring a persistent shell session. */
@@ -1288,6 +2052,105 @@ export interface ShellOptions {
   sessionEnv?: Record<string, string>
   /** Optional snapshot file to source on session creation. */
   snapshotPath?: string
+  /** Rules checked before each simple command runs, after expansion. */
+  policy?: Array<ShellPolicyRule>
+  /** Record each `run` command line in the session's history. */
+  history?: ShellHistoryOptions
+}
+
+/**
+ * Output chunk tagged with the stream it was read from.
+ *
+ * Stdout and stderr are read from separate pipes that are drained after
+ * every command, so chunks follow the order of writes; only one command's
+ * writes to both streams may arrive out of order with each other.
+ */
+export interface ShellOutputChunk {
+  /** Stream that produced the chunk. */
+  stream: ShellStream
+  /** Decoded chunk text. */
+  text: string
+}
+
+/** What to do when a policy rule matches. */
+export declare enum ShellPolicyAction {
+  /** Never run the command. */
+  Deny = 'deny',
+  /** Only run the command when the run was explicitly confirmed. */
+  Confirm = 'confirm'
+}
+
+/** Rule matching simple commands by name, path, and arguments. */
+export interface ShellPolicyRule {
+  /**
+   * Command name (`rm`) or path (`/usr/bin/rm`). Names match any path with
+   * that basename; paths also match bare names that resolve to them via
+   * `PATH`.
+   */
+  command: string
+  /**
+   * Regex searched in the expanded arguments joined by single spaces. When
+   * omitted the rule matches any arguments.
+   */
+  args?: string
+  /** Action taken when the rule matches. */
+  action: ShellPolicyAction
+  /** Explanation reported with the blocked command. */
+  reason?: string
+}
+
+/**
+ * Conditions that mark a background command as ready. All conditions that
+ * are set must hold; at least one is required.
+ */
+export interface ShellReadyCondition {
+  /** Regex matched against the command's combined stdout and stderr. */
+  pattern?: string
+  /** TCP port on localhost (`127.0.0.1` or `::1`) that must accept connections. */
+  port?: number
+  /** File that must exist, relative to the command's working directory. */
+  path?: string
+}
+
+/** Redirection attached to a command. */
+export interface ShellRedirect {
+  /** Explicit file descriptor, e.g. `2` in `2>err.log`. */
+  fd?: number
+  /** Redirection operator. */
+  kind: ShellRedirectKind
+  /**
+   * File, descriptor or here-string word; absent for here-documents and
+   * process substitutions.
+   */
+  target?: ShellWord
+  /** Here-document body. */
+  body?: string
+}
+
+/** Kind of redirection. */
+export declare enum ShellRedirectKind {
+  /** `<` */
+  Read = 'read',
+  /** `>` */
+  Write = 'write',
+  /** `>>` */
+  Append = 'append',
+  /** `<>` */
+  ReadWrite = 'readWrite',
+  /** `>|` */
+  Clobber = 'clobber',
+  /** `<&` */
+  DuplicateInput = 'duplicateInput',
+  /** `>&` */
+  DuplicateOutput = 'duplicateOutput',
+  /** `&>` */
+  OutputAndError = 'outputAndError',
+  /** `&>>` */
+  AppendOutputAndError = 'appendOutputAndError',
+  /** `<<` or `<<-` */
+  HereDocument = 'hereDocument',
+  /** `<<<` */
+  HereString = 'hereString'
 }
 
 /** Options for running a shell command. */
@@ -1302,6 +2165,21 @@ export interface ShellRunOptions {
   timeoutMs?: number
   /** Abort signal for cancelling the operation. */
   signal?: unknown
+  /**
+   * Byte budget and head/tail retention applied before output reaches the
+   * callbacks.
+   */
+  outputLimit?: OutputLimit
+  /** Allow commands matched by `confirm` policy rules for this run. */
+  confirmed?: boolean
+  /** Resource limits for the external processes the command spawns. */
+  limits?: ResourceLimits
+  /**
+   * Run external processes in an isolated sandbox (Linux only). Files the
+   * shell opens for output redirections must lie in the writable paths too;
+   * builtins otherwise run unconfined in this process.
+   */
+  sandbox?: SandboxOptions
 }
 
 /** Result of running a shell command. */
@@ -1312,6 +2190,87 @@ export interface ShellRunResult {
   cancelled: boolean
   /** Whether the command timed out before completion. */
   timedOut: boolean
+  /** Bytes of output produced by the command. */
+  totalBytes: number
+  /** Bytes dropped from the middle of the output by `output_limit`. */
+  elidedBytes: number
+  /**
+   * Temp file holding the full output when `output_limit.spill` was set and
+   * output was elided.
+   */
+  outputPath?: string
+  /** Command refused by the policy; the run stops at the first refusal. */
+  blocked?: ShellBlockedCommand
+}
+
+/** Options for starting a background command. */
+export interface ShellStartOptions {
+  /** Command string to run in the background. */
+  command: string
+  /** Working directory for the command. */
+  cwd?: string
+  /** Environment variables to apply for this command only. */
+  env?: Record<string, string>
+  /** When the command counts as ready. */
+  ready: ShellReadyCondition
+  /** Milliseconds to wait for readiness. */
+  timeoutMs?: number
+  /** Abort signal for giving up on the wait. */
+  signal?: unknown
+}
+
+/** Result of starting a background command. */
+export interface ShellStartResult {
+  /** Job number of the started command. */
+  jobId: number
+  /** Process ID of the job's most recently spawned external process. */
+  pid?: number
+  /** Whether the readiness condition held. */
+  ready: boolean
+  /** Exit status when the command finished before becoming ready. */
+  exitCode?: number
+  /** Whether the timeout elapsed first. */
+  timedOut: boolean
+  /** Whether the abort signal fired first. */
+  cancelled: boolean
+  /** Output written by the command up to this point. */
+  output: string
+}
+
+/** Output stream a chunk was read from. */
+export declare enum ShellStream {
+  /** Standard output. */
+  Stdout = 'stdout',
+  /** Standard error. */
+  Stderr = 'stderr'
+}
+
+/** Part of a word that can only be resolved at runtime. */
+export declare enum ShellUnresolvedPart {
+  /** Parameter or variable expansion. */
+  Variable = 'variable',
+  /** Command substitution. */
+  CommandSubstitution = 'commandSubstitution',
+  /** Process substitution. */
+  ProcessSubstitution = 'processSubstitution',
+  /** Arithmetic expansion. */
+  Arithmetic = 'arithmetic',
+  /** Unquoted glob pattern. */
+  Glob = 'glob',
+  /** Unquoted brace expansion. */
+  Brace = 'brace',
+  /** Tilde prefix. */
+  Tilde = 'tilde'
+}
+
+/** Word as written in the source, with its static value when it has one. */
+export interface ShellWord {
+  /** Source text of the word. */
+  raw: string
+  /** Value after quote removal; absent when any part is unresolved. */
+  value?: string
+  /** Parts that depend on runtime state. */
+  unresolved: Array<ShellUnresolvedPart>
 }
 
 /**
@@ -1340,6 +2299,52 @@ export declare function sliceWithWidth(line: string, startCol: number, length: n
  */
 export declare function supportsLanguage(lang: string): boolean
 
+/**
+ * Terminate `pid`, its descendants and the process group it leads.
+ *
+ * Sends `signal` bottom-up, then polls until every process has exited or the
+ * grace period ends, signalling descendants and group members that appear
+ * meanwhile. Whatever is still running gets SIGKILL. Zombies count as exited.
+ */
+export declare function terminateTree(pid: number, options?: TerminateTreeOptions | undefined | null): Promise<TerminateTreeResult>
+
+/** Options for `terminate_tree`. */
+export interface TerminateTreeOptions {
+  /** Signal sent first (default: 15, SIGTERM). Pass 2 for SIGINT. */
+  signal?: number
+  /**
+   * Milliseconds to wait for the tree to exit before sending SIGKILL
+   * (default: 2000).
+   */
+  graceMs?: number
+  /**
+   * Also signal the members of the process group `pid` leads (default:
+   * true). The caller's own group is never signalled.
+   */
+  processGroup?: boolean
+}
+
+/**
+ * Which processes exited at each stage of `terminate_tree`. Each list is
+ * sorted by PID.
+ */
+export interface TerminateTreeResult {
+  /**
+   * Processes sent the first signal, including ones forked during the grace
+   * period.
+   */
+  signalled: Array<number>
+  /** Processes that exited within the grace period. */
+  exited: Array<number>
+  /** Processes still running after the grace period, sent SIGKILL. */
+  killed: Array<number>
+  /**
+   * Processes still running shortly after SIGKILL, usually stuck in
+   * uninterruptible sleep.
+   */
+  survivors: Array<number>
+}
+
 /**
  * Truncate text to a visible width, preserving ANSI codes.
  *
@@ -269,6 +269,11 @@ module.exports.ChunkRegion = {
   Head: '^',
   Body: '~',
 };
+module.exports.ColorDepth = {
+  TrueColor: 'truecolor',
+  Ansi256: '256',
+  Ansi16: '16',
+};
 module.exports.Ellipsis = {
   Unicode: 0,
   Ascii: 1,
@@ -284,6 +289,11 @@ module.exports.GrepOutputMode = {
   Count: 'count',
   FilesWithMatches: 'filesWithMatches',
 };
+module.exports.HighlightFormat = {
+  Ansi: 'ansi',
+  Html: 'html',
+  Svg: 'svg',
+};
 module.exports.ImageFormat = {
   PNG: 0,
   JPEG: 1,
@@ -299,6 +309,19 @@ module.exports.MacOSAppearance = {
   Dark: 'dark',
   Light: 'light',
 };
+module.exports.ProcessState = {
+  Running: 'running',
+  Sleeping: 'sleeping',
+  DiskSleep: 'diskSleep',
+  Stopped: 'stopped',
+  Zombie: 'zombie',
+  Idle: 'idle',
+  Unknown: 'unknown',
+};
+module.exports.PtyExpectTarget = {
+  Output: 'output',
+  Screen: 'screen',
+};
 module.exports.SamplingFilter = {
   Nearest: 1,
   Triangle: 2,
@@ -306,4 +329,61 @@ module.exports.SamplingFilter = {
   Gaussian: 4,
   Lanczos3: 5,
 };
+module.exports.ShellCommandContext = {
+  Main: 'main',
+  Subshell: 'subshell',
+  CommandSubstitution: 'commandSubstitution',
+  ProcessSubstitution: 'processSubstitution',
+  Function: 'function',
+};
+module.exports.ShellCommandKind = {
+  Builtin: 'builtin',
+  Function: 'function',
+  External: 'external',
+};
+module.exports.ShellHistoryDedupe = {
+  None: 'none',
+  Consecutive: 'consecutive',
+  All: 'all',
+};
+module.exports.ShellHistoryMatch = {
+  Prefix: 'prefix',
+  Contains: 'contains',
+  Exact: 'exact',
+};
+module.exports.ShellJobState = {
+  Running: 'running',
+  Stopped: 'stopped',
+  Done: 'done',
+};
+module.exports.ShellPolicyAction = {
+  Deny: 'deny',
+  Confirm: 'confirm',
+};
+module.exports.ShellRedirectKind = {
+  Read: 'read',
+  Write: 'write',
+  Append: 'append',
+  ReadWrite: 'readWrite',
+  Clobber: 'clobber',
+  DuplicateInput: 'duplicateInput',
+  DuplicateOutput: 'duplicateOutput',
+  OutputAndError: 'outputAndError',
+  AppendOutputAndError: 'appendOutputAndError',
+  HereDocument: 'hereDocument',
+  HereString: 'hereString',
+};
+module.exports.ShellStream = {
+  Stdout: 'stdout',
+  Stderr: 'stderr',
+};
+module.exports.ShellUnresolvedPart = {
+  Variable: 'variable',
+  CommandSubstitution: 'commandSubstitution',
+  ProcessSubstitution: 'processSubstitution',
+  Arithmetic: 'arithmetic',
+  Glob: 'glob',
+  Brace: 'brace',
+  Tilde: 'tilde',
+};
 // --- end generated const enum exports ---